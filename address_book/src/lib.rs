@@ -7,15 +7,27 @@
 
 #![doc = include_str!("../README.md")]
 #[warn(unused_imports)]
-use async_std::task::{sleep, spawn, JoinHandle};
-use jf_cap::keys::{UserAddress, UserPubKey};
+use async_std::channel::{bounded, Receiver};
+use async_std::io::ReadExt;
+use async_std::task::{sleep, spawn, spawn_blocking, JoinHandle};
+use futures::{StreamExt, TryStreamExt};
+use jf_cap::keys::{UserAddress, UserKeyPair, UserPubKey};
 use jf_cap::Signature;
-use rand::{distributions::Alphanumeric, Rng};
+use lru::LruCache;
+use rand::{distributions::Alphanumeric, Rng, SeedableRng};
+use rand_chacha::ChaChaRng;
+use request_id::{RequestIdMiddleware, REQUEST_ID_HEADER};
 use serde::{Deserialize, Serialize};
 use std::env;
-use std::path::PathBuf;
-use std::sync::Arc;
-use std::{fs, time::Duration};
+use std::path::{Path, PathBuf};
+use std::sync::{
+    atomic::{AtomicBool, Ordering},
+    Arc, Mutex,
+};
+use std::{
+    fs,
+    time::{Duration, Instant, SystemTime, UNIX_EPOCH},
+};
 use tempdir::TempDir;
 use tide::{
     convert::json,
@@ -24,26 +36,198 @@ use tide::{
     StatusCode,
 };
 
+#[cfg(feature = "rocksdb-store")]
+mod rocksdb_store;
 pub mod signal;
 
+#[cfg(feature = "rocksdb-store")]
+pub use rocksdb_store::{RocksDbStore, TransientRocksDbStore};
+
 pub const DEFAULT_PORT: u16 = 50078u16;
 const ADDRESS_BOOK_STARTUP_RETRIES: usize = 8;
 
+/// Maximum size of a request body accepted by the server by default.
+pub const DEFAULT_MAX_BODY_SIZE: u64 = 64 * 1024;
+
+/// Number of entries read at once by `stream_all`.
+const STREAM_BATCH_SIZE: usize = 100;
+
+/// Number of entries returned by `list` if no limit is given.
+pub const DEFAULT_LIST_LIMIT: usize = 50;
+/// Maximum number of entries returned by a single `list` request.
+pub const MAX_LIST_LIMIT: usize = 500;
+
+/// Time between two sweeps for expired entries.
+const EXPIRED_SWEEP_INTERVAL: Duration = Duration::from_secs(60);
+
+/// Default time `healthcheck/deep` waits for the store probe to complete.
+pub const DEFAULT_DEEP_HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// Default time during which `healthcheck/deep` answers with the result of
+/// the previous probe instead of writing to the store again.
+pub const DEFAULT_DEEP_HEALTHCHECK_INTERVAL: Duration = Duration::from_secs(10);
+
 pub trait Store: Clone + Send + Sync {
     fn save(&self, address: &UserAddress, pub_key: &UserPubKey) -> Result<(), std::io::Error>;
+    /// Save a public key which is only returned by `load` until `ttl` has
+    /// elapsed.
+    fn save_with_ttl(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Duration,
+    ) -> Result<(), std::io::Error>;
     fn load(&self, address: &UserAddress) -> Result<Option<UserPubKey>, std::io::Error>;
+    fn delete(&self, address: &UserAddress) -> Result<(), std::io::Error>;
+    /// Replace the public key of `address` with `new` if it is `current`,
+    /// atomically with respect to other writes to the store.
+    ///
+    /// Returns the public key stored before the call, as `load` would.
+    fn compare_and_swap(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+        new: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error>;
+    /// Delete the entry of `address` if its public key is `current`,
+    /// atomically with respect to other writes to the store.
+    ///
+    /// Returns the public key stored before the call, as `load` would.
+    fn compare_and_delete(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error>;
+    /// Delete all expired entries. Returns the number of deleted entries.
+    fn remove_expired(&self) -> Result<usize, std::io::Error> {
+        Ok(0)
+    }
+    /// List at most `limit` entries, in a stable order, starting after the
+    /// entry identified by `cursor`.
+    fn list_paginated(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+    ) -> Result<ListResponse, std::io::Error>;
+    /// The number of unexpired entries, not including the entry written by
+    /// `healthcheck` (see [healthcheck_address]).
+    fn count(&self) -> Result<usize, std::io::Error>;
+    /// All unexpired entries, in no particular order. Fails if any entry
+    /// can't be read.
+    fn list_all(&self) -> Result<Vec<(UserAddress, UserPubKey)>, std::io::Error>;
+    /// Send all entries through a channel, from a background task.
+    ///
+    /// The channel is closed once all entries are sent, or if reading the
+    /// entries fails.
+    fn stream_all(&self) -> Receiver<(UserAddress, UserPubKey)>
+    where
+        Self: 'static,
+    {
+        let (sender, receiver) = bounded(STREAM_BATCH_SIZE);
+        let store = self.clone();
+        spawn(async move {
+            let mut cursor = None;
+            loop {
+                let page = match store.list_paginated(cursor, STREAM_BATCH_SIZE) {
+                    Ok(page) => page,
+                    Err(err) => {
+                        tracing::error!("Failed to list entries: {}", err);
+                        return;
+                    }
+                };
+                for entry in page.entries {
+                    if sender.send(entry).await.is_err() {
+                        // The receiver is gone.
+                        return;
+                    }
+                }
+                match page.next_cursor {
+                    Some(next) => cursor = Some(next),
+                    None => return,
+                }
+            }
+        });
+        receiver
+    }
+}
+
+/// A page of address book entries.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct ListResponse {
+    pub entries: Vec<(UserAddress, UserPubKey)>,
+    /// Pass this as `cursor` to get the next page. `None` if this is the last
+    /// page.
+    pub next_cursor: Option<String>,
+}
+
+/// Current version of the [StoredPubKey] format.
+const STORED_PUB_KEY_VERSION: u8 = 1;
+
+/// The contents of a file in a [FileStore].
+///
+/// Files written before versioning was introduced contain just the
+/// serialized `UserPubKey`.
+#[derive(Debug, Deserialize, Serialize)]
+struct StoredPubKey {
+    version: u8,
+    pub_key: UserPubKey,
+    /// Milliseconds since the Unix epoch after which the entry is expired.
+    expires_at: Option<u64>,
+}
+
+impl StoredPubKey {
+    fn new(pub_key: &UserPubKey, ttl: Option<Duration>) -> Self {
+        Self {
+            version: STORED_PUB_KEY_VERSION,
+            pub_key: pub_key.clone(),
+            expires_at: ttl.map(|ttl| (unix_time() + ttl).as_millis() as u64),
+        }
+    }
+
+    /// Fails with `InvalidData` if `bytes` are neither a [StoredPubKey] nor
+    /// a serialized `UserPubKey`.
+    fn from_bytes(bytes: &[u8]) -> Result<Self, std::io::Error> {
+        match bincode::deserialize::<Self>(bytes) {
+            Ok(stored) if stored.version == STORED_PUB_KEY_VERSION => Ok(stored),
+            _ => Ok(Self {
+                version: STORED_PUB_KEY_VERSION,
+                pub_key: bincode::deserialize(bytes)
+                    .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidData, err))?,
+                expires_at: None,
+            }),
+        }
+    }
+
+    fn is_expired(&self) -> bool {
+        match self.expires_at {
+            Some(expires_at) => unix_time().as_millis() as u64 >= expires_at,
+            None => false,
+        }
+    }
+}
+
+fn unix_time() -> Duration {
+    SystemTime::now()
+        .duration_since(UNIX_EPOCH)
+        .expect("System time is before the Unix epoch.")
 }
 
 #[derive(Debug, Clone)]
 pub struct FileStore {
     dir: PathBuf,
+    /// Held while writing or deleting an entry, so that `remove_expired` does
+    /// not delete an entry which was replaced after it was found expired.
+    write_lock: Arc<Mutex<()>>,
 }
 
 /// Persistent file backed store.
 /// Each (address, pub_key) pair is store in a single file inside `dir`.
 impl FileStore {
     pub fn new(dir: PathBuf) -> Self {
-        Self { dir }
+        Self {
+            dir,
+            write_lock: Default::default(),
+        }
     }
 
     fn path(&self, address: &UserAddress) -> PathBuf {
@@ -60,14 +244,29 @@ impl FileStore {
 
         self.path(address).with_extension(rand_string)
     }
-}
 
-impl Store for FileStore {
-    fn save(&self, address: &UserAddress, pub_key: &UserPubKey) -> Result<(), std::io::Error> {
+    fn write(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Option<Duration>,
+    ) -> Result<(), std::io::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        self.write_locked(address, pub_key, ttl)
+    }
+
+    /// Write an entry while already holding `write_lock`.
+    fn write_locked(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Option<Duration>,
+    ) -> Result<(), std::io::Error> {
         let tmp_path = self.tmp_path(address);
         match fs::write(
             &tmp_path,
-            bincode::serialize(&pub_key).expect("Failed to serialize public key."),
+            bincode::serialize(&StoredPubKey::new(pub_key, ttl))
+                .expect("Failed to serialize public key."),
         ) {
             Ok(_) => fs::rename(&tmp_path, self.path(address)),
             Err(e) => {
@@ -76,12 +275,34 @@ impl Store for FileStore {
             }
         }
     }
+}
+
+impl Store for FileStore {
+    fn save(&self, address: &UserAddress, pub_key: &UserPubKey) -> Result<(), std::io::Error> {
+        self.write(address, pub_key, None)
+    }
+
+    fn save_with_ttl(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Duration,
+    ) -> Result<(), std::io::Error> {
+        self.write(address, pub_key, Some(ttl))
+    }
+
     fn load(&self, address: &UserAddress) -> Result<Option<UserPubKey>, std::io::Error> {
         let path = self.path(address);
         match fs::read(&path) {
-            Ok(bytes) => Ok(Some(
-                bincode::deserialize(&bytes).expect("Failed to deserialize public key."),
-            )),
+            Ok(bytes) => {
+                let stored = StoredPubKey::from_bytes(&bytes)?;
+                if stored.is_expired() {
+                    tracing::info!("Address {} expired.", address);
+                    Ok(None)
+                } else {
+                    Ok(Some(stored.pub_key))
+                }
+            }
             Err(err) => match err.kind() {
                 std::io::ErrorKind::NotFound => {
                     tracing::info!("Address {} not found.", address);
@@ -94,6 +315,196 @@ impl Store for FileStore {
             },
         }
     }
+
+    fn delete(&self, address: &UserAddress) -> Result<(), std::io::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        fs::remove_file(self.path(address))
+    }
+
+    /// The entry is read and replaced under the write lock.
+    fn compare_and_swap(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+        new: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        let stored = self.load(address)?;
+        if stored.as_ref() == Some(current) {
+            self.write_locked(address, new, None)?;
+        }
+        Ok(stored)
+    }
+
+    /// The entry is read and deleted under the write lock.
+    fn compare_and_delete(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        let stored = self.load(address)?;
+        if stored.as_ref() == Some(current) {
+            fs::remove_file(self.path(address))?;
+        }
+        Ok(stored)
+    }
+
+    /// Each entry is read again under the write lock before it is deleted,
+    /// so an entry saved again since it expired is kept.
+    fn remove_expired(&self) -> Result<usize, std::io::Error> {
+        let mut removed = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "bin") {
+                continue;
+            }
+            let _guard = self.write_lock.lock().unwrap();
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                // The entry may have been replaced concurrently.
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            if StoredPubKey::from_bytes(&bytes)?.is_expired() {
+                fs::remove_file(&path)?;
+                removed += 1;
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Entries are ordered by file name, and the cursor is the file name
+    /// (without extension) of the last entry of the previous page.
+    fn list_paginated(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+    ) -> Result<ListResponse, std::io::Error> {
+        let mut names = vec![];
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "bin") {
+                continue;
+            }
+            if let Some(name) = path.file_stem().and_then(|stem| stem.to_str()) {
+                if cursor
+                    .as_ref()
+                    .map_or(true, |cursor| name > cursor.as_str())
+                {
+                    names.push(name.to_string());
+                }
+            }
+        }
+        names.sort();
+
+        let more = names.len() > limit;
+        names.truncate(limit);
+        let mut entries = vec![];
+        for name in &names {
+            let address: UserAddress = match hex::decode(name)
+                .ok()
+                .and_then(|bytes| bincode::deserialize(&bytes).ok())
+            {
+                Some(address) => address,
+                None => {
+                    tracing::warn!("Ignoring unexpected file {}.bin", name);
+                    continue;
+                }
+            };
+            // Skips entries which expired or were removed since listing the
+            // directory.
+            if let Some(pub_key) = self.load(&address)? {
+                entries.push((address, pub_key));
+            }
+        }
+        Ok(ListResponse {
+            entries,
+            next_cursor: if more { names.pop() } else { None },
+        })
+    }
+
+    /// Counts the `.bin` files in the store directory, skipping entries
+    /// which have expired but have not been swept yet, like `load` does.
+    fn count(&self) -> Result<usize, std::io::Error> {
+        let healthcheck_path = self.path(&healthcheck_address());
+        let mut count = 0;
+        for entry in fs::read_dir(&self.dir)? {
+            let path = entry?.path();
+            if path.extension().map_or(true, |ext| ext != "bin") || path == healthcheck_path {
+                continue;
+            }
+            let bytes = match fs::read(&path) {
+                Ok(bytes) => bytes,
+                // The entry may have been deleted concurrently.
+                Err(err) if err.kind() == std::io::ErrorKind::NotFound => continue,
+                Err(err) => return Err(err),
+            };
+            if !StoredPubKey::from_bytes(&bytes)?.is_expired() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Deserializes all `.bin` files in the store directory, skipping
+    /// expired entries.
+    fn list_all(&self) -> Result<Vec<(UserAddress, UserPubKey)>, std::io::Error> {
+        Ok(self.list_paginated(None, usize::MAX)?.entries)
+    }
+
+    /// Entries are sent in directory order, reading [STREAM_BATCH_SIZE] files
+    /// at a time.
+    fn stream_all(&self) -> Receiver<(UserAddress, UserPubKey)> {
+        let (sender, receiver) = bounded(STREAM_BATCH_SIZE);
+        let store = self.clone();
+        spawn(async move {
+            let dir = match fs::read_dir(&store.dir) {
+                Ok(dir) => dir,
+                Err(err) => {
+                    tracing::error!("Failed to read store directory: {}", err);
+                    return;
+                }
+            };
+            let paths = dir
+                .filter_map(|entry| {
+                    let path = entry.ok()?.path();
+                    if path.extension()? == "bin" {
+                        Some(path)
+                    } else {
+                        None
+                    }
+                })
+                .collect::<Vec<_>>();
+            for batch in paths.chunks(STREAM_BATCH_SIZE) {
+                let mut entries = Vec::with_capacity(STREAM_BATCH_SIZE);
+                for path in batch {
+                    let address: Option<UserAddress> = path
+                        .file_stem()
+                        .and_then(|stem| hex::decode(stem.to_str()?).ok())
+                        .and_then(|bytes| bincode::deserialize(&bytes).ok());
+                    let address = match address {
+                        Some(address) => address,
+                        None => continue,
+                    };
+                    match store.load(&address) {
+                        Ok(Some(pub_key)) => entries.push((address, pub_key)),
+                        Ok(None) => {}
+                        Err(err) => {
+                            tracing::error!("Failed to read {:?}: {}", path, err);
+                            return;
+                        }
+                    }
+                }
+                for entry in entries {
+                    if sender.send(entry).await.is_err() {
+                        return;
+                    }
+                }
+            }
+        });
+        receiver
+    }
 }
 
 /// Non-persistent store. Suitable for testing only.
@@ -114,6 +525,13 @@ impl Default for TransientFileStore {
     }
 }
 
+impl TransientFileStore {
+    /// The temporary directory holding the entries.
+    pub fn dir(&self) -> &Path {
+        &self.store.dir
+    }
+}
+
 impl Drop for TransientFileStore {
     fn drop(&mut self) {
         fs::remove_dir_all(self.store.dir.clone()).expect("Failed to remove store path.");
@@ -125,20 +543,333 @@ impl Store for TransientFileStore {
         self.store.save(address, pub_key)
     }
 
+    fn save_with_ttl(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Duration,
+    ) -> Result<(), std::io::Error> {
+        self.store.save_with_ttl(address, pub_key, ttl)
+    }
+
     fn load(&self, address: &UserAddress) -> Result<Option<UserPubKey>, std::io::Error> {
         self.store.load(address)
     }
+
+    fn delete(&self, address: &UserAddress) -> Result<(), std::io::Error> {
+        self.store.delete(address)
+    }
+
+    fn compare_and_swap(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+        new: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        self.store.compare_and_swap(address, current, new)
+    }
+
+    fn compare_and_delete(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        self.store.compare_and_delete(address, current)
+    }
+
+    fn remove_expired(&self) -> Result<usize, std::io::Error> {
+        self.store.remove_expired()
+    }
+
+    fn list_paginated(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+    ) -> Result<ListResponse, std::io::Error> {
+        self.store.list_paginated(cursor, limit)
+    }
+
+    fn count(&self) -> Result<usize, std::io::Error> {
+        self.store.count()
+    }
+
+    fn list_all(&self) -> Result<Vec<(UserAddress, UserPubKey)>, std::io::Error> {
+        self.store.list_all()
+    }
+
+    fn stream_all(&self) -> Receiver<(UserAddress, UserPubKey)> {
+        self.store.stream_all()
+    }
+}
+
+/// Number of entries cached by [CachedFileStore] by default.
+pub const DEFAULT_CACHE_CAPACITY: usize = 10_000;
+
+/// Time after which a cached entry is reloaded from the underlying store.
+///
+/// This bounds how long an expired entry can still be served from the cache.
+const CACHE_ENTRY_MAX_AGE: Duration = Duration::from_secs(60);
+
+/// A store which keeps recently loaded entries in memory.
+///
+/// Clones share the same cache, so that all handlers of a server benefit from
+/// it.
+#[derive(Clone)]
+pub struct CachedFileStore<S: Store> {
+    store: S,
+    cache: Arc<Mutex<LruCache<UserAddress, (UserPubKey, Instant)>>>,
+}
+
+impl<S: Store> CachedFileStore<S> {
+    pub fn new(store: S, capacity: usize) -> Self {
+        Self {
+            store,
+            cache: Arc::new(Mutex::new(LruCache::new(capacity))),
+        }
+    }
+
+    fn invalidate(&self, address: &UserAddress) {
+        self.cache.lock().unwrap().pop(address);
+    }
 }
 
+impl<S: Store + 'static> Store for CachedFileStore<S> {
+    fn save(&self, address: &UserAddress, pub_key: &UserPubKey) -> Result<(), std::io::Error> {
+        self.invalidate(address);
+        self.store.save(address, pub_key)
+    }
+
+    fn save_with_ttl(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Duration,
+    ) -> Result<(), std::io::Error> {
+        self.invalidate(address);
+        self.store.save_with_ttl(address, pub_key, ttl)
+    }
+
+    fn load(&self, address: &UserAddress) -> Result<Option<UserPubKey>, std::io::Error> {
+        if let Some((pub_key, cached_at)) = self.cache.lock().unwrap().get(address) {
+            if cached_at.elapsed() < CACHE_ENTRY_MAX_AGE {
+                return Ok(Some(pub_key.clone()));
+            }
+        }
+        let pub_key = self.store.load(address)?;
+        match &pub_key {
+            Some(pub_key) => {
+                self.cache
+                    .lock()
+                    .unwrap()
+                    .put(address.clone(), (pub_key.clone(), Instant::now()));
+            }
+            None => self.invalidate(address),
+        }
+        Ok(pub_key)
+    }
+
+    fn delete(&self, address: &UserAddress) -> Result<(), std::io::Error> {
+        self.invalidate(address);
+        self.store.delete(address)
+    }
+
+    fn compare_and_swap(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+        new: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        self.invalidate(address);
+        self.store.compare_and_swap(address, current, new)
+    }
+
+    fn compare_and_delete(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        self.invalidate(address);
+        self.store.compare_and_delete(address, current)
+    }
+
+    fn remove_expired(&self) -> Result<usize, std::io::Error> {
+        self.store.remove_expired()
+    }
+
+    fn list_paginated(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+    ) -> Result<ListResponse, std::io::Error> {
+        self.store.list_paginated(cursor, limit)
+    }
+
+    fn count(&self) -> Result<usize, std::io::Error> {
+        self.store.count()
+    }
+
+    fn list_all(&self) -> Result<Vec<(UserAddress, UserPubKey)>, std::io::Error> {
+        self.store.list_all()
+    }
+
+    fn stream_all(&self) -> Receiver<(UserAddress, UserPubKey)> {
+        self.store.stream_all()
+    }
+}
+
+/// Request to insert a public key. `sig` is the signature of
+/// [insert_pubkey_payload] with the key pair of the address.
 #[derive(Debug, Deserialize, Serialize)]
 pub struct InsertPubKey {
     pub pub_key_bytes: Vec<u8>,
     pub sig: Signature,
 }
 
+/// The bytes signed in an `insert_pubkey` request with the given `ttl_secs`
+/// query parameter: `pub_key_bytes`, followed by `ttl_secs` in little endian
+/// if it is given.
+///
+/// Signing the TTL prevents a signed request from being replayed with a TTL,
+/// which would make a permanent entry expire.
+pub fn insert_pubkey_payload(pub_key_bytes: &[u8], ttl_secs: Option<u64>) -> Vec<u8> {
+    let mut payload = pub_key_bytes.to_vec();
+    if let Some(ttl_secs) = ttl_secs {
+        payload.extend_from_slice(&ttl_secs.to_le_bytes());
+    }
+    payload
+}
+
+/// Request to delete the public key `pub_key_bytes`. `sig` is the signature
+/// of [delete_pubkey_payload] with the key pair of the address.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct DeletePubKey {
+    pub pub_key_bytes: Vec<u8>,
+    pub sig: Signature,
+}
+
+/// Domain separation tag of [delete_pubkey_payload].
+const DELETE_PUBKEY_DOMAIN: &[u8] = b"CAPE address book delete_pubkey";
+
+/// The bytes signed in a `delete_pubkey` request: the bincode serialization of
+/// a domain separation tag and the public key to delete.
+///
+/// The request only deletes the entry while it holds that key, so it can't be
+/// replayed against a key registered again for the address later.
+pub fn delete_pubkey_payload(pub_key: &UserPubKey) -> Vec<u8> {
+    bincode::serialize(&(DELETE_PUBKEY_DOMAIN, pub_key)).unwrap()
+}
+
+/// Middleware rejecting requests whose body exceeds `limit` bytes with
+/// StatusCode::PayloadTooLarge.
+///
+/// Requests with a `Content-Length` header are rejected without reading the
+/// body. Other requests, e.g. with a chunked body, are read up to the limit
+/// before being passed on.
+#[derive(Clone, Copy, Debug)]
+pub struct MaxBodySizeMiddleware {
+    limit: u64,
+}
+
+impl MaxBodySizeMiddleware {
+    pub fn new(limit: u64) -> Self {
+        Self { limit }
+    }
+}
+
+impl Default for MaxBodySizeMiddleware {
+    fn default() -> Self {
+        Self::new(DEFAULT_MAX_BODY_SIZE)
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> tide::Middleware<State> for MaxBodySizeMiddleware {
+    async fn handle(
+        &self,
+        mut req: tide::Request<State>,
+        next: tide::Next<'_, State>,
+    ) -> tide::Result {
+        if let Some(len) = req.len() {
+            if len as u64 > self.limit {
+                return Ok(tide::Response::new(StatusCode::PayloadTooLarge));
+            }
+            return Ok(next.run(req).await);
+        }
+
+        // Without a length we have to count the bytes while reading. Request
+        // bodies are small, so buffering them is fine.
+        let mut bytes = vec![];
+        req.take_body()
+            .take(self.limit + 1)
+            .read_to_end(&mut bytes)
+            .await?;
+        if bytes.len() as u64 > self.limit {
+            return Ok(tide::Response::new(StatusCode::PayloadTooLarge));
+        }
+        req.set_body(bytes);
+        Ok(next.run(req).await)
+    }
+}
+
+/// Request to replace the public key of an address.
+///
+/// `old_sig` signs [rotate_pubkey_payload] and `new_sig` signs
+/// `new_pub_key_bytes`, both with the key pair of the address. Both public
+/// keys must belong to the same address.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct RotatePubKey {
+    pub old_pub_key_bytes: Vec<u8>,
+    pub new_pub_key_bytes: Vec<u8>,
+    pub old_sig: Signature,
+    pub new_sig: Signature,
+}
+
+/// Domain separation tag of [rotate_pubkey_payload].
+const ROTATE_PUBKEY_DOMAIN: &[u8] = b"CAPE address book rotate_pubkey";
+
+/// The bytes signed by `old_sig` in a `rotate_pubkey` request: the bincode
+/// serialization of a domain separation tag, the key being replaced and the new
+/// key.
+///
+/// The tag keeps the signature of another request, such as `insert_pubkey`,
+/// from authorizing a rotation, and the old key keeps the request from being
+/// replayed once the key it replaces is gone.
+pub fn rotate_pubkey_payload(old_pub_key: &UserPubKey, new_pub_key: &UserPubKey) -> Vec<u8> {
+    bincode::serialize(&(ROTATE_PUBKEY_DOMAIN, old_pub_key, new_pub_key)).unwrap()
+}
+
+/// Query parameters of `insert_pubkey`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct InsertPubKeyQuery {
+    /// Number of seconds after which the entry expires. Never expires if
+    /// omitted.
+    pub ttl_secs: Option<u64>,
+}
+
+/// Query parameters of `list`.
+#[derive(Debug, Default, Deserialize, Serialize)]
+pub struct ListQuery {
+    pub cursor: Option<String>,
+    pub limit: Option<usize>,
+}
+
 #[derive(Clone)]
 struct ServerState<T: Store> {
     store: Arc<T>,
+    deep_healthcheck: Arc<DeepHealthcheck>,
+}
+
+/// Configuration and state of `healthcheck/deep`.
+struct DeepHealthcheck {
+    timeout: Duration,
+    interval: Duration,
+    /// The time and result of the last probe. Locked while a probe runs, so
+    /// that concurrent requests share its result.
+    last: async_std::sync::Mutex<Option<(Instant, Result<(), String>)>>,
+    /// Set while a probe runs on the blocking thread pool. A probe which
+    /// timed out keeps running, and no other probe is started until it
+    /// completes.
+    running: Arc<AtomicBool>,
 }
 
 pub fn address_book_temp_dir() -> TempDir {
@@ -149,6 +880,24 @@ pub fn address_book_port() -> String {
     std::env::var("CAPE_ADDRESS_BOOK_PORT").unwrap_or_else(|_| DEFAULT_PORT.to_string())
 }
 
+/// Read from `CAPE_ADDRESS_BOOK_DEEP_HEALTHCHECK_TIMEOUT_SECS`, defaulting to
+/// [DEFAULT_DEEP_HEALTHCHECK_TIMEOUT].
+pub fn deep_healthcheck_timeout() -> Duration {
+    std::env::var("CAPE_ADDRESS_BOOK_DEEP_HEALTHCHECK_TIMEOUT_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .map_or(DEFAULT_DEEP_HEALTHCHECK_TIMEOUT, Duration::from_secs)
+}
+
+/// Read from `CAPE_ADDRESS_BOOK_DEEP_HEALTHCHECK_INTERVAL_SECS`, defaulting to
+/// [DEFAULT_DEEP_HEALTHCHECK_INTERVAL].
+pub fn deep_healthcheck_interval() -> Duration {
+    std::env::var("CAPE_ADDRESS_BOOK_DEEP_HEALTHCHECK_INTERVAL_SECS")
+        .ok()
+        .and_then(|secs| secs.parse().ok())
+        .map_or(DEFAULT_DEEP_HEALTHCHECK_INTERVAL, Duration::from_secs)
+}
+
 pub fn cape_data_path() -> PathBuf {
     dirs::data_local_dir()
         .unwrap_or_else(|| env::current_dir().unwrap_or_else(|_| PathBuf::from("./")))
@@ -167,23 +916,53 @@ pub fn address_book_store_path() -> PathBuf {
 pub async fn init_web_server<T: Store + 'static>(
     store: T,
 ) -> std::io::Result<JoinHandle<std::io::Result<()>>> {
+    let store = Arc::new(store);
+    spawn(sweep_expired(store.clone()));
     let mut app = tide::with_state(ServerState {
-        store: Arc::new(store),
+        store,
+        deep_healthcheck: Arc::new(DeepHealthcheck {
+            timeout: deep_healthcheck_timeout(),
+            interval: deep_healthcheck_interval(),
+            last: Default::default(),
+            running: Default::default(),
+        }),
     });
+    app.with(RequestIdMiddleware);
+    app.with(MaxBodySizeMiddleware::default());
     app.with(
         CorsMiddleware::new()
-            .allow_methods("GET, POST".parse::<HeaderValue>().unwrap())
+            .allow_methods("GET, POST, DELETE".parse::<HeaderValue>().unwrap())
             .allow_headers("*".parse::<HeaderValue>().unwrap())
             .allow_origin(Origin::from("*"))
+            .expose_headers(REQUEST_ID_HEADER.parse::<HeaderValue>().unwrap())
             .allow_credentials(true),
     );
     app.at("/insert_pubkey").post(insert_pubkey);
     app.at("/request_pubkey").post(request_pubkey);
+    app.at("/request_pubkeys").post(request_pubkeys);
+    app.at("/list").get(list);
+    app.at("/delete_pubkey").delete(delete_pubkey);
+    app.at("/export").get(export);
+    app.at("/rotate_pubkey").post(rotate_pubkey);
+    app.at("/count").get(count);
     app.at("/healthcheck").get(healthcheck);
+    app.at("/healthcheck/deep").get(healthcheck_deep);
     let address = format!("0.0.0.0:{}", address_book_port());
     Ok(spawn(app.listen(address)))
 }
 
+/// Periodically delete expired entries from `store`.
+async fn sweep_expired<T: Store>(store: Arc<T>) {
+    loop {
+        sleep(EXPIRED_SWEEP_INTERVAL).await;
+        match store.remove_expired() {
+            Ok(0) => {}
+            Ok(n) => tracing::info!("Removed {} expired entries.", n),
+            Err(err) => tracing::error!("Failed to remove expired entries: {}", err),
+        }
+    }
+}
+
 pub async fn wait_for_server() {
     // Wait for the server to come up and start serving.
     let mut backoff = Duration::from_millis(100);
@@ -204,25 +983,127 @@ pub async fn wait_for_server() {
 /// Lookup a user public key from a signed public key address. Fail with
 /// tide::StatusCode::BadRequest if key deserialization or the signature check
 /// fail.
-fn verify_sig_and_get_pub_key(insert_request: InsertPubKey) -> Result<UserPubKey, tide::Error> {
+///
+/// The signature must cover the TTL of the entry, see [insert_pubkey_payload].
+fn verify_sig_and_get_pub_key(
+    insert_request: InsertPubKey,
+    ttl_secs: Option<u64>,
+) -> Result<UserPubKey, tide::Error> {
     let pub_key: UserPubKey = bincode::deserialize(&insert_request.pub_key_bytes)
         .map_err(|e| tide::Error::new(tide::StatusCode::BadRequest, e))?;
+    // bincode ignores trailing bytes, which would let the signed payload of a
+    // request with a TTL pass as the public key of a request without one.
+    if bincode::serialize(&pub_key).unwrap() != insert_request.pub_key_bytes {
+        return Err(tide::Error::from_str(
+            tide::StatusCode::BadRequest,
+            "trailing bytes after the public key",
+        ));
+    }
     pub_key
-        .verify_sig(&insert_request.pub_key_bytes, &insert_request.sig)
+        .verify_sig(
+            &insert_pubkey_payload(&insert_request.pub_key_bytes, ttl_secs),
+            &insert_request.sig,
+        )
         .map_err(|e| tide::Error::new(tide::StatusCode::BadRequest, e))?;
     Ok(pub_key)
 }
 
 /// Insert or update the public key at the given address.
+///
+/// If the `ttl_secs` query parameter is given, the entry expires after that
+/// many seconds. The signature must then cover the TTL, see
+/// [insert_pubkey_payload].
 async fn insert_pubkey<T: Store>(
     mut req: tide::Request<ServerState<T>>,
 ) -> Result<tide::Response, tide::Error> {
+    let query: InsertPubKeyQuery = req.query()?;
     let insert_request: InsertPubKey = net::server::request_body(&mut req).await?;
-    let pub_key = verify_sig_and_get_pub_key(insert_request)?;
-    req.state().store.save(&pub_key.address(), &pub_key)?;
+    let pub_key = verify_sig_and_get_pub_key(insert_request, query.ttl_secs)?;
+    match query.ttl_secs {
+        Some(ttl_secs) => req.state().store.save_with_ttl(
+            &pub_key.address(),
+            &pub_key,
+            Duration::from_secs(ttl_secs),
+        )?,
+        None => req.state().store.save(&pub_key.address(), &pub_key)?,
+    }
     Ok(tide::Response::new(StatusCode::Ok))
 }
 
+/// Replace the public key at an address, see [RotatePubKey].
+///
+/// Fail with StatusCode::BadRequest if a key can't be deserialized, the keys
+/// belong to different addresses, or the new signature is invalid; with
+/// StatusCode::Unauthorized if the old signature is invalid; with
+/// StatusCode::NotFound if no key is registered for the address; and with
+/// StatusCode::Conflict if the registered key is not the old key.
+///
+/// The registered key is checked and replaced with [Store::compare_and_swap],
+/// so concurrent writes can't slip in between, and lookups never fail during a
+/// rotation.
+async fn rotate_pubkey<T: Store>(
+    mut req: tide::Request<ServerState<T>>,
+) -> Result<tide::Response, tide::Error> {
+    let rotate_request: RotatePubKey = net::server::request_body(&mut req).await?;
+    let old_pub_key: UserPubKey = bincode::deserialize(&rotate_request.old_pub_key_bytes)
+        .map_err(|e| tide::Error::new(tide::StatusCode::BadRequest, e))?;
+    let new_pub_key = verify_sig_and_get_pub_key(
+        InsertPubKey {
+            pub_key_bytes: rotate_request.new_pub_key_bytes,
+            sig: rotate_request.new_sig,
+        },
+        None,
+    )?;
+    if old_pub_key.address() != new_pub_key.address() {
+        return Ok(tide::Response::new(StatusCode::BadRequest));
+    }
+    if old_pub_key
+        .verify_sig(
+            &rotate_pubkey_payload(&old_pub_key, &new_pub_key),
+            &rotate_request.old_sig,
+        )
+        .is_err()
+    {
+        return Ok(tide::Response::new(StatusCode::Unauthorized));
+    }
+
+    let store = &req.state().store;
+    match store.compare_and_swap(&old_pub_key.address(), &old_pub_key, &new_pub_key)? {
+        Some(pub_key) if pub_key == old_pub_key => Ok(tide::Response::new(StatusCode::Ok)),
+        Some(_) => Ok(tide::Response::new(StatusCode::Conflict)),
+        None => Ok(tide::Response::new(StatusCode::NotFound)),
+    }
+}
+
+/// Delete the given public key, see [DeletePubKey].
+///
+/// Fail with StatusCode::BadRequest if the key can't be deserialized; with
+/// StatusCode::Unauthorized if the signature does not verify with the key;
+/// with StatusCode::NotFound if no key is registered for the address; and with
+/// StatusCode::Conflict if the registered key is not the given key.
+async fn delete_pubkey<T: Store>(
+    mut req: tide::Request<ServerState<T>>,
+) -> Result<tide::Response, tide::Error> {
+    let delete_request: DeletePubKey = net::server::request_body(&mut req).await?;
+    let pub_key: UserPubKey = bincode::deserialize(&delete_request.pub_key_bytes)
+        .map_err(|e| tide::Error::new(tide::StatusCode::BadRequest, e))?;
+    if pub_key
+        .verify_sig(&delete_pubkey_payload(&pub_key), &delete_request.sig)
+        .is_err()
+    {
+        return Ok(tide::Response::new(StatusCode::Unauthorized));
+    }
+    match req
+        .state()
+        .store
+        .compare_and_delete(&pub_key.address(), &pub_key)?
+    {
+        Some(stored) if stored == pub_key => Ok(tide::Response::new(StatusCode::Ok)),
+        Some(_) => Ok(tide::Response::new(StatusCode::Conflict)),
+        None => Ok(tide::Response::new(StatusCode::NotFound)),
+    }
+}
+
 /// Fetch the public key for the given address. If not found, return
 /// StatusCode::NotFound.
 async fn request_pubkey<T: Store>(
@@ -245,6 +1126,88 @@ async fn request_pubkey<T: Store>(
     }
 }
 
+/// Fetch the public keys for a list of addresses. The result contains
+/// `None` for each address that is not found, in the order of the request.
+async fn request_pubkeys<T: Store>(
+    mut req: tide::Request<ServerState<T>>,
+) -> Result<tide::Response, tide::Error> {
+    let addresses: Vec<UserAddress> = net::server::request_body(&mut req).await?;
+    let mut pub_keys = Vec::with_capacity(addresses.len());
+    for address in &addresses {
+        match req.state().store.load(address) {
+            Ok(pub_key) => pub_keys.push(pub_key),
+            Err(_) => return Ok(tide::Response::new(StatusCode::InternalServerError)),
+        }
+    }
+    let bytes = bincode::serialize(&pub_keys).unwrap();
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .body(bytes)
+        .content_type(tide::http::mime::BYTE_STREAM)
+        .build())
+}
+
+/// List a page of address book entries as JSON, see [ListResponse].
+///
+/// The `limit` query parameter defaults to [DEFAULT_LIST_LIMIT] and is clamped
+/// to at most [MAX_LIST_LIMIT].
+async fn list<T: Store>(req: tide::Request<ServerState<T>>) -> Result<tide::Response, tide::Error> {
+    let query: ListQuery = req.query()?;
+    let limit = query
+        .limit
+        .unwrap_or(DEFAULT_LIST_LIMIT)
+        .clamp(1, MAX_LIST_LIMIT);
+    let page = req.state().store.list_paginated(query.cursor, limit)?;
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .content_type(tide::http::mime::JSON)
+        .body(json!(page))
+        .build())
+}
+
+/// Return the number of unexpired entries as JSON: `{"count": N}`. Fail with
+/// StatusCode::InternalServerError if the store can't be read.
+async fn count<T: Store>(
+    req: tide::Request<ServerState<T>>,
+) -> Result<tide::Response, tide::Error> {
+    match req.state().store.count() {
+        Ok(count) => Ok(tide::Response::builder(StatusCode::Ok)
+            .content_type(tide::http::mime::JSON)
+            .body(json!({ "count": count }))
+            .build()),
+        Err(err) => {
+            tracing::error!("Failed to count entries: {}", err);
+            Ok(tide::Response::new(StatusCode::InternalServerError))
+        }
+    }
+}
+
+/// A line of the `export` output.
+#[derive(Debug, Deserialize, Serialize)]
+pub struct ExportEntry {
+    /// Base64 encoding of the bincode serialized address.
+    pub address: String,
+    /// Base64 encoding of the bincode serialized public key.
+    pub pub_key: String,
+}
+
+/// Stream all entries as newline-delimited JSON, see [ExportEntry].
+async fn export<T: Store + 'static>(
+    req: tide::Request<ServerState<T>>,
+) -> Result<tide::Response, tide::Error> {
+    let lines = req.state().store.stream_all().map(|(address, pub_key)| {
+        let entry = ExportEntry {
+            address: base64::encode(bincode::serialize(&address).unwrap()),
+            pub_key: base64::encode(bincode::serialize(&pub_key).unwrap()),
+        };
+        let mut line = serde_json::to_vec(&entry).unwrap();
+        line.push(b'\n');
+        Ok::<_, std::io::Error>(line)
+    });
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .content_type("application/x-ndjson")
+        .body(tide::Body::from_reader(lines.into_async_read(), None))
+        .build())
+}
+
 /// Return a JSON expression with status 200 indicating the server
 /// is up and running. The JSON expression is simply,
 ///    {"status": "available"}
@@ -257,10 +1220,96 @@ async fn healthcheck<T: Store>(
     // Ensure we can write to storage.
     req.state()
         .store
-        .save(&UserAddress::default(), &UserPubKey::default())?;
+        .save(&healthcheck_address(), &UserPubKey::default())?;
 
     Ok(tide::Response::builder(StatusCode::Ok)
         .content_type(tide::http::mime::JSON)
         .body(json!({"status": "available"}))
         .build())
 }
+
+/// The address of the entry `healthcheck` overwrites on every call.
+///
+/// It is not a real entry, so it is left out of [Store::count].
+pub fn healthcheck_address() -> UserAddress {
+    UserAddress::default()
+}
+
+/// Save a probe entry for a fresh random address, read it back and delete it.
+///
+/// The delete is attempted even if reading the entry back fails.
+fn probe_store<T: Store>(store: &T) -> Result<(), String> {
+    let pub_key = UserKeyPair::generate(&mut ChaChaRng::from_entropy()).pub_key();
+    let address = pub_key.address();
+    store
+        .save(&address, &pub_key)
+        .map_err(|err| format!("failed to save probe entry: {}", err))?;
+    let loaded = store
+        .load(&address)
+        .map_err(|err| format!("failed to load probe entry: {}", err));
+    let deleted = store
+        .delete(&address)
+        .map_err(|err| format!("failed to delete probe entry: {}", err));
+    if loaded? != Some(pub_key) {
+        return Err(String::from("probe entry read back does not match"));
+    }
+    deleted
+}
+
+/// Check that the store can be written, read and deleted from.
+///
+/// Returns status 200 and {"status": "available"} if the probe succeeds
+/// within the configured timeout (see [deep_healthcheck_timeout]).
+/// Otherwise, the response has status 503 and payload
+/// {"status": "unavailable", "error": "..."}.
+///
+/// The store is probed at most once per configured interval (see
+/// [deep_healthcheck_interval]), other requests get the result of the last
+/// probe, so that this endpoint cannot be used to load the store.
+async fn healthcheck_deep<T: Store + 'static>(
+    req: tide::Request<ServerState<T>>,
+) -> Result<tide::Response, tide::Error> {
+    let healthcheck = &req.state().deep_healthcheck;
+    let mut last = healthcheck.last.lock().await;
+    let result = match &*last {
+        Some((probed_at, result)) if probed_at.elapsed() < healthcheck.interval => result.clone(),
+        _ => {
+            let result = if healthcheck.running.swap(true, Ordering::SeqCst) {
+                Err(String::from("the previous probe is still running"))
+            } else {
+                let store = req.state().store.clone();
+                let running = healthcheck.running.clone();
+                let probe = spawn_blocking(move || {
+                    let result = probe_store(&*store);
+                    running.store(false, Ordering::SeqCst);
+                    result
+                });
+                async_std::future::timeout(healthcheck.timeout, probe)
+                    .await
+                    .unwrap_or_else(|_| {
+                        Err(format!(
+                            "store did not respond in {:?}",
+                            healthcheck.timeout
+                        ))
+                    })
+            };
+            *last = Some((Instant::now(), result.clone()));
+            result
+        }
+    };
+    drop(last);
+    let (status, body) = match result {
+        Ok(()) => (StatusCode::Ok, json!({"status": "available"})),
+        Err(err) => {
+            tracing::error!("Deep healthcheck failed: {}", err);
+            (
+                StatusCode::ServiceUnavailable,
+                json!({"status": "unavailable", "error": err}),
+            )
+        }
+    };
+    Ok(tide::Response::builder(status)
+        .content_type(tide::http::mime::JSON)
+        .body(body)
+        .build())
+}
@@ -7,7 +7,10 @@
 
 use std::fs;
 
-use address_book::{address_book_store_path, init_web_server, signal::handle_signals, FileStore};
+use address_book::{
+    address_book_store_path, init_web_server, signal::handle_signals, CachedFileStore, FileStore,
+    DEFAULT_CACHE_CAPACITY,
+};
 use signal_hook::consts::{SIGINT, SIGTERM};
 use signal_hook_async_std::Signals;
 
@@ -26,7 +29,7 @@ async fn main() -> Result<(), std::io::Error> {
     let store_path = address_book_store_path();
     tracing::info!("Using store path {:?}", store_path);
     fs::create_dir_all(&store_path)?;
-    let store = FileStore::new(store_path);
+    let store = CachedFileStore::new(FileStore::new(store_path), DEFAULT_CACHE_CAPACITY);
 
     init_web_server(store)
         .await
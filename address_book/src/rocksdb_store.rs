@@ -0,0 +1,318 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use crate::{healthcheck_address, ListResponse, Store, StoredPubKey};
+use jf_cap::keys::{UserAddress, UserPubKey};
+use rocksdb::{Direction, IteratorMode, DB};
+use std::path::PathBuf;
+use std::sync::{Arc, Mutex};
+use std::{fs, time::Duration};
+use tempdir::TempDir;
+
+fn io_error(err: rocksdb::Error) -> std::io::Error {
+    std::io::Error::new(std::io::ErrorKind::Other, err)
+}
+
+/// Persistent store backed by a RocksDB database.
+///
+/// Keys are the bincode serialized addresses, values are stored in the same
+/// format as the files of a [crate::FileStore].
+#[derive(Clone)]
+pub struct RocksDbStore {
+    db: Arc<DB>,
+    /// Held while writing or deleting an entry, so that `remove_expired` does
+    /// not delete an entry which was replaced after it was found expired.
+    write_lock: Arc<Mutex<()>>,
+}
+
+impl RocksDbStore {
+    /// Open the database at `path`, creating it if it does not exist.
+    pub fn new(path: PathBuf) -> Result<Self, std::io::Error> {
+        Ok(Self {
+            db: Arc::new(DB::open_default(path).map_err(io_error)?),
+            write_lock: Default::default(),
+        })
+    }
+
+    fn key(address: &UserAddress) -> Vec<u8> {
+        bincode::serialize(address).unwrap()
+    }
+
+    fn put(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Option<Duration>,
+    ) -> Result<(), std::io::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        self.put_locked(address, pub_key, ttl)
+    }
+
+    /// Write an entry while already holding `write_lock`.
+    fn put_locked(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Option<Duration>,
+    ) -> Result<(), std::io::Error> {
+        self.db
+            .put(
+                Self::key(address),
+                bincode::serialize(&StoredPubKey::new(pub_key, ttl))
+                    .expect("Failed to serialize public key."),
+            )
+            .map_err(|err| {
+                tracing::error!("Failed to write pubkey {}", err);
+                io_error(err)
+            })
+    }
+}
+
+impl Store for RocksDbStore {
+    fn save(&self, address: &UserAddress, pub_key: &UserPubKey) -> Result<(), std::io::Error> {
+        self.put(address, pub_key, None)
+    }
+
+    fn save_with_ttl(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Duration,
+    ) -> Result<(), std::io::Error> {
+        self.put(address, pub_key, Some(ttl))
+    }
+
+    fn load(&self, address: &UserAddress) -> Result<Option<UserPubKey>, std::io::Error> {
+        match self.db.get(Self::key(address)).map_err(io_error)? {
+            Some(bytes) => {
+                let stored = StoredPubKey::from_bytes(&bytes)?;
+                if stored.is_expired() {
+                    tracing::info!("Address {} expired.", address);
+                    Ok(None)
+                } else {
+                    Ok(Some(stored.pub_key))
+                }
+            }
+            None => {
+                tracing::info!("Address {} not found.", address);
+                Ok(None)
+            }
+        }
+    }
+
+    fn delete(&self, address: &UserAddress) -> Result<(), std::io::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        self.db.delete(Self::key(address)).map_err(io_error)
+    }
+
+    /// The entry is read and replaced under the write lock.
+    fn compare_and_swap(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+        new: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        let stored = self.load(address)?;
+        if stored.as_ref() == Some(current) {
+            self.put_locked(address, new, None)?;
+        }
+        Ok(stored)
+    }
+
+    /// The entry is read and deleted under the write lock.
+    fn compare_and_delete(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        let _guard = self.write_lock.lock().unwrap();
+        let stored = self.load(address)?;
+        if stored.as_ref() == Some(current) {
+            self.db.delete(Self::key(address)).map_err(io_error)?;
+        }
+        Ok(stored)
+    }
+
+    /// Expired entries are read again under the write lock before they are
+    /// deleted, so an entry saved again since it expired is kept.
+    fn remove_expired(&self) -> Result<usize, std::io::Error> {
+        let mut expired = vec![];
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, value) = item.map_err(io_error)?;
+            if StoredPubKey::from_bytes(&value)?.is_expired() {
+                expired.push(key);
+            }
+        }
+        let mut removed = 0;
+        for key in &expired {
+            let _guard = self.write_lock.lock().unwrap();
+            if let Some(value) = self.db.get(key).map_err(io_error)? {
+                if StoredPubKey::from_bytes(&value)?.is_expired() {
+                    self.db.delete(key).map_err(io_error)?;
+                    removed += 1;
+                }
+            }
+        }
+        Ok(removed)
+    }
+
+    /// Entries are ordered by key, and the cursor is the hex encoded key of
+    /// the last entry of the previous page.
+    fn list_paginated(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+    ) -> Result<ListResponse, std::io::Error> {
+        let cursor = cursor
+            .map(hex::decode)
+            .transpose()
+            .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+        let iter = match &cursor {
+            Some(cursor) => self
+                .db
+                .iterator(IteratorMode::From(cursor, Direction::Forward)),
+            None => self.db.iterator(IteratorMode::Start),
+        };
+        let mut entries = vec![];
+        let mut last_key = None;
+        let mut more = false;
+        let mut consumed = 0;
+        for item in iter {
+            let (key, value) = item.map_err(io_error)?;
+            if Some(&*key) == cursor.as_deref() {
+                continue;
+            }
+            if consumed == limit {
+                more = true;
+                break;
+            }
+            consumed += 1;
+            // Expired entries count towards the limit, like in a FileStore.
+            let stored = StoredPubKey::from_bytes(&value)?;
+            if !stored.is_expired() {
+                entries.push((stored.pub_key.address(), stored.pub_key));
+            }
+            last_key = Some(key);
+        }
+        Ok(ListResponse {
+            entries,
+            next_cursor: if more {
+                last_key.map(hex::encode)
+            } else {
+                None
+            },
+        })
+    }
+
+    /// Counts all keys, skipping entries which have expired but have not
+    /// been swept yet, like `load` does.
+    fn count(&self) -> Result<usize, std::io::Error> {
+        let healthcheck_key = Self::key(&healthcheck_address());
+        let mut count = 0;
+        for item in self.db.iterator(IteratorMode::Start) {
+            let (key, value) = item.map_err(io_error)?;
+            if *key != *healthcheck_key && !StoredPubKey::from_bytes(&value)?.is_expired() {
+                count += 1;
+            }
+        }
+        Ok(count)
+    }
+
+    /// Skips expired entries.
+    fn list_all(&self) -> Result<Vec<(UserAddress, UserPubKey)>, std::io::Error> {
+        Ok(self.list_paginated(None, usize::MAX)?.entries)
+    }
+}
+
+/// Non-persistent RocksDB store. Suitable for testing only.
+#[derive(Clone)]
+pub struct TransientRocksDbStore {
+    store: RocksDbStore,
+    dir: Arc<PathBuf>,
+}
+
+impl Default for TransientRocksDbStore {
+    fn default() -> Self {
+        let dir = TempDir::new("cape-address-book-rocksdb")
+            .expect("Failed to create temporary directory.")
+            .into_path();
+        Self {
+            store: RocksDbStore::new(dir.clone()).expect("Failed to open database."),
+            dir: Arc::new(dir),
+        }
+    }
+}
+
+impl Drop for TransientRocksDbStore {
+    fn drop(&mut self) {
+        // Only the last clone removes the database.
+        if Arc::strong_count(&self.dir) == 1 {
+            fs::remove_dir_all(&*self.dir).expect("Failed to remove store path.");
+        }
+    }
+}
+
+impl Store for TransientRocksDbStore {
+    fn save(&self, address: &UserAddress, pub_key: &UserPubKey) -> Result<(), std::io::Error> {
+        self.store.save(address, pub_key)
+    }
+
+    fn save_with_ttl(
+        &self,
+        address: &UserAddress,
+        pub_key: &UserPubKey,
+        ttl: Duration,
+    ) -> Result<(), std::io::Error> {
+        self.store.save_with_ttl(address, pub_key, ttl)
+    }
+
+    fn load(&self, address: &UserAddress) -> Result<Option<UserPubKey>, std::io::Error> {
+        self.store.load(address)
+    }
+
+    fn delete(&self, address: &UserAddress) -> Result<(), std::io::Error> {
+        self.store.delete(address)
+    }
+
+    fn compare_and_swap(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+        new: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        self.store.compare_and_swap(address, current, new)
+    }
+
+    fn compare_and_delete(
+        &self,
+        address: &UserAddress,
+        current: &UserPubKey,
+    ) -> Result<Option<UserPubKey>, std::io::Error> {
+        self.store.compare_and_delete(address, current)
+    }
+
+    fn remove_expired(&self) -> Result<usize, std::io::Error> {
+        self.store.remove_expired()
+    }
+
+    fn list_paginated(
+        &self,
+        cursor: Option<String>,
+        limit: usize,
+    ) -> Result<ListResponse, std::io::Error> {
+        self.store.list_paginated(cursor, limit)
+    }
+
+    fn count(&self) -> Result<usize, std::io::Error> {
+        self.store.count()
+    }
+
+    fn list_all(&self) -> Result<Vec<(UserAddress, UserPubKey)>, std::io::Error> {
+        self.store.list_all()
+    }
+}
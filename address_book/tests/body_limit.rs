@@ -0,0 +1,68 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, init_web_server, wait_for_server, InsertPubKey, TransientFileStore,
+    DEFAULT_MAX_BODY_SIZE,
+};
+use jf_cap::keys::UserKeyPair;
+use rand_chacha::rand_core::SeedableRng;
+
+/// A valid JSON insert request, padded with whitespace to `len` bytes.
+fn padded_insert_request(len: usize) -> Vec<u8> {
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let user_key = UserKeyPair::generate(&mut rng);
+    let pub_key_bytes = bincode::serialize(&user_key.pub_key()).unwrap();
+    let sig = user_key.sign(&pub_key_bytes);
+    let mut body = serde_json::to_vec(&InsertPubKey { pub_key_bytes, sig }).unwrap();
+    assert!(body.len() <= len);
+    body.resize(len, b' ');
+    body
+}
+
+async fn insert(body: surf::Body) -> tide::StatusCode {
+    surf::post(format!(
+        "http://127.0.0.1:{}/insert_pubkey",
+        address_book_port()
+    ))
+    .content_type(surf::http::mime::JSON)
+    .body(body)
+    .await
+    .unwrap()
+    .status()
+}
+
+// This test has its own file because the address book port is currently not
+// configurable so we can't start more than one server concurrently.
+#[async_std::test]
+async fn test_max_body_size() {
+    init_web_server(TransientFileStore::default())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+
+    let limit = DEFAULT_MAX_BODY_SIZE as usize;
+
+    // With a Content-Length header.
+    assert_eq!(
+        insert(padded_insert_request(limit + 1).into()).await,
+        tide::StatusCode::PayloadTooLarge
+    );
+    assert_eq!(
+        insert(padded_insert_request(limit - 1).into()).await,
+        tide::StatusCode::Ok
+    );
+
+    // Chunked, without a Content-Length header.
+    let chunked =
+        |len| surf::Body::from_reader(async_std::io::Cursor::new(padded_insert_request(len)), None);
+    assert_eq!(
+        insert(chunked(limit + 1)).await,
+        tide::StatusCode::PayloadTooLarge
+    );
+    assert_eq!(insert(chunked(limit - 1)).await, tide::StatusCode::Ok);
+}
@@ -0,0 +1,78 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_temp_dir, CachedFileStore, FileStore, Store, DEFAULT_CACHE_CAPACITY,
+};
+use jf_cap::keys::UserKeyPair;
+use rand_chacha::rand_core::SeedableRng;
+use std::{fs, path::Path};
+
+fn remove_all_files(dir: &Path) {
+    for entry in fs::read_dir(dir).unwrap() {
+        fs::remove_file(entry.unwrap().path()).unwrap();
+    }
+}
+
+#[test]
+fn test_cache_hit() {
+    let temp_dir = address_book_temp_dir();
+    let store = CachedFileStore::new(
+        FileStore::new(temp_dir.path().to_path_buf()),
+        DEFAULT_CACHE_CAPACITY,
+    );
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let pub_key = UserKeyPair::generate(&mut rng).pub_key();
+    let other = UserKeyPair::generate(&mut rng).pub_key();
+    store.save(&pub_key.address(), &pub_key).unwrap();
+    store.save(&other.address(), &other).unwrap();
+    assert_eq!(
+        store.load(&pub_key.address()).unwrap(),
+        Some(pub_key.clone())
+    );
+
+    // Once cached, an entry is served without reading from disk, also by
+    // clones of the store.
+    remove_all_files(temp_dir.path());
+    assert_eq!(
+        store.load(&pub_key.address()).unwrap(),
+        Some(pub_key.clone())
+    );
+    assert_eq!(
+        store.clone().load(&pub_key.address()).unwrap(),
+        Some(pub_key)
+    );
+    // Entries which were never loaded are not cached.
+    assert_eq!(store.load(&other.address()).unwrap(), None);
+}
+
+#[test]
+fn test_cache_invalidation() {
+    let temp_dir = address_book_temp_dir();
+    let store = CachedFileStore::new(FileStore::new(temp_dir.path().to_path_buf()), 1);
+    let mut rng = rand_chacha::ChaChaRng::from_seed([1u8; 32]);
+    let pub_key = UserKeyPair::generate(&mut rng).pub_key();
+    store.save(&pub_key.address(), &pub_key).unwrap();
+    assert_eq!(
+        store.load(&pub_key.address()).unwrap(),
+        Some(pub_key.clone())
+    );
+
+    // Saving replaces the cached entry. Store a different key under the
+    // same address, which the address book server would never do, to
+    // observe it.
+    let replacement = UserKeyPair::generate(&mut rng).pub_key();
+    store.save(&pub_key.address(), &replacement).unwrap();
+    assert_eq!(store.load(&pub_key.address()).unwrap(), Some(replacement));
+
+    // With a capacity of 1, loading another entry evicts the first one.
+    let other = UserKeyPair::generate(&mut rng).pub_key();
+    store.save(&other.address(), &other).unwrap();
+    assert_eq!(store.load(&other.address()).unwrap(), Some(other));
+    remove_all_files(temp_dir.path());
+    assert_eq!(store.load(&pub_key.address()).unwrap(), None);
+}
@@ -0,0 +1,94 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, address_book_temp_dir, init_web_server, wait_for_server, FileStore, Store,
+    TransientFileStore,
+};
+use jf_cap::keys::UserKeyPair;
+use rand_chacha::rand_core::SeedableRng;
+use serde_json::Value;
+use std::collections::HashMap;
+use std::time::Duration;
+
+async fn count() -> u64 {
+    let mut response = surf::get(format!("http://127.0.0.1:{}/count", address_book_port()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), tide::StatusCode::Ok);
+    let body: Value = response.body_json().await.unwrap();
+    body["count"].as_u64().unwrap()
+}
+
+// This test has its own file because the address book port is currently not
+// configurable so we can't start more than one server concurrently.
+#[async_std::test]
+async fn test_count() {
+    let store = TransientFileStore::default();
+    init_web_server(store.clone())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+    assert_eq!(count().await, 0);
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let users = (0..10)
+        .map(|_| UserKeyPair::generate(&mut rng))
+        .collect::<Vec<_>>();
+    for user in &users {
+        store.save(&user.address(), &user.pub_key()).unwrap();
+    }
+    assert_eq!(count().await, 10);
+
+    store.delete(&users[0].address()).unwrap();
+    assert_eq!(count().await, 9);
+
+    // Expired entries are not counted, even before they are swept.
+    store
+        .save_with_ttl(&users[1].address(), &users[1].pub_key(), Duration::ZERO)
+        .unwrap();
+    assert_eq!(count().await, 8);
+}
+
+#[test]
+fn test_list_all() {
+    let store = TransientFileStore::default();
+    assert!(store.list_all().unwrap().is_empty());
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([1u8; 32]);
+    let users = (0..5)
+        .map(|_| UserKeyPair::generate(&mut rng))
+        .collect::<Vec<_>>();
+    for user in &users {
+        store.save(&user.address(), &user.pub_key()).unwrap();
+    }
+
+    // Entries are returned in any order.
+    let listed = store
+        .list_all()
+        .unwrap()
+        .into_iter()
+        .collect::<HashMap<_, _>>();
+    let expected = users
+        .iter()
+        .map(|user| (user.address(), user.pub_key()))
+        .collect::<HashMap<_, _>>();
+    assert_eq!(listed, expected);
+    assert_eq!(store.count().unwrap(), store.list_all().unwrap().len());
+
+    store.delete(&users[0].address()).unwrap();
+    assert_eq!(store.list_all().unwrap().len(), 4);
+    assert_eq!(store.count().unwrap(), store.list_all().unwrap().len());
+}
+
+#[test]
+fn test_list_all_fails() {
+    // Errors reading the store are returned instead of an empty list.
+    let dir = address_book_temp_dir();
+    let store = FileStore::new(dir.path().join("missing"));
+    assert!(store.list_all().is_err());
+}
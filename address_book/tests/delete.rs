@@ -0,0 +1,94 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, delete_pubkey_payload, init_web_server, wait_for_server, DeletePubKey,
+    Store, TransientFileStore,
+};
+use jf_cap::keys::{UserKeyPair, UserPubKey};
+use jf_cap::Signature;
+use rand_chacha::rand_core::SeedableRng;
+
+async fn delete(pub_key: &UserPubKey, signer: &UserKeyPair) -> tide::StatusCode {
+    delete_with_sig(pub_key, signer.sign(&delete_pubkey_payload(pub_key))).await
+}
+
+async fn delete_with_sig(pub_key: &UserPubKey, sig: Signature) -> tide::StatusCode {
+    surf::delete(format!(
+        "http://127.0.0.1:{}/delete_pubkey",
+        address_book_port()
+    ))
+    .content_type(surf::http::mime::JSON)
+    .body_json(&DeletePubKey {
+        pub_key_bytes: bincode::serialize(pub_key).unwrap(),
+        sig,
+    })
+    .unwrap()
+    .await
+    .unwrap()
+    .status()
+}
+
+// This test has its own file because the address book port is currently not
+// configurable so we can't start more than one server concurrently.
+#[async_std::test]
+async fn test_delete_pubkey() {
+    let store = TransientFileStore::default();
+    init_web_server(store.clone())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let user = UserKeyPair::generate(&mut rng);
+    let other = UserKeyPair::generate(&mut rng);
+    store.save(&user.address(), &user.pub_key()).unwrap();
+
+    // Signed with the wrong key.
+    assert_eq!(
+        delete(&user.pub_key(), &other).await,
+        tide::StatusCode::Unauthorized
+    );
+    // Signed without the domain separation tag.
+    assert_eq!(
+        delete_with_sig(
+            &user.pub_key(),
+            user.sign(&bincode::serialize(&user.address()).unwrap())
+        )
+        .await,
+        tide::StatusCode::Unauthorized
+    );
+    assert_eq!(store.load(&user.address()).unwrap(), Some(user.pub_key()));
+
+    // Signed with the key of the address.
+    let sig = user.sign(&delete_pubkey_payload(&user.pub_key()));
+    assert_eq!(
+        delete_with_sig(&user.pub_key(), sig.clone()).await,
+        tide::StatusCode::Ok
+    );
+    assert_eq!(store.load(&user.address()).unwrap(), None);
+
+    // Nothing left to delete.
+    assert_eq!(
+        delete(&user.pub_key(), &user).await,
+        tide::StatusCode::NotFound
+    );
+    assert_eq!(
+        delete(&other.pub_key(), &other).await,
+        tide::StatusCode::NotFound
+    );
+
+    // The request can't be replayed once another key is registered for the
+    // address.
+    let new_pub_key = UserPubKey::new(user.address(), other.pub_key().enc_key());
+    store.save(&user.address(), &new_pub_key).unwrap();
+    assert_eq!(
+        delete_with_sig(&user.pub_key(), sig).await,
+        tide::StatusCode::Conflict
+    );
+    assert_eq!(store.load(&user.address()).unwrap(), Some(new_pub_key));
+}
@@ -0,0 +1,55 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, init_web_server, wait_for_server, ExportEntry, Store, TransientFileStore,
+};
+use jf_cap::keys::{UserAddress, UserKeyPair, UserPubKey};
+use rand_chacha::rand_core::SeedableRng;
+use std::collections::HashMap;
+
+const ENTRY_COUNT: usize = 50;
+
+// This test has its own file because the address book port is currently not
+// configurable so we can't start more than one server concurrently.
+#[async_std::test]
+async fn test_export() {
+    let store = TransientFileStore::default();
+    init_web_server(store.clone())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let mut expected = HashMap::new();
+    for _ in 0..ENTRY_COUNT {
+        let pub_key = UserKeyPair::generate(&mut rng).pub_key();
+        store.save(&pub_key.address(), &pub_key).unwrap();
+        expected.insert(pub_key.address(), pub_key);
+    }
+
+    let mut response = surf::get(format!("http://127.0.0.1:{}/export", address_book_port()))
+        .await
+        .unwrap();
+    assert_eq!(response.status(), tide::StatusCode::Ok);
+    assert_eq!(
+        response.content_type().unwrap().essence(),
+        "application/x-ndjson"
+    );
+    let body = response.body_string().await.unwrap();
+
+    let mut exported = HashMap::new();
+    for line in body.lines() {
+        let entry: ExportEntry = serde_json::from_str(line).unwrap();
+        let address: UserAddress =
+            bincode::deserialize(&base64::decode(entry.address).unwrap()).unwrap();
+        let pub_key: UserPubKey =
+            bincode::deserialize(&base64::decode(entry.pub_key).unwrap()).unwrap();
+        assert!(exported.insert(address, pub_key).is_none());
+    }
+    assert_eq!(exported, expected);
+}
@@ -0,0 +1,58 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, init_web_server, wait_for_server, Store, TransientFileStore,
+};
+use serde_json::Value;
+use std::fs;
+
+async fn deep_healthcheck() -> (tide::StatusCode, Value) {
+    let mut response = surf::get(format!(
+        "http://127.0.0.1:{}/healthcheck/deep",
+        address_book_port()
+    ))
+    .await
+    .unwrap();
+    (response.status(), response.body_json().await.unwrap())
+}
+
+// This test has its own file because the address book port is currently not
+// configurable so we can't start more than one server concurrently.
+#[async_std::test]
+async fn test_healthcheck_deep() {
+    // Probe the store on every request.
+    std::env::set_var("CAPE_ADDRESS_BOOK_DEEP_HEALTHCHECK_INTERVAL_SECS", "0");
+    let store = TransientFileStore::default();
+    init_web_server(store.clone())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+
+    let (status, body) = deep_healthcheck().await;
+    assert_eq!(status, tide::StatusCode::Ok);
+    assert_eq!(body["status"], "available");
+    // The probe entry is deleted.
+    assert_eq!(store.count().unwrap(), 0);
+
+    let original = fs::metadata(store.dir()).unwrap().permissions();
+    let mut read_only = original.clone();
+    read_only.set_readonly(true);
+    fs::set_permissions(store.dir(), read_only).unwrap();
+    // Permissions are not enforced for privileged users.
+    let writable = fs::write(store.dir().join("probe"), b"").is_ok();
+
+    if !writable {
+        let (status, body) = deep_healthcheck().await;
+        assert_eq!(status, tide::StatusCode::ServiceUnavailable);
+        assert_eq!(body["status"], "unavailable");
+        assert!(body["error"].is_string());
+    }
+
+    // Let the store clean up its directory.
+    fs::set_permissions(store.dir(), original).unwrap();
+}
@@ -22,4 +22,11 @@ async fn test_healthcheck_ok() {
 
     let response = surf::get(&healthcheck_url).await.unwrap();
     assert_eq!(response.status(), tide::StatusCode::Ok);
+
+    let count_url = format!("http://127.0.0.1:{}/count", address_book_port());
+    let mut response = surf::get(&count_url).await.unwrap();
+    assert_eq!(response.status(), tide::StatusCode::Ok);
+    // The entry written by the healthcheck is not counted.
+    let body: serde_json::Value = response.body_json().await.unwrap();
+    assert_eq!(body["count"], 0);
 }
@@ -0,0 +1,85 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, address_book_temp_dir, init_web_server, wait_for_server, FileStore,
+    ListResponse, Store,
+};
+use jf_cap::keys::UserKeyPair;
+use rand_chacha::rand_core::SeedableRng;
+use std::collections::HashMap;
+
+const ENTRY_COUNT: usize = 200;
+
+// This test has its own file because the address book port is currently not
+// configurable so we can't start more than one server concurrently.
+#[async_std::test]
+async fn test_list() {
+    let temp_dir = address_book_temp_dir();
+    let store = FileStore::new(temp_dir.path().to_path_buf());
+    init_web_server(store.clone())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let mut expected = HashMap::new();
+    for _ in 0..ENTRY_COUNT {
+        let pub_key = UserKeyPair::generate(&mut rng).pub_key();
+        store.save(&pub_key.address(), &pub_key).unwrap();
+        expected.insert(pub_key.address(), pub_key);
+    }
+
+    let list = |query: String| async move {
+        surf::get(format!(
+            "http://127.0.0.1:{}/list{}",
+            address_book_port(),
+            query
+        ))
+        .recv_json::<ListResponse>()
+        .await
+        .unwrap()
+    };
+
+    // Page through all entries with the default limit.
+    let mut listed = HashMap::new();
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let query = match &cursor {
+            Some(cursor) => format!("?cursor={}", cursor),
+            None => String::new(),
+        };
+        let page = list(query).await;
+        pages += 1;
+        assert!(page.entries.len() <= 50);
+        for (address, pub_key) in page.entries {
+            assert!(listed.insert(address, pub_key).is_none());
+        }
+        match page.next_cursor {
+            Some(next) => cursor = Some(next),
+            None => break,
+        }
+    }
+    assert_eq!(pages, ENTRY_COUNT / 50);
+    assert_eq!(listed, expected);
+
+    // A larger limit returns everything at once.
+    let page = list("?limit=1000".to_string()).await;
+    assert_eq!(page.entries.len(), ENTRY_COUNT);
+    assert_eq!(page.next_cursor, None);
+
+    // Smaller pages.
+    let page = list("?limit=7".to_string()).await;
+    assert_eq!(page.entries.len(), 7);
+    let next = list(format!("?limit=7&cursor={}", page.next_cursor.unwrap())).await;
+    assert_eq!(next.entries.len(), 7);
+    assert!(next
+        .entries
+        .iter()
+        .all(|entry| !page.entries.contains(entry)));
+}
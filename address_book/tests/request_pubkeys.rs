@@ -0,0 +1,95 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, init_web_server, wait_for_server, InsertPubKey, TransientFileStore,
+};
+use jf_cap::keys::{UserAddress, UserKeyPair, UserPubKey};
+use rand_chacha::rand_core::SeedableRng;
+
+async fn insert(key: &UserKeyPair) {
+    let pub_key_bytes = bincode::serialize(&key.pub_key()).unwrap();
+    let sig = key.sign(&pub_key_bytes);
+    let response = surf::post(format!(
+        "http://127.0.0.1:{}/insert_pubkey",
+        address_book_port()
+    ))
+    .content_type(surf::http::mime::JSON)
+    .body_json(&InsertPubKey { pub_key_bytes, sig })
+    .unwrap()
+    .await
+    .unwrap();
+    assert_eq!(response.status(), tide::StatusCode::Ok);
+}
+
+async fn request_pubkeys(addresses: &[UserAddress]) -> Vec<Option<UserPubKey>> {
+    let mut response = surf::post(format!(
+        "http://127.0.0.1:{}/request_pubkeys",
+        address_book_port()
+    ))
+    .content_type(surf::http::mime::BYTE_STREAM)
+    .body_bytes(&bincode::serialize(addresses).unwrap())
+    .await
+    .unwrap();
+    assert_eq!(response.status(), tide::StatusCode::Ok);
+    bincode::deserialize(&response.body_bytes().await.unwrap()).unwrap()
+}
+
+// This test has its own file because the address book port is currently not
+// configurable so we can't start more than one server concurrently.
+#[async_std::test]
+async fn test_request_pubkeys() {
+    init_web_server(TransientFileStore::default())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let inserted = (0..5)
+        .map(|_| UserKeyPair::generate(&mut rng))
+        .collect::<Vec<_>>();
+    let missing = (0..3)
+        .map(|_| UserKeyPair::generate(&mut rng))
+        .collect::<Vec<_>>();
+    for key in &inserted {
+        insert(key).await;
+    }
+
+    // All found.
+    let addresses = inserted.iter().map(|k| k.address()).collect::<Vec<_>>();
+    assert_eq!(
+        request_pubkeys(&addresses).await,
+        inserted
+            .iter()
+            .map(|k| Some(k.pub_key()))
+            .collect::<Vec<_>>()
+    );
+
+    // Some found, in the order of the request.
+    let addresses = vec![
+        missing[0].address(),
+        inserted[3].address(),
+        missing[1].address(),
+        inserted[0].address(),
+    ];
+    assert_eq!(
+        request_pubkeys(&addresses).await,
+        vec![
+            None,
+            Some(inserted[3].pub_key()),
+            None,
+            Some(inserted[0].pub_key())
+        ]
+    );
+
+    // None found.
+    let addresses = missing.iter().map(|k| k.address()).collect::<Vec<_>>();
+    assert_eq!(request_pubkeys(&addresses).await, vec![None; 3]);
+
+    // An empty request gets an empty response.
+    assert_eq!(request_pubkeys(&[]).await, vec![]);
+}
@@ -0,0 +1,108 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(feature = "rocksdb-store")]
+
+use address_book::{address_book_temp_dir, RocksDbStore, Store, TransientRocksDbStore};
+use async_std::task::sleep;
+use jf_cap::keys::UserKeyPair;
+use rand_chacha::rand_core::SeedableRng;
+use std::collections::HashMap;
+use std::time::Duration;
+
+const ROUND_TRIP_COUNT: usize = 100;
+
+fn round_trip<T: Store>(store: &T) {
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let pub_keys = (0..ROUND_TRIP_COUNT)
+        .map(|_| UserKeyPair::generate(&mut rng).pub_key())
+        .collect::<Vec<_>>();
+    for pub_key in &pub_keys {
+        store.save(&pub_key.address(), pub_key).unwrap();
+    }
+    for pub_key in &pub_keys {
+        assert_eq!(
+            store.load(&pub_key.address()).unwrap(),
+            Some(pub_key.clone())
+        );
+    }
+    let missing = UserKeyPair::generate(&mut rng).pub_key();
+    assert_eq!(store.load(&missing.address()).unwrap(), None);
+}
+
+#[test]
+fn test_rocksdb_store() {
+    let temp_dir = address_book_temp_dir();
+    let store = RocksDbStore::new(temp_dir.path().to_path_buf()).unwrap();
+    round_trip(&store);
+
+    // Entries are persisted.
+    drop(store);
+    let store = RocksDbStore::new(temp_dir.path().to_path_buf()).unwrap();
+    assert_eq!(
+        store.list_paginated(None, 1000).unwrap().entries.len(),
+        ROUND_TRIP_COUNT
+    );
+
+    round_trip(&TransientRocksDbStore::default());
+}
+
+#[test]
+fn test_rocksdb_store_delete() {
+    let store = TransientRocksDbStore::default();
+    let mut rng = rand_chacha::ChaChaRng::from_seed([1u8; 32]);
+    let pub_key = UserKeyPair::generate(&mut rng).pub_key();
+    store.save(&pub_key.address(), &pub_key).unwrap();
+    store.delete(&pub_key.address()).unwrap();
+    assert_eq!(store.load(&pub_key.address()).unwrap(), None);
+}
+
+#[test]
+fn test_rocksdb_store_list_paginated() {
+    let store = TransientRocksDbStore::default();
+    let mut rng = rand_chacha::ChaChaRng::from_seed([2u8; 32]);
+    let mut expected = HashMap::new();
+    for _ in 0..25 {
+        let pub_key = UserKeyPair::generate(&mut rng).pub_key();
+        store.save(&pub_key.address(), &pub_key).unwrap();
+        expected.insert(pub_key.address(), pub_key);
+    }
+
+    let mut listed = HashMap::new();
+    let mut cursor = None;
+    let mut pages = 0;
+    loop {
+        let page = store.list_paginated(cursor, 10).unwrap();
+        pages += 1;
+        for (address, pub_key) in page.entries {
+            assert!(listed.insert(address, pub_key).is_none());
+        }
+        cursor = page.next_cursor;
+        if cursor.is_none() {
+            break;
+        }
+    }
+    assert_eq!(pages, 3);
+    assert_eq!(listed, expected);
+}
+
+#[async_std::test]
+async fn test_rocksdb_store_ttl() {
+    let store = TransientRocksDbStore::default();
+    let mut rng = rand_chacha::ChaChaRng::from_seed([3u8; 32]);
+    let pub_key = UserKeyPair::generate(&mut rng).pub_key();
+    store
+        .save_with_ttl(&pub_key.address(), &pub_key, Duration::from_secs(1))
+        .unwrap();
+    assert_eq!(
+        store.load(&pub_key.address()).unwrap(),
+        Some(pub_key.clone())
+    );
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(store.load(&pub_key.address()).unwrap(), None);
+    assert_eq!(store.remove_expired().unwrap(), 1);
+}
@@ -0,0 +1,168 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, init_web_server, insert_pubkey_payload, rotate_pubkey_payload,
+    wait_for_server, RotatePubKey, Store, TransientFileStore,
+};
+use jf_cap::keys::{UserKeyPair, UserPubKey};
+use jf_cap::Signature;
+use rand_chacha::rand_core::SeedableRng;
+use std::sync::Arc;
+
+async fn rotate(
+    old_pub_key: &UserPubKey,
+    new_pub_key: &UserPubKey,
+    old_signer: &UserKeyPair,
+    new_signer: &UserKeyPair,
+) -> tide::StatusCode {
+    let old_sig = old_signer.sign(&rotate_pubkey_payload(old_pub_key, new_pub_key));
+    rotate_with_sig(old_pub_key, new_pub_key, old_sig, new_signer).await
+}
+
+async fn rotate_with_sig(
+    old_pub_key: &UserPubKey,
+    new_pub_key: &UserPubKey,
+    old_sig: Signature,
+    new_signer: &UserKeyPair,
+) -> tide::StatusCode {
+    let old_pub_key_bytes = bincode::serialize(old_pub_key).unwrap();
+    let new_pub_key_bytes = bincode::serialize(new_pub_key).unwrap();
+    let new_sig = new_signer.sign(&new_pub_key_bytes);
+    surf::post(format!(
+        "http://127.0.0.1:{}/rotate_pubkey",
+        address_book_port()
+    ))
+    .content_type(surf::http::mime::JSON)
+    .body_json(&RotatePubKey {
+        old_pub_key_bytes,
+        new_pub_key_bytes,
+        old_sig,
+        new_sig,
+    })
+    .unwrap()
+    .await
+    .unwrap()
+    .status()
+}
+
+// This test has its own file because the address book port is currently not
+// configurable so we can't start more than one server concurrently.
+#[async_std::test]
+async fn test_rotate_pubkey() {
+    let store = TransientFileStore::default();
+    init_web_server(store.clone())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let user = UserKeyPair::generate(&mut rng);
+    let other = UserKeyPair::generate(&mut rng);
+    let old_pub_key = user.pub_key();
+    let new_pub_key = UserPubKey::new(user.address(), other.pub_key().enc_key());
+
+    // Nothing to rotate yet.
+    assert_eq!(
+        rotate(&old_pub_key, &new_pub_key, &user, &user).await,
+        tide::StatusCode::NotFound
+    );
+
+    store.save(&user.address(), &old_pub_key).unwrap();
+
+    // The new key must belong to the same address.
+    assert_eq!(
+        rotate(&old_pub_key, &other.pub_key(), &user, &other).await,
+        tide::StatusCode::BadRequest
+    );
+    // Both keys must be signed by the key pair of the address.
+    assert_eq!(
+        rotate(&old_pub_key, &new_pub_key, &other, &user).await,
+        tide::StatusCode::Unauthorized
+    );
+    assert_eq!(
+        rotate(&old_pub_key, &new_pub_key, &user, &other).await,
+        tide::StatusCode::BadRequest
+    );
+    // The signature of an insert request does not authorize a rotation.
+    let insert_sig = user.sign(&insert_pubkey_payload(
+        &bincode::serialize(&old_pub_key).unwrap(),
+        None,
+    ));
+    assert_eq!(
+        rotate_with_sig(&old_pub_key, &new_pub_key, insert_sig, &user).await,
+        tide::StatusCode::Unauthorized
+    );
+    // Neither does a signature for another new key.
+    let other_sig = user.sign(&rotate_pubkey_payload(&old_pub_key, &old_pub_key));
+    assert_eq!(
+        rotate_with_sig(&old_pub_key, &new_pub_key, other_sig, &user).await,
+        tide::StatusCode::Unauthorized
+    );
+    assert_eq!(
+        store.load(&user.address()).unwrap(),
+        Some(old_pub_key.clone())
+    );
+
+    assert_eq!(
+        rotate(&old_pub_key, &new_pub_key, &user, &user).await,
+        tide::StatusCode::Ok
+    );
+    assert_eq!(
+        store.load(&user.address()).unwrap(),
+        Some(new_pub_key.clone())
+    );
+
+    // The old key is no longer registered.
+    assert_eq!(
+        rotate(&old_pub_key, &new_pub_key, &user, &user).await,
+        tide::StatusCode::Conflict
+    );
+}
+
+#[test]
+fn test_compare_and_swap_concurrent() {
+    // Clones of a TransientFileStore remove the directory when dropped.
+    let store = Arc::new(TransientFileStore::default());
+    let mut rng = rand_chacha::ChaChaRng::from_seed([1u8; 32]);
+    let user = UserKeyPair::generate(&mut rng);
+    let old_pub_key = user.pub_key();
+    store.save(&user.address(), &old_pub_key).unwrap();
+
+    // Of several concurrent rotations from the same key, exactly one wins.
+    let new_pub_keys = (0..8)
+        .map(|_| {
+            UserPubKey::new(
+                user.address(),
+                UserKeyPair::generate(&mut rng).pub_key().enc_key(),
+            )
+        })
+        .collect::<Vec<_>>();
+    let swapped = new_pub_keys
+        .iter()
+        .cloned()
+        .map(|new_pub_key| {
+            let store = store.clone();
+            let address = user.address();
+            let old_pub_key = old_pub_key.clone();
+            std::thread::spawn(move || {
+                let stored = store
+                    .compare_and_swap(&address, &old_pub_key, &new_pub_key)
+                    .unwrap();
+                (stored == Some(old_pub_key)).then(|| new_pub_key)
+            })
+        })
+        .collect::<Vec<_>>()
+        .into_iter()
+        .filter_map(|handle| handle.join().unwrap())
+        .collect::<Vec<_>>();
+    assert_eq!(swapped.len(), 1);
+    assert_eq!(
+        store.load(&user.address()).unwrap(),
+        Some(swapped[0].clone())
+    );
+}
@@ -0,0 +1,154 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use address_book::{
+    address_book_port, init_web_server, insert_pubkey_payload, wait_for_server, InsertPubKey,
+    Store, TransientFileStore,
+};
+use async_std::task::sleep;
+use jf_cap::{
+    keys::{UserKeyPair, UserPubKey},
+    Signature,
+};
+use rand_chacha::rand_core::SeedableRng;
+use std::fs;
+use std::io::ErrorKind;
+use std::time::Duration;
+
+#[async_std::test]
+async fn test_store_ttl() {
+    let mut rng = rand_chacha::ChaChaRng::from_seed([0u8; 32]);
+    let store = TransientFileStore::default();
+    let expiring = UserKeyPair::generate(&mut rng).pub_key();
+    let permanent = UserKeyPair::generate(&mut rng).pub_key();
+
+    store
+        .save_with_ttl(&expiring.address(), &expiring, Duration::from_secs(1))
+        .unwrap();
+    store.save(&permanent.address(), &permanent).unwrap();
+    assert_eq!(
+        store.load(&expiring.address()).unwrap(),
+        Some(expiring.clone())
+    );
+
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(store.load(&expiring.address()).unwrap(), None);
+    assert_eq!(
+        store.load(&permanent.address()).unwrap(),
+        Some(permanent.clone())
+    );
+
+    // Only the expired entry is removed.
+    assert_eq!(store.remove_expired().unwrap(), 1);
+    assert_eq!(store.remove_expired().unwrap(), 0);
+    assert_eq!(store.load(&permanent.address()).unwrap(), Some(permanent));
+
+    // An expired entry which is saved again is not removed.
+    store
+        .save_with_ttl(&expiring.address(), &expiring, Duration::from_millis(1))
+        .unwrap();
+    sleep(Duration::from_millis(10)).await;
+    store.save(&expiring.address(), &expiring).unwrap();
+    assert_eq!(store.remove_expired().unwrap(), 0);
+    assert_eq!(store.load(&expiring.address()).unwrap(), Some(expiring));
+}
+
+#[test]
+fn test_store_invalid_data() {
+    let mut rng = rand_chacha::ChaChaRng::from_seed([2u8; 32]);
+    let store = TransientFileStore::default();
+    let legacy = UserKeyPair::generate(&mut rng).pub_key();
+    let corrupt = UserKeyPair::generate(&mut rng).pub_key();
+    let path = |pub_key: &UserPubKey| {
+        store.dir().join(format!(
+            "{}.bin",
+            hex::encode(bincode::serialize(&pub_key.address()).unwrap())
+        ))
+    };
+
+    // Entries written before versioning are plain serialized public keys.
+    fs::write(path(&legacy), bincode::serialize(&legacy).unwrap()).unwrap();
+    assert_eq!(store.load(&legacy.address()).unwrap(), Some(legacy));
+
+    // Anything else is reported as invalid data instead of panicking.
+    fs::write(path(&corrupt), [0xffu8; 4]).unwrap();
+    assert_eq!(
+        store.load(&corrupt.address()).unwrap_err().kind(),
+        ErrorKind::InvalidData
+    );
+    assert_eq!(
+        store.remove_expired().unwrap_err().kind(),
+        ErrorKind::InvalidData
+    );
+}
+
+// This test starts a server, so it must be the only one in this file that
+// does, because the address book port is currently not configurable.
+#[async_std::test]
+async fn test_insert_pubkey_ttl() {
+    init_web_server(TransientFileStore::default())
+        .await
+        .expect("Failed to run server.");
+    wait_for_server().await;
+
+    let mut rng = rand_chacha::ChaChaRng::from_seed([1u8; 32]);
+    let user_key = UserKeyPair::generate(&mut rng);
+    let pub_key = user_key.pub_key();
+    let pub_key_bytes = bincode::serialize(&pub_key).unwrap();
+    let insert = |query: &str, pub_key_bytes: Vec<u8>, sig: Signature| {
+        surf::post(format!(
+            "http://127.0.0.1:{}/insert_pubkey{}",
+            address_book_port(),
+            query
+        ))
+        .content_type(surf::http::mime::JSON)
+        .body_json(&InsertPubKey { pub_key_bytes, sig })
+        .unwrap()
+    };
+
+    // A signature without the TTL cannot be used to insert an expiring entry.
+    let permanent_sig = user_key.sign(&pub_key_bytes);
+    let response = insert("?ttl_secs=1", pub_key_bytes.clone(), permanent_sig)
+        .await
+        .unwrap();
+    assert_eq!(response.status(), tide::StatusCode::BadRequest);
+
+    // The signed payload of an expiring entry cannot pass as a public key.
+    let payload = insert_pubkey_payload(&pub_key_bytes, Some(1));
+    let ttl_sig = user_key.sign(&payload);
+    let response = insert("", payload, ttl_sig.clone()).await.unwrap();
+    assert_eq!(response.status(), tide::StatusCode::BadRequest);
+
+    let response = insert("?ttl_secs=2", pub_key_bytes.clone(), ttl_sig.clone())
+        .await
+        .unwrap();
+    assert_eq!(response.status(), tide::StatusCode::BadRequest);
+
+    let response = insert("?ttl_secs=1", pub_key_bytes, ttl_sig).await.unwrap();
+    assert_eq!(response.status(), tide::StatusCode::Ok);
+
+    let address_bytes = bincode::serialize(&pub_key.address()).unwrap();
+    let request_pubkey = || {
+        surf::post(format!(
+            "http://127.0.0.1:{}/request_pubkey",
+            address_book_port()
+        ))
+        .content_type(surf::http::mime::BYTE_STREAM)
+        .body_bytes(&address_bytes)
+    };
+    let mut response = request_pubkey().await.unwrap();
+    assert_eq!(response.status(), tide::StatusCode::Ok);
+    let gotten_pub_key: UserPubKey =
+        bincode::deserialize(&response.body_bytes().await.unwrap()).unwrap();
+    assert_eq!(gotten_pub_key, pub_key);
+
+    sleep(Duration::from_millis(1500)).await;
+    assert_eq!(
+        request_pubkey().await.unwrap().status(),
+        tide::StatusCode::NotFound
+    );
+}
@@ -0,0 +1,270 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Command line client for the relayer, to submit transactions by hand.
+//!
+//! The transaction is read as a JSON `SubmitBody` from stdin, or from the file given with `--file`.
+//!
+//! `block inspect` decodes an ABI encoded `CapeBlock`, such as the block argument of a
+//! `submitCapeBlockWithMemos` call, for debugging.
+//!
+//! `eqs snapshot` prints the persisted state of a stopped EQS as a genesis snapshot, to start
+//! another EQS with `--genesis-block`.
+
+use cap_rust_sandbox::{cape::CapeBlock, types as sol};
+use eqs::{
+    genesis::GenesisSnapshot,
+    store::{open_store, StoreBackend},
+};
+use ethers::{abi::AbiDecode, prelude::H256};
+use jf_cap::{keys::UserAddress, structs::Nullifier};
+use net::{
+    client::{parse_error_body, response_body},
+    Error as _,
+};
+use relayer::{Error, SubmitBody, ValidationResult};
+use serde::Serialize;
+use std::io::Read;
+use std::path::{Path, PathBuf};
+use std::process::exit;
+use structopt::StructOpt;
+use surf::Url;
+
+#[derive(Debug, StructOpt)]
+#[structopt(name = "cape-cli", about = "Command line client for the CAPE relayer")]
+struct Options {
+    /// URL of the relayer.
+    #[structopt(
+        long,
+        env = "CAPE_RELAYER_URL",
+        default_value = "http://localhost:50077"
+    )]
+    relayer_url: Url,
+
+    #[structopt(subcommand)]
+    command: Command,
+}
+
+#[derive(Debug, StructOpt)]
+enum Command {
+    /// Submit a transaction and print the hash of the Ethereum transaction carrying it.
+    Submit {
+        /// Read the transaction from this file instead of stdin.
+        #[structopt(long)]
+        file: Option<PathBuf>,
+    },
+    /// Check whether the CAPE contract would accept a transaction, without submitting it.
+    Validate {
+        /// Read the transaction from this file instead of stdin.
+        #[structopt(long)]
+        file: Option<PathBuf>,
+    },
+    /// Work with raw CAPE blocks.
+    Block(BlockCommand),
+    /// Work with the state of an EQS.
+    Eqs(EqsCommand),
+}
+
+#[derive(Debug, StructOpt)]
+enum BlockCommand {
+    /// Decode an ABI encoded block and print a summary of its contents.
+    Inspect {
+        /// The hex encoded block. Read from stdin if not given.
+        #[structopt(long)]
+        hex: Option<String>,
+        /// Print the summary as JSON.
+        #[structopt(long)]
+        json: bool,
+    },
+}
+
+#[derive(Debug, StructOpt)]
+enum EqsCommand {
+    /// Print the persisted state of a stopped EQS as a base64 encoded genesis snapshot.
+    Snapshot {
+        /// Store path of the EQS.
+        #[structopt(long, env = "CAPE_EQS_STORE_PATH")]
+        store_path: PathBuf,
+        /// Storage backend of the EQS, `file` or `sqlite`.
+        #[structopt(long, default_value = "file", env = "CAPE_EQS_STORE_BACKEND")]
+        store_backend: StoreBackend,
+        /// Ethereum block of the snapshot. Defaults to the block of the last event processed by
+        /// the EQS. Events between that block and a later one are skipped by an EQS starting from
+        /// the snapshot.
+        #[structopt(long)]
+        at_block: Option<u64>,
+    },
+}
+
+/// Summary of a decoded block, printed by `block inspect`.
+#[derive(Debug, Serialize)]
+struct BlockSummary {
+    miner: UserAddress,
+    transfers: usize,
+    mints: usize,
+    freezes: usize,
+    burns: usize,
+    nullifiers: Vec<Nullifier>,
+}
+
+impl From<&CapeBlock> for BlockSummary {
+    fn from(block: &CapeBlock) -> Self {
+        Self {
+            miner: block.miner_addr.clone(),
+            transfers: block.transfer_notes.len(),
+            mints: block.mint_notes.len(),
+            freezes: block.freeze_notes.len(),
+            burns: block.burn_notes.len(),
+            nullifiers: block.nullifiers().collect(),
+        }
+    }
+}
+
+impl std::fmt::Display for BlockSummary {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        writeln!(f, "miner: {}", self.miner)?;
+        writeln!(f, "transfers: {}", self.transfers)?;
+        writeln!(f, "mints: {}", self.mints)?;
+        writeln!(f, "freezes: {}", self.freezes)?;
+        writeln!(f, "burns: {}", self.burns)?;
+        writeln!(f, "nullifiers:")?;
+        for nullifier in &self.nullifiers {
+            writeln!(f, "  {}", nullifier)?;
+        }
+        Ok(())
+    }
+}
+
+fn read_body(file: Option<PathBuf>) -> Result<SubmitBody, String> {
+    let json = match file {
+        Some(path) => std::fs::read_to_string(&path)
+            .map_err(|err| format!("cannot read {}: {}", path.display(), err))?,
+        None => {
+            let mut json = String::new();
+            std::io::stdin()
+                .read_to_string(&mut json)
+                .map_err(|err| format!("cannot read stdin: {}", err))?;
+            json
+        }
+    };
+    serde_json::from_str(&json).map_err(|err| format!("invalid transaction: {}", err))
+}
+
+fn decode_block(hex: Option<String>) -> Result<CapeBlock, String> {
+    let hex = match hex {
+        Some(hex) => hex,
+        None => {
+            let mut hex = String::new();
+            std::io::stdin()
+                .read_to_string(&mut hex)
+                .map_err(|err| format!("cannot read stdin: {}", err))?;
+            hex
+        }
+    };
+    let bytes = hex::decode(hex.trim().trim_start_matches("0x"))
+        .map_err(|err| format!("invalid hex: {}", err))?;
+    let block: sol::CapeBlock =
+        AbiDecode::decode(bytes).map_err(|err| format!("invalid block: {}", err))?;
+    block
+        .try_into()
+        .map_err(|err| format!("invalid block: {}", err))
+}
+
+fn inspect(hex: Option<String>, json: bool) -> Result<(), String> {
+    let summary = BlockSummary::from(&decode_block(hex)?);
+    if json {
+        let json = serde_json::to_string_pretty(&summary).map_err(|err| err.to_string())?;
+        println!("{}", json);
+    } else {
+        print!("{}", summary);
+    }
+    Ok(())
+}
+
+fn eqs_snapshot(
+    store_backend: StoreBackend,
+    store_path: &Path,
+    at_block: Option<u64>,
+) -> Result<(), String> {
+    let (state, _) = open_store(store_backend, store_path, false)
+        .and_then(|store| store.load_latest_state())
+        .map_err(|err| format!("cannot load EQS state: {}", err))?;
+    let at_block = at_block
+        .or_else(|| state.last_reported_index.map(|(block, _)| block))
+        .ok_or("the EQS has not processed any event, pass --at-block")?;
+    let snapshot = GenesisSnapshot::new(state, at_block)
+        .and_then(|snapshot| snapshot.to_base64())
+        .map_err(|err| err.to_string())?;
+    println!("{}", snapshot);
+    Ok(())
+}
+
+async fn post(relayer_url: &Url, route: &str, body: &SubmitBody) -> Result<surf::Response, Error> {
+    let url = relayer_url.join(route).map_err(|err| Error::Internal {
+        msg: err.to_string(),
+    })?;
+    let req = surf::post(url)
+        .body_json(body)
+        .map_err(Error::from_client_error)?;
+    surf::client()
+        .with(parse_error_body::<Error>)
+        .send(req)
+        .await
+        .map_err(Error::from_client_error)
+}
+
+async fn submit(relayer_url: &Url, body: &SubmitBody) -> Result<H256, Error> {
+    let mut res = post(relayer_url, "submit", body).await?;
+    response_body(&mut res)
+        .await
+        .map_err(Error::from_client_error)
+}
+
+async fn validate(relayer_url: &Url, body: &SubmitBody) -> Result<ValidationResult, Error> {
+    let mut res = post(relayer_url, "validate", body).await?;
+    response_body(&mut res)
+        .await
+        .map_err(Error::from_client_error)
+}
+
+#[async_std::main]
+async fn main() {
+    let opt = Options::from_args();
+    let result = match opt.command {
+        Command::Submit { file } => match read_body(file) {
+            Ok(body) => submit(&opt.relayer_url, &body)
+                .await
+                .map(|hash| println!("{:#x}", hash))
+                .map_err(|err| err.to_string()),
+            Err(err) => Err(err),
+        },
+        Command::Validate { file } => match read_body(file) {
+            Ok(body) => match validate(&opt.relayer_url, &body).await {
+                Ok(ValidationResult { valid: true, .. }) => {
+                    println!("valid");
+                    Ok(())
+                }
+                Ok(ValidationResult { error, .. }) => Err(format!(
+                    "invalid transaction: {}",
+                    error.unwrap_or_default()
+                )),
+                Err(err) => Err(err.to_string()),
+            },
+            Err(err) => Err(err),
+        },
+        Command::Block(BlockCommand::Inspect { hex, json }) => inspect(hex, json),
+        Command::Eqs(EqsCommand::Snapshot {
+            store_path,
+            store_backend,
+            at_block,
+        }) => eqs_snapshot(store_backend, &store_path, at_block),
+    };
+    if let Err(err) = result {
+        eprintln!("{}", err);
+        exit(1);
+    }
+}
@@ -0,0 +1,147 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+use async_std::task::spawn_blocking;
+use cap_rust_sandbox::{
+    assertion::EnsureMined,
+    cape::CapeBlock,
+    model::{CapeModelTxn, CAPE_MERKLE_HEIGHT},
+    types as sol,
+};
+use ethers::{
+    abi::AbiEncode,
+    prelude::{Middleware, PendingTransaction, H256},
+};
+use jf_cap::{keys::UserKeyPair, utils::TxnsParams, TransactionNote};
+use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+use relayer::{
+    testing::{generate_transfer, start_minimal_relayer_for_test},
+    SubmitBody,
+};
+use std::io::Write;
+use std::process::{Command, Output, Stdio};
+
+/// Run `cape-cli` with `args`, writing `stdin` to its standard input.
+fn run(args: &[&str], stdin: &[u8]) -> Output {
+    let mut child = Command::new(env!("CARGO_BIN_EXE_cape-cli"))
+        .args(args)
+        .stdin(Stdio::piped())
+        .stdout(Stdio::piped())
+        .stderr(Stdio::piped())
+        .spawn()
+        .unwrap();
+    child.stdin.take().unwrap().write_all(stdin).unwrap();
+    child.wait_with_output().unwrap()
+}
+
+/// Run `cape-cli <command>` against the relayer on `port`, with `body` on stdin.
+async fn cape_cli(port: u16, command: &str, body: &SubmitBody) -> Output {
+    let url = format!("http://localhost:{}", port);
+    let command = command.to_string();
+    let json = serde_json::to_vec(body).unwrap();
+    spawn_blocking(move || run(&["--relayer-url", &url, &command], &json)).await
+}
+
+#[async_std::test]
+async fn test_cli_submit() {
+    let mut rng = ChaChaRng::from_seed([42; 32]);
+    let user = UserKeyPair::generate(&mut rng);
+
+    let port = portpicker::pick_unused_port().unwrap();
+    let (contract, faucet, faucet_rec, records) = start_minimal_relayer_for_test(port, None).await;
+    let provider = contract.client().provider().clone();
+    let (transaction, memos, signature) = generate_transfer(
+        &mut rng,
+        &faucet,
+        faucet_rec.clone(),
+        user.pub_key(),
+        &records,
+    );
+    let body = SubmitBody {
+        transaction,
+        memos,
+        signature,
+    };
+
+    let output = cape_cli(port, "validate", &body).await;
+    assert!(output.status.success());
+    assert_eq!(String::from_utf8(output.stdout).unwrap().trim(), "valid");
+
+    let output = cape_cli(port, "submit", &body).await;
+    assert!(output.status.success());
+    let hash: H256 = String::from_utf8(output.stdout)
+        .unwrap()
+        .trim()
+        .parse()
+        .unwrap();
+    PendingTransaction::new(hash, &provider)
+        .await
+        .unwrap()
+        .ensure_mined();
+    assert_eq!(contract.get_num_leaves().call().await.unwrap(), 3u64.into());
+
+    // A transfer posing as a burn does not form a valid block.
+    let xfr = match body.transaction.clone() {
+        CapeModelTxn::CAP(TransactionNote::Transfer(xfr)) => xfr,
+        _ => unreachable!(),
+    };
+    let bad_block = SubmitBody {
+        transaction: CapeModelTxn::Burn {
+            xfr,
+            ro: Box::new(faucet_rec),
+        },
+        ..body
+    };
+    let output = cape_cli(port, "submit", &bad_block).await;
+    assert!(!output.status.success());
+    let stderr = String::from_utf8(output.stderr).unwrap();
+    assert!(
+        stderr.contains("submitted transaction does not form a valid block"),
+        "unexpected error: {}",
+        stderr
+    );
+}
+
+#[test]
+fn test_cli_block_inspect() {
+    let mut rng = ChaChaRng::from_seed([42; 32]);
+    let miner = UserKeyPair::generate(&mut rng);
+    let params = TxnsParams::generate_txns(&mut rng, 2, 1, 1, CAPE_MERKLE_HEIGHT);
+    let block = CapeBlock::generate(params.txns, vec![], miner.address()).unwrap();
+    let hex = hex::encode(sol::CapeBlock::from(block.clone()).encode());
+
+    // The block is read from stdin, with or without the 0x prefix.
+    let output = run(
+        &["block", "inspect", "--json"],
+        format!("0x{}\n", hex).as_bytes(),
+    );
+    assert!(output.status.success());
+    let summary: serde_json::Value = serde_json::from_slice(&output.stdout).unwrap();
+    assert_eq!(
+        summary["miner"],
+        serde_json::to_value(miner.address()).unwrap()
+    );
+    assert_eq!(summary["transfers"], 2);
+    assert_eq!(summary["mints"], 1);
+    assert_eq!(summary["freezes"], 1);
+    assert_eq!(summary["burns"], 0);
+    assert_eq!(
+        summary["nullifiers"],
+        serde_json::to_value(block.nullifiers().collect::<Vec<_>>()).unwrap()
+    );
+
+    let output = run(&["block", "inspect", "--hex", &hex], &[]);
+    assert!(output.status.success());
+    let stdout = String::from_utf8(output.stdout).unwrap();
+    assert!(stdout.contains("transfers: 2"));
+    assert!(stdout.contains("mints: 1"));
+    assert!(stdout.contains("freezes: 1"));
+    assert!(stdout.contains("burns: 0"));
+
+    let output = run(&["block", "inspect", "--hex", "not hex"], &[]);
+    assert!(!output.status.success());
+}
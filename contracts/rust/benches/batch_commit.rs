@@ -0,0 +1,38 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Compare computing record commitments one by one and in parallel.
+//!
+//! Run with `cargo bench --features parallel-verify --bench batch_commit`.
+
+use cap_rust_sandbox::cape::batch_commit;
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use jf_cap::structs::{RecordCommitment, RecordOpening};
+
+fn commitments(c: &mut Criterion) {
+    let mut group = c.benchmark_group("record_commitments");
+    let rng = &mut ark_std::test_rng();
+    for num_records in [10, 100, 1000] {
+        let ros: Vec<RecordOpening> = (0..num_records)
+            .map(|_| RecordOpening::rand_for_test(rng))
+            .collect();
+        group.bench_with_input(
+            BenchmarkId::new("sequential", num_records),
+            &ros,
+            |b, ros| b.iter(|| ros.iter().map(RecordCommitment::from).collect::<Vec<_>>()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("batch_commit", num_records),
+            &ros,
+            |b, ros| b.iter(|| batch_commit(ros)),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, commitments);
+criterion_main!(benches);
@@ -0,0 +1,62 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Compare generating Merkle witnesses in a batch and one by one.
+//!
+//! Run with `cargo bench --bench merkle_witness`.
+
+use cap_rust_sandbox::{merkle_utils::MerkleWitnessBatch, model::CAPE_MERKLE_HEIGHT};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+use jf_cap::{
+    structs::{RecordCommitment, RecordOpening},
+    AccMemberWitness, MerkleTree,
+};
+
+const NUM_LEAVES: u64 = 256;
+
+fn merkle_witness(c: &mut Criterion) {
+    let rng = &mut ark_std::test_rng();
+    let mut tree = MerkleTree::new(CAPE_MERKLE_HEIGHT).unwrap();
+    for _ in 0..NUM_LEAVES {
+        let ro = RecordOpening::rand_for_test(rng);
+        tree.push(RecordCommitment::from(&ro).to_field_element());
+    }
+
+    let mut group = c.benchmark_group("merkle_witness");
+    for num_inputs in [1u64, 4, 16] {
+        // Spread the inputs over the whole tree, in decreasing order.
+        let indices: Vec<u64> = (0..num_inputs)
+            .map(|i| NUM_LEAVES - 1 - i * NUM_LEAVES / num_inputs)
+            .collect();
+        group.bench_with_input(
+            BenchmarkId::new("independent", num_inputs),
+            &indices,
+            |b, indices| {
+                b.iter(|| {
+                    indices
+                        .iter()
+                        .map(|index| {
+                            AccMemberWitness::lookup_from_tree(&tree, *index)
+                                .expect_ok()
+                                .unwrap()
+                                .1
+                        })
+                        .collect::<Vec<_>>()
+                })
+            },
+        );
+        group.bench_with_input(
+            BenchmarkId::new("batch", num_inputs),
+            &indices,
+            |b, indices| b.iter(|| MerkleWitnessBatch::generate(&tree, indices).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, merkle_witness);
+criterion_main!(benches);
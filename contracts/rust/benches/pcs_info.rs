@@ -0,0 +1,40 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Compare sequential and parallel preparation of the PCS info for batches of plonk proofs.
+//!
+//! Run with `cargo bench --features parallel-verify --bench pcs_info`.
+
+use cap_rust_sandbox::plonk_verifier::{
+    gen_plonk_proof_for_test, prepare_pcs_infos, prepare_pcs_infos_parallel, ProofAndVk,
+};
+use criterion::{criterion_group, criterion_main, BenchmarkId, Criterion};
+
+fn pcs_info(c: &mut Criterion) {
+    let mut group = c.benchmark_group("prepare_pcs_infos");
+    for batch_size in [1, 2, 4, 8] {
+        let proofs_and_vks: Vec<ProofAndVk> = gen_plonk_proof_for_test(batch_size)
+            .unwrap()
+            .into_iter()
+            .map(|(proof, vk, pub_input, extra_msg, _)| (proof, vk, pub_input, extra_msg))
+            .collect();
+        group.bench_with_input(
+            BenchmarkId::new("sequential", batch_size),
+            &proofs_and_vks,
+            |b, proofs_and_vks| b.iter(|| prepare_pcs_infos(proofs_and_vks).unwrap()),
+        );
+        group.bench_with_input(
+            BenchmarkId::new("parallel", batch_size),
+            &proofs_and_vks,
+            |b, proofs_and_vks| b.iter(|| prepare_pcs_infos_parallel(proofs_and_vks).unwrap()),
+        );
+    }
+    group.finish();
+}
+
+criterion_group!(benches, pcs_info);
+criterion_main!(benches);
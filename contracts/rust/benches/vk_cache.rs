@@ -0,0 +1,40 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Measure the time saved by caching the keys of the plonk verifier test circuits.
+//!
+//! Run with `cargo bench --bench vk_cache`.
+
+use cap_rust_sandbox::plonk_verifier::{test_circuit_keys, VerifyingKeyCache};
+use criterion::{criterion_group, criterion_main, Criterion};
+
+const CIRCUIT_SIZE: usize = 2;
+const NUM_CALLS: usize = 10;
+
+fn vk_cache(c: &mut Criterion) {
+    let mut group = c.benchmark_group("test_circuit_keys");
+    group.sample_size(10);
+    group.bench_function("uncached", |b| {
+        b.iter(|| {
+            for _ in 0..NUM_CALLS {
+                test_circuit_keys(&mut VerifyingKeyCache::default(), CIRCUIT_SIZE).unwrap();
+            }
+        })
+    });
+    group.bench_function("cached", |b| {
+        b.iter(|| {
+            let mut cache = VerifyingKeyCache::default();
+            for _ in 0..NUM_CALLS {
+                test_circuit_keys(&mut cache, CIRCUIT_SIZE).unwrap();
+            }
+        })
+    });
+    group.finish();
+}
+
+criterion_group!(benches, vk_cache);
+criterion_main!(benches);
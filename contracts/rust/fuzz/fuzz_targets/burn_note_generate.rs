@@ -0,0 +1,41 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Fuzz [BurnNote::generate] with arbitrary, possibly malformed, transfer notes.
+
+#![no_main]
+
+use ark_serialize::CanonicalDeserialize;
+use cap_rust_sandbox::cape::BurnNote;
+use jf_cap::{
+    structs::{RecordCommitment, RecordOpening},
+    transfer::TransferNote,
+};
+use libfuzzer_sys::fuzz_target;
+use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+
+fuzz_target!(|data: &[u8]| {
+    let note = match TransferNote::deserialize(data) {
+        Ok(note) => note,
+        Err(_) => return,
+    };
+    let mut rng = ChaChaRng::from_seed([0; 32]);
+    let burned_ro = RecordOpening::rand_for_test(&mut rng);
+
+    // Constructing a burn note must either fail or produce a structurally valid note.
+    if let Ok(burn_note) = BurnNote::generate(note, burned_ro.clone()) {
+        let note = &burn_note.transfer_note;
+        assert!(note.output_commitments.len() >= 2);
+        assert_eq!(
+            note.output_commitments[1],
+            RecordCommitment::from(&burned_ro)
+        );
+        assert_eq!(note.aux_info.extra_proof_bound_data.len(), 32);
+        assert!(BurnNote::is_burn_note(note));
+        assert_eq!(burn_note.burned_ro, burned_ro);
+    }
+});
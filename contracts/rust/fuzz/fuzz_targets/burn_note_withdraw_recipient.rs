@@ -0,0 +1,42 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Fuzz [BurnNote::withdraw_recipient] with arbitrary proof-bound data.
+
+#![no_main]
+
+use cap_rust_sandbox::{cape::BurnNote, model::CAPE_MERKLE_HEIGHT};
+use jf_cap::{structs::RecordOpening, utils::TxnsParams, TransactionNote};
+use lazy_static::lazy_static;
+use libfuzzer_sys::fuzz_target;
+use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+
+lazy_static! {
+    // Generating a transfer note is expensive, so we do it once and only replace its proof-bound
+    // data on each run. `withdraw_recipient` does not look at any other part of the note.
+    static ref TEMPLATE: BurnNote = {
+        let mut rng = ChaChaRng::from_seed([0; 32]);
+        let params = TxnsParams::generate_txns(&mut rng, 1, 0, 0, CAPE_MERKLE_HEIGHT);
+        let transfer_note = match &params.txns[0] {
+            TransactionNote::Transfer(note) => (**note).clone(),
+            _ => unreachable!(),
+        };
+        BurnNote {
+            transfer_note,
+            burned_ro: RecordOpening::rand_for_test(&mut rng),
+        }
+    };
+}
+
+fuzz_target!(|data: &[u8]| {
+    let mut burn_note = TEMPLATE.clone();
+    burn_note.transfer_note.aux_info.extra_proof_bound_data = data.to_vec();
+
+    // Must not panic, whatever the data. The alias must agree with the original.
+    let recipient = burn_note.withdraw_recipient().ok();
+    assert_eq!(burn_note.erc20_recipient().ok(), recipient);
+});
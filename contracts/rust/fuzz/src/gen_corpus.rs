@@ -0,0 +1,104 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Write seed inputs for the fuzz targets to `corpus/`.
+//!
+//! The seeds are derived from the inputs used by the burn note tests in
+//! `contracts/rust/src/cape/note_types.rs` and `contracts/rust/src/test_utils.rs`. Run from the
+//! `fuzz` directory with `cargo run --release --bin gen-corpus`.
+
+use ark_serialize::CanonicalSerialize;
+use cap_rust_sandbox::{cape::DOM_SEP_CAPE_BURN, model::CAPE_MERKLE_HEIGHT};
+use jf_cap::{transfer::TransferNote, utils::TxnsParams, TransactionNote};
+use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+use std::fs;
+use std::path::Path;
+
+/// Interesting values for the proof-bound data of a burn note.
+fn extra_proof_bound_data_seeds() -> Vec<(&'static str, Vec<u8>)> {
+    let recipient = [0x42u8; 20];
+    vec![
+        ("empty", vec![]),
+        ("single_byte", b"x".to_vec()),
+        ("truncated_prefix", b"EsSCAPE bur".to_vec()),
+        ("not_a_burn", b"more data but but still not a burn".to_vec()),
+        ("prefix_only", DOM_SEP_CAPE_BURN.to_vec()),
+        (
+            "prefix_and_text",
+            [DOM_SEP_CAPE_BURN, b"more stuff"].concat(),
+        ),
+        (
+            "prefix_and_raw_address",
+            [DOM_SEP_CAPE_BURN, &recipient].concat(),
+        ),
+        (
+            "prefix_and_hex_address",
+            [
+                DOM_SEP_CAPE_BURN,
+                b"0x4242424242424242424242424242424242424242",
+            ]
+            .concat(),
+        ),
+        (
+            "bad_prefix_and_address",
+            [[0xffu8; 12].as_slice(), &[0u8; 20]].concat(),
+        ),
+        (
+            "prefix_and_invalid_utf8",
+            [DOM_SEP_CAPE_BURN, &[0xc3, 0x28, 0xa0, 0xa1]].concat(),
+        ),
+    ]
+}
+
+fn write_seed(dir: &Path, name: &str, bytes: &[u8]) {
+    fs::write(dir.join(name), bytes).unwrap();
+}
+
+fn serialize(note: &TransferNote) -> Vec<u8> {
+    let mut bytes = vec![];
+    note.serialize(&mut bytes).unwrap();
+    bytes
+}
+
+fn main() {
+    let corpus = Path::new("corpus");
+
+    let dir = corpus.join("burn_note_withdraw_recipient");
+    fs::create_dir_all(&dir).unwrap();
+    for (name, data) in extra_proof_bound_data_seeds() {
+        write_seed(&dir, name, &data);
+    }
+
+    // Transfer notes with each kind of proof-bound data, plus structurally malformed notes.
+    let dir = corpus.join("burn_note_generate");
+    fs::create_dir_all(&dir).unwrap();
+    let mut rng = ChaChaRng::from_seed([0; 32]);
+    let params = TxnsParams::generate_txns(&mut rng, 1, 0, 0, CAPE_MERKLE_HEIGHT);
+    let note = match &params.txns[0] {
+        TransactionNote::Transfer(note) => (**note).clone(),
+        _ => unreachable!(),
+    };
+    write_seed(&dir, "transfer", &serialize(&note));
+    for (name, data) in extra_proof_bound_data_seeds() {
+        let mut note = note.clone();
+        note.aux_info.extra_proof_bound_data = data;
+        write_seed(&dir, &format!("transfer_{}", name), &serialize(&note));
+    }
+
+    let mut one_output = note.clone();
+    one_output.aux_info.extra_proof_bound_data = [DOM_SEP_CAPE_BURN, &[0u8; 20]].concat();
+    one_output.output_commitments.truncate(1);
+    write_seed(&dir, "one_output", &serialize(&one_output));
+
+    let mut no_outputs = one_output;
+    no_outputs.output_commitments.clear();
+    write_seed(&dir, "no_outputs", &serialize(&no_outputs));
+
+    let bytes = serialize(&note);
+    write_seed(&dir, "truncated", &bytes[..bytes.len() / 2]);
+    write_seed(&dir, "empty", &[]);
+}
@@ -10,7 +10,7 @@
 //!
 //! The list of transaction types supported are declared through `SUPPORTED_VKS`.
 
-use cap_rust_sandbox::{types as sol, universal_param::UNIVERSAL_PARAM};
+use cap_rust_sandbox::{types as sol, universal_param::universal_param};
 use jf_cap::proof::{freeze, mint, transfer};
 use jf_cap::structs::NoteType;
 use std::process::Command;
@@ -30,7 +30,7 @@ const SUPPORTED_VKS: [(NoteType, u8, u8, u8); 7] = [
 ];
 
 fn main() {
-    let srs = &UNIVERSAL_PARAM;
+    let srs = universal_param();
 
     for (note_type, num_input, num_output, tree_depth) in SUPPORTED_VKS {
         // calculate the path to solidity file
@@ -6,9 +6,10 @@
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::{cape::CapeBlock, types::BlockCommittedFilter};
-use ethers::{abi::AbiDecode, prelude::AbiError};
+use anyhow::Result;
+use ethers::abi::AbiDecode;
 
-pub fn decode_cape_block_from_event(block: BlockCommittedFilter) -> Result<CapeBlock, AbiError> {
+pub fn decode_cape_block_from_event(block: BlockCommittedFilter) -> Result<CapeBlock> {
     Ok(crate::types::CapeBlock {
         miner_addr: AbiDecode::decode(block.miner_addr)?,
         note_types: AbiDecode::decode(block.note_types)?,
@@ -17,7 +18,7 @@ pub fn decode_cape_block_from_event(block: BlockCommittedFilter) -> Result<CapeB
         freeze_notes: AbiDecode::decode(block.freeze_notes)?,
         burn_notes: AbiDecode::decode(block.burn_notes)?,
     }
-    .into())
+    .try_into()?)
 }
 
 #[cfg(test)]
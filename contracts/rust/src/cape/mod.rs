@@ -16,18 +16,36 @@ use crate::model::CapeModelTxn;
 use crate::types as sol;
 use anyhow::{anyhow, bail, Result};
 use ark_serialize::*;
-use ethers::prelude::Address;
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::prelude::{Address, U256};
 use itertools::Itertools;
 use jf_cap::freeze::FreezeNote;
-use jf_cap::keys::UserAddress;
+use jf_cap::keys::{UserAddress, UserPubKey};
 use jf_cap::mint::MintNote;
-use jf_cap::structs::{ReceiverMemo, RecordCommitment, RecordOpening};
+use jf_cap::structs::{
+    Amount, AssetDefinition, Nullifier, ReceiverMemo, RecordCommitment, RecordOpening,
+};
 use jf_cap::transfer::TransferNote;
 use jf_cap::{Signature, TransactionNote};
 use num_traits::{FromPrimitive, ToPrimitive};
-use std::str::from_utf8;
+#[cfg(feature = "parallel-verify")]
+use rayon::prelude::*;
+use serde::{Deserialize, Serialize};
+use sha3::{Digest, Keccak256};
+use snafu::Snafu;
+use std::collections::{HashMap, HashSet};
+use std::str::FromStr;
 
 pub const DOM_SEP_CAPE_BURN: &[u8] = b"EsSCAPE burn";
+
+/// Default maximum size of a block built by [CapeBlock::from_cape_transactions], as measured by
+/// [CapeBlock::size_in_bytes].
+///
+/// Blocks are submitted to the contract in a single Ethereum transaction, and most Ethereum nodes
+/// will not relay transactions larger than 128KB. Use
+/// [CapeBlock::from_cape_transactions_with_max_size] for a different limit.
+pub const DEFAULT_MAX_BLOCK_BYTES: usize = 128 * 1024;
+
 type BlockMemos = Vec<(Vec<ReceiverMemo>, Signature)>;
 
 /// Burning transaction structure for a single asset (with fee)
@@ -58,9 +76,39 @@ impl BurnNote {
 
     /// Retrieve the Ethereum recipient address
     pub fn withdraw_recipient(&self) -> Result<Address> {
-        from_utf8(&self.transfer_note.aux_info.extra_proof_bound_data[DOM_SEP_CAPE_BURN.len()..])?
-            .parse::<Address>()
-            .map_err(|_| anyhow!("Invalid Ethereum address!"))
+        let recipient = self
+            .transfer_note
+            .aux_info
+            .extra_proof_bound_data
+            .get(DOM_SEP_CAPE_BURN.len()..)
+            .ok_or_else(|| anyhow!("Missing Ethereum address!"))?;
+        // The address is encoded as its 20 raw bytes, see [crate::test_utils::generate_burn_tx].
+        if recipient.len() != Address::len_bytes() {
+            bail!("Invalid Ethereum address!");
+        }
+        Ok(Address::from_slice(recipient))
+    }
+
+    /// Retrieve the Ethereum address receiving the withdrawn ERC20 tokens (same as
+    /// [BurnNote::withdraw_recipient]).
+    pub fn erc20_recipient(&self) -> Result<Address> {
+        self.withdraw_recipient()
+    }
+
+    /// Amount of the burned record
+    pub fn amount(&self) -> Amount {
+        self.burned_ro.amount
+    }
+
+    /// Asset definition of the burned record
+    pub fn asset_type(&self) -> &AssetDefinition {
+        &self.burned_ro.asset_def
+    }
+
+    /// Index of the fee change record among the output commitments of the transfer note. It is
+    /// followed by the burned record.
+    pub fn fee_record_index() -> usize {
+        0
     }
 
     /// utility function to check if a `TransferNote` is a `BurnNote`
@@ -80,12 +128,14 @@ impl From<BurnNote> for sol::BurnNote {
     }
 }
 
-impl From<sol::BurnNote> for BurnNote {
-    fn from(note_sol: sol::BurnNote) -> Self {
-        Self {
-            transfer_note: note_sol.transfer_note.into(),
+impl TryFrom<sol::BurnNote> for BurnNote {
+    type Error = sol::ConversionError;
+
+    fn try_from(note_sol: sol::BurnNote) -> Result<Self, Self::Error> {
+        Ok(Self {
+            transfer_note: note_sol.transfer_note.try_into()?,
             burned_ro: note_sol.record_opening.into(),
-        }
+        })
     }
 }
 
@@ -165,9 +215,137 @@ impl CapeBlock {
         txns.iter().flat_map(|tx| tx.commitments()).collect_vec()
     }
 
+    /// The number of transactions in the block.
+    pub fn len(&self) -> usize {
+        self.note_types.len()
+    }
+
+    /// Whether the block contains no transactions, that is, whether every count returned by
+    /// [CapeBlock::count_by_note_type] is zero.
+    pub fn is_empty(&self) -> bool {
+        self.note_types.is_empty()
+    }
+
+    /// The number of transactions of each type in the block.
+    ///
+    /// Types without any transaction in the block are not in the map.
+    pub fn count_by_note_type(&self) -> HashMap<NoteType, usize> {
+        let mut counts = HashMap::new();
+        for note_type in &self.note_types {
+            *counts.entry(*note_type).or_insert(0) += 1;
+        }
+        counts
+    }
+
+    /// Append the transactions of `other` to this block.
+    ///
+    /// Fails if the blocks have different miners.
+    pub fn merge(mut self, other: Self) -> Result<Self> {
+        if self.miner_addr != other.miner_addr {
+            bail!("Cannot merge blocks with different miners");
+        }
+        self.note_types.extend(other.note_types);
+        self.transfer_notes.extend(other.transfer_notes);
+        self.mint_notes.extend(other.mint_notes);
+        self.freeze_notes.extend(other.freeze_notes);
+        self.burn_notes.extend(other.burn_notes);
+        Ok(self)
+    }
+
+    /// Split the block into a block with the first `n` transactions and a block with the rest.
+    ///
+    /// Both blocks have the same miner. Panics if `n > self.len()`.
+    pub fn split_at(mut self, n: usize) -> (Self, Self) {
+        let tail_note_types = self.note_types.split_off(n);
+        let count = |note_type| {
+            self.note_types
+                .iter()
+                .filter(|&&head_type| head_type == note_type)
+                .count()
+        };
+        let num_transfers = count(NoteType::Transfer);
+        let num_mints = count(NoteType::Mint);
+        let num_freezes = count(NoteType::Freeze);
+        let num_burns = count(NoteType::Burn);
+
+        let tail = Self {
+            miner_addr: self.miner_addr.clone(),
+            note_types: tail_note_types,
+            transfer_notes: self.transfer_notes.split_off(num_transfers),
+            mint_notes: self.mint_notes.split_off(num_mints),
+            freeze_notes: self.freeze_notes.split_off(num_freezes),
+            burn_notes: self.burn_notes.split_off(num_burns),
+        };
+        (self, tail)
+    }
+
+    /// The input nullifiers of all transactions in the block.
+    pub fn nullifier_set(&self) -> HashSet<Nullifier> {
+        self.nullifiers().collect()
+    }
+
+    /// Check whether a nullifier is spent by more than one input in the block.
+    pub fn has_duplicate_nullifiers(&self) -> bool {
+        let mut seen = HashSet::new();
+        self.nullifiers().any(|nf| !seen.insert(nf))
+    }
+
+    /// The input nullifiers of all transactions in the block, grouped by note type.
+    pub fn nullifiers(&self) -> impl Iterator<Item = Nullifier> + '_ {
+        self.transfer_notes
+            .iter()
+            .flat_map(|note| note.inputs_nullifiers.iter().copied())
+            .chain(self.mint_notes.iter().map(|note| note.input_nullifier))
+            .chain(
+                self.freeze_notes
+                    .iter()
+                    .flat_map(|note| note.input_nullifiers.iter().copied()),
+            )
+            .chain(
+                self.burn_notes
+                    .iter()
+                    .flat_map(|note| note.transfer_note.inputs_nullifiers.iter().copied()),
+            )
+    }
+
+    /// The total fee paid by the transactions in the block, which the miner collects.
+    ///
+    /// Every note, burns included, states its fee in its auxiliary info. The sum is computed as a
+    /// [U256], so that it cannot overflow even if every note pays the largest possible fee.
+    pub fn total_fee(&self) -> U256 {
+        self.transfer_notes
+            .iter()
+            .map(|note| note.aux_info.fee)
+            .chain(self.mint_notes.iter().map(|note| note.aux_info.fee))
+            .chain(self.freeze_notes.iter().map(|note| note.aux_info.fee))
+            .chain(
+                self.burn_notes
+                    .iter()
+                    .map(|note| note.transfer_note.aux_info.fee),
+            )
+            .map(|fee| U256::from(u128::from(fee)))
+            .fold(U256::zero(), |total, fee| total + fee)
+    }
+
+    /// Build a block of at most [DEFAULT_MAX_BLOCK_BYTES].
     pub fn from_cape_transactions(
         transactions: Vec<CapeModelTxn>,
         miner: UserAddress,
+    ) -> Result<Self> {
+        Self::from_cape_transactions_with_max_size(
+            transactions,
+            miner,
+            Some(DEFAULT_MAX_BLOCK_BYTES),
+        )
+    }
+
+    /// Build a block, failing if its [size](Self::size_in_bytes) exceeds `max_bytes`.
+    ///
+    /// If `max_bytes` is `None` the size of the block is not limited.
+    pub fn from_cape_transactions_with_max_size(
+        transactions: Vec<CapeModelTxn>,
+        miner: UserAddress,
+        max_bytes: Option<usize>,
     ) -> Result<Self> {
         let mut burned_ros = vec![];
         let mut notes = vec![];
@@ -175,19 +353,54 @@ impl CapeBlock {
         for tx in transactions {
             match tx {
                 CapeModelTxn::CAP(note) => notes.push(note),
-                CapeModelTxn::Burn { xfr, ro } => {
-                    notes.push(TransactionNote::from(*xfr));
-                    burned_ros.push(*ro);
+                CapeModelTxn::Burn { .. } => {
+                    let burn = BurnNote::try_from(tx)?;
+                    notes.push(TransactionNote::from(burn.transfer_note));
+                    burned_ros.push(burn.burned_ro);
                 }
             }
         }
-        Self::generate(notes, burned_ros, miner)
+        let block = Self::generate(notes, burned_ros, miner)?;
+        if let Some(max_bytes) = max_bytes {
+            let size = block.size_in_bytes()?;
+            if size > max_bytes {
+                bail!(
+                    "Block size {} exceeds the maximum of {} bytes",
+                    size,
+                    max_bytes
+                );
+            }
+        }
+        Ok(block)
     }
+
+    /// Estimate the serialized size of the block.
+    pub fn size_in_bytes(&self) -> Result<usize> {
+        let notes_size = self
+            .transfer_notes
+            .iter()
+            .map(|note| note.serialized_size())
+            .chain(self.mint_notes.iter().map(|note| note.serialized_size()))
+            .chain(self.freeze_notes.iter().map(|note| note.serialized_size()))
+            .chain(self.burn_notes.iter().map(|note| note.serialized_size()))
+            .sum::<usize>();
+        let miner_size = bincode::serialized_size(&self.miner_addr)? as usize;
+        let note_types_size = bincode::serialized_size(&self.note_types)? as usize;
+        Ok(notes_size + miner_size + note_types_size)
+    }
+
+    /// Keccak-256 hash of the canonical serialization of the block.
+    pub fn content_hash(&self) -> [u8; 32] {
+        let mut bytes = vec![];
+        CanonicalSerialize::serialize(self, &mut bytes).expect("serializing to a Vec cannot fail");
+        Keccak256::digest(&bytes).into()
+    }
+
     pub fn into_cape_transactions(self) -> Result<(Vec<CapeModelTxn>, UserAddress)> {
-        let mut transfer_notes = self.transfer_notes.into_iter().rev();
-        let mut mint_notes = self.mint_notes.into_iter().rev();
-        let mut freeze_notes = self.freeze_notes.into_iter().rev();
-        let mut burn_notes = self.burn_notes.into_iter().rev();
+        let mut transfer_notes = self.transfer_notes.into_iter();
+        let mut mint_notes = self.mint_notes.into_iter();
+        let mut freeze_notes = self.freeze_notes.into_iter();
+        let mut burn_notes = self.burn_notes.into_iter();
         let txns: Option<Vec<CapeModelTxn>> = self
             .note_types
             .into_iter()
@@ -195,13 +408,7 @@ impl CapeBlock {
                 NoteType::Transfer => Some(CapeModelTxn::CAP(transfer_notes.next()?.into())),
                 NoteType::Mint => Some(CapeModelTxn::CAP(mint_notes.next()?.into())),
                 NoteType::Freeze => Some(CapeModelTxn::CAP(freeze_notes.next()?.into())),
-                NoteType::Burn => {
-                    let burn_note = burn_notes.next()?;
-                    Some(CapeModelTxn::Burn {
-                        xfr: Box::new(burn_note.transfer_note),
-                        ro: Box::new(burn_note.burned_ro),
-                    })
-                }
+                NoteType::Burn => Some(burn_notes.next()?.into()),
             })
             .collect();
         Ok((
@@ -209,6 +416,13 @@ impl CapeBlock {
             self.miner_addr,
         ))
     }
+
+    /// Iterate over the transactions of the block, in the order given by `note_types`.
+    ///
+    /// Fails if the block is malformed (see [CapeBlock::into_cape_transactions]).
+    pub fn iter(&self) -> Result<std::vec::IntoIter<CapeModelTxn>> {
+        Ok(self.clone().into_cape_transactions()?.0.into_iter())
+    }
 }
 
 impl From<CapeBlock> for sol::CapeBlock {
@@ -232,9 +446,11 @@ impl From<CapeBlock> for sol::CapeBlock {
     }
 }
 
-impl From<sol::CapeBlock> for CapeBlock {
-    fn from(blk_sol: sol::CapeBlock) -> Self {
-        Self {
+impl TryFrom<sol::CapeBlock> for CapeBlock {
+    type Error = sol::ConversionError;
+
+    fn try_from(blk_sol: sol::CapeBlock) -> Result<Self, Self::Error> {
+        Ok(Self {
             miner_addr: blk_sol.miner_addr.into(),
             note_types: blk_sol
                 .note_types
@@ -243,25 +459,57 @@ impl From<sol::CapeBlock> for CapeBlock {
                 .collect(),
             transfer_notes: blk_sol
                 .transfer_notes
-                .iter()
-                .map(|n| n.clone().into())
-                .collect(),
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
             mint_notes: blk_sol
                 .mint_notes
-                .iter()
-                .map(|n| n.clone().into())
-                .collect(),
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
             freeze_notes: blk_sol
                 .freeze_notes
-                .iter()
-                .map(|n| n.clone().into())
-                .collect(),
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
             burn_notes: blk_sol
                 .burn_notes
-                .iter()
-                .map(|n| n.clone().into())
-                .collect(),
+                .into_iter()
+                .map(TryInto::try_into)
+                .collect::<Result<_, _>>()?,
+        })
+    }
+}
+
+/// Blocks are serialized as the length prefixed ABI encoding of the corresponding
+/// [sol::CapeBlock], so the encoding is deterministic.
+impl CanonicalSerialize for CapeBlock {
+    fn serialize<W: Write>(&self, writer: W) -> Result<(), SerializationError> {
+        CanonicalSerialize::serialize(&sol::CapeBlock::from(self.clone()).encode(), writer)
+    }
+
+    fn serialized_size(&self) -> usize {
+        sol::CapeBlock::from(self.clone())
+            .encode()
+            .serialized_size()
+    }
+}
+
+impl CanonicalDeserialize for CapeBlock {
+    fn deserialize<R: Read>(reader: R) -> Result<Self, SerializationError> {
+        let bytes = <Vec<u8> as CanonicalDeserialize>::deserialize(reader)?;
+        let block = sol::CapeBlock::decode(bytes).map_err(|_| SerializationError::InvalidData)?;
+        // Converting from `sol::CapeBlock` does not fail on unknown note types.
+        if block
+            .note_types
+            .iter()
+            .any(|ty| NoteType::from_u8(*ty).is_none())
+        {
+            return Err(SerializationError::InvalidData);
         }
+        block
+            .try_into()
+            .map_err(|_| SerializationError::InvalidData)
     }
 }
 
@@ -271,14 +519,92 @@ pub struct BlockWithMemos {
     pub memos: BlockMemos,
 }
 
+/// Error returned by [BlockWithMemos::verify_memos].
+#[derive(Clone, Debug, Snafu, PartialEq, Eq)]
+#[snafu(display("invalid memos for transaction {}: {}", index, msg))]
+pub struct MemoVerificationError {
+    /// Position of the offending transaction in the block.
+    pub index: usize,
+    pub msg: String,
+}
+
 impl BlockWithMemos {
     pub fn new(block: CapeBlock, memos: BlockMemos) -> Self {
         Self { block, memos }
     }
+
+    /// A block without any transaction, mined by `miner`.
+    ///
+    /// Submitting an empty block credits the pending deposits.
+    pub fn new_empty(miner: UserPubKey) -> Result<Self> {
+        Ok(Self::new(
+            CapeBlock::from_cape_transactions(vec![], miner.address())?,
+            vec![],
+        ))
+    }
+
+    /// The number of transactions in the block.
+    pub fn transaction_count(&self) -> usize {
+        self.block.len()
+    }
+
+    /// Check the memos signature of each transaction in the block.
+    ///
+    /// Each signature is verified with the memos verification key from the auxiliary information
+    /// of the corresponding note. Fails on the first transaction whose memos do not verify.
+    pub fn verify_memos(&self) -> Result<(), MemoVerificationError> {
+        let (txns, _) =
+            self.block
+                .clone()
+                .into_cape_transactions()
+                .map_err(|err| MemoVerificationError {
+                    index: 0,
+                    msg: err.to_string(),
+                })?;
+        if txns.len() != self.memos.len() {
+            return Err(MemoVerificationError {
+                index: txns.len().min(self.memos.len()),
+                msg: format!(
+                    "block has {} transactions but {} memo lists",
+                    txns.len(),
+                    self.memos.len()
+                ),
+            });
+        }
+        for (index, (txn, (memos, sig))) in txns.into_iter().zip(&self.memos).enumerate() {
+            let note = match txn {
+                CapeModelTxn::CAP(note) => note,
+                CapeModelTxn::Burn { xfr, .. } => TransactionNote::from(*xfr),
+            };
+            note.verify_receiver_memos_signature(memos, sig)
+                .map_err(|err| MemoVerificationError {
+                    index,
+                    msg: err.to_string(),
+                })?;
+        }
+        Ok(())
+    }
+}
+
+/// The commitment of each record opening, in the same order.
+///
+/// With the `parallel-verify` feature the Rescue hashes are computed in parallel, otherwise this is
+/// the same as calling `RecordCommitment::from` on each opening.
+pub fn batch_commit(ros: &[RecordOpening]) -> Vec<RecordCommitment> {
+    #[cfg(feature = "parallel-verify")]
+    let iter = ros.par_iter();
+    #[cfg(not(feature = "parallel-verify"))]
+    let iter = ros.iter();
+    iter.map(RecordCommitment::from).collect()
 }
 
 /// Note type available in CAPE.
-#[derive(FromPrimitive, ToPrimitive, Clone, Copy, Debug, PartialEq, Eq, Hash)]
+///
+/// [Display](std::fmt::Display) and [FromStr] use the lowercase name of the variant. The serde
+/// representation is unchanged, so JSON still uses the variant name (for example `"Transfer"`).
+#[derive(
+    FromPrimitive, ToPrimitive, Clone, Copy, Debug, PartialEq, Eq, Hash, Serialize, Deserialize,
+)]
 pub enum NoteType {
     Transfer,
     Mint,
@@ -286,6 +612,56 @@ pub enum NoteType {
     Burn,
 }
 
+impl NoteType {
+    /// Index of the fee change record among the output commitments of a note of this type.
+    ///
+    /// Every note type puts the fee change first. For burns, see also
+    /// [BurnNote::fee_record_index].
+    pub fn fee_record_index(&self) -> usize {
+        match self {
+            NoteType::Transfer | NoteType::Mint | NoteType::Freeze => 0,
+            NoteType::Burn => BurnNote::fee_record_index(),
+        }
+    }
+}
+
+impl std::fmt::Display for NoteType {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            NoteType::Transfer => "transfer",
+            NoteType::Mint => "mint",
+            NoteType::Freeze => "freeze",
+            NoteType::Burn => "burn",
+        };
+        f.write_str(name)
+    }
+}
+
+#[derive(Clone, Debug, Snafu, PartialEq, Eq)]
+#[snafu(display(
+    "unknown note type {:?}, use \"transfer\", \"mint\", \"freeze\" or \"burn\"",
+    input
+))]
+pub struct ParseNoteTypeError {
+    pub input: String,
+}
+
+impl FromStr for NoteType {
+    type Err = ParseNoteTypeError;
+
+    fn from_str(input: &str) -> Result<NoteType, Self::Err> {
+        match input {
+            "transfer" => Ok(NoteType::Transfer),
+            "mint" => Ok(NoteType::Mint),
+            "freeze" => Ok(NoteType::Freeze),
+            "burn" => Ok(NoteType::Burn),
+            _ => Err(ParseNoteTypeError {
+                input: input.to_string(),
+            }),
+        }
+    }
+}
+
 impl From<TransactionNote> for NoteType {
     fn from(note: TransactionNote) -> Self {
         match note {
@@ -352,13 +728,17 @@ mod tests {
     use crate::deploy::deploy_test_cape;
     use crate::ethereum::get_funded_client;
     use crate::ledger::CapeLedger;
+    use crate::test_utils::generate_burn_tx;
     use crate::types::{GenericInto, MerkleRootSol, RecordCommitmentSol, TestCapeTypes};
+    use crate::universal_param::universal_param;
     use anyhow::Result;
-    use ethers::prelude::U256;
     use itertools::Itertools;
     use jf_cap::keys::UserKeyPair;
-    use jf_cap::structs::RecordOpening;
+    use jf_cap::structs::{AssetCode, AssetPolicy, FreezeFlag, RecordOpening};
+    use jf_cap::transfer::TransferNoteInput;
     use jf_cap::utils::TxnsParams;
+    use jf_cap::{sign_receiver_memos, AccMemberWitness, KeyPair, MerkleTree};
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
     use reef::Ledger;
 
     #[tokio::test]
@@ -388,6 +768,598 @@ mod tests {
         Ok(())
     }
 
+    #[test]
+    fn test_batch_commit() {
+        let rng = &mut ark_std::test_rng();
+        let ros = (0..10)
+            .map(|_| RecordOpening::rand_for_test(rng))
+            .collect::<Vec<_>>();
+        let expected = ros.iter().map(RecordCommitment::from).collect::<Vec<_>>();
+        assert_eq!(batch_commit(&ros), expected);
+        assert!(batch_commit(&[]).is_empty());
+    }
+
+    #[test]
+    fn test_note_type_strings() {
+        for note_type in [
+            NoteType::Transfer,
+            NoteType::Mint,
+            NoteType::Freeze,
+            NoteType::Burn,
+        ] {
+            let name = note_type.to_string();
+            assert_eq!(name.parse::<NoteType>().unwrap(), note_type);
+        }
+
+        // The JSON wire format is not affected by the string representation.
+        for (note_type, json) in [
+            (NoteType::Transfer, "\"Transfer\""),
+            (NoteType::Mint, "\"Mint\""),
+            (NoteType::Freeze, "\"Freeze\""),
+            (NoteType::Burn, "\"Burn\""),
+        ] {
+            assert_eq!(serde_json::to_string(&note_type).unwrap(), json);
+            assert_eq!(serde_json::from_str::<NoteType>(json).unwrap(), note_type);
+        }
+        assert_eq!(
+            "Transfer".parse::<NoteType>(),
+            Err(ParseNoteTypeError {
+                input: "Transfer".to_string()
+            })
+        );
+    }
+
+    #[test]
+    fn test_block_size_in_bytes() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 2, 1, 1, CapeLedger::merkle_height());
+        let miner = UserKeyPair::generate(rng);
+
+        // An empty block only contains the miner address and the (empty) list of note types.
+        let empty = CapeBlock::generate(vec![], vec![], miner.address())?;
+        assert!(empty.size_in_bytes()? > 0);
+        assert!(empty.size_in_bytes()? < 1024);
+
+        // A block with one note of each type should be larger than the sum of its notes, but well
+        // below the maximum block size.
+        let notes = params.txns[1..].to_vec();
+        let block = CapeBlock::generate(notes.clone(), vec![], miner.address())?;
+        let notes_size = notes
+            .iter()
+            .map(|note| match note {
+                TransactionNote::Transfer(note) => note.serialized_size(),
+                TransactionNote::Mint(note) => note.serialized_size(),
+                TransactionNote::Freeze(note) => note.serialized_size(),
+            })
+            .sum::<usize>();
+        assert!(block.size_in_bytes()? > notes_size);
+        assert!(block.size_in_bytes()? < notes_size + 1024);
+        assert!(block.size_in_bytes()? < DEFAULT_MAX_BLOCK_BYTES);
+
+        // Adding a note should increase the size.
+        let bigger = CapeBlock::generate(params.txns.clone(), vec![], miner.address())?;
+        assert!(bigger.size_in_bytes()? > block.size_in_bytes()?);
+
+        // The same notes are rejected under a smaller limit, and accepted without one.
+        let txns = params
+            .txns
+            .into_iter()
+            .map(CapeModelTxn::CAP)
+            .collect::<Vec<_>>();
+        let size = bigger.size_in_bytes()?;
+        assert!(CapeBlock::from_cape_transactions_with_max_size(
+            txns.clone(),
+            miner.address(),
+            Some(size - 1)
+        )
+        .is_err());
+        assert_eq!(
+            CapeBlock::from_cape_transactions_with_max_size(
+                txns.clone(),
+                miner.address(),
+                Some(size)
+            )?,
+            bigger
+        );
+        assert_eq!(
+            CapeBlock::from_cape_transactions_with_max_size(txns, miner.address(), None)?,
+            bigger
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_memos_count() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 1, 0, 0, CapeLedger::merkle_height());
+        let miner = UserKeyPair::generate(rng);
+
+        let empty = BlockWithMemos::new_empty(miner.pub_key())?;
+        assert!(empty.block.is_empty());
+        assert_eq!(empty.transaction_count(), 0);
+        empty.verify_memos().unwrap();
+
+        // A transaction without memos is rejected.
+        let block = BlockWithMemos::new(
+            CapeBlock::generate(params.txns, vec![], miner.address())?,
+            vec![],
+        );
+        assert_eq!(block.verify_memos().unwrap_err().index, 0);
+        assert_eq!(block.transaction_count(), 1);
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_verify_memos_bad_signature() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 1, 0, 0, CapeLedger::merkle_height());
+        let miner = UserKeyPair::generate(rng);
+        let owner = UserKeyPair::generate(rng);
+
+        // Memos signed with a key other than the note's memos verification key are rejected.
+        let memos = params.txns[0]
+            .output_commitments()
+            .iter()
+            .map(|_| {
+                let ro = RecordOpening::new(
+                    rng,
+                    1u64.into(),
+                    AssetDefinition::native(),
+                    owner.pub_key(),
+                    FreezeFlag::Unfrozen,
+                );
+                ReceiverMemo::from_ro(rng, &ro, &[]).unwrap()
+            })
+            .collect::<Vec<_>>();
+        let sig = sign_receiver_memos(&KeyPair::generate(rng), &memos).unwrap();
+        let block = BlockWithMemos::new(
+            CapeBlock::generate(params.txns, vec![], miner.address())?,
+            vec![(memos, sig)],
+        );
+        assert_eq!(block.verify_memos().unwrap_err().index, 0);
+
+        Ok(())
+    }
+
+    /// A native transfer spending the record `input` with leaf index `uid`, together with the
+    /// memos of its outputs signed with the note's memos signing key.
+    fn transfer_with_memos(
+        rng: &mut ChaChaRng,
+        owner: &UserKeyPair,
+        input: RecordOpening,
+        uid: u64,
+        records: &MerkleTree,
+    ) -> (CapeModelTxn, Vec<ReceiverMemo>, Signature) {
+        let xfr_prove_key = jf_cap::proof::transfer::preprocess(
+            universal_param(),
+            1,
+            2,
+            CapeLedger::merkle_height(),
+        )
+        .unwrap()
+        .0;
+        let valid_until = 2u64.pow(jf_cap::constants::MAX_TIMESTAMP_LEN as u32) - 1;
+        let inputs = vec![TransferNoteInput {
+            ro: input,
+            acc_member_witness: AccMemberWitness::lookup_from_tree(records, uid)
+                .expect_ok()
+                .unwrap()
+                .1,
+            owner_keypair: owner,
+            cred: None,
+        }];
+        let output = RecordOpening::new(
+            rng,
+            1u64.into(),
+            AssetDefinition::native(),
+            UserKeyPair::generate(rng).pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+        let (note, sign_key, fee_output) = TransferNote::generate_native(
+            rng,
+            inputs,
+            &[output.clone()],
+            1u64.into(),
+            valid_until,
+            &xfr_prove_key,
+        )
+        .unwrap();
+        let memos = [fee_output, output]
+            .iter()
+            .map(|ro| ReceiverMemo::from_ro(rng, ro, &[]).unwrap())
+            .collect::<Vec<_>>();
+        let sig = sign_receiver_memos(&sign_key, &memos).unwrap();
+        (
+            CapeModelTxn::CAP(TransactionNote::Transfer(Box::new(note))),
+            memos,
+            sig,
+        )
+    }
+
+    #[test]
+    fn test_verify_memos_order() -> Result<()> {
+        let rng = &mut ChaChaRng::from_seed([0x42u8; 32]);
+        let miner = UserKeyPair::generate(rng);
+        let owner = UserKeyPair::generate(rng);
+        let inputs = (0..2)
+            .map(|_| {
+                RecordOpening::new(
+                    rng,
+                    2u64.into(),
+                    AssetDefinition::native(),
+                    owner.pub_key(),
+                    FreezeFlag::Unfrozen,
+                )
+            })
+            .collect::<Vec<_>>();
+        let mut records = MerkleTree::new(CapeLedger::merkle_height()).unwrap();
+        for ro in &inputs {
+            records.push(RecordCommitment::from(ro).to_field_element());
+        }
+        let (txns, memos): (Vec<_>, Vec<_>) = inputs
+            .into_iter()
+            .enumerate()
+            .map(|(uid, ro)| {
+                let (txn, memos, sig) = transfer_with_memos(rng, &owner, ro, uid as u64, &records);
+                (txn, (memos, sig))
+            })
+            .unzip();
+        let block = CapeBlock::from_cape_transactions(txns, miner.address())?;
+
+        // The memos of each transfer are checked against that transfer.
+        BlockWithMemos::new(block.clone(), memos.clone())
+            .verify_memos()
+            .unwrap();
+
+        // Memos given in the wrong order are rejected at the first transaction.
+        let swapped = memos.into_iter().rev().collect::<Vec<_>>();
+        assert_eq!(
+            BlockWithMemos::new(block, swapped)
+                .verify_memos()
+                .unwrap_err()
+                .index,
+            0
+        );
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_iter() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        // Create a block with 2 transfer, 1 mint, 1 freeze
+        let params = TxnsParams::generate_txns(rng, 2, 1, 1, CapeLedger::merkle_height());
+        let miner = UserKeyPair::generate(rng);
+        let block = CapeBlock::generate(params.txns.clone(), vec![], miner.address())?;
+        assert_eq!(block.len(), 4);
+        assert!(!block.is_empty());
+
+        let txns = block.iter()?.collect::<Vec<_>>();
+        assert_eq!(
+            txns,
+            params
+                .txns
+                .into_iter()
+                .map(CapeModelTxn::CAP)
+                .collect::<Vec<_>>()
+        );
+        let note_types = txns.iter().map(CapeModelTxn::note_type).collect::<Vec<_>>();
+        assert_eq!(note_types, block.note_types);
+
+        // The iterator yields the same transactions in the same order as the conversion.
+        assert_eq!(block.clone().into_cape_transactions()?.0, txns);
+
+        let empty = CapeBlock::generate(vec![], vec![], miner.address())?;
+        assert!(empty.is_empty());
+        assert_eq!(empty.iter()?.count(), 0);
+
+        // A block with more note types than notes is malformed.
+        let malformed = CapeBlock {
+            note_types: vec![NoteType::Transfer],
+            ..empty
+        };
+        assert!(malformed.iter().is_err());
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_merge_and_split() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 3, 2, 2, CapeLedger::merkle_height());
+        let miner = UserKeyPair::generate(rng);
+        // Interleave the note types, so that both blocks contain notes of the same type.
+        let (a_notes, b_notes): (Vec<_>, Vec<_>) = params
+            .txns
+            .iter()
+            .cloned()
+            .enumerate()
+            .partition(|(i, _)| i % 2 == 0);
+        let a_notes = a_notes.into_iter().map(|(_, note)| note).collect_vec();
+        let b_notes = b_notes.into_iter().map(|(_, note)| note).collect_vec();
+        let a = CapeBlock::generate(a_notes.clone(), vec![], miner.address())?;
+        let b = CapeBlock::generate(b_notes.clone(), vec![], miner.address())?;
+
+        // Merging yields all the transactions of `a`, followed by all the transactions of `b`.
+        let merged = a.clone().merge(b.clone())?;
+        assert_eq!(merged.len(), a.len() + b.len());
+        let (txns, miner_addr) = merged.clone().into_cape_transactions()?;
+        assert_eq!(miner_addr, miner.address());
+        assert_eq!(
+            txns,
+            a_notes
+                .iter()
+                .chain(&b_notes)
+                .cloned()
+                .map(CapeModelTxn::CAP)
+                .collect_vec()
+        );
+        assert_eq!(
+            merged,
+            CapeBlock::generate([a_notes, b_notes].concat(), vec![], miner.address())?
+        );
+
+        // The merged block survives the conversion to and from the contract type.
+        assert_eq!(
+            CapeBlock::try_from(sol::CapeBlock::from(merged.clone()))?,
+            merged
+        );
+
+        // Splitting is the inverse of merging.
+        assert_eq!(merged.clone().split_at(a.len()), (a.clone(), b.clone()));
+        let (head, tail) = merged.clone().split_at(0);
+        assert!(head.is_empty());
+        assert_eq!(tail, merged);
+        let (head, tail) = merged.clone().split_at(merged.len());
+        assert_eq!(head, merged);
+        assert!(tail.is_empty());
+
+        // Blocks with different miners cannot be merged.
+        let other_miner = UserKeyPair::generate(rng);
+        let c = CapeBlock::generate(vec![], vec![], other_miner.address())?;
+        assert!(a.merge(c).is_err());
+
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_nullifiers() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 2, 1, 1, CapeLedger::merkle_height());
+        let miner = UserKeyPair::generate(rng);
+
+        // A block of distinct transactions contains each of their nullifiers exactly once.
+        let block = CapeBlock::generate(params.txns.clone(), vec![], miner.address())?;
+        let nullifiers = params
+            .txns
+            .iter()
+            .flat_map(|txn| txn.nullifiers())
+            .collect::<Vec<_>>();
+        let nullifier_set = block.nullifier_set();
+        assert_eq!(nullifier_set.len(), nullifiers.len());
+        assert_eq!(
+            nullifier_set,
+            nullifiers.into_iter().collect::<HashSet<_>>()
+        );
+        assert!(!block.has_duplicate_nullifiers());
+
+        // Including the same transaction twice spends its nullifiers twice.
+        let mut txns = params.txns.clone();
+        txns.push(params.txns[0].clone());
+        let block = CapeBlock::generate(txns, vec![], miner.address())?;
+        assert_eq!(block.nullifier_set(), nullifier_set);
+        assert!(block.has_duplicate_nullifiers());
+
+        let empty = CapeBlock::generate(vec![], vec![], miner.address())?;
+        assert!(empty.nullifier_set().is_empty());
+        assert!(!empty.has_duplicate_nullifiers());
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_canonical_serialization() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 2, 1, 1, CapeLedger::merkle_height());
+        let miner = UserKeyPair::generate(rng);
+        let block = CapeBlock::generate(params.txns.clone(), vec![], miner.address())?;
+
+        let serialize = |block: &CapeBlock| {
+            let mut bytes = vec![];
+            CanonicalSerialize::serialize(block, &mut bytes).unwrap();
+            assert_eq!(bytes.len(), block.serialized_size());
+            bytes
+        };
+        let bytes = serialize(&block);
+
+        // Equal blocks have the same serialization and content hash.
+        let same_block = CapeBlock::generate(params.txns.clone(), vec![], miner.address())?;
+        assert_eq!(serialize(&same_block), bytes);
+        assert_eq!(same_block.content_hash(), block.content_hash());
+
+        // Round trip.
+        let deserialized = <CapeBlock as CanonicalDeserialize>::deserialize(&bytes[..])?;
+        assert_eq!(deserialized, block);
+
+        // A different block has a different content hash.
+        let other_block = CapeBlock::generate(params.txns[1..].to_vec(), vec![], miner.address())?;
+        assert_ne!(other_block.content_hash(), block.content_hash());
+
+        // Truncated bytes are rejected.
+        <CapeBlock as CanonicalDeserialize>::deserialize(&bytes[..bytes.len() - 1]).unwrap_err();
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_total_fee() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        let miner = UserKeyPair::generate(rng);
+        let params = TxnsParams::generate_txns(rng, 2, 1, 1, CapeLedger::merkle_height());
+        let fee = |note: &TransactionNote| -> u128 {
+            match note {
+                TransactionNote::Transfer(note) => note.aux_info.fee,
+                TransactionNote::Mint(note) => note.aux_info.fee,
+                TransactionNote::Freeze(note) => note.aux_info.fee,
+            }
+            .into()
+        };
+
+        let empty = CapeBlock::generate(vec![], vec![], miner.address())?;
+        assert_eq!(empty.total_fee(), U256::zero());
+
+        for note in &params.txns {
+            let block = CapeBlock::generate(vec![note.clone()], vec![], miner.address())?;
+            assert_eq!(block.total_fee(), fee(note).into());
+        }
+
+        let block = CapeBlock::generate(params.txns.clone(), vec![], miner.address())?;
+        let expected: u128 = params.txns.iter().map(fee).sum();
+        assert_eq!(block.total_fee(), expected.into());
+
+        // The fee of a burn is counted like the fee of any other note.
+        let mut burn_block = block.clone();
+        let burn_note = match &params.txns[0] {
+            TransactionNote::Transfer(note) => BurnNote {
+                transfer_note: (**note).clone(),
+                burned_ro: RecordOpening::rand_for_test(rng),
+            },
+            _ => unreachable!(),
+        };
+        burn_block.burn_notes.push(burn_note);
+        burn_block.note_types.push(NoteType::Burn);
+        assert_eq!(
+            burn_block.total_fee(),
+            (expected + fee(&params.txns[0])).into()
+        );
+
+        // The total does not overflow, even if the fees add up to more than an [Amount] can hold.
+        let mut max_fee_block = burn_block;
+        for note in max_fee_block.transfer_notes.iter_mut() {
+            note.aux_info.fee = u128::MAX.into();
+        }
+        for note in max_fee_block.burn_notes.iter_mut() {
+            note.transfer_note.aux_info.fee = u128::MAX.into();
+        }
+        let max_fees = max_fee_block.transfer_notes.len() + max_fee_block.burn_notes.len();
+        assert!(max_fees > 1);
+        let expected = U256::from(u128::MAX) * max_fees
+            + max_fee_block
+                .mint_notes
+                .iter()
+                .map(|note| u128::from(note.aux_info.fee))
+                .chain(
+                    max_fee_block
+                        .freeze_notes
+                        .iter()
+                        .map(|note| u128::from(note.aux_info.fee)),
+                )
+                .sum::<u128>();
+        assert_eq!(max_fee_block.total_fee(), expected);
+        Ok(())
+    }
+
+    #[test]
+    fn test_block_count_by_note_type() -> Result<()> {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 3, 1, 2, CapeLedger::merkle_height());
+        let miner = UserKeyPair::generate(rng);
+
+        let empty = CapeBlock::generate(vec![], vec![], miner.address())?;
+        assert!(empty.count_by_note_type().is_empty());
+        assert!(empty.is_empty());
+
+        let block = CapeBlock::generate(params.txns.clone(), vec![], miner.address())?;
+        let counts = block.count_by_note_type();
+        assert_eq!(counts.len(), 3);
+        assert_eq!(counts[&NoteType::Transfer], 3);
+        assert_eq!(counts[&NoteType::Mint], 1);
+        assert_eq!(counts[&NoteType::Freeze], 2);
+        assert!(!counts.contains_key(&NoteType::Burn));
+        assert_eq!(counts.values().sum::<usize>(), block.len());
+        assert!(!block.is_empty());
+        Ok(())
+    }
+
+    #[test]
+    fn test_fee_record_index() {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 1, 1, 1, CapeLedger::merkle_height());
+        for note in &params.txns {
+            let txn = CapeModelTxn::CAP(note.clone());
+            assert!(txn.note_type().fee_record_index() < note.output_commitments().len());
+        }
+
+        let transfer_note = match &params.txns[0] {
+            TransactionNote::Transfer(note) => (**note).clone(),
+            _ => unreachable!(),
+        };
+        let burn_note = BurnNote {
+            transfer_note,
+            burned_ro: RecordOpening::rand_for_test(rng),
+        };
+        assert!(BurnNote::fee_record_index() < burn_note.transfer_note.output_commitments.len());
+        assert_eq!(
+            NoteType::Burn.fee_record_index(),
+            BurnNote::fee_record_index()
+        );
+    }
+
+    #[test]
+    fn test_burn_note_accessors() {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 1, 0, 0, CapeLedger::merkle_height());
+        let transfer_note = match &params.txns[0] {
+            TransactionNote::Transfer(note) => (**note).clone(),
+            _ => unreachable!(),
+        };
+        let burned_ro = RecordOpening::rand_for_test(rng);
+        let burn_note = BurnNote {
+            transfer_note,
+            burned_ro: burned_ro.clone(),
+        };
+        assert_eq!(burn_note.amount(), burned_ro.amount);
+        assert_eq!(burn_note.asset_type(), &burned_ro.asset_def);
+    }
+
+    #[test]
+    fn test_burn_note_withdraw_recipient() {
+        let mut rng = ChaChaRng::from_seed([0; 32]);
+        let owner = UserKeyPair::generate(&mut rng);
+        let fee_ro = RecordOpening::new(
+            &mut rng,
+            100u64.into(),
+            AssetDefinition::native(),
+            owner.pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+        let wrapped_asset =
+            AssetDefinition::new(AssetCode::random(&mut rng).0, AssetPolicy::default()).unwrap();
+        let wrapped_ro = RecordOpening::new(
+            &mut rng,
+            10u64.into(),
+            wrapped_asset,
+            owner.pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+        let mut mt = MerkleTree::new(CapeLedger::merkle_height()).unwrap();
+        mt.push(RecordCommitment::from(&fee_ro).to_field_element());
+        mt.push(RecordCommitment::from(&wrapped_ro).to_field_element());
+
+        let recipient = Address::from_low_u64_be(0x1234_5678);
+        let burn_note = generate_burn_tx(&owner, fee_ro, wrapped_ro, &mt, 0, 1, recipient);
+        assert_eq!(burn_note.withdraw_recipient().unwrap(), recipient);
+        assert_eq!(burn_note.erc20_recipient().unwrap(), recipient);
+
+        // Proof-bound data too short to hold an address is rejected.
+        let mut truncated = burn_note;
+        truncated
+            .transfer_note
+            .aux_info
+            .extra_proof_bound_data
+            .pop();
+        assert!(truncated.withdraw_recipient().is_err());
+    }
+
     #[tokio::test]
     async fn test_derive_record_commitment() {
         let contract = deploy_test_cape().await;
@@ -444,6 +1416,106 @@ mod tests {
         };
         use jf_plonk::proof_system::structs::Proof;
         use jf_primitives::elgamal;
+        use lazy_static::lazy_static;
+        use proptest::prelude::*;
+        use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+
+        lazy_static! {
+            // Generating notes is expensive, so the round-trip properties draw from a fixed pool.
+            static ref NOTES: Vec<TransactionNote> = {
+                let rng = &mut ark_std::test_rng();
+                TxnsParams::generate_txns(rng, 3, 2, 2, CapeLedger::merkle_height()).txns
+            };
+        }
+
+        fn note_type() -> impl Strategy<Value = NoteType> {
+            prop_oneof![
+                Just(NoteType::Transfer),
+                Just(NoteType::Mint),
+                Just(NoteType::Freeze),
+                Just(NoteType::Burn),
+            ]
+        }
+
+        /// Build a block with notes of the given types, in the given order.
+        ///
+        /// Burn notes are not checked for validity, since the conversions do not depend on it.
+        fn block_with_note_types(note_types: Vec<NoteType>, seed: u64) -> CapeBlock {
+            let rng = &mut ChaChaRng::seed_from_u64(seed);
+            let transfers = NOTES.iter().filter_map(|note| match note {
+                TransactionNote::Transfer(note) => Some((**note).clone()),
+                _ => None,
+            });
+            let mints = NOTES.iter().filter_map(|note| match note {
+                TransactionNote::Mint(note) => Some((**note).clone()),
+                _ => None,
+            });
+            let freezes = NOTES.iter().filter_map(|note| match note {
+                TransactionNote::Freeze(note) => Some((**note).clone()),
+                _ => None,
+            });
+            let mut transfers = transfers.cycle();
+            let mut mints = mints.cycle();
+            let mut freezes = freezes.cycle();
+
+            let mut block = CapeBlock {
+                miner_addr: UserKeyPair::generate(rng).address(),
+                note_types: note_types.clone(),
+                transfer_notes: vec![],
+                mint_notes: vec![],
+                freeze_notes: vec![],
+                burn_notes: vec![],
+            };
+            for note_type in note_types {
+                match note_type {
+                    NoteType::Transfer => block.transfer_notes.push(transfers.next().unwrap()),
+                    NoteType::Mint => block.mint_notes.push(mints.next().unwrap()),
+                    NoteType::Freeze => block.freeze_notes.push(freezes.next().unwrap()),
+                    NoteType::Burn => block.burn_notes.push(BurnNote {
+                        transfer_note: transfers.next().unwrap(),
+                        burned_ro: RecordOpening::rand_for_test(rng),
+                    }),
+                }
+            }
+            block
+        }
+
+        fn check_block_round_trip(block: CapeBlock) {
+            let round_tripped = CapeBlock::try_from(sol::CapeBlock::from(block.clone())).unwrap();
+            assert_eq!(round_tripped, block);
+
+            // The transactions must still come out in the order given by `note_types`.
+            let note_types = round_tripped
+                .into_iter()
+                .map(|txn| txn.note_type())
+                .collect::<Vec<_>>();
+            assert_eq!(note_types, block.note_types);
+        }
+
+        #[test]
+        fn test_cape_block_round_trip_edge_cases() {
+            check_block_round_trip(block_with_note_types(vec![], 0));
+            for note_type in [
+                NoteType::Transfer,
+                NoteType::Mint,
+                NoteType::Freeze,
+                NoteType::Burn,
+            ] {
+                check_block_round_trip(block_with_note_types(vec![note_type], 0));
+            }
+        }
+
+        proptest! {
+            #![proptest_config(ProptestConfig::with_cases(32))]
+
+            #[test]
+            fn prop_cape_block_round_trip(
+                note_types in prop::collection::vec(note_type(), 0..8),
+                seed in any::<u64>(),
+            ) {
+                check_block_round_trip(block_with_note_types(note_types, seed));
+            }
+        }
 
         #[tokio::test]
         async fn test_nullifier() -> Result<()> {
@@ -684,42 +1756,45 @@ mod tests {
                 let proof = txn.validity_proof();
                 assert_eq!(
                     proof.clone(),
-                    contract
-                        .check_plonk_proof(proof.into())
-                        .call()
-                        .await?
-                        .generic_into::<Proof<Bn254>>()
+                    Proof::<Bn254>::try_from(
+                        contract.check_plonk_proof(proof.into()).call().await?
+                    )?
                 );
 
                 match txn {
                     TransactionNote::Transfer(note) => {
                         assert_eq!(
                             *note.clone(),
-                            contract
-                                .check_transfer_note((*note).generic_into::<sol::TransferNote>())
-                                .call()
-                                .await?
-                                .generic_into::<TransferNote>()
+                            TransferNote::try_from(
+                                contract
+                                    .check_transfer_note(
+                                        (*note).generic_into::<sol::TransferNote>()
+                                    )
+                                    .call()
+                                    .await?
+                            )?
                         )
                     }
                     TransactionNote::Mint(note) => {
                         assert_eq!(
                             *note.clone(),
-                            contract
-                                .check_mint_note((*note).generic_into::<sol::MintNote>())
-                                .call()
-                                .await?
-                                .generic_into::<MintNote>()
+                            MintNote::try_from(
+                                contract
+                                    .check_mint_note((*note).generic_into::<sol::MintNote>())
+                                    .call()
+                                    .await?
+                            )?
                         )
                     }
                     TransactionNote::Freeze(note) => {
                         assert_eq!(
                             *note.clone(),
-                            contract
-                                .check_freeze_note((*note).generic_into::<sol::FreezeNote>())
-                                .call()
-                                .await?
-                                .generic_into::<FreezeNote>()
+                            FreezeNote::try_from(
+                                contract
+                                    .check_freeze_note((*note).generic_into::<sol::FreezeNote>())
+                                    .call()
+                                    .await?
+                            )?
                         )
                     }
                 }
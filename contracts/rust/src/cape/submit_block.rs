@@ -15,6 +15,7 @@ use ethers::prelude::signer::SignerMiddlewareError;
 use ethers::prelude::{BlockNumber, Provider, Wallet};
 use ethers::prelude::{Bytes, Http, Middleware, PendingTransaction, TxHash};
 use ethers::providers::ProviderError;
+use ethers::types::transaction::eip2718::TypedTransaction;
 use ethers_core::k256::ecdsa::SigningKey;
 
 use super::{BlockMemos, BlockWithMemos};
@@ -43,13 +44,29 @@ pub async fn fetch_cape_memos(
     Ok(Some(decoded_memos))
 }
 
+/// Error of a request through the [EthMiddleware].
+pub type SubmitBlockError = SignerMiddlewareError<Provider<Http>, Wallet<SigningKey>>;
+
 pub async fn submit_cape_block_with_memos(
     contract: &CAPE<EthMiddleware>,
     block: BlockWithMemos,
     block_number: BlockNumber,
     extra_gas: u64,
-) -> Result<PendingTransaction<'_, Http>, SignerMiddlewareError<Provider<Http>, Wallet<SigningKey>>>
-{
+) -> Result<PendingTransaction<'_, Http>, SubmitBlockError> {
+    let tx = build_cape_block_tx(contract, block, block_number, extra_gas).await?;
+    contract.client().send_transaction(tx, None).await
+}
+
+/// Build the Ethereum transaction submitting `block`, without sending it.
+///
+/// Errors returned here happen before anything is sent to the node, so the
+/// transaction can safely be built again through another provider.
+pub async fn build_cape_block_tx(
+    contract: &CAPE<EthMiddleware>,
+    block: BlockWithMemos,
+    block_number: BlockNumber,
+    extra_gas: u64,
+) -> Result<TypedTransaction, SubmitBlockError> {
     let mut memos_bytes: Vec<u8> = vec![];
     block.memos.serialize(&mut memos_bytes).unwrap();
 
@@ -114,7 +131,7 @@ pub async fn submit_cape_block_with_memos(
         block.gas_limit,
     ));
 
-    contract.client().send_transaction(tx, None).await
+    Ok(tx)
 }
 
 #[cfg(test)]
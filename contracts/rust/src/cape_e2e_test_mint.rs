@@ -19,7 +19,7 @@ use crate::{
     test_utils::keysets_for_test,
     types::field_to_u256,
     types::{GenericInto, NullifierSol},
-    universal_param::UNIVERSAL_PARAM,
+    universal_param::universal_param,
 };
 use anyhow::Result;
 use ethers::prelude::U256;
@@ -58,7 +58,7 @@ async fn test_mint_maybe_submit(should_submit: bool) -> Result<()> {
 
     let mut prng = ChaChaRng::from_seed([0x8au8; 32]);
 
-    let (prove_keys, verif_keys) = keysets_for_test(&UNIVERSAL_PARAM);
+    let (prove_keys, verif_keys) = keysets_for_test(universal_param());
 
     println!("CRS set up: {}s", now.elapsed().as_secs_f32());
     let now = Instant::now();
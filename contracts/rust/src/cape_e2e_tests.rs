@@ -0,0 +1,261 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+#![cfg(all(test, feature = "slow-tests"))]
+
+use crate::assertion::EnsureMined;
+use crate::cape::{BurnNote, CapeBlock};
+use crate::deploy::{deploy_erc20_token, deploy_test_cape};
+use crate::ethereum::{get_funded_client, GAS_LIMIT_OVERRIDE};
+use crate::ledger::CapeLedger;
+use crate::model::{erc20_asset_description, Erc20Code, EthereumAddr};
+use crate::test_utils::{
+    check_erc20_token_balance, compare_roots_records_test_cape_contract, generate_burn_tx,
+    upcast_test_cape_to_cape, PrintGas,
+};
+use crate::types::{self as sol, field_to_u256, GenericInto, TestCAPE};
+use crate::universal_param::universal_param;
+use anyhow::Result;
+use ethers::prelude::U256;
+use jf_cap::keys::{CredIssuerPubKey, UserKeyPair, UserPubKey};
+use jf_cap::structs::{
+    AssetCode, AssetDefinition, AssetPolicy, FeeInput, FreezeFlag, RecordCommitment, RecordOpening,
+    TxnFeeInfo,
+};
+use jf_cap::transfer::{TransferNote, TransferNoteInput};
+use jf_cap::{AccMemberWitness, MerkleTree, TransactionNote};
+use reef::Ledger;
+use std::sync::Arc;
+
+/// Wrap ERC20 tokens, transfer the wrapped asset to another user inside CAPE,
+/// and unwrap it back to ERC20 tokens.
+#[tokio::test]
+async fn test_wrap_transfer_unwrap() -> Result<()> {
+    let rng = &mut ark_std::test_rng();
+    let cape_contract = deploy_test_cape().await;
+    let erc20_token_contract = deploy_erc20_token().await;
+    let erc20_address = erc20_token_contract.address();
+    let cape_address = cape_contract.address();
+
+    // The client deploying the ERC20 token contract owns all the tokens.
+    let erc20_owner = erc20_token_contract.client().clone();
+    let cape_contract_for_erc20_owner = TestCAPE::new(cape_address, Arc::new(erc20_owner.clone()));
+
+    let alice = UserKeyPair::generate(rng);
+    let bob = UserKeyPair::generate(rng);
+    let miner = UserPubKey::default();
+
+    // Both users start with a native record to pay fees.
+    let fee_ros = [&alice, &bob]
+        .iter()
+        .map(|user| {
+            RecordOpening::new(
+                rng,
+                100u64.into(),
+                AssetDefinition::native(),
+                user.pub_key(),
+                FreezeFlag::Unfrozen,
+            )
+        })
+        .collect::<Vec<_>>();
+    let mut mt = MerkleTree::new(CapeLedger::merkle_height()).unwrap();
+    for ro in &fee_ros {
+        mt.push(RecordCommitment::from(ro).to_field_element());
+    }
+    cape_contract
+        .set_initial_record_commitments(
+            fee_ros
+                .iter()
+                .map(|ro| field_to_u256(RecordCommitment::from(ro).to_field_element()))
+                .collect(),
+        )
+        .send()
+        .await?
+        .await?
+        .ensure_mined();
+    const POS_ALICE_FEE: u64 = 0;
+    const POS_BOB_FEE: u64 = 1;
+
+    // (1) Sponsor a CAPE asset for the ERC20 token. The policy does not track
+    // credentials, since the users have none.
+    let erc20_code = Erc20Code(EthereumAddr(erc20_address.to_fixed_bytes()));
+    let asset_policy =
+        AssetPolicy::rand_for_test(rng).set_cred_issuer_pub_key(CredIssuerPubKey::default());
+    let description = erc20_asset_description(
+        &erc20_code,
+        &EthereumAddr(erc20_owner.address().to_fixed_bytes()),
+        asset_policy.clone(),
+    );
+    let asset_def =
+        AssetDefinition::new(AssetCode::new_foreign(&description), asset_policy).unwrap();
+    cape_contract_for_erc20_owner
+        .sponsor_cape_asset(
+            erc20_address,
+            asset_def.clone().generic_into::<sol::AssetDefinition>(),
+        )
+        .send()
+        .await?
+        .await?
+        .ensure_mined();
+
+    // (2) Deposit ERC20 tokens for Alice.
+    let amount = 1000u64;
+    erc20_token_contract
+        .approve(cape_address, U256::from(amount))
+        .send()
+        .await?
+        .await?
+        .ensure_mined();
+    let alice_wrapped_ro = RecordOpening::new(
+        rng,
+        amount.into(),
+        asset_def.clone(),
+        alice.pub_key(),
+        FreezeFlag::Unfrozen,
+    );
+    cape_contract_for_erc20_owner
+        .deposit_erc_20(
+            alice_wrapped_ro
+                .clone()
+                .generic_into::<sol::RecordOpening>(),
+            erc20_address,
+        )
+        .send()
+        .await?
+        .await?
+        .ensure_mined();
+    check_erc20_token_balance(&erc20_token_contract, cape_address, U256::from(amount)).await;
+
+    // (3) An empty block adds the pending deposit to the records Merkle tree.
+    let empty_block = CapeBlock::generate(vec![], vec![], miner.address())?;
+    cape_contract
+        .submit_cape_block(empty_block.into())
+        .gas(GAS_LIMIT_OVERRIDE) // out of gas with estimate
+        .send()
+        .await?
+        .await?
+        .ensure_mined()
+        .print_gas("Credit deposit");
+    mt.push(RecordCommitment::from(&alice_wrapped_ro).to_field_element());
+    const POS_ALICE_WRAPPED: u64 = 2;
+    compare_roots_records_test_cape_contract(
+        &mt,
+        &upcast_test_cape_to_cape(cape_contract.clone()),
+        true,
+    )
+    .await;
+
+    // (4) Alice transfers the wrapped asset to Bob.
+    let bob_wrapped_ro = RecordOpening::new(
+        rng,
+        amount.into(),
+        asset_def,
+        bob.pub_key(),
+        FreezeFlag::Unfrozen,
+    );
+    let xfr_prove_key =
+        jf_cap::proof::transfer::preprocess(universal_param(), 2, 2, CapeLedger::merkle_height())
+            .unwrap()
+            .0;
+    let valid_until = 2u64.pow(jf_cap::constants::MAX_TIMESTAMP_LEN as u32) - 1;
+    let fee_input = FeeInput {
+        ro: fee_ros[0].clone(),
+        acc_member_witness: AccMemberWitness::lookup_from_tree(&mt, POS_ALICE_FEE)
+            .expect_ok()
+            .unwrap()
+            .1,
+        owner_keypair: &alice,
+    };
+    let (fee_info, _) = TxnFeeInfo::new(rng, fee_input, 1u64.into()).unwrap();
+    let inputs = vec![TransferNoteInput {
+        ro: alice_wrapped_ro,
+        acc_member_witness: AccMemberWitness::lookup_from_tree(&mt, POS_ALICE_WRAPPED)
+            .expect_ok()
+            .unwrap()
+            .1,
+        owner_keypair: &alice,
+        cred: None,
+    }];
+    let (transfer, ..) = TransferNote::generate_non_native(
+        rng,
+        inputs,
+        &[bob_wrapped_ro.clone()],
+        fee_info,
+        valid_until,
+        &xfr_prove_key,
+        vec![],
+    )
+    .unwrap();
+    for comm in &transfer.output_commitments {
+        mt.push(comm.to_field_element());
+    }
+    const POS_BOB_WRAPPED: u64 = 4;
+
+    let transfer_block = CapeBlock::generate(
+        vec![TransactionNote::Transfer(Box::new(transfer))],
+        vec![],
+        miner.address(),
+    )?;
+    cape_contract
+        .submit_cape_block(transfer_block.into())
+        .gas(GAS_LIMIT_OVERRIDE) // out of gas with estimate
+        .send()
+        .await?
+        .await?
+        .ensure_mined()
+        .print_gas("Transfer");
+    compare_roots_records_test_cape_contract(
+        &mt,
+        &upcast_test_cape_to_cape(cape_contract.clone()),
+        true,
+    )
+    .await;
+
+    // (5) Bob burns the wrapped asset, sending the ERC20 tokens to a fresh
+    // Ethereum address.
+    let recipient = get_funded_client().await?.address();
+    let burn = generate_burn_tx(
+        &bob,
+        fee_ros[1].clone(),
+        bob_wrapped_ro,
+        &mt,
+        POS_BOB_FEE,
+        POS_BOB_WRAPPED,
+        recipient,
+    );
+    let burn_block = CapeBlock::generate(
+        vec![TransactionNote::Transfer(Box::new(
+            burn.transfer_note.clone(),
+        ))],
+        vec![burn.burned_ro],
+        miner.address(),
+    )?;
+    cape_contract
+        .submit_cape_block(burn_block.into())
+        .gas(GAS_LIMIT_OVERRIDE) // out of gas with estimate
+        .send()
+        .await?
+        .await?
+        .ensure_mined()
+        .print_gas("Burn");
+
+    // (6) The recipient has received the ERC20 tokens, and none are left in
+    // the CAPE contract.
+    check_erc20_token_balance(&erc20_token_contract, recipient, U256::from(amount)).await;
+    check_erc20_token_balance(&erc20_token_contract, cape_address, U256::from(0)).await;
+
+    // Only the fee change of the burn is added to the records Merkle tree.
+    mt.push(burn.transfer_note.output_commitments[BurnNote::fee_record_index()].to_field_element());
+    compare_roots_records_test_cape_contract(
+        &mt,
+        &upcast_test_cape_to_cape(cape_contract.clone()),
+        true,
+    )
+    .await;
+
+    Ok(())
+}
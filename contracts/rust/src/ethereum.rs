@@ -98,6 +98,18 @@ pub fn get_provider_from_url(rpc_url: &str) -> Provider<Http> {
     Provider::<Http>::try_from(rpc_url).expect("could not instantiate HTTP Provider")
 }
 
+/// Obtain the Web3 provider from an url, failing any request which takes longer than `timeout`.
+pub fn get_provider_from_url_with_timeout(rpc_url: &str, timeout: Duration) -> Provider<Http> {
+    let client = reqwest::Client::builder()
+        .timeout(timeout)
+        .build()
+        .expect("could not instantiate HTTP client");
+    let url = rpc_url
+        .parse::<reqwest::Url>()
+        .expect("invalid provider URL");
+    Provider::new(Http::new_with_client(url, client))
+}
+
 /// Obtain a client already funded with 1 ETH.
 pub async fn get_funded_client() -> Result<Arc<EthMiddleware>> {
     let mut provider = get_provider();
@@ -5,7 +5,7 @@
 // This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use crate::{model::*, universal_param::UNIVERSAL_PARAM};
+use crate::{model::*, universal_param::universal_param};
 use arbitrary::{Arbitrary, Unstructured};
 use arbitrary_wrappers::*;
 use ark_serialize::*;
@@ -329,6 +329,6 @@ impl Ledger for CapeLedger {
     }
 
     fn srs() -> &'static UniversalParam {
-        &UNIVERSAL_PARAM
+        universal_param()
     }
 }
@@ -22,13 +22,15 @@ mod bn254;
 pub mod cape;
 mod cape_e2e_test_mint;
 mod cape_e2e_test_transfer;
+mod cape_e2e_tests;
 pub mod deploy;
 mod ed_on_bn254;
 pub mod ethereum;
 pub mod helpers;
 pub mod ledger;
+pub mod merkle_utils;
 pub mod model;
-mod plonk_verifier;
+pub mod plonk_verifier;
 mod records_merkle_tree;
 mod root_store;
 pub mod test_utils;
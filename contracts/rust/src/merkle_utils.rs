@@ -0,0 +1,89 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Membership witnesses for several leaves of the records Merkle tree.
+
+use jf_cap::{AccMemberWitness, MerkleTree};
+use snafu::Snafu;
+
+#[derive(Clone, Debug, Snafu, PartialEq, Eq)]
+pub enum MerkleWitnessError {
+    #[snafu(display("no witness for leaf {} in the records Merkle tree", index))]
+    LeafNotFound { index: u64 },
+}
+
+/// Generation of the witnesses of several inputs of a transaction at once.
+pub struct MerkleWitnessBatch;
+
+impl MerkleWitnessBatch {
+    /// The membership witness of each leaf in `leaf_indices`, in the same order.
+    ///
+    /// The leaves are looked up in increasing order, so that consecutive lookups share most of
+    /// their path through the tree, and each distinct leaf is looked up once. Fails if a leaf is out
+    /// of range or has been forgotten by `tree`.
+    pub fn generate(
+        tree: &MerkleTree,
+        leaf_indices: &[u64],
+    ) -> Result<Vec<AccMemberWitness>, MerkleWitnessError> {
+        let mut sorted = leaf_indices.to_vec();
+        sorted.sort_unstable();
+        sorted.dedup();
+
+        let mut witnesses = Vec::with_capacity(sorted.len());
+        for &index in &sorted {
+            if index >= tree.num_leaves() {
+                return Err(MerkleWitnessError::LeafNotFound { index });
+            }
+            let (_, witness) = AccMemberWitness::lookup_from_tree(tree, index)
+                .expect_ok()
+                .map_err(|_| MerkleWitnessError::LeafNotFound { index })?;
+            witnesses.push(witness);
+        }
+
+        Ok(leaf_indices
+            .iter()
+            .map(|index| {
+                // `sorted` contains every requested index.
+                witnesses[sorted.binary_search(index).unwrap()].clone()
+            })
+            .collect())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::model::CAPE_MERKLE_HEIGHT;
+    use jf_cap::structs::{RecordCommitment, RecordOpening};
+
+    #[test]
+    fn test_merkle_witness_batch() {
+        let rng = &mut ark_std::test_rng();
+        let mut tree = MerkleTree::new(CAPE_MERKLE_HEIGHT).unwrap();
+        for _ in 0..8 {
+            let ro = RecordOpening::rand_for_test(rng);
+            tree.push(RecordCommitment::from(&ro).to_field_element());
+        }
+
+        let indices = [5, 0, 7, 5];
+        let witnesses = MerkleWitnessBatch::generate(&tree, &indices).unwrap();
+        assert_eq!(witnesses.len(), indices.len());
+        for (index, witness) in indices.iter().zip(witnesses) {
+            let expected = AccMemberWitness::lookup_from_tree(&tree, *index)
+                .expect_ok()
+                .unwrap()
+                .1;
+            assert_eq!(witness, expected);
+        }
+
+        assert!(MerkleWitnessBatch::generate(&tree, &[]).unwrap().is_empty());
+        assert_eq!(
+            MerkleWitnessBatch::generate(&tree, &[1, 8]).unwrap_err(),
+            MerkleWitnessError::LeafNotFound { index: 8 }
+        );
+    }
+}
@@ -5,6 +5,7 @@
 // This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::cape::{BurnNote, NoteType};
 use crate::types;
 use ark_serialize::*;
 use core::convert::TryFrom;
@@ -44,17 +45,50 @@ impl CapeModelTxn {
     pub fn nullifiers(&self) -> Vec<Nullifier> {
         match self {
             CapeModelTxn::Burn { xfr, .. } => xfr.inputs_nullifiers.clone(),
+            CapeModelTxn::CAP(note) => note.nullifiers(),
+        }
+    }
 
-            CapeModelTxn::CAP(TransactionNote::Transfer(xfr)) => xfr.inputs_nullifiers.clone(),
+    /// All output commitments of the underlying note.
+    ///
+    /// Unlike [CapeModelTxn::commitments], this includes the burned output of a burn transaction.
+    pub fn output_commitments(&self) -> Vec<RecordCommitment> {
+        match self {
+            CapeModelTxn::Burn { xfr, .. } => xfr.output_commitments.clone(),
+            CapeModelTxn::CAP(note) => note.output_commitments(),
+        }
+    }
 
-            CapeModelTxn::CAP(TransactionNote::Mint(mint)) => {
-                vec![mint.input_nullifier]
+    pub fn note_type(&self) -> NoteType {
+        match self {
+            CapeModelTxn::Burn { .. } => NoteType::Burn,
+            CapeModelTxn::CAP(TransactionNote::Transfer(xfr)) => {
+                if BurnNote::is_burn_note(xfr) {
+                    NoteType::Burn
+                } else {
+                    NoteType::Transfer
+                }
             }
+            CapeModelTxn::CAP(TransactionNote::Mint(_)) => NoteType::Mint,
+            CapeModelTxn::CAP(TransactionNote::Freeze(_)) => NoteType::Freeze,
+        }
+    }
 
-            CapeModelTxn::CAP(TransactionNote::Freeze(freeze)) => freeze.input_nullifiers.clone(),
+    /// Whether the transaction can no longer be included in a block at `current_height`.
+    ///
+    /// Only transfer notes expire. Mint and freeze notes have no expiry, and burn transactions are
+    /// never considered expired.
+    pub fn is_expired(&self, current_height: u64) -> bool {
+        match self {
+            CapeModelTxn::CAP(TransactionNote::Transfer(note)) => {
+                note.aux_info.valid_until < current_height
+            }
+            CapeModelTxn::CAP(TransactionNote::Mint(_) | TransactionNote::Freeze(_)) => false,
+            CapeModelTxn::Burn { .. } => false,
         }
     }
 
+    /// Output commitments which are added to the records Merkle tree.
     pub fn commitments(&self) -> Vec<RecordCommitment> {
         match self {
             CapeModelTxn::Burn { xfr, .. } => {
@@ -72,6 +106,34 @@ impl CapeModelTxn {
     }
 }
 
+impl From<BurnNote> for CapeModelTxn {
+    fn from(note: BurnNote) -> Self {
+        Self::Burn {
+            xfr: Box::new(note.transfer_note),
+            ro: Box::new(note.burned_ro),
+        }
+    }
+}
+
+/// Error converting a [CapeModelTxn] other than [CapeModelTxn::Burn] into a [BurnNote].
+#[derive(Clone, Copy, Debug, Snafu, PartialEq, Eq)]
+#[snafu(display("transaction is not a burn"))]
+pub struct NotABurnNote;
+
+impl TryFrom<CapeModelTxn> for BurnNote {
+    type Error = NotABurnNote;
+
+    fn try_from(txn: CapeModelTxn) -> Result<Self, Self::Error> {
+        match txn {
+            CapeModelTxn::Burn { xfr, ro } => Ok(Self {
+                transfer_note: *xfr,
+                burned_ro: *ro,
+            }),
+            CapeModelTxn::CAP(_) => Err(NotABurnNote),
+        }
+    }
+}
+
 #[tagged_blob("EADDR")]
 #[derive(Debug, Default, Clone, PartialEq, Eq, Hash)]
 pub struct EthereumAddr(pub [u8; 20]);
@@ -703,3 +765,92 @@ mod ser_display {
         Ok(Err(Deserialize::deserialize(d)?))
     }
 }
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use crate::ledger::CapeLedger;
+    use crate::test_utils::generate_burn_tx;
+    use jf_cap::{
+        keys::{UserKeyPair, UserPubKey},
+        structs::{AssetCode, FreezeFlag},
+        utils::TxnsParams,
+    };
+    use reef::Ledger;
+
+    #[test]
+    fn test_is_expired() {
+        let rng = &mut ark_std::test_rng();
+        let params = TxnsParams::generate_txns(rng, 1, 1, 1, CapeLedger::merkle_height());
+        let height = 10;
+
+        for txn in params.txns {
+            match txn {
+                TransactionNote::Transfer(mut note) => {
+                    note.aux_info.valid_until = height;
+                    let txn = CapeModelTxn::CAP(TransactionNote::Transfer(note.clone()));
+                    assert!(!txn.is_expired(height));
+                    assert!(txn.is_expired(height + 1));
+
+                    let burn = CapeModelTxn::Burn {
+                        xfr: note,
+                        ro: Box::new(RecordOpening::new(
+                            rng,
+                            1u64.into(),
+                            AssetDefinition::native(),
+                            UserPubKey::default(),
+                            FreezeFlag::Unfrozen,
+                        )),
+                    };
+                    assert!(!burn.is_expired(height + 1));
+                }
+                note => {
+                    let txn = CapeModelTxn::CAP(note);
+                    assert!(!txn.is_expired(0));
+                    assert!(!txn.is_expired(u64::MAX));
+                }
+            }
+        }
+    }
+
+    #[test]
+    fn test_burn_note_conversions() {
+        let rng = &mut ark_std::test_rng();
+        let owner = UserKeyPair::generate(rng);
+        let fee_ro = RecordOpening::new(
+            rng,
+            100u64.into(),
+            AssetDefinition::native(),
+            owner.pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+        let wrapped_ro = RecordOpening::new(
+            rng,
+            10u64.into(),
+            AssetDefinition::new(AssetCode::random(rng).0, AssetPolicy::default()).unwrap(),
+            owner.pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+        let mut mt = MerkleTree::new(CapeLedger::merkle_height()).unwrap();
+        mt.push(RecordCommitment::from(&fee_ro).to_field_element());
+        mt.push(RecordCommitment::from(&wrapped_ro).to_field_element());
+        let burn = generate_burn_tx(&owner, fee_ro, wrapped_ro, &mt, 0, 1, Default::default());
+
+        let txn = CapeModelTxn::from(burn.clone());
+        match &txn {
+            CapeModelTxn::Burn { xfr, ro } => {
+                assert_eq!(**xfr, burn.transfer_note);
+                assert_eq!(**ro, burn.burned_ro);
+            }
+            CapeModelTxn::CAP(_) => panic!("expected a burn, got {:?}", txn),
+        }
+        assert_eq!(BurnNote::try_from(txn), Ok(burn.clone()));
+
+        // A burn which is not wrapped in the Burn variant is not converted.
+        let note = TransactionNote::from(burn.transfer_note);
+        assert_eq!(
+            BurnNote::try_from(CapeModelTxn::CAP(note)),
+            Err(NotABurnNote)
+        );
+    }
+}
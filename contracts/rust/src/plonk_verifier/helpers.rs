@@ -5,7 +5,7 @@
 // This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use crate::universal_param::UNIVERSAL_PARAM;
+use crate::universal_param::universal_param;
 use anyhow::Result;
 use ark_bn254::{Bn254, Fq, Fr};
 use ark_ff::PrimeField;
@@ -14,15 +14,61 @@ use itertools::izip;
 use jf_plonk::{
     circuit::{Arithmetization, Circuit, PlonkCircuit},
     proof_system::{
-        structs::{Proof, ProofEvaluations, VerifyingKey},
+        structs::{Proof, ProofEvaluations, ProvingKey, VerifyingKey},
         PlonkKzgSnark, Snark,
     },
     transcript::SolidityTranscript,
 };
 use jf_utils::fq_to_fr;
+use lazy_static::lazy_static;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex};
+
+type CircuitKeys = (ProvingKey<'static, Bn254>, VerifyingKey<Bn254>);
+
+/// Preprocessed keys of the test circuits, keyed by circuit size.
+///
+/// Preprocessing only depends on the shape of a circuit, so circuits of the same size can share
+/// their keys. The proving key is kept next to the verifying key since both are needed to produce
+/// a proof. The keys are shared rather than cloned, since proving keys are large.
+#[derive(Default)]
+pub struct VerifyingKeyCache {
+    keys: HashMap<usize, Arc<CircuitKeys>>,
+}
+
+impl VerifyingKeyCache {
+    /// Get the keys for circuits of size `size`, calling `load` to compute them on a cache miss.
+    pub fn get_or_insert(
+        &mut self,
+        size: usize,
+        load: impl FnOnce() -> Result<CircuitKeys>,
+    ) -> Result<Arc<CircuitKeys>> {
+        if let Some(keys) = self.keys.get(&size) {
+            return Ok(keys.clone());
+        }
+        let keys = Arc::new(load()?);
+        self.keys.insert(size, keys.clone());
+        Ok(keys)
+    }
+}
+
+lazy_static! {
+    static ref VK_CACHE: Mutex<VerifyingKeyCache> = Mutex::new(VerifyingKeyCache::default());
+}
+
+/// Get the proving and verifying keys of the test circuit of size `m`.
+pub fn test_circuit_keys(cache: &mut VerifyingKeyCache, m: usize) -> Result<Arc<CircuitKeys>> {
+    cache.get_or_insert(m, || {
+        let circuit = gen_circuit_for_test::<Fr>(m, 1)?;
+        Ok(PlonkKzgSnark::<Bn254>::preprocess(
+            universal_param(),
+            &circuit,
+        )?)
+    })
+}
 
 /// return list of (proof, ver_key, public_input, extra_msg, domain_size)
-pub(crate) fn gen_plonk_proof_for_test(
+pub fn gen_plonk_proof_for_test(
     num_proof: usize,
 ) -> Result<
     Vec<(
@@ -33,14 +79,14 @@ pub(crate) fn gen_plonk_proof_for_test(
         usize,
     )>,
 > {
-    // 1. Simulate universal setup
     let rng = &mut test_rng();
-    let srs = &UNIVERSAL_PARAM;
 
-    // 2. Create circuits
-    let circuits = (0..num_proof)
-        .map(|i| {
-            let m = 2 + i / 3;
+    // 1. Create circuits
+    let sizes: Vec<usize> = (0..num_proof).map(|i| 2 + i / 3).collect();
+    let circuits = sizes
+        .iter()
+        .enumerate()
+        .map(|(i, &m)| {
             let a0 = 1 + i % 3;
             gen_circuit_for_test::<Fr>(m, a0)
         })
@@ -50,24 +96,23 @@ pub(crate) fn gen_plonk_proof_for_test(
         .map(|c| c.eval_domain_size().unwrap())
         .collect();
 
-    // 3. Preprocessing
-    let mut prove_keys = vec![];
-    let mut ver_keys = vec![];
-    for c in circuits.iter() {
-        let (pk, vk) = PlonkKzgSnark::<Bn254>::preprocess(srs, c)?;
-        prove_keys.push(pk);
-        ver_keys.push(vk);
+    // 2. Preprocessing, which only depends on the circuit size and is cached across calls
+    let mut keys = vec![];
+    let mut cache = VK_CACHE.lock().unwrap();
+    for &m in sizes.iter() {
+        keys.push(test_circuit_keys(&mut cache, m)?);
     }
+    drop(cache);
 
-    // 4. Proving
+    // 3. Proving
     let mut proofs = vec![];
     let mut extra_msgs = vec![];
 
     circuits
         .iter()
-        .zip(prove_keys.iter())
+        .zip(keys.iter())
         .enumerate()
-        .for_each(|(i, (cs, pk))| {
+        .for_each(|(i, (cs, keys))| {
             let extra_msg = if i % 2 == 0 {
                 None
             } else {
@@ -77,7 +122,7 @@ pub(crate) fn gen_plonk_proof_for_test(
                 PlonkKzgSnark::<Bn254>::prove::<_, _, SolidityTranscript>(
                     rng,
                     cs,
-                    &pk,
+                    &keys.0,
                     extra_msg.clone(),
                 )
                 .unwrap(),
@@ -90,6 +135,7 @@ pub(crate) fn gen_plonk_proof_for_test(
         .map(|cs| cs.public_input().unwrap())
         .collect();
 
+    let ver_keys = keys.iter().map(|keys| keys.1.clone());
     Ok(izip!(proofs, ver_keys, public_inputs, extra_msgs, domain_sizes).collect())
 }
 
@@ -0,0 +1,52 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Preparation of the PCS info passed to `batchVerifyOpeningProofs` of the plonk verifier
+//! contract.
+
+use anyhow::Result;
+use ark_bn254::{Bn254, Fq, Fr};
+use ark_ff::PrimeField;
+use jf_plonk::{
+    proof_system::structs::{Proof, VerifyingKey},
+    testing_apis::{PcsInfo, Verifier},
+    transcript::SolidityTranscript,
+};
+#[cfg(feature = "parallel-verify")]
+use rayon::prelude::*;
+
+/// A proof together with its verifying key, public inputs and extra transcript message.
+pub type ProofAndVk = (Proof<Bn254>, VerifyingKey<Bn254>, Vec<Fr>, Option<Vec<u8>>);
+
+fn domain_size(vk: &VerifyingKey<Bn254>) -> usize {
+    // The domain size is the first scalar of the serialized verifying key.
+    let scalars: Vec<Fq> = vk.clone().into();
+    scalars[0].into_repr().as_ref()[0] as usize
+}
+
+fn prepare_pcs_info((proof, vk, pub_input, extra_msg): &ProofAndVk) -> Result<PcsInfo<Bn254>> {
+    let verifier = Verifier::<Bn254>::new(domain_size(vk))?;
+    Ok(verifier.prepare_pcs_info::<SolidityTranscript>(
+        &[vk],
+        &[pub_input],
+        &proof.clone().into(),
+        extra_msg,
+    )?)
+}
+
+/// Compute the PCS info of each proof, one after another.
+pub fn prepare_pcs_infos(proofs_and_vks: &[ProofAndVk]) -> Result<Vec<PcsInfo<Bn254>>> {
+    proofs_and_vks.iter().map(prepare_pcs_info).collect()
+}
+
+/// Compute the PCS info of each proof in parallel.
+///
+/// The result is the same as [prepare_pcs_infos], in the same order.
+#[cfg(feature = "parallel-verify")]
+pub fn prepare_pcs_infos_parallel(proofs_and_vks: &[ProofAndVk]) -> Result<Vec<PcsInfo<Bn254>>> {
+    proofs_and_vks.par_iter().map(prepare_pcs_info).collect()
+}
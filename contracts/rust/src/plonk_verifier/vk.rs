@@ -7,7 +7,7 @@
 
 use crate::deploy::deploy_test_verifying_keys_contract;
 use crate::ethereum::get_funded_client;
-use crate::universal_param::UNIVERSAL_PARAM;
+use crate::universal_param::universal_param;
 use crate::{types as sol, types::TestVerifyingKeys};
 use anyhow::Result;
 use ark_std::{rand::Rng, test_rng};
@@ -51,7 +51,7 @@ async fn test_get_encoded_id() -> Result<()> {
 #[tokio::test]
 async fn test_get_vk_by_id() -> Result<()> {
     let contract = deploy_test_verifying_keys_contract().await;
-    let srs = &UNIVERSAL_PARAM;
+    let srs = universal_param();
 
     for (note_type, num_input, num_output, tree_depth) in SUPPORTED_VKS {
         // load rust vk
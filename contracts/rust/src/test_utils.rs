@@ -11,7 +11,7 @@ use crate::helpers::compare_merkle_root_from_contract_and_jf_tree;
 use crate::ledger::CapeLedger;
 use crate::types::{RecordsMerkleTree, CAPE};
 use crate::types::{SimpleToken, TestCAPE};
-use crate::universal_param::UNIVERSAL_PARAM;
+use crate::universal_param::universal_param;
 use ethers::prelude::TransactionReceipt;
 use ethers::prelude::{Address, H160, U256};
 use jf_cap::keys::{UserKeyPair, UserPubKey};
@@ -163,7 +163,7 @@ pub fn generate_burn_tx(
     // 2 inputs: fee input record and wrapped asset record
     // 2 outputs: changed fee asset record, burn output record
     let xfr_prove_key =
-        jf_cap::proof::transfer::preprocess(&UNIVERSAL_PARAM, 2, 2, CapeLedger::merkle_height())
+        jf_cap::proof::transfer::preprocess(universal_param(), 2, 2, CapeLedger::merkle_height())
             .unwrap()
             .0;
     let valid_until = 2u64.pow(jf_cap::constants::MAX_TIMESTAMP_LEN as u32) - 1;
@@ -303,3 +303,100 @@ pub fn keysets_for_test(srs: &UniversalParam) -> (ProverKeySet, VerifierKeySet)
     };
     (prove_keys, verif_keys)
 }
+
+#[cfg(any(test, feature = "testing"))]
+pub use strategies::{arb_burn_txn, arb_mint_txn, arb_transfer_txn};
+
+/// [proptest] strategies generating CAPE transactions.
+///
+/// Every generated transaction carries a real proof, so these strategies are slow: property tests
+/// using them should run with a small number of cases.
+#[cfg(any(test, feature = "testing"))]
+mod strategies {
+    use super::*;
+    use crate::model::CapeModelTxn;
+    use jf_cap::{
+        structs::{AssetCode, AssetPolicy, RecordCommitment},
+        utils::TxnsParams,
+        TransactionNote,
+    };
+    use proptest::prelude::*;
+
+    /// Generate between 1 and `max_count` transactions of a single kind from a random seed, and
+    /// pick one of them.
+    fn arb_generated_txn(
+        max_count: usize,
+        generate: fn(&mut ChaChaRng, usize) -> TxnsParams,
+    ) -> BoxedStrategy<TransactionNote> {
+        (1..=max_count, any::<u64>(), any::<prop::sample::Index>())
+            .prop_map(move |(count, seed, index)| {
+                let mut rng = ChaChaRng::seed_from_u64(seed);
+                let params = generate(&mut rng, count);
+                params.txns[index.index(params.txns.len())].clone()
+            })
+            .boxed()
+    }
+
+    /// A native transfer.
+    pub fn arb_transfer_txn() -> BoxedStrategy<CapeModelTxn> {
+        arb_generated_txn(2, |rng, count| {
+            TxnsParams::generate_txns(rng, count, 0, 0, CapeLedger::merkle_height())
+        })
+        .prop_map(CapeModelTxn::CAP)
+        .boxed()
+    }
+
+    /// A mint of a new asset.
+    pub fn arb_mint_txn() -> BoxedStrategy<CapeModelTxn> {
+        arb_generated_txn(2, |rng, count| {
+            TxnsParams::generate_txns(rng, 0, count, 0, CapeLedger::merkle_height())
+        })
+        .prop_map(CapeModelTxn::CAP)
+        .boxed()
+    }
+
+    /// A burn of a random amount of a wrapped asset to a random Ethereum address.
+    ///
+    /// [TxnsParams] does not generate burns, so the burn is built with [generate_burn_tx] from a
+    /// fee record and a wrapped asset record owned by a random key.
+    pub fn arb_burn_txn() -> BoxedStrategy<CapeModelTxn> {
+        (1u64..1_000_000, any::<[u8; 20]>(), any::<u64>())
+            .prop_map(|(amount, recipient, seed)| {
+                let mut rng = ChaChaRng::seed_from_u64(seed);
+                let owner = UserKeyPair::generate(&mut rng);
+                let fee_ro = RecordOpening::new(
+                    &mut rng,
+                    100u64.into(),
+                    AssetDefinition::native(),
+                    owner.pub_key(),
+                    FreezeFlag::Unfrozen,
+                );
+                let wrapped_asset =
+                    AssetDefinition::new(AssetCode::random(&mut rng).0, AssetPolicy::default())
+                        .unwrap();
+                let wrapped_ro = RecordOpening::new(
+                    &mut rng,
+                    amount.into(),
+                    wrapped_asset,
+                    owner.pub_key(),
+                    FreezeFlag::Unfrozen,
+                );
+
+                let mut mt = MerkleTree::new(CapeLedger::merkle_height()).unwrap();
+                mt.push(RecordCommitment::from(&fee_ro).to_field_element());
+                mt.push(RecordCommitment::from(&wrapped_ro).to_field_element());
+
+                let note = generate_burn_tx(
+                    &owner,
+                    fee_ro,
+                    wrapped_ro,
+                    &mt,
+                    0,
+                    1,
+                    Address::from(recipient),
+                );
+                CapeModelTxn::from(note)
+            })
+            .boxed()
+    }
+}
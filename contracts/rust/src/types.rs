@@ -7,7 +7,7 @@
 
 use ark_bn254::Fr;
 use ark_bn254::{Bn254, Fq};
-use ark_ff::{to_bytes, PrimeField, Zero};
+use ark_ff::{to_bytes, FpParameters, PrimeField, Zero};
 use ark_poly::EvaluationDomain;
 use ark_poly::Radix2EvaluationDomain;
 use ark_serialize::{CanonicalDeserialize, CanonicalSerialize};
@@ -23,7 +23,11 @@ use jf_cap::{
 };
 use jf_plonk::proof_system::structs::Proof;
 use jf_primitives::elgamal::{self, EncKey};
-use std::convert::TryInto;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use snafu::Snafu;
+use std::convert::{TryFrom, TryInto};
+use std::fmt::{self, Display, Formatter};
+use std::hash::{Hash, Hasher};
 
 pub use crate::bindings::{
     cape_mod::BlockCommittedFilter, AssetDefinition, AssetPolicy, AssetRegistry, AuditMemo,
@@ -38,6 +42,80 @@ pub use crate::bindings::{
 // The number of input wires of TurboPlonk.
 const GATE_WIDTH: usize = 4;
 
+// The bindings for the Solidity point types are generated, so we cannot derive these traits
+// directly. Coordinates are serialized as hex strings.
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "G1Point")]
+struct G1PointDef {
+    x: U256,
+    y: U256,
+}
+
+#[derive(Serialize, Deserialize)]
+#[serde(remote = "G2Point")]
+struct G2PointDef {
+    x_0: U256,
+    x_1: U256,
+    y_0: U256,
+    y_1: U256,
+}
+
+impl Serialize for G1Point {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        G1PointDef::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for G1Point {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        G1PointDef::deserialize(deserializer)
+    }
+}
+
+impl Serialize for G2Point {
+    fn serialize<S: Serializer>(&self, serializer: S) -> Result<S::Ok, S::Error> {
+        G2PointDef::serialize(self, serializer)
+    }
+}
+
+impl<'de> Deserialize<'de> for G2Point {
+    fn deserialize<D: Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+        G2PointDef::deserialize(deserializer)
+    }
+}
+
+impl Hash for G1Point {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.x.hash(state);
+        self.y.hash(state);
+    }
+}
+
+impl Hash for G2Point {
+    fn hash<H: Hasher>(&self, state: &mut H) {
+        self.x_0.hash(state);
+        self.x_1.hash(state);
+        self.y_0.hash(state);
+        self.y_1.hash(state);
+    }
+}
+
+impl Display for G1Point {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(f, "({:#x}, {:#x})", self.x, self.y)
+    }
+}
+
+impl Display for G2Point {
+    fn fmt(&self, f: &mut Formatter<'_>) -> fmt::Result {
+        write!(
+            f,
+            "(({:#x}, {:#x}), ({:#x}, {:#x}))",
+            self.x_0, self.x_1, self.y_0, self.y_1
+        )
+    }
+}
+
 impl From<ark_bn254::G1Affine> for G1Point {
     fn from(p: ark_bn254::G1Affine) -> Self {
         if p.is_zero() {
@@ -73,12 +151,39 @@ impl From<(ark_bn254::Fq, ark_bn254::Fq)> for G1Point {
     }
 }
 
-impl From<G1Point> for ark_bn254::G1Affine {
-    fn from(p_sol: G1Point) -> Self {
+/// Error converting a field element or point from its Solidity representation.
+#[derive(Clone, Debug, Snafu, PartialEq, Eq)]
+pub enum ConversionError {
+    /// The value is not a canonical field element.
+    #[snafu(display("{:#x} is not less than the field modulus {:#x}", value, modulus))]
+    OutOfRange { value: U256, modulus: U256 },
+    /// The coordinates do not describe a point on the curve.
+    NotOnCurve,
+    /// The point is on the curve but not in the prime-order subgroup.
+    NotInSubgroup,
+    /// The proof could not be assembled from its points and evaluations.
+    #[snafu(display("malformed proof: {}", msg))]
+    MalformedProof { msg: String },
+}
+
+impl TryFrom<G1Point> for ark_bn254::G1Affine {
+    type Error = ConversionError;
+
+    fn try_from(p_sol: G1Point) -> Result<Self, Self::Error> {
         if p_sol.x.is_zero() && p_sol.y.is_zero() {
-            Self::zero()
+            return Ok(Self::zero());
+        }
+        let p = Self::new(
+            try_u256_to_field(p_sol.x)?,
+            try_u256_to_field(p_sol.y)?,
+            false,
+        );
+        if !p.is_on_curve() {
+            Err(ConversionError::NotOnCurve)
+        } else if !p.is_in_correct_subgroup_assuming_on_curve() {
+            Err(ConversionError::NotInSubgroup)
         } else {
-            Self::new(u256_to_field(p_sol.x), u256_to_field(p_sol.y), false)
+            Ok(p)
         }
     }
 }
@@ -95,6 +200,26 @@ impl From<ark_bn254::G2Affine> for G2Point {
     }
 }
 
+impl TryFrom<G2Point> for ark_bn254::G2Affine {
+    type Error = ConversionError;
+
+    fn try_from(p_sol: G2Point) -> Result<Self, Self::Error> {
+        // NOTE: in contract, x = x0 * z + x1, whereas in arkwork x = c0 + c1 * X.
+        let p = Self::new(
+            ark_bn254::Fq2::new(try_u256_to_field(p_sol.x_1)?, try_u256_to_field(p_sol.x_0)?),
+            ark_bn254::Fq2::new(try_u256_to_field(p_sol.y_1)?, try_u256_to_field(p_sol.y_0)?),
+            false,
+        );
+        if !p.is_on_curve() {
+            Err(ConversionError::NotOnCurve)
+        } else if !p.is_in_correct_subgroup_assuming_on_curve() {
+            Err(ConversionError::NotInSubgroup)
+        } else {
+            Ok(p)
+        }
+    }
+}
+
 /// convert a field element (at most BigInteger256).
 pub fn field_to_u256<F: PrimeField>(f: F) -> U256 {
     if F::size_in_bits() > 256 {
@@ -103,13 +228,30 @@ pub fn field_to_u256<F: PrimeField>(f: F) -> U256 {
     U256::from_little_endian(&to_bytes!(&f).unwrap())
 }
 
-/// convert a U256 to a field element.
+/// convert a U256 to a field element, reducing it modulo the field modulus.
+///
+/// Use [try_u256_to_field] for values which are not known to be canonical.
 pub fn u256_to_field<F: PrimeField>(v: U256) -> F {
     let mut bytes = vec![0u8; 32];
     v.to_little_endian(&mut bytes);
     F::from_le_bytes_mod_order(&bytes)
 }
 
+/// The modulus of a field (at most BigInteger256), in the same representation as
+/// [field_to_u256].
+pub fn field_modulus_u256<F: PrimeField>() -> U256 {
+    U256::from_little_endian(&to_bytes!(<F::Params as FpParameters>::MODULUS).unwrap())
+}
+
+/// convert a U256 to a field element, failing if it is not less than the field modulus.
+pub fn try_u256_to_field<F: PrimeField>(v: U256) -> Result<F, ConversionError> {
+    let modulus = field_modulus_u256::<F>();
+    if v >= modulus {
+        return Err(ConversionError::OutOfRange { value: v, modulus });
+    }
+    Ok(u256_to_field(v))
+}
+
 impl From<ark_ed_on_bn254::EdwardsAffine> for EdOnBN254Point {
     fn from(p: ark_ed_on_bn254::EdwardsAffine) -> Self {
         // Even though solidity precompile for BN254 has a different Point of Infinity
@@ -529,11 +671,13 @@ impl From<Proof<Bn254>> for PlonkProof {
     }
 }
 
-impl From<PlonkProof> for Proof<Bn254> {
-    fn from(pf_sol: PlonkProof) -> Self {
-        fn g1_point_to_fields(p: G1Point) -> Vec<ark_bn254::Fq> {
-            let p: ark_bn254::G1Affine = p.into();
-            vec![p.x, p.y]
+impl TryFrom<PlonkProof> for Proof<Bn254> {
+    type Error = ConversionError;
+
+    fn try_from(pf_sol: PlonkProof) -> Result<Self, Self::Error> {
+        fn g1_point_to_fields(p: G1Point) -> Result<Vec<ark_bn254::Fq>, ConversionError> {
+            let p: ark_bn254::G1Affine = p.try_into()?;
+            Ok(vec![p.x, p.y])
         }
 
         let wires_evals = vec![
@@ -552,19 +696,19 @@ impl From<PlonkProof> for Proof<Bn254> {
         let perm_next_eval = u256_to_field(pf_sol.prod_perm_zeta_omega_eval);
 
         let fields: Vec<ark_bn254::Fq> = [
-            g1_point_to_fields(pf_sol.wire_0),
-            g1_point_to_fields(pf_sol.wire_1),
-            g1_point_to_fields(pf_sol.wire_2),
-            g1_point_to_fields(pf_sol.wire_3),
-            g1_point_to_fields(pf_sol.wire_4),
-            g1_point_to_fields(pf_sol.split_0),
-            g1_point_to_fields(pf_sol.split_1),
-            g1_point_to_fields(pf_sol.split_2),
-            g1_point_to_fields(pf_sol.split_3),
-            g1_point_to_fields(pf_sol.split_4),
-            g1_point_to_fields(pf_sol.prod_perm),
-            g1_point_to_fields(pf_sol.zeta),
-            g1_point_to_fields(pf_sol.zeta_omega),
+            g1_point_to_fields(pf_sol.wire_0)?,
+            g1_point_to_fields(pf_sol.wire_1)?,
+            g1_point_to_fields(pf_sol.wire_2)?,
+            g1_point_to_fields(pf_sol.wire_3)?,
+            g1_point_to_fields(pf_sol.wire_4)?,
+            g1_point_to_fields(pf_sol.split_0)?,
+            g1_point_to_fields(pf_sol.split_1)?,
+            g1_point_to_fields(pf_sol.split_2)?,
+            g1_point_to_fields(pf_sol.split_3)?,
+            g1_point_to_fields(pf_sol.split_4)?,
+            g1_point_to_fields(pf_sol.prod_perm)?,
+            g1_point_to_fields(pf_sol.zeta)?,
+            g1_point_to_fields(pf_sol.zeta_omega)?,
             wires_evals,
             wire_sigma_evals,
             vec![perm_next_eval],
@@ -573,7 +717,9 @@ impl From<PlonkProof> for Proof<Bn254> {
 
         fields
             .try_into()
-            .expect("Failed to convert base fields to Proof.")
+            .map_err(|err| ConversionError::MalformedProof {
+                msg: format!("{}", err),
+            })
     }
 }
 
@@ -599,8 +745,10 @@ impl From<jf_cap::transfer::TransferNote> for TransferNote {
     }
 }
 
-impl From<TransferNote> for jf_cap::transfer::TransferNote {
-    fn from(note_sol: TransferNote) -> Self {
+impl TryFrom<TransferNote> for jf_cap::transfer::TransferNote {
+    type Error = ConversionError;
+
+    fn try_from(note_sol: TransferNote) -> Result<Self, Self::Error> {
         let inputs_nullifiers = note_sol
             .input_nullifiers
             .iter()
@@ -611,13 +759,13 @@ impl From<TransferNote> for jf_cap::transfer::TransferNote {
             .iter()
             .map(|&cm| cm.generic_into::<RecordCommitmentSol>().into())
             .collect();
-        Self {
+        Ok(Self {
             inputs_nullifiers,
             output_commitments,
-            proof: note_sol.proof.into(),
+            proof: note_sol.proof.try_into()?,
             audit_memo: note_sol.audit_memo.into(),
             aux_info: note_sol.aux_info.into(),
-        }
+        })
     }
 }
 
@@ -640,9 +788,11 @@ impl From<jf_cap::mint::MintNote> for MintNote {
     }
 }
 
-impl From<MintNote> for jf_cap::mint::MintNote {
-    fn from(note_sol: MintNote) -> Self {
-        Self {
+impl TryFrom<MintNote> for jf_cap::mint::MintNote {
+    type Error = ConversionError;
+
+    fn try_from(note_sol: MintNote) -> Result<Self, Self::Error> {
+        Ok(Self {
             input_nullifier: note_sol
                 .input_nullifier
                 .generic_into::<NullifierSol>()
@@ -661,10 +811,10 @@ impl From<MintNote> for jf_cap::mint::MintNote {
                 .mint_internal_asset_code
                 .generic_into::<InternalAssetCodeSol>()
                 .into(),
-            proof: note_sol.proof.into(),
+            proof: note_sol.proof.try_into()?,
             audit_memo: note_sol.audit_memo.into(),
             aux_info: note_sol.aux_info.into(),
-        }
+        })
     }
 }
 
@@ -689,8 +839,10 @@ impl From<jf_cap::freeze::FreezeNote> for FreezeNote {
     }
 }
 
-impl From<FreezeNote> for jf_cap::freeze::FreezeNote {
-    fn from(note_sol: FreezeNote) -> Self {
+impl TryFrom<FreezeNote> for jf_cap::freeze::FreezeNote {
+    type Error = ConversionError;
+
+    fn try_from(note_sol: FreezeNote) -> Result<Self, Self::Error> {
         let input_nullifiers = note_sol
             .input_nullifiers
             .iter()
@@ -701,12 +853,12 @@ impl From<FreezeNote> for jf_cap::freeze::FreezeNote {
             .iter()
             .map(|&cm| cm.generic_into::<RecordCommitmentSol>().into())
             .collect();
-        Self {
+        Ok(Self {
             input_nullifiers,
             output_commitments,
-            proof: note_sol.proof.into(),
+            proof: note_sol.proof.try_into()?,
             aux_info: note_sol.aux_info.into(),
-        }
+        })
     }
 }
 
@@ -788,8 +940,8 @@ impl From<jf_plonk::testing_apis::PcsInfo<Bn254>> for PcsInfo {
 #[cfg(test)]
 mod test {
     use super::*;
-    use ark_bn254::{Fq, G1Affine, G2Affine};
-    use ark_ec::AffineCurve;
+    use ark_bn254::{Fq, G1Affine, G1Projective, G2Affine};
+    use ark_ec::{AffineCurve, ProjectiveCurve};
     use ark_ed_on_bn254::EdwardsAffine;
     use ark_ff::field_new;
     use ark_std::UniformRand;
@@ -811,6 +963,113 @@ mod test {
 
         assert_eq!(f1, u256_to_field(field_to_u256(f1)));
         assert_eq!(f2, u256_to_field(field_to_u256(f2)));
+        assert_eq!(Ok(f1), try_u256_to_field(field_to_u256(f1)));
+        assert_eq!(Ok(f2), try_u256_to_field(field_to_u256(f2)));
+    }
+
+    #[test]
+    fn u256_to_fr_fails_with_modulus() {
+        let modulus = field_modulus_u256::<ark_bn254::Fr>();
+        assert_eq!(
+            modulus,
+            U256::from_dec_str(
+                "21888242871839275222246405745257275088548364400416034343698204186575808495617"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            try_u256_to_field::<ark_bn254::Fr>(modulus),
+            Err(ConversionError::OutOfRange {
+                value: modulus,
+                modulus
+            })
+        );
+        assert!(try_u256_to_field::<ark_bn254::Fr>(U256::MAX).is_err());
+        assert_eq!(
+            try_u256_to_field::<ark_bn254::Fr>(modulus - 1),
+            Ok(-ark_bn254::Fr::from(1u64))
+        );
+    }
+
+    #[test]
+    fn u256_to_fq_fails_with_modulus() {
+        let modulus = field_modulus_u256::<Fq>();
+        assert_eq!(
+            modulus,
+            U256::from_dec_str(
+                "21888242871839275222246405745257275088696311157297823662689037894645226208583"
+            )
+            .unwrap()
+        );
+        assert_eq!(
+            try_u256_to_field::<Fq>(modulus),
+            Err(ConversionError::OutOfRange {
+                value: modulus,
+                modulus
+            })
+        );
+        assert_eq!(try_u256_to_field::<Fq>(modulus - 1), Ok(-Fq::from(1u64)));
+
+        // Points with out-of-range coordinates are rejected.
+        let p = G1Point {
+            x: modulus,
+            y: U256::from(2),
+        };
+        assert!(matches!(
+            G1Affine::try_from(p),
+            Err(ConversionError::OutOfRange { .. })
+        ));
+    }
+
+    #[test]
+    fn group_types_serde_and_hash() {
+        use std::collections::hash_map::DefaultHasher;
+
+        fn hash(value: &impl Hash) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let rng = &mut ark_std::test_rng();
+        let p1: G1Point = G1Projective::rand(rng).into_affine().into();
+        let json = serde_json::to_string(&p1).unwrap();
+        assert_eq!(serde_json::from_str::<G1Point>(&json).unwrap(), p1);
+        assert_eq!(hash(&p1), hash(&p1.clone()));
+        assert_eq!(p1.to_string(), format!("({:#x}, {:#x})", p1.x, p1.y));
+
+        let p2: G2Point = G2Affine::prime_subgroup_generator().into();
+        let json = serde_json::to_string(&p2).unwrap();
+        assert_eq!(serde_json::from_str::<G2Point>(&json).unwrap(), p2);
+        assert_eq!(hash(&p2), hash(&p2.clone()));
+        assert!(p2.to_string().starts_with("((0x198e9393920d483a"));
+
+        // Coordinates are hex strings.
+        let p3 = G1Point {
+            x: U256::from(255),
+            y: U256::from(16),
+        };
+        assert_eq!(
+            serde_json::to_string(&p3).unwrap(),
+            r#"{"x":"0xff","y":"0x10"}"#
+        );
+        assert_eq!(p3.to_string(), "(0xff, 0x10)");
+    }
+
+    #[test]
+    fn plonk_proof_conversion() {
+        // a proof with a point which is not on the curve
+        let proof = PlonkProof {
+            wire_0: G1Point {
+                x: U256::from(12345),
+                y: U256::from(2),
+            },
+            ..Default::default()
+        };
+        assert_eq!(
+            Proof::<Bn254>::try_from(proof),
+            Err(ConversionError::NotOnCurve)
+        );
     }
 
     #[test]
@@ -820,14 +1079,19 @@ mod test {
         let p1_sol: G1Point = p1.into();
         assert_eq!(p1_sol.x, U256::from(0));
         assert_eq!(p1_sol.y, U256::from(0));
-        assert_eq!(p1, p1_sol.generic_into::<G1Affine>());
+        assert_eq!(Ok(p1), G1Affine::try_from(p1_sol));
 
-        // a point (not on the curve, which doesn't matter since we only check conversion)
+        // a point not on the curve
         let p2 = G1Affine::new(field_new!(Fq, "12345"), field_new!(Fq, "2"), false);
         let p2_sol: G1Point = p2.into();
         assert_eq!(p2_sol.x, U256::from(12345));
         assert_eq!(p2_sol.y, U256::from(2));
-        assert_eq!(p2, p2_sol.generic_into::<G1Affine>());
+        assert_eq!(G1Affine::try_from(p2_sol), Err(ConversionError::NotOnCurve));
+
+        // a valid point
+        let rng = &mut ark_std::test_rng();
+        let p = G1Projective::rand(rng).into_affine();
+        assert_eq!(Ok(p), G1Affine::try_from(G1Point::from(p)));
 
         // check G2 point conversion
         let p3 = G2Affine::prime_subgroup_generator();
@@ -865,6 +1129,14 @@ mod test {
             .unwrap()
         );
 
+        assert_eq!(Ok(p3), G2Affine::try_from(G2Point::from(p3)));
+        let mut bad_p3_sol = G2Point::from(p3);
+        bad_p3_sol.y_1 += U256::from(1);
+        assert_eq!(
+            G2Affine::try_from(bad_p3_sol),
+            Err(ConversionError::NotOnCurve)
+        );
+
         // check ed_on_bn254 point conversion
         let p4 = EdwardsAffine::prime_subgroup_generator();
         let p4_sol: EdOnBN254Point = p4.into();
@@ -885,4 +1157,132 @@ mod test {
             .unwrap()
         );
     }
+
+    /// Golden files checking the conversion of points to their Solidity representation.
+    ///
+    /// Each entry stores the coordinates of an arkworks point as little-endian hex, and the
+    /// coordinates of the corresponding Solidity point as decimal strings. Run the test with
+    /// `CAPE_UPDATE_GOLDEN=1` to overwrite the Solidity coordinates with the current output of
+    /// the conversion.
+    mod golden {
+        use super::*;
+        use std::path::PathBuf;
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct Golden<Ark, Sol> {
+            name: String,
+            ark: Ark,
+            sol: Sol,
+        }
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct ArkG1 {
+            x: String,
+            y: String,
+            infinity: bool,
+        }
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct ArkFq2 {
+            c0: String,
+            c1: String,
+        }
+
+        #[derive(Debug, Deserialize, Serialize)]
+        struct ArkG2 {
+            x: ArkFq2,
+            y: ArkFq2,
+            infinity: bool,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct SolG1 {
+            x: String,
+            y: String,
+        }
+
+        #[derive(Debug, Deserialize, PartialEq, Serialize)]
+        struct SolG2 {
+            x_0: String,
+            x_1: String,
+            y_0: String,
+            y_1: String,
+        }
+
+        fn golden_path(name: &str) -> PathBuf {
+            [env!("CARGO_MANIFEST_DIR"), "tests", "golden", name]
+                .iter()
+                .collect()
+        }
+
+        /// Check each entry of a golden file, or update it if `CAPE_UPDATE_GOLDEN` is set.
+        fn check_golden<Ark, Sol>(name: &str, convert: impl Fn(&Ark) -> Sol)
+        where
+            Ark: for<'a> Deserialize<'a> + Serialize,
+            Sol: for<'a> Deserialize<'a> + Serialize + PartialEq + fmt::Debug,
+        {
+            let path = golden_path(name);
+            let mut entries: Vec<Golden<Ark, Sol>> =
+                serde_json::from_slice(&std::fs::read(&path).unwrap()).unwrap();
+            if std::env::var_os("CAPE_UPDATE_GOLDEN").is_some() {
+                for entry in &mut entries {
+                    entry.sol = convert(&entry.ark);
+                }
+                let json = serde_json::to_string_pretty(&entries).unwrap();
+                std::fs::write(&path, json + "\n").unwrap();
+            } else {
+                for entry in &entries {
+                    assert_eq!(convert(&entry.ark), entry.sol, "{}: {}", name, entry.name);
+                }
+            }
+        }
+
+        fn fq(hex: &str) -> Fq {
+            Fq::deserialize(&hex::decode(hex).unwrap()[..]).unwrap()
+        }
+
+        fn fq2(c: &ArkFq2) -> ark_bn254::Fq2 {
+            ark_bn254::Fq2::new(fq(&c.c0), fq(&c.c1))
+        }
+
+        #[test]
+        fn test_golden_g1_g2_serde() {
+            check_golden("g1.json", |p: &ArkG1| {
+                let p = G1Affine::new(fq(&p.x), fq(&p.y), p.infinity);
+                assert!(p.is_zero() || p.is_on_curve());
+                let p = G1Point::from(p);
+                SolG1 {
+                    x: p.x.to_string(),
+                    y: p.y.to_string(),
+                }
+            });
+            check_golden("g2.json", |p: &ArkG2| {
+                let p = G2Affine::new(fq2(&p.x), fq2(&p.y), p.infinity);
+                assert!(p.is_zero() || p.is_on_curve());
+                let p = G2Point::from(p);
+                SolG2 {
+                    x_0: p.x_0.to_string(),
+                    x_1: p.x_1.to_string(),
+                    y_0: p.y_0.to_string(),
+                    y_1: p.y_1.to_string(),
+                }
+            });
+        }
+
+        #[test]
+        fn test_golden_generators() {
+            // The golden files start with the generator and the point at infinity.
+            let g1: Vec<Golden<ArkG1, SolG1>> =
+                serde_json::from_slice(&std::fs::read(golden_path("g1.json")).unwrap()).unwrap();
+            let g = G1Affine::prime_subgroup_generator();
+            assert_eq!((fq(&g1[0].ark.x), fq(&g1[0].ark.y)), (g.x, g.y));
+            assert!(g1[1].ark.infinity);
+
+            let g2: Vec<Golden<ArkG2, SolG2>> =
+                serde_json::from_slice(&std::fs::read(golden_path("g2.json")).unwrap()).unwrap();
+            let g = G2Affine::prime_subgroup_generator();
+            assert_eq!((fq2(&g2[0].ark.x), fq2(&g2[0].ark.y)), (g.x, g.y));
+            assert!(g2[1].ark.infinity);
+        }
+    }
 }
@@ -6,9 +6,10 @@
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use jf_cap::proof::universal_setup_for_staging;
+use jf_cap::proof::UniversalParam;
 use jf_cap::TransactionVerifyingKey;
 use key_set::{KeySet, VerifierKeySet};
-use lazy_static::lazy_static;
+use once_cell::sync::OnceCell;
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaChaRng;
 
@@ -16,10 +17,20 @@ use crate::model::CAPE_MERKLE_HEIGHT;
 
 const MAX_DEGREE_SUPPORTED: usize = 2u64.pow(17) as usize;
 
-lazy_static! {
-    pub static ref UNIVERSAL_PARAM: jf_cap::proof::UniversalParam =
+static UNIVERSAL_PARAM: OnceCell<UniversalParam> = OnceCell::new();
+
+/// The universal structured reference string for CAP proofs.
+///
+/// The SRS is expensive to generate, so it is only computed the first time this function is
+/// called. Concurrent callers block until the first initialization completes and then all share
+/// the same instance.
+pub fn universal_param() -> &'static UniversalParam {
+    UNIVERSAL_PARAM.get_or_init(|| {
+        #[cfg(test)]
+        tests::SETUP_COUNT.fetch_add(1, std::sync::atomic::Ordering::SeqCst);
         universal_setup_for_staging(MAX_DEGREE_SUPPORTED, &mut ChaChaRng::from_seed([0u8; 32]))
-            .unwrap();
+            .unwrap()
+    })
 }
 
 pub const SUPPORTED_TRANSFER_SIZES: &[(usize, usize)] = &[(1, 2), (2, 2), (2, 3), (3, 3)];
@@ -28,7 +39,7 @@ pub const SUPPORTED_FREEZE_SIZES: &[usize] = &[2, 3];
 /// Compute the verifier keys for different types and sizes of CAP transactions.
 pub fn verifier_keys() -> VerifierKeySet {
     use TransactionVerifyingKey::*;
-    let univ_setup = &UNIVERSAL_PARAM;
+    let univ_setup = universal_param();
     let xfr_verif_keys = SUPPORTED_TRANSFER_SIZES.iter().map(|&(inputs, outputs)| {
         Transfer(
             jf_cap::proof::transfer::preprocess(univ_setup, inputs, outputs, CAPE_MERKLE_HEIGHT)
@@ -54,3 +65,24 @@ pub fn verifier_keys() -> VerifierKeySet {
         freeze: KeySet::new(freeze_verif_keys).unwrap(),
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::{AtomicUsize, Ordering};
+
+    pub(super) static SETUP_COUNT: AtomicUsize = AtomicUsize::new(0);
+
+    #[tokio::test(flavor = "multi_thread")]
+    async fn test_universal_param_initialized_once() {
+        let tasks = (0..8)
+            .map(|_| tokio::spawn(async { universal_param() as *const UniversalParam as usize }))
+            .collect::<Vec<_>>();
+        let mut addrs = vec![];
+        for task in tasks {
+            addrs.push(task.await.unwrap());
+        }
+        assert!(addrs.iter().all(|&addr| addr == addrs[0]));
+        assert_eq!(SETUP_COUNT.load(Ordering::SeqCst), 1);
+    }
+}
@@ -7,13 +7,13 @@
 
 //! This crate describes the workflow and interfaces of a CAPE contract deployed on Ethereum.
 
-use cap_rust_sandbox::model::{is_erc20_asset_def_valid, Erc20Code, EthereumAddr};
+use cap_rust_sandbox::cape::BurnNote;
+use cap_rust_sandbox::model::{is_erc20_asset_def_valid, CapeModelTxn, Erc20Code, EthereumAddr};
 use cap_rust_sandbox::types::GenericInto;
 use ethers::prelude::*;
 use itertools::Itertools;
 use jf_cap::keys::UserPubKey;
 use jf_cap::structs::{AssetDefinition, FreezeFlag, Nullifier, RecordCommitment, RecordOpening};
-use jf_cap::TransactionNote::Transfer;
 use jf_cap::{txn_batch_verify, MerkleCommitment, MerkleFrontier, NodeValue, TransactionNote};
 use std::collections::{HashMap, HashSet, LinkedList};
 
@@ -28,6 +28,21 @@ use crate::merkle_tree::RecordMerkleTree;
 #[derive(Debug, Clone)]
 pub struct NullifierRepeatedError;
 
+/// Reasons for rejecting an ERC20 deposit.
+#[derive(Debug, Clone, PartialEq, Eq)]
+pub enum DepositError {
+    /// The asset of the record is not registered in CAPE.
+    UnregisteredAsset,
+    /// The asset of the record is registered with a different ERC20 token.
+    AddressMismatch,
+    /// The record has a zero amount.
+    ZeroAmount,
+    /// The record is owned by the default public key, so nobody can spend it.
+    DefaultPubKey,
+    /// The record is frozen.
+    FrozenRecord,
+}
+
 /// A block in CAPE blockchain
 #[derive(Default, Clone)]
 pub struct CapeBlock {
@@ -41,14 +56,18 @@ pub struct CapeBlock {
 
 const CAPE_BURN_PREFIX_BYTES: &str = "EsSCAPE burn";
 const CAPE_BURN_PREFIX_BYTES_LEN: usize = 12;
+/// Position of the burned output among the outputs of a burn transaction, after the fee change.
+const POS_BURNED_RC: usize = 1;
 
 // Check that the transaction note corresponds to a transfer and that the prefix of the auxiliary
 // information corresponds to some burn transaction.
 fn is_burn_txn(txn: &TransactionNote) -> bool {
     match txn {
         TransactionNote::Transfer(tx) => {
-            tx.aux_info.extra_proof_bound_data[0..CAPE_BURN_PREFIX_BYTES_LEN]
-                == *CAPE_BURN_PREFIX_BYTES.as_bytes()
+            tx.aux_info
+                .extra_proof_bound_data
+                .get(0..CAPE_BURN_PREFIX_BYTES_LEN)
+                == Some(CAPE_BURN_PREFIX_BYTES.as_bytes())
         }
         TransactionNote::Mint(_) => false,
         TransactionNote::Freeze(_) => false,
@@ -97,7 +116,7 @@ impl CapeBlock {
             let merkle_root = txn.merkle_root();
             if recent_merkle_roots.contains(&merkle_root)
                 && CapeBlock::check_nullifiers_are_fresh(txn, contract_nullifiers)
-                && is_burn_txn(txn)
+                && CapeBlock::validate_burn_outputs(txn, &burned_ros[i])
             {
                 filtered_block.burn_txns.push(txn.clone());
                 filtered_burn_ros.push(burned_ros[i].clone());
@@ -134,11 +153,44 @@ impl CapeBlock {
         }
     }
 
+    /// Checks that `txn` is a burn transaction whose burned output (the second one) is opened by
+    /// `burned_ro`.
+    pub fn validate_burn_outputs(txn: &TransactionNote, burned_ro: &RecordOpening) -> bool {
+        let output_commitments = txn.output_commitments();
+        output_commitments.len() > POS_BURNED_RC
+            && is_burn_txn(txn)
+            && output_commitments[POS_BURNED_RC] == RecordCommitment::from(burned_ro)
+    }
+
     fn is_expired(txn: &TransactionNote, height: u64) -> bool {
-        match txn {
-            Transfer(tx) => tx.aux_info.valid_until < height,
-            _ => true,
-        }
+        CapeModelTxn::CAP(txn.clone()).is_expired(height)
+    }
+
+    /// Remove the standard transactions which are expired at `height`.
+    ///
+    /// Burn transactions never expire. Returns the number of transactions removed.
+    pub fn filter_expired(&mut self, height: u64) -> usize {
+        let len = self.txns.len();
+        self.txns.retain(|txn| !CapeBlock::is_expired(txn, height));
+        len - self.txns.len()
+    }
+
+    /// The input nullifiers of all transactions in the block, including burn transactions.
+    pub fn nullifier_set(&self) -> HashSet<Nullifier> {
+        self.nullifiers().collect()
+    }
+
+    /// Checks whether a nullifier is spent by more than one input in the block.
+    pub fn has_duplicate_nullifiers(&self) -> bool {
+        let mut seen = HashSet::new();
+        self.nullifiers().any(|nf| !seen.insert(nf))
+    }
+
+    fn nullifiers(&self) -> impl Iterator<Item = Nullifier> + '_ {
+        self.txns
+            .iter()
+            .chain(&self.burn_txns)
+            .flat_map(|txn| txn.nullifiers())
     }
 
     /// Checks that all the nullifiers of a transaction have not been published in a previous block
@@ -155,6 +207,18 @@ impl CapeBlock {
     }
 }
 
+/// A copy of the mutable state of a [CapeContract], to restore with [CapeContract::rollback].
+#[derive(Clone)]
+pub struct CapeSnapshot {
+    nullifiers: HashSet<Nullifier>,
+    height: u64,
+    merkle_commitment: MerkleCommitment,
+    mt_frontier: MerkleFrontier,
+    recent_merkle_roots: LinkedList<NodeValue>,
+    wrapped_erc20_registrar: HashMap<AssetDefinition, Address>,
+    pending_deposit_queue: Vec<RecordCommitment>,
+}
+
 /// State and methods of a CAPE contract
 pub struct CapeContract {
     /// set of spent records' nullifiers, stored as mapping in contract
@@ -178,28 +242,38 @@ pub struct CapeContract {
 }
 
 impl CapeContract {
-    /// Inserts a nullifier in the nullifiers hash set.
-    /// If the nullifier has already been inserted previously return an error.
-    /// In practice (solidity code), the ethereum transaction will be reverted and the smart contract state will be restored.
-    /// This approach, compared to checking no duplicates appear in the list of nullifiers for a block, aims at saving ethereum gas in the concrete solidity implementation.
-    fn insert_nullifier_or_revert(
-        &mut self,
-        nullifier: &Nullifier,
-    ) -> Result<(), NullifierRepeatedError> {
-        if self.nullifiers.contains(nullifier) {
-            Err(NullifierRepeatedError)
-        } else {
-            self.nullifiers.insert(*nullifier);
-            Ok(())
-        }
-    }
-
     /// Return the address of the contract.
     pub(crate) fn address(&self) -> Address {
         // NOTE: in Solidity, use expression: `address(this)`
         Address::from_low_u64_le(666u64)
     }
 
+    /// Capture the current state of the contract.
+    pub fn snapshot(&self) -> CapeSnapshot {
+        CapeSnapshot {
+            nullifiers: self.nullifiers.clone(),
+            height: self.height,
+            merkle_commitment: self.merkle_commitment,
+            mt_frontier: self.mt_frontier.clone(),
+            recent_merkle_roots: self.recent_merkle_roots.clone(),
+            wrapped_erc20_registrar: self.wrapped_erc20_registrar.clone(),
+            pending_deposit_queue: self.pending_deposit_queue.clone(),
+        }
+    }
+
+    /// Restore the state captured by [CapeContract::snapshot].
+    /// NOTE: this has no Solidity counterpart, where a reverted transaction leaves the state
+    /// untouched. It allows to model error recovery in tests.
+    pub fn rollback(&mut self, snapshot: CapeSnapshot) {
+        self.nullifiers = snapshot.nullifiers;
+        self.height = snapshot.height;
+        self.merkle_commitment = snapshot.merkle_commitment;
+        self.mt_frontier = snapshot.mt_frontier;
+        self.recent_merkle_roots = snapshot.recent_merkle_roots;
+        self.wrapped_erc20_registrar = snapshot.wrapped_erc20_registrar;
+        self.pending_deposit_queue = snapshot.pending_deposit_queue;
+    }
+
     /// Check if an asset is already registered.
     /// Assets need to be registered and bound to some ERC-20 before allowing users to wrap/unwrap.
     pub fn is_cape_asset_registered(&self, asset_def: &AssetDefinition) -> bool {
@@ -230,25 +304,53 @@ impl CapeContract {
         self.wrapped_erc20_registrar.insert(new_asset, erc20_addr);
     }
 
+    /// Check that `ro` is a valid record to credit for a deposit of the ERC20 token at
+    /// `erc20_addr`.
+    pub fn verify_erc20_deposit(
+        &self,
+        ro: &RecordOpening,
+        erc20_addr: Address,
+    ) -> Result<(), DepositError> {
+        // 1. verify matching registered CAPE asset and the erc20 address
+        let registered_addr = self
+            .wrapped_erc20_registrar
+            .get(&ro.asset_def)
+            .ok_or(DepositError::UnregisteredAsset)?;
+        if *registered_addr != erc20_addr {
+            return Err(DepositError::AddressMismatch);
+        }
+
+        // 1.1 (optional) more sanity check on user provided CAPE asset record,
+        // may help prevent users from crediting into some unspendable record or waste more gas.
+        if ro.amount == 0u64.into() {
+            return Err(DepositError::ZeroAmount);
+        }
+        if ro.pub_key == UserPubKey::default() {
+            // this would be EC point equality check
+            return Err(DepositError::DefaultPubKey);
+        }
+        if ro.freeze_flag != FreezeFlag::Unfrozen {
+            // just a boolean flag
+            return Err(DepositError::FrozenRecord);
+        }
+        Ok(())
+    }
+
     /// Deposit some ERC20 tokens so that these are wrapped into asset records
     /// NOTE: in Solidity, we can
     /// - avoid passing in `ro.freeze_flag` (e.g: to save a bit of gas)
     /// - remove `depositor` from input parameters, and directly replaced with `msg.sender`
-    pub fn deposit_erc20(&mut self, ro: RecordOpening, erc20_addr: Address, depositor: Address) {
-        let mut erc20_contract = Erc20Contract::at(erc20_addr);
-
-        // 1. verify matching registered CAPE asset and the erc20 address
-        assert_eq!(
-            self.wrapped_erc20_registrar.get(&ro.asset_def).unwrap(),
-            &erc20_addr,
-            "Mismatched `erc20_addr` for the CAPE record."
-        );
+    pub fn deposit_erc20(
+        &mut self,
+        ro: RecordOpening,
+        erc20_addr: Address,
+        depositor: Address,
+    ) -> Result<(), DepositError> {
+        // 1. validate the record against the registered asset, see `verify_erc20_deposit`.
+        // NOTE: in Solidity, a failed check reverts the transaction.
+        self.verify_erc20_deposit(&ro, erc20_addr)?;
 
-        // 1.1 (optional) more sanity check on user provided CAPE asset record,
-        // may help prevent users from crediting into some unspendable record or waste more gas.
-        assert!(ro.amount > 0u64.into());
-        assert_ne!(ro.pub_key, UserPubKey::default()); // this would be EC point equality check
-        assert_eq!(ro.freeze_flag, FreezeFlag::Unfrozen); // just a boolean flag
+        let mut erc20_contract = Erc20Contract::at(erc20_addr);
 
         // 2. attempt to `transferFrom` before mutating contract state to mitigate reentrancy attack
         erc20_contract.transfer_from(
@@ -264,6 +366,7 @@ impl CapeContract {
 
         // 4. append the commitment to pending queue to be inserted into the MT by relayer.
         self.pending_deposit_queue.push(rc);
+        Ok(())
     }
 
     /// Relayer submits the next block, and withdraw for users who had burn transactions included
@@ -273,7 +376,10 @@ impl CapeContract {
         &mut self,
         new_block: CapeBlock,
         burned_ros: Vec<RecordOpening>,
-    ) -> Result<(), NullifierRepeatedError> {
+    ) -> Result<CapeSnapshot, NullifierRepeatedError> {
+        // Keep the state prior to this block, for the caller to roll back to.
+        let snapshot = self.snapshot();
+
         // 1. verify the block, and insert its input nullifiers and output record commitments
         let (new_block, new_burned_ros) = new_block.validate(
             &self.recent_merkle_roots,
@@ -287,12 +393,15 @@ impl CapeContract {
         // * Empty blocks allow to flush the queue of asset records to be inserted into the merkle tree after some call to wrap/faucet
         // * As the block height is our proxy for time it looks desirable to be able to create new blocks even though no new transactions are produced
 
+        // Nullifiers must not be repeated inside a block. In practice (solidity code), the ethereum
+        // transaction will be reverted and the smart contract state will be restored.
+        if new_block.has_duplicate_nullifiers() {
+            return Err(NullifierRepeatedError);
+        }
+        self.nullifiers.extend(new_block.nullifier_set());
+
         let mut rc_to_be_inserted = vec![];
         for txn in new_block.txns.iter() {
-            for &nf in txn.nullifiers().iter() {
-                self.insert_nullifier_or_revert(&nf)
-                    .expect("Nullifiers should not be repeated inside a block.");
-            }
             rc_to_be_inserted.extend_from_slice(&txn.output_commitments());
         }
 
@@ -302,39 +411,29 @@ impl CapeContract {
             // burn transaction is basically a "Transfer-to-dedicated-burn-pk" transaction
             if let TransactionNote::Transfer(note) = burn_txn {
                 // 2.1. validate the burned record opening against the burn transaction.
-                let withdraw_amount = burned_ro.amount;
-                // get recipient address from txn's proof bounded data field
-                let recipient = {
-                    let mut proof_bounded_address = [0u8; 20];
-                    proof_bounded_address
-                        .copy_from_slice(&note.aux_info.extra_proof_bound_data[..20]);
-                    Address::from(proof_bounded_address)
-                };
-
                 // Since the transaction only contains record commitments,
                 // we require the user to provide the record opening and check against it.
-                assert_eq!(
-                    RecordCommitment::from(burned_ro),
-                    burn_txn.output_commitments()[1]
-                );
+                let burn_note = BurnNote::generate((**note).clone(), burned_ro.clone())
+                    .expect("burned record opening should match the burn transaction");
+                let withdraw_amount = burn_note.amount();
+                // get recipient address from txn's proof bounded data field
+                let recipient = burn_note
+                    .erc20_recipient()
+                    .expect("burn transaction should contain the recipient address");
 
                 let erc20_addr = self
                     .wrapped_erc20_registrar
-                    .get(&burned_ro.asset_def)
+                    .get(burn_note.asset_type())
                     .unwrap();
 
                 // 2.2. upon successful verification, execute the withdraw for user
                 let mut erc20_contract = Erc20Contract::at(*erc20_addr);
                 erc20_contract.transfer(recipient, withdraw_amount.generic_into::<u128>().into());
 
-                // 2.3. like other txn, insert input nullifiers and output record commitments
-                for &nf in burn_txn.nullifiers().iter() {
-                    self.nullifiers.insert(nf);
-                }
-
+                // 2.3. like other txn, insert output record commitments (the input nullifiers have
+                // already been inserted in step 1).
                 // We insert all the output commitments except the second one that corresponds to the burned output.
                 // That way we ensure that this burned output cannot be spent
-                const POS_BURNED_RC: usize = 1;
                 for (i, rc) in burn_txn.output_commitments().iter().enumerate() {
                     if i != POS_BURNED_RC {
                         rc_to_be_inserted.push(*rc);
@@ -366,18 +465,23 @@ impl CapeContract {
         // Store the new frontier
         self.mt_frontier = updated_mt_frontier;
 
-        Ok(())
+        Ok(snapshot)
     }
 }
 
 #[cfg(test)]
 mod test {
+    use cap_rust_sandbox::{
+        model::{erc20_asset_description, CAPE_MERKLE_HEIGHT},
+        universal_param::universal_param,
+    };
     use jf_cap::{
         keys::{AuditorKeyPair, FreezerKeyPair, UserKeyPair},
-        structs::{AssetCode, AssetPolicy, FreezeFlag},
-        transfer::TransferNote,
-        NodeValue,
+        structs::{AssetCode, AssetPolicy, FeeInput, FreezeFlag, TxnFeeInfo},
+        transfer::{TransferNote, TransferNoteInput},
+        AccMemberWitness, MerkleTree,
     };
+    use rand::{CryptoRng, RngCore};
 
     use super::*;
     use constants::*;
@@ -388,12 +492,8 @@ mod test {
             Self {
                 nullifiers: HashSet::default(),
                 height: 0,
-                merkle_commitment: MerkleCommitment {
-                    root_value: NodeValue::empty_node_value(),
-                    height: 20,
-                    num_leaves: 0,
-                },
-                mt_frontier: MerkleFrontier::Empty { height: 0 },
+                merkle_commitment: MerkleTree::new(CAPE_MERKLE_HEIGHT).unwrap().commitment(),
+                mt_frontier: MerkleTree::new(CAPE_MERKLE_HEIGHT).unwrap().frontier(),
                 recent_merkle_roots: LinkedList::default(),
                 wrapped_erc20_registrar: HashMap::default(),
                 pending_deposit_queue: vec![],
@@ -408,9 +508,8 @@ mod test {
         }
     }
 
-    fn usdc_cape_asset_def() -> AssetDefinition {
+    fn usdc_cape_asset_def(sponsor: Address) -> AssetDefinition {
         let mut rng = rand::thread_rng();
-        let asset_code = AssetCode::new_foreign(b"Official wrapped USDC in CAPE system.");
         let usdc_freezer = FreezerKeyPair::generate(&mut rng);
         let usdc_auditor = AuditorKeyPair::generate(&mut rng);
         // USDC have freezer, auditor, but temporarily leave credential issuer as empty
@@ -420,29 +519,105 @@ mod test {
             .set_auditor_pub_key(usdc_auditor.pub_key())
             .reveal_record_opening()
             .unwrap();
+        // The asset code commits to the ERC20 token, the sponsor and the policy, so that the
+        // contract can check the asset definition when it is sponsored.
+        let description = erc20_asset_description(
+            &Erc20Code(EthereumAddr(usdc_address().to_fixed_bytes())),
+            &EthereumAddr(sponsor.to_fixed_bytes()),
+            asset_policy.clone(),
+        );
+        let asset_code = AssetCode::new_foreign(&description);
         AssetDefinition::new(asset_code, asset_policy).unwrap()
     }
 
-    fn generate_burn_transaction(_ro: &RecordOpening) -> TransferNote {
-        // internally call `transfer.rs::generate_non_native()`
-        // for simplicity, we skip preparing for all input params.
-        unimplemented!();
+    /// Generate a transaction burning `burned_ro`, owned by `owner`, so that the ERC20 tokens it
+    /// wraps are withdrawn to `recipient`.
+    ///
+    /// Returns the transfer note and the opening of its burn output.
+    fn generate_burn_transaction(
+        rng: &mut (impl RngCore + CryptoRng),
+        owner: &UserKeyPair,
+        burned_ro: &RecordOpening,
+        recipient: Address,
+    ) -> (TransferNote, RecordOpening) {
+        // The user also owns a native record to pay the fee with. Both records need to be in the
+        // records merkle tree for the user to prove their membership.
+        let fee_ro = RecordOpening::new(
+            rng,
+            10u64.into(),
+            AssetDefinition::native(),
+            owner.pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+        let mut records = MerkleTree::new(CAPE_MERKLE_HEIGHT).unwrap();
+        records.push(RecordCommitment::from(&fee_ro).to_field_element());
+        records.push(RecordCommitment::from(burned_ro).to_field_element());
+
+        // 2 inputs: fee record and burned record
+        // 2 outputs: fee change record and burn output record
+        let xfr_prove_key =
+            jf_cap::proof::transfer::preprocess(universal_param(), 2, 2, CAPE_MERKLE_HEIGHT)
+                .unwrap()
+                .0;
+        let fee_input = FeeInput {
+            ro: fee_ro,
+            acc_member_witness: AccMemberWitness::lookup_from_tree(&records, 0)
+                .expect_ok()
+                .unwrap()
+                .1,
+            owner_keypair: owner,
+        };
+        let (txn_fee_info, _fee_change_ro) = TxnFeeInfo::new(rng, fee_input, 1u64.into()).unwrap();
+        let inputs = vec![TransferNoteInput {
+            ro: burned_ro.clone(),
+            acc_member_witness: AccMemberWitness::lookup_from_tree(&records, 1)
+                .expect_ok()
+                .unwrap()
+                .1,
+            owner_keypair: owner,
+            cred: None,
+        }];
+
+        // The burned amount is sent to the burn public key. It becomes the second output of the
+        // note, after the fee change.
+        let burn_ro = RecordOpening::new(
+            rng,
+            burned_ro.amount,
+            burned_ro.asset_def.clone(),
+            burn_pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+
+        // The proof is bound to the burn prefix followed by the recipient address.
+        let extra_proof_bound_data =
+            [CAPE_BURN_PREFIX_BYTES.as_bytes(), recipient.as_bytes()].concat();
+
+        let valid_until = 2u64.pow(jf_cap::constants::MAX_TIMESTAMP_LEN as u32) - 1;
+        let (note, _sig_key) = TransferNote::generate_non_native(
+            rng,
+            inputs,
+            &[burn_ro.clone()],
+            txn_fee_info,
+            valid_until,
+            &xfr_prove_key,
+            extra_proof_bound_data,
+        )
+        .unwrap();
+        (note, burn_ro)
     }
 
     #[test]
-    #[ignore = "ignore panic due to unimplemented logic"]
     fn asset_registration_workflow() {
         let mut cape_contract = CapeContract::mock();
         // 1. sponsor: design  the CAPE asset type (off-chain).
-        let asset_def = usdc_cape_asset_def();
         let sponsor = Address::random();
+        let asset_def = usdc_cape_asset_def(sponsor);
 
         // 2. sponsor: register the asset (on-L1-chain).
         cape_contract.sponsor_cape_asset(usdc_address(), sponsor, asset_def);
     }
 
     #[test]
-    #[ignore = "ignore panic due to unimplemented logic"]
     fn wrap_workflow() {
         let mut rng = rand::thread_rng();
         let mut cape_contract = CapeContract::mock();
@@ -451,8 +626,12 @@ mod test {
         let cape_user_keypair = UserKeyPair::generate(&mut rng);
         let eth_user_address = Address::random();
 
+        // 0. sponsor: register the asset (see `asset_registration_workflow`).
+        let sponsor = Address::random();
+        let asset_def = usdc_cape_asset_def(sponsor);
+        cape_contract.sponsor_cape_asset(usdc_address(), sponsor, asset_def.clone());
+
         // 1. user: fetch the CAPE asset definition from UI or sponsor.
-        let asset_def = usdc_cape_asset_def();
 
         // 2. user: invoke ERC20's approve (on-L1-chain)
         //
@@ -467,24 +646,30 @@ mod test {
             cape_user_keypair.pub_key(),
             FreezeFlag::Unfrozen,
         );
-        cape_contract.deposit_erc20(ro, usdc_address(), eth_user_address);
+        cape_contract
+            .deposit_erc20(ro, usdc_address(), eth_user_address)
+            .unwrap();
 
         // 4. relayer: build next block and user's deposit will be credited (inserted into
         // record merkle tree) when CAPE contract process the next valid block.
         let new_block = CapeBlock::build_next();
-        let _res = cape_contract.submit_cape_block(new_block, vec![]);
-        // Handle result of call to `submit_cape_block`.
+        cape_contract.submit_cape_block(new_block, vec![]).unwrap();
+        assert_eq!(cape_contract.merkle_commitment.num_leaves, 1);
+        assert!(cape_contract.pending_deposit_queue.is_empty());
     }
 
     #[test]
-    #[ignore = "ignore panic due to unimplemented logic"]
     fn unwrap_workflow() {
         let mut rng = rand::thread_rng();
         let mut cape_contract = CapeContract::mock();
         let cape_user_keypair = UserKeyPair::generate(&mut rng);
 
+        // 0. sponsor: register the asset (see `asset_registration_workflow`).
+        let sponsor = Address::random();
+        let asset_def = usdc_cape_asset_def(sponsor);
+        cape_contract.sponsor_cape_asset(usdc_address(), sponsor, asset_def.clone());
+
         // 1. user: build and send a burn transaction to relayer (off-chain)
-        let asset_def = usdc_cape_asset_def();
         let burn_amount = 1000u64;
         let burned_ro = RecordOpening::new(
             &mut rng,
@@ -493,22 +678,253 @@ mod test {
             cape_user_keypair.pub_key(),
             FreezeFlag::Unfrozen,
         );
-        let burn_txn = generate_burn_transaction(&burned_ro);
+        let eth_user_address = Address::random();
+        let (burn_txn, burn_output_ro) =
+            generate_burn_transaction(&mut rng, &cape_user_keypair, &burned_ro, eth_user_address);
+        assert_eq!(
+            BurnNote::generate(burn_txn.clone(), burn_output_ro.clone())
+                .unwrap()
+                .withdraw_recipient()
+                .unwrap(),
+            eth_user_address
+        );
 
         // 2. user: send over the burn_txn and burned_ro to relayer.
 
         // 3. relayer: build a new block containing the burn txn broadcasted by the user (off-chain)
+        let burn_txn = TransactionNote::Transfer(Box::new(burn_txn));
         let mut new_block = CapeBlock::build_next();
-        new_block
-            .burn_txns
-            .push(TransactionNote::Transfer(Box::new(burn_txn)));
-        let mut burned_ros = vec![];
-        burned_ros.push(burned_ro);
+        new_block.burn_txns.push(burn_txn.clone());
+        let burned_ros = vec![burn_output_ro];
+
+        // The records spent by the burn are in a records merkle tree whose root the contract
+        // accepts.
+        cape_contract
+            .recent_merkle_roots
+            .push_back(burn_txn.merkle_root());
 
-        let _res = cape_contract.submit_cape_block(new_block.clone(), burned_ros);
-        // Handle result of call to `submit_cape_block`.
+        cape_contract
+            .submit_cape_block(new_block, burned_ros)
+            .unwrap();
 
         // done! when CAPE contract process a new block,
         // it will automatically withdraw for user that has submitted the burn transaction.
+        // The burned records are spent, and only the fee change is added to the records.
+        assert_eq!(cape_contract.height, 1);
+        for nullifier in burn_txn.nullifiers() {
+            assert!(cape_contract.nullifiers.contains(&nullifier));
+        }
+        assert_eq!(cape_contract.merkle_commitment.num_leaves, 1);
+    }
+
+    #[test]
+    fn record_merkle_tree_batch_insert() {
+        let mut rng = rand::thread_rng();
+        let mut cape_contract = CapeContract::mock();
+        let mut random_comms = |n| {
+            (0..n)
+                .map(|_| RecordCommitment::from(&RecordOpening::rand_for_test(&mut rng)))
+                .collect::<Vec<_>>()
+        };
+
+        let (comm, frontier) = cape_contract
+            .batch_insert_with_frontier(cape_contract.mt_frontier.clone(), &random_comms(3));
+        cape_contract.merkle_commitment = comm;
+        cape_contract.mt_frontier = frontier;
+        assert_eq!(comm.num_leaves, 3);
+
+        let (comm, frontier) = cape_contract
+            .batch_insert_with_frontier(cape_contract.mt_frontier.clone(), &random_comms(10));
+        assert_eq!(comm.num_leaves, 3 + 10);
+        assert!(MerkleTree::restore_from_frontier(comm, &frontier).is_some());
+    }
+
+    #[test]
+    fn verify_erc20_deposit() {
+        let mut rng = rand::thread_rng();
+        let mut cape_contract = CapeContract::mock();
+        let sponsor = Address::random();
+        let asset_def = usdc_cape_asset_def(sponsor);
+        cape_contract.sponsor_cape_asset(usdc_address(), sponsor, asset_def.clone());
+        let owner = UserKeyPair::generate(&mut rng).pub_key();
+        let mut ro = |amount: u64, asset_def: &AssetDefinition, owner: &UserPubKey, flag| {
+            RecordOpening::new(
+                &mut rng,
+                amount.into(),
+                asset_def.clone(),
+                owner.clone(),
+                flag,
+            )
+        };
+
+        let valid = ro(10, &asset_def, &owner, FreezeFlag::Unfrozen);
+        assert_eq!(
+            cape_contract.verify_erc20_deposit(&valid, usdc_address()),
+            Ok(())
+        );
+        assert_eq!(
+            cape_contract.verify_erc20_deposit(&valid, Address::random()),
+            Err(DepositError::AddressMismatch)
+        );
+
+        let unregistered = ro(
+            10,
+            &usdc_cape_asset_def(Address::random()),
+            &owner,
+            FreezeFlag::Unfrozen,
+        );
+        assert_eq!(
+            cape_contract.verify_erc20_deposit(&unregistered, usdc_address()),
+            Err(DepositError::UnregisteredAsset)
+        );
+
+        let zero = ro(0, &asset_def, &owner, FreezeFlag::Unfrozen);
+        assert_eq!(
+            cape_contract.verify_erc20_deposit(&zero, usdc_address()),
+            Err(DepositError::ZeroAmount)
+        );
+
+        let unspendable = ro(10, &asset_def, &UserPubKey::default(), FreezeFlag::Unfrozen);
+        assert_eq!(
+            cape_contract.verify_erc20_deposit(&unspendable, usdc_address()),
+            Err(DepositError::DefaultPubKey)
+        );
+
+        let frozen = ro(10, &asset_def, &owner, FreezeFlag::Frozen);
+        assert_eq!(
+            cape_contract.verify_erc20_deposit(&frozen, usdc_address()),
+            Err(DepositError::FrozenRecord)
+        );
+
+        // A rejected deposit leaves the contract unchanged.
+        assert_eq!(
+            cape_contract.deposit_erc20(frozen, usdc_address(), Address::random()),
+            Err(DepositError::FrozenRecord)
+        );
+        assert!(cape_contract.pending_deposit_queue.is_empty());
+    }
+
+    #[test]
+    fn validate_burn_outputs() {
+        let mut rng = rand::thread_rng();
+        let owner = UserKeyPair::generate(&mut rng);
+        let burned_ro = RecordOpening::new(
+            &mut rng,
+            1000u64.into(),
+            usdc_cape_asset_def(Address::random()),
+            owner.pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+        let (burn_txn, burn_output_ro) =
+            generate_burn_transaction(&mut rng, &owner, &burned_ro, Address::random());
+        let txn = TransactionNote::Transfer(Box::new(burn_txn.clone()));
+        assert!(CapeBlock::validate_burn_outputs(&txn, &burn_output_ro));
+
+        // Wrong commitment: the opening of the burned input instead of the burned output.
+        assert!(!CapeBlock::validate_burn_outputs(&txn, &burned_ro));
+
+        // Not a burn: the proof is not bound to the burn prefix.
+        let mut not_burn = burn_txn.clone();
+        not_burn.aux_info.extra_proof_bound_data = vec![];
+        assert!(!CapeBlock::validate_burn_outputs(
+            &TransactionNote::Transfer(Box::new(not_burn)),
+            &burn_output_ro
+        ));
+
+        // Too few outputs: the burned output is missing.
+        let mut truncated = burn_txn;
+        truncated.output_commitments.truncate(1);
+        assert!(!CapeBlock::validate_burn_outputs(
+            &TransactionNote::Transfer(Box::new(truncated)),
+            &burn_output_ro
+        ));
+    }
+
+    #[test]
+    fn rollback_workflow() {
+        let mut rng = rand::thread_rng();
+        let mut cape_contract = CapeContract::mock();
+        let sponsor = Address::random();
+        let asset_def = usdc_cape_asset_def(sponsor);
+        cape_contract.sponsor_cape_asset(usdc_address(), sponsor, asset_def.clone());
+        let owner = UserKeyPair::generate(&mut rng).pub_key();
+        let mut deposit = |cape_contract: &mut CapeContract| {
+            let ro = RecordOpening::new(
+                &mut rng,
+                100u64.into(),
+                asset_def.clone(),
+                owner.clone(),
+                FreezeFlag::Unfrozen,
+            );
+            cape_contract
+                .deposit_erc20(ro, usdc_address(), Address::random())
+                .unwrap();
+        };
+
+        deposit(&mut cape_contract);
+        cape_contract
+            .submit_cape_block(CapeBlock::build_next(), vec![])
+            .unwrap();
+        let snapshot = cape_contract.snapshot();
+
+        deposit(&mut cape_contract);
+        let before_block = cape_contract
+            .submit_cape_block(CapeBlock::build_next(), vec![])
+            .unwrap();
+        assert_eq!(cape_contract.height, 2);
+        assert_eq!(cape_contract.merkle_commitment.num_leaves, 2);
+        // The returned snapshot holds the state right before the block, with a pending deposit.
+        assert_eq!(before_block.height, 1);
+        assert_eq!(before_block.pending_deposit_queue.len(), 1);
+
+        cape_contract.rollback(snapshot.clone());
+        assert_eq!(cape_contract.height, snapshot.height);
+        assert_eq!(cape_contract.merkle_commitment, snapshot.merkle_commitment);
+        assert_eq!(
+            cape_contract.recent_merkle_roots,
+            snapshot.recent_merkle_roots
+        );
+        assert_eq!(cape_contract.nullifiers, snapshot.nullifiers);
+        assert!(cape_contract.pending_deposit_queue.is_empty());
+        assert!(MerkleTree::restore_from_frontier(
+            cape_contract.merkle_commitment,
+            &cape_contract.mt_frontier
+        )
+        .is_some());
+    }
+
+    #[test]
+    fn filter_expired() {
+        let mut rng = rand::thread_rng();
+        let owner = UserKeyPair::generate(&mut rng);
+        let burned_ro = RecordOpening::new(
+            &mut rng,
+            1000u64.into(),
+            usdc_cape_asset_def(Address::random()),
+            owner.pub_key(),
+            FreezeFlag::Unfrozen,
+        );
+        let (burn_txn, _) =
+            generate_burn_transaction(&mut rng, &owner, &burned_ro, Address::random());
+        let with_valid_until = |valid_until| {
+            let mut note = burn_txn.clone();
+            note.aux_info.valid_until = valid_until;
+            TransactionNote::Transfer(Box::new(note))
+        };
+
+        let mut block = CapeBlock::build_next();
+        block.txns = vec![
+            with_valid_until(5),
+            with_valid_until(10),
+            with_valid_until(20),
+        ];
+        block.burn_txns = vec![with_valid_until(5)];
+
+        assert_eq!(block.filter_expired(10), 1);
+        assert_eq!(block.txns.len(), 2);
+        assert_eq!(block.burn_txns.len(), 1);
+        assert_eq!(block.filter_expired(10), 0);
+        assert_eq!(block.filter_expired(u64::MAX), 2);
+        assert!(block.txns.is_empty());
     }
 }
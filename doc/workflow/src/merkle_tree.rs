@@ -6,8 +6,7 @@
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 //! Describes the interface of the Records Merkle tree
-#![allow(unused_variables)]
-use jf_cap::{structs::RecordCommitment, MerkleCommitment, MerkleFrontier};
+use jf_cap::{structs::RecordCommitment, MerkleCommitment, MerkleFrontier, MerkleTree};
 
 use crate::CapeContract;
 
@@ -28,7 +27,7 @@ pub(crate) trait RecordMerkleTree {
 
 impl RecordMerkleTree for CapeContract {
     fn verify_frontier(&self, frontier: &MerkleFrontier) -> bool {
-        unimplemented!();
+        MerkleTree::restore_from_frontier(self.merkle_commitment, frontier).is_some()
     }
     fn batch_insert_with_frontier(
         &mut self,
@@ -36,6 +35,13 @@ impl RecordMerkleTree for CapeContract {
         rcs: &[RecordCommitment],
     ) -> (MerkleCommitment, MerkleFrontier) {
         assert!(self.verify_frontier(&current_frontier));
-        unimplemented!();
+        // NOTE: in Solidity, the tree is rebuilt from the frontier in memory, and only the new root
+        // and frontier are written to storage.
+        let mut mt = MerkleTree::restore_from_frontier(self.merkle_commitment, &current_frontier)
+            .expect("the frontier was verified above");
+        for rc in rcs {
+            mt.push(rc.to_field_element());
+        }
+        (mt.commitment(), mt.frontier())
     }
 }
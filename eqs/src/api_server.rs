@@ -8,15 +8,19 @@
 #![allow(clippy::format_push_string)]
 use crate::configuration::EQSOptions;
 use crate::errors::EQSNetError;
+use crate::metrics::EqsMetrics;
 use crate::query_result_state::QueryResultState;
+use crate::rate_limit::RateLimitMiddleware;
+use crate::reindex::Reindex;
 use crate::route_parsing::{RouteBinding, UrlSegmentType, UrlSegmentValue};
-use crate::routes::dispatch_url;
+use crate::routes::{dispatch_url, stream_events, CapeEvent};
 
 use async_std::{
     sync::{Arc, RwLock},
     task,
 };
 use net::server;
+use request_id::{RequestIdMiddleware, REQUEST_ID_HEADER};
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
 use std::collections::hash_map::HashMap;
@@ -27,6 +31,8 @@ use tide::{
     security::{CorsMiddleware, Origin},
     StatusCode,
 };
+use tide_websockets::WebSocket;
+use tokio::sync::broadcast;
 
 #[derive(Clone, Debug, Snafu, Serialize, Deserialize)]
 pub enum Error {
@@ -56,6 +62,8 @@ pub struct WebState {
     pub(crate) query_result_state: Arc<RwLock<QueryResultState>>,
     pub(crate) web_path: PathBuf,
     pub(crate) api: toml::Value,
+    pub(crate) event_sender: broadcast::Sender<CapeEvent>,
+    pub(crate) reindex: Arc<Reindex>,
 }
 
 // Get the route pattern that matches the URL of a request, and the bindings for parameters in the
@@ -207,25 +215,40 @@ async fn entry_page(req: tide::Request<WebState>) -> Result<tide::Response, tide
 pub(crate) fn init_web_server(
     opt: &EQSOptions,
     query_result_state: Arc<RwLock<QueryResultState>>,
+    event_sender: broadcast::Sender<CapeEvent>,
+    reindex: Arc<Reindex>,
 ) -> Result<task::JoinHandle<Result<(), std::io::Error>>, tide::Error> {
     let api = crate::disco::load_messages(&opt.api_path());
     let mut web_server = tide::with_state(WebState {
         query_result_state,
         web_path: opt.web_path(),
         api: api.clone(),
+        event_sender,
+        reindex,
     });
     web_server
+        .with(RequestIdMiddleware)
         .with(
             CorsMiddleware::new()
                 .allow_methods("GET, POST".parse::<HeaderValue>().unwrap())
                 .allow_headers("*".parse::<HeaderValue>().unwrap())
                 .allow_origin(Origin::from("*"))
+                .expose_headers(REQUEST_ID_HEADER.parse::<HeaderValue>().unwrap())
                 .allow_credentials(true),
         )
+        .with(RateLimitMiddleware::new(
+            opt.rate_limit_burst(),
+            opt.rate_limit_rps(),
+        ))
         .with(server::trace)
         .with(server::add_error_body::<_, EQSNetError>);
     web_server.at("/").get(crate::disco::compose_help);
 
+    // Push events to clients as blocks are processed, see [crate::routes::stream_events].
+    web_server
+        .at("/ws/events")
+        .get(WebSocket::new(stream_events));
+
     // Add routes from a configuration file.
     if let Some(api_map) = api["route"].as_table() {
         api_map.values().for_each(|v| {
@@ -246,9 +269,14 @@ pub(crate) fn init_web_server(
                     .collect(),
                 _ => panic!("Expecting a toml::String or toml::Array, but got: {:?}", &v),
             };
+            let post = v.get("METHOD").and_then(|method| method.as_str()) == Some("POST");
             for path in routes {
                 let mut route = web_server.at(&path);
-                route.get(entry_page);
+                if post {
+                    route.post(entry_page);
+                } else {
+                    route.get(entry_page);
+                }
             }
         });
     }
@@ -258,3 +286,303 @@ pub(crate) fn init_web_server(
     let join_handle = async_std::task::spawn(web_server.listen(addr));
     Ok(join_handle)
 }
+
+/// State of the admin server, which is not meant to be exposed publicly.
+#[derive(Clone)]
+pub(crate) struct AdminState {
+    pub(crate) metrics: Arc<EqsMetrics>,
+    pub(crate) reindex: Arc<Reindex>,
+}
+
+async fn serve_metrics(req: tide::Request<AdminState>) -> Result<tide::Response, tide::Error> {
+    let body =
+        req.state().metrics.encode().map_err(|err| {
+            tide::Error::from_str(StatusCode::InternalServerError, err.to_string())
+        })?;
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .content_type("text/plain; version=0.0.4")
+        .body(body)
+        .build())
+}
+
+/// Request a full rebuild of the state, which is then done by the polling task.
+///
+/// Fails with 409 Conflict if a reindex is already in progress.
+async fn reindex(req: tide::Request<AdminState>) -> Result<tide::Response, tide::Error> {
+    if !req.state().reindex.request() {
+        return Ok(tide::Response::builder(StatusCode::Conflict)
+            .body("A reindex is already in progress")
+            .build());
+    }
+    tracing::info!("Reindex requested");
+    Ok(tide::Response::builder(StatusCode::Accepted)
+        .body(tide::Body::from_json(&req.state().reindex.status())?)
+        .build())
+}
+
+async fn reindex_status(req: tide::Request<AdminState>) -> Result<tide::Response, tide::Error> {
+    Ok(tide::Response::builder(StatusCode::Ok)
+        .body(tide::Body::from_json(&req.state().reindex.status())?)
+        .build())
+}
+
+/// Serve the Prometheus metrics at `/metrics` and the reindex endpoints, on the port given by
+/// --metrics_port.
+///
+/// `POST /reindex` drops the state, so this port must only be reachable by operators.
+pub(crate) fn init_admin_server(
+    opt: &EQSOptions,
+    metrics: Arc<EqsMetrics>,
+    reindex: Arc<Reindex>,
+) -> task::JoinHandle<Result<(), std::io::Error>> {
+    let mut admin_server = tide::with_state(AdminState { metrics, reindex });
+    admin_server.with(server::trace);
+    admin_server.at("/metrics").get(serve_metrics);
+
+    // Rebuild the state from the chain, see [crate::reindex].
+    admin_server.at("/reindex").post(reindex);
+    admin_server.at("/reindex/status").get(reindex_status);
+
+    let addr = format!("0.0.0.0:{}", opt.metrics_port());
+    async_std::task::spawn(admin_server.listen(addr))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::configuration::Confirmations;
+    use crate::eth_polling::EthPolling;
+    use crate::reindex::ReindexStatus;
+    use crate::routes::REINDEXING_HEADER;
+    use crate::store::{open_store, StoreBackend};
+    use async_std::stream::StreamExt;
+    use async_tungstenite::async_std::connect_async;
+    use cap_rust_sandbox::universal_param::verifier_keys;
+    use ethers::prelude::{Address, H256};
+    use portpicker::pick_unused_port;
+    use std::time::Duration;
+    use tempdir::TempDir;
+
+    fn options_for_test(eqs_port: u16, metrics_port: u16) -> EQSOptions {
+        EQSOptions {
+            config: None,
+            web_path: String::new(),
+            api_path: [env!("CARGO_MANIFEST_DIR"), "api", "api.toml"]
+                .iter()
+                .collect::<PathBuf>()
+                .to_str()
+                .unwrap()
+                .to_string(),
+            store_path: String::new(),
+            rpc_url: String::new(),
+            cape_address: None,
+            temp_test_run: true,
+            reset_store_state: false,
+            query_interval: 500,
+            ethers_block_max: 5000,
+            eqs_port,
+            metrics_port,
+            num_confirmations: Confirmations::default(),
+            snapshot_interval: 1000,
+            snapshots_retained: 10,
+            finality_depth: 12,
+            blocks_retained: 100000,
+            rate_limit_burst: 200,
+            rate_limit_rps: 100.0,
+            genesis_block: None,
+            store_backend: Default::default(),
+        }
+    }
+
+    #[async_std::test]
+    async fn test_stream_events() {
+        let port = pick_unused_port().unwrap();
+        let opt = options_for_test(port, pick_unused_port().unwrap());
+        let state = Arc::new(RwLock::new(QueryResultState::new(verifier_keys())));
+        let (event_sender, _) = broadcast::channel(10);
+        init_web_server(
+            &opt,
+            state,
+            event_sender.clone(),
+            Arc::new(Reindex::default()),
+        )
+        .unwrap();
+
+        let url = format!("ws://localhost:{}/ws/events", port);
+        let (mut ws, _) = loop {
+            match connect_async(&url).await {
+                Ok(conn) => break conn,
+                Err(_) => task::sleep(Duration::from_millis(100)).await,
+            }
+        };
+        // Wait for the handler to subscribe before publishing anything.
+        while event_sender.receiver_count() == 0 {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Publish the events the EQS emits when it processes a block.
+        let events = vec![
+            CapeEvent::NewBlock {
+                height: 1,
+                block_hash: H256::from_low_u64_be(1),
+            },
+            CapeEvent::NewNullifiers {
+                height: 1,
+                nullifiers: vec![],
+            },
+            CapeEvent::NewCommitments {
+                height: 1,
+                commitments: vec![],
+            },
+            CapeEvent::Rollback { height: 1 },
+        ];
+        for event in events.clone() {
+            event_sender.send(event).unwrap();
+        }
+        for expected in events {
+            let msg = ws.next().await.unwrap().unwrap();
+            let event: CapeEvent = serde_json::from_str(msg.to_text().unwrap()).unwrap();
+            assert_eq!(event, expected);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_reindex() {
+        let port = pick_unused_port().unwrap();
+        let opt = options_for_test(port, pick_unused_port().unwrap());
+        let state = Arc::new(RwLock::new(QueryResultState::new(verifier_keys())));
+        let (event_sender, _) = broadcast::channel(10);
+        let reindex = Arc::new(Reindex::default());
+        init_web_server(&opt, state, event_sender, reindex.clone()).unwrap();
+        init_admin_server(&opt, Arc::new(EqsMetrics::new().unwrap()), reindex.clone());
+
+        let base = format!("http://localhost:{}", port);
+        let admin = format!("http://localhost:{}", opt.metrics_port);
+        let state_url = format!("{}/get_cap_state", base);
+        let status_url = format!("{}/reindex/status", admin);
+        scrape(&state_url).await;
+        scrape(&status_url).await;
+
+        // The reindex endpoints are only served on the admin port.
+        let res = surf::post(format!("{}/reindex", base)).await.unwrap();
+        assert_ne!(res.status(), StatusCode::Accepted);
+        assert!(!reindex.is_active());
+
+        let res = surf::post(format!("{}/reindex", admin)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::Accepted);
+        assert!(reindex.is_active());
+        // Only one reindex can run at a time.
+        let res = surf::post(format!("{}/reindex", admin)).await.unwrap();
+        assert_eq!(res.status(), StatusCode::Conflict);
+
+        // Simulate the polling task resetting the state and replaying part of the chain.
+        assert!(reindex.take_request());
+        reindex.record_progress(40, 160);
+
+        let mut res = surf::get(&state_url).await.unwrap();
+        assert_eq!(res.status(), StatusCode::ServiceUnavailable);
+        assert_eq!(res.header(REINDEXING_HEADER).unwrap().as_str(), "25");
+        let body: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(
+            body,
+            serde_json::json!({"status": "reindexing", "progress": 25})
+        );
+        let status: ReindexStatus = serde_json::from_str(&scrape(&status_url).await).unwrap();
+        assert_eq!(
+            status,
+            ReindexStatus {
+                blocks_processed: 40,
+                total_blocks: 160
+            }
+        );
+        let body: serde_json::Value =
+            serde_json::from_str(&scrape(&format!("{}/healthcheck", base)).await).unwrap();
+        assert_eq!(body["status"], "available");
+
+        // Once caught up, the state is served again.
+        reindex.finish();
+        let mut res = surf::get(&state_url).await.unwrap();
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert!(res.header(REINDEXING_HEADER).is_none());
+        let body: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(body["num_events"], 0);
+        let status: ReindexStatus = serde_json::from_str(&scrape(&status_url).await).unwrap();
+        assert_eq!(status.blocks_processed, 160);
+    }
+
+    #[async_std::test]
+    async fn test_reindex_closes_event_streams() {
+        let port = pick_unused_port().unwrap();
+        let opt = options_for_test(port, pick_unused_port().unwrap());
+        let state = Arc::new(RwLock::new(QueryResultState::new(verifier_keys())));
+        let (event_sender, _) = broadcast::channel(10);
+        let reindex = Arc::new(Reindex::default());
+        init_web_server(&opt, state, event_sender.clone(), reindex.clone()).unwrap();
+
+        let url = format!("ws://localhost:{}/ws/events", port);
+        let (mut ws, _) = loop {
+            match connect_async(&url).await {
+                Ok(conn) => break conn,
+                Err(_) => task::sleep(Duration::from_millis(100)).await,
+            }
+        };
+        while event_sender.receiver_count() == 0 {
+            task::sleep(Duration::from_millis(10)).await;
+        }
+
+        // Simulate the polling task resetting the state.
+        assert!(reindex.request());
+        assert!(reindex.take_request());
+        reindex.notify_reset();
+
+        let msg = ws.next().await.unwrap().unwrap();
+        assert!(msg.is_close());
+    }
+
+    async fn scrape(url: &str) -> String {
+        loop {
+            match surf::get(url).recv_string().await {
+                Ok(body) => break body,
+                Err(_) => task::sleep(Duration::from_millis(100)).await,
+            }
+        }
+    }
+
+    #[async_std::test]
+    async fn test_metrics() {
+        let mut opt = options_for_test(pick_unused_port().unwrap(), pick_unused_port().unwrap());
+        let metrics = Arc::new(EqsMetrics::new().unwrap());
+        init_admin_server(&opt, metrics.clone(), Arc::new(Reindex::default()));
+        let url = format!("http://localhost:{}/metrics", opt.metrics_port);
+        let body = scrape(&url).await;
+        assert!(body.contains("cape_eqs_blocks_processed_total 0"));
+        assert!(body.contains("cape_eqs_eth_poll_errors_total 0"));
+
+        // Poll an Ethereum node which is not running. Each failed poll is counted, and no block is
+        // processed.
+        let store_dir = TempDir::new("test_metrics_store").unwrap();
+        opt.cape_address = Some(Address::zero());
+        opt.rpc_url = format!("http://localhost:{}", pick_unused_port().unwrap());
+        opt.temp_test_run = false;
+        let (event_sender, _) = broadcast::channel(10);
+        let mut polling = EthPolling::new(
+            &opt,
+            Arc::new(RwLock::new(QueryResultState::new(verifier_keys()))),
+            open_store(StoreBackend::File, store_dir.path(), true).unwrap(),
+            event_sender,
+            metrics.clone(),
+            Arc::new(Reindex::default()),
+        )
+        .await;
+        for _ in 0..2 {
+            polling.check().await.unwrap_err();
+        }
+
+        let body = scrape(&url).await;
+        assert!(body.contains("cape_eqs_eth_poll_errors_total 2"));
+        assert!(body.contains("cape_eqs_blocks_processed_total 0"));
+        assert!(body.contains("cape_eqs_nullifiers_indexed_total 0"));
+        assert!(body.contains("cape_eqs_commitments_indexed_total 0"));
+        assert!(body.contains("cape_eqs_block_processing_duration_seconds_count 0"));
+    }
+}
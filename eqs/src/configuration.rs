@@ -5,17 +5,20 @@
 // This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
+use crate::genesis::GenesisSnapshot;
+use crate::store::StoreBackend;
 use dirs::data_local_dir;
 use ethers::prelude::Address;
 use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
 use std::{
-    env,
+    env, fs, io,
     num::{NonZeroU64, ParseIntError},
-    path::PathBuf,
+    path::{Path, PathBuf},
     str::FromStr,
     time::Duration,
 };
-use structopt::StructOpt;
+use structopt::{clap::ArgMatches, StructOpt};
 
 #[derive(Debug, Clone, Copy, Serialize, Deserialize, PartialEq, Eq, PartialOrd)]
 pub struct Confirmations(u64);
@@ -46,13 +49,59 @@ impl FromStr for Confirmations {
     }
 }
 
+const DEFAULT_RPC_URL: &str = "http://localhost:8545";
+const DEFAULT_QUERY_INTERVAL_MS: &str = "500";
+const DEFAULT_ETHERS_BLOCK_MAX: &str = "5000";
+const DEFAULT_EQS_PORT: &str = "50087";
+const DEFAULT_METRICS_PORT: &str = "50088";
+const DEFAULT_NUM_CONFIRMATIONS: &str = "6";
+const DEFAULT_SNAPSHOT_INTERVAL: &str = "1000";
+const DEFAULT_SNAPSHOTS_RETAINED: &str = "10";
+const DEFAULT_FINALITY_DEPTH: &str = "12";
+const DEFAULT_BLOCKS_RETAINED: &str = "100000";
+const DEFAULT_RATE_LIMIT_BURST: &str = "200";
+const DEFAULT_RATE_LIMIT_RPS: &str = "100";
+
+#[derive(Debug, Snafu)]
+pub enum ConfigError {
+    #[snafu(display("failed to read config file {}: {}", path.display(), source))]
+    ReadConfig {
+        path: PathBuf,
+        source: std::io::Error,
+    },
+
+    #[snafu(display("failed to parse config file {}: {}", path.display(), source))]
+    ParseConfig {
+        path: PathBuf,
+        source: toml::de::Error,
+    },
+
+    #[snafu(display("a CAPE contract address is required unless temp_test_run is set"))]
+    MissingCapeAddress,
+
+    #[snafu(display("rate_limit_rps must be a positive finite number, not {}", rps))]
+    InvalidRateLimitRps { rps: f64 },
+
+    #[snafu(display("rate_limit_burst must be at least 1"))]
+    InvalidRateLimitBurst,
+}
+
 // TODO: migrate to clap; clap 3.0 incorporates most of StructOpt
-#[derive(Debug, StructOpt)]
+#[derive(Debug, Deserialize, StructOpt)]
+#[serde(default, deny_unknown_fields)]
 #[structopt(
     name = "CAPE Ethereum Query Server",
     about = "Monitors for changes on the CAPE contract, provides query service for contract state"
 )]
 pub struct EQSOptions {
+    /// TOML file with default values for the other options.
+    ///
+    /// Options given on the command line or through the environment take
+    /// precedence over the values in this file.
+    #[structopt(long, parse(from_os_str))]
+    #[serde(skip)]
+    pub config: Option<PathBuf>,
+
     /// Path to assets including web server files.
     #[structopt(long = "assets", default_value = "")]
     pub web_path: String,
@@ -71,12 +120,12 @@ pub struct EQSOptions {
     #[structopt(
         long,
         env = "CAPE_WEB3_PROVIDER_URL",
-        default_value = "http://localhost:8545"
+        default_value = DEFAULT_RPC_URL
     )]
     pub rpc_url: String,
 
     /// Address for CAPE contract
-    #[structopt(long, env = "CAPE_CONTRACT_ADDRESS", required_unless("temp-test-run"))]
+    #[structopt(long, env = "CAPE_CONTRACT_ADDRESS")]
     pub cape_address: Option<Address>,
 
     /// Invoke as a test-only instance; will create and use test contract
@@ -89,23 +138,69 @@ pub struct EQSOptions {
     pub reset_store_state: bool,
 
     /// Polling interval, in milliseconds, for commits to the contract.
-    #[structopt(long, default_value = "500", env = "CAPE_EQS_POLL_INTERVAL_MS")]
+    #[structopt(long, default_value = DEFAULT_QUERY_INTERVAL_MS, env = "CAPE_EQS_POLL_INTERVAL_MS")]
     pub query_interval: u64,
 
     /// maximum number of ETH blocks to select on for events.
-    #[structopt(long, default_value = "5000", env = "CAPE_EQS_MAX_ETHER_QUERY_BLOCKS")]
+    #[structopt(long, default_value = DEFAULT_ETHERS_BLOCK_MAX, env = "CAPE_EQS_MAX_ETHER_QUERY_BLOCKS")]
     pub ethers_block_max: u64,
 
     /// Web service port .
-    #[structopt(long, default_value = "50087", env = "CAPE_EQS_PORT")]
+    #[structopt(long, default_value = DEFAULT_EQS_PORT, env = "CAPE_EQS_PORT")]
     pub eqs_port: u16,
 
+    /// Port of the admin service, serving the Prometheus metrics and the reindex endpoints.
+    ///
+    /// It must not be exposed publicly.
+    #[structopt(long, default_value = DEFAULT_METRICS_PORT, env = "CAPE_EQS_METRICS_PORT")]
+    pub metrics_port: u16,
+
     /// Number of Ethereum block confirmations required to include a block.
     /// Setting it to 1 means a block is accepted as valid when first seen.
     /// The default values of 6 makes it unlikely larger re-orgs are observed
     /// on the Goerli testnet.
-    #[structopt(long, default_value = "6", env = "CAPE_EQS_NUM_CONFIRMATIONS")]
+    #[structopt(long, default_value = DEFAULT_NUM_CONFIRMATIONS, env = "CAPE_EQS_NUM_CONFIRMATIONS")]
     pub num_confirmations: Confirmations,
+
+    /// Number of CAPE blocks between full snapshots of the state.
+    #[structopt(long, default_value = DEFAULT_SNAPSHOT_INTERVAL, env = "CAPE_EQS_SNAPSHOT_INTERVAL")]
+    pub snapshot_interval: u64,
+
+    /// Number of most recent snapshots kept on disk, or 0 to keep all of them.
+    #[structopt(long, default_value = DEFAULT_SNAPSHOTS_RETAINED, env = "CAPE_EQS_SNAPSHOTS_RETAINED")]
+    pub snapshots_retained: usize,
+
+    /// Number of processed Ethereum blocks tracked to detect chain reorganizations.
+    #[structopt(long, default_value = DEFAULT_FINALITY_DEPTH, env = "CAPE_EQS_FINALITY_DEPTH")]
+    pub finality_depth: usize,
+
+    /// Number of most recent CAPE blocks kept for the `blocks` query, or 0 to keep all of them.
+    ///
+    /// The encoded blocks are part of the persisted state, so this bounds its size.
+    #[structopt(long, default_value = DEFAULT_BLOCKS_RETAINED, env = "CAPE_EQS_BLOCKS_RETAINED")]
+    pub blocks_retained: u64,
+
+    /// Number of requests a client can make in a burst before being rate limited.
+    #[structopt(long, default_value = DEFAULT_RATE_LIMIT_BURST, env = "CAPE_EQS_RATE_LIMIT_BURST")]
+    pub rate_limit_burst: u32,
+
+    /// Number of requests per second a client can sustain.
+    #[structopt(long, default_value = DEFAULT_RATE_LIMIT_RPS, env = "CAPE_EQS_RATE_LIMIT_RPS")]
+    pub rate_limit_rps: f64,
+
+    /// Genesis snapshot to start from when there is no persisted state, as created by
+    /// `cape-cli eqs snapshot`.
+    ///
+    /// Either the base64 encoded snapshot, or `@` followed by the path of a file containing it.
+    /// Ethereum polling starts after the block of the snapshot.
+    #[structopt(long)]
+    pub genesis_block: Option<String>,
+
+    /// Storage backend of the persisted state, `file` or `sqlite`.
+    ///
+    /// The `sqlite` backend requires the `sqlite-store` feature.
+    #[structopt(long, default_value = "file", env = "CAPE_EQS_STORE_BACKEND")]
+    pub store_backend: StoreBackend,
 }
 
 fn default_data_path() -> PathBuf {
@@ -116,7 +211,113 @@ fn default_data_path() -> PathBuf {
     data_dir
 }
 
+impl Default for EQSOptions {
+    fn default() -> Self {
+        Self {
+            config: None,
+            web_path: String::new(),
+            api_path: String::new(),
+            store_path: String::new(),
+            rpc_url: DEFAULT_RPC_URL.to_string(),
+            cape_address: None,
+            temp_test_run: false,
+            reset_store_state: false,
+            query_interval: DEFAULT_QUERY_INTERVAL_MS.parse().unwrap(),
+            ethers_block_max: DEFAULT_ETHERS_BLOCK_MAX.parse().unwrap(),
+            eqs_port: DEFAULT_EQS_PORT.parse().unwrap(),
+            metrics_port: DEFAULT_METRICS_PORT.parse().unwrap(),
+            num_confirmations: DEFAULT_NUM_CONFIRMATIONS.parse().unwrap(),
+            snapshot_interval: DEFAULT_SNAPSHOT_INTERVAL.parse().unwrap(),
+            snapshots_retained: DEFAULT_SNAPSHOTS_RETAINED.parse().unwrap(),
+            finality_depth: DEFAULT_FINALITY_DEPTH.parse().unwrap(),
+            blocks_retained: DEFAULT_BLOCKS_RETAINED.parse().unwrap(),
+            rate_limit_burst: DEFAULT_RATE_LIMIT_BURST.parse().unwrap(),
+            rate_limit_rps: DEFAULT_RATE_LIMIT_RPS.parse().unwrap(),
+            genesis_block: None,
+            store_backend: StoreBackend::default(),
+        }
+    }
+}
+
 impl EQSOptions {
+    /// Read options from a TOML file whose keys are the field names of
+    /// [EQSOptions]. Missing keys take their default value.
+    pub fn from_toml_file(path: &Path) -> Result<Self, ConfigError> {
+        let contents = fs::read_to_string(path).context(ReadConfigSnafu { path })?;
+        let opt: Self = toml::from_str(&contents).context(ParseConfigSnafu { path })?;
+        opt.check_rate_limit()?;
+        Ok(opt)
+    }
+
+    /// Parse the command line, filling in the options which are neither given
+    /// on the command line nor through the environment from the `--config`
+    /// file, if any.
+    pub fn from_args_and_config() -> Result<Self, ConfigError> {
+        Self::from_matches_and_config(&Self::clap().get_matches())
+    }
+
+    fn from_matches_and_config(matches: &ArgMatches) -> Result<Self, ConfigError> {
+        let mut opt = Self::from_clap(matches);
+        if let Some(path) = &opt.config {
+            let file = Self::from_toml_file(path)?;
+            // An option keeps its default value if it is given neither on the
+            // command line nor through its environment variable.
+            let is_default = |arg: &str, var: Option<&str>| {
+                matches.occurrences_of(arg) == 0 && var.map_or(true, |var| env::var(var).is_err())
+            };
+            macro_rules! fill_from_file {
+                ($($field:ident: $arg:literal, $var:expr;)*) => {
+                    $(
+                        if is_default($arg, $var) {
+                            opt.$field = file.$field;
+                        }
+                    )*
+                };
+            }
+            fill_from_file! {
+                web_path: "web-path", None;
+                api_path: "api-path", None;
+                store_path: "store-path", Some("CAPE_EQS_STORE_PATH");
+                rpc_url: "rpc-url", Some("CAPE_WEB3_PROVIDER_URL");
+                cape_address: "cape-address", Some("CAPE_CONTRACT_ADDRESS");
+                temp_test_run: "temp-test-run", None;
+                reset_store_state: "reset-store-state", None;
+                query_interval: "query-interval", Some("CAPE_EQS_POLL_INTERVAL_MS");
+                ethers_block_max: "ethers-block-max", Some("CAPE_EQS_MAX_ETHER_QUERY_BLOCKS");
+                eqs_port: "eqs-port", Some("CAPE_EQS_PORT");
+                metrics_port: "metrics-port", Some("CAPE_EQS_METRICS_PORT");
+                num_confirmations: "num-confirmations", Some("CAPE_EQS_NUM_CONFIRMATIONS");
+                snapshot_interval: "snapshot-interval", Some("CAPE_EQS_SNAPSHOT_INTERVAL");
+                snapshots_retained: "snapshots-retained", Some("CAPE_EQS_SNAPSHOTS_RETAINED");
+                finality_depth: "finality-depth", Some("CAPE_EQS_FINALITY_DEPTH");
+                blocks_retained: "blocks-retained", Some("CAPE_EQS_BLOCKS_RETAINED");
+                rate_limit_burst: "rate-limit-burst", Some("CAPE_EQS_RATE_LIMIT_BURST");
+                rate_limit_rps: "rate-limit-rps", Some("CAPE_EQS_RATE_LIMIT_RPS");
+                genesis_block: "genesis-block", None;
+                store_backend: "store-backend", Some("CAPE_EQS_STORE_BACKEND");
+            }
+        }
+        if opt.cape_address.is_none() && !opt.temp_test_run {
+            return Err(ConfigError::MissingCapeAddress);
+        }
+        opt.check_rate_limit()?;
+        Ok(opt)
+    }
+
+    /// Check that the rate limit refills (`rate_limit_rps` is positive and finite) and lets
+    /// requests through (`rate_limit_burst` is at least 1).
+    fn check_rate_limit(&self) -> Result<(), ConfigError> {
+        if !(self.rate_limit_rps.is_finite() && self.rate_limit_rps > 0.0) {
+            return Err(ConfigError::InvalidRateLimitRps {
+                rps: self.rate_limit_rps,
+            });
+        }
+        if self.rate_limit_burst == 0 {
+            return Err(ConfigError::InvalidRateLimitBurst);
+        }
+        Ok(())
+    }
+
     pub fn web_path(&self) -> PathBuf {
         let web_path = &self.web_path;
         if web_path.is_empty() {
@@ -152,6 +353,10 @@ impl EQSOptions {
         }
     }
 
+    pub(crate) fn store_backend(&self) -> StoreBackend {
+        self.store_backend
+    }
+
     pub(crate) fn rpc_url(&self) -> &str {
         &self.rpc_url
     }
@@ -179,4 +384,182 @@ impl EQSOptions {
     pub(crate) fn eqs_port(&self) -> u16 {
         self.eqs_port
     }
+
+    pub(crate) fn metrics_port(&self) -> u16 {
+        self.metrics_port
+    }
+
+    pub(crate) fn snapshot_interval(&self) -> u64 {
+        self.snapshot_interval
+    }
+
+    pub(crate) fn snapshots_retained(&self) -> usize {
+        self.snapshots_retained
+    }
+
+    pub(crate) fn finality_depth(&self) -> usize {
+        self.finality_depth
+    }
+
+    pub(crate) fn blocks_retained(&self) -> u64 {
+        self.blocks_retained
+    }
+
+    pub(crate) fn rate_limit_burst(&self) -> u32 {
+        self.rate_limit_burst
+    }
+
+    pub(crate) fn rate_limit_rps(&self) -> f64 {
+        self.rate_limit_rps
+    }
+
+    /// Decode the `--genesis-block` snapshot, reading it from a file if needed.
+    pub(crate) fn genesis_snapshot(&self) -> io::Result<Option<GenesisSnapshot>> {
+        let encoded = match &self.genesis_block {
+            Some(arg) => match arg.strip_prefix('@') {
+                Some(path) => fs::read_to_string(path)?,
+                None => arg.clone(),
+            },
+            None => return Ok(None),
+        };
+        GenesisSnapshot::from_base64(&encoded)
+            .map(Some)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err.to_string()))
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tempdir::TempDir;
+
+    fn write_config(dir: &TempDir, contents: &str) -> PathBuf {
+        let path = dir.path().join("eqs.toml");
+        fs::write(&path, contents).unwrap();
+        path
+    }
+
+    #[test]
+    fn test_from_toml_file() {
+        let dir = TempDir::new("eqs_config").unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+                web_path = "/web"
+                api_path = "/api/api.toml"
+                store_path = "/store"
+                rpc_url = "http://geth:8545"
+                cape_address = "0x0000000000000000000000000000000000000001"
+                temp_test_run = true
+                reset_store_state = true
+                query_interval = 1
+                ethers_block_max = 2
+                eqs_port = 3
+                metrics_port = 4
+                num_confirmations = 5
+                snapshot_interval = 6
+                snapshots_retained = 7
+                finality_depth = 8
+                rate_limit_burst = 10
+                rate_limit_rps = 11.5
+                blocks_retained = 12
+                store_backend = "sqlite"
+            "#,
+        );
+        let opt = EQSOptions::from_toml_file(&path).unwrap();
+        assert_eq!(opt.config, None);
+        assert_eq!(opt.web_path, "/web");
+        assert_eq!(opt.api_path, "/api/api.toml");
+        assert_eq!(opt.store_path, "/store");
+        assert_eq!(opt.rpc_url, "http://geth:8545");
+        assert_eq!(opt.cape_address, Some(Address::from_low_u64_be(1)));
+        assert!(opt.temp_test_run);
+        assert!(opt.reset_store_state);
+        assert_eq!(opt.query_interval, 1);
+        assert_eq!(opt.ethers_block_max, 2);
+        assert_eq!(opt.eqs_port, 3);
+        assert_eq!(opt.metrics_port, 4);
+        assert_eq!(opt.num_confirmations, Confirmations::from_str("5").unwrap());
+        assert_eq!(opt.snapshot_interval, 6);
+        assert_eq!(opt.snapshots_retained, 7);
+        assert_eq!(opt.finality_depth, 8);
+        assert_eq!(opt.rate_limit_burst, 10);
+        assert_eq!(opt.rate_limit_rps, 11.5);
+        assert_eq!(opt.blocks_retained, 12);
+        assert_eq!(opt.store_backend, StoreBackend::Sqlite);
+
+        // Missing keys take their default value, unknown keys are rejected.
+        let path = write_config(&dir, "eqs_port = 3");
+        let opt = EQSOptions::from_toml_file(&path).unwrap();
+        assert_eq!(opt.eqs_port, 3);
+        assert_eq!(opt.finality_depth, 12);
+        let path = write_config(&dir, "eqs_prot = 3");
+        assert!(matches!(
+            EQSOptions::from_toml_file(&path),
+            Err(ConfigError::ParseConfig { .. })
+        ));
+    }
+
+    #[test]
+    fn test_invalid_rate_limit() {
+        let dir = TempDir::new("eqs_config").unwrap();
+        for rps in ["0.0", "-1.0", "nan", "inf"] {
+            let path = write_config(&dir, &format!("rate_limit_rps = {}", rps));
+            assert!(matches!(
+                EQSOptions::from_toml_file(&path),
+                Err(ConfigError::InvalidRateLimitRps { .. })
+            ));
+        }
+        let path = write_config(&dir, "rate_limit_burst = 0");
+        assert!(matches!(
+            EQSOptions::from_toml_file(&path),
+            Err(ConfigError::InvalidRateLimitBurst)
+        ));
+
+        // Values given on the command line are checked as well.
+        for (arg, value) in [("--rate-limit-rps", "0"), ("--rate-limit-burst", "0")] {
+            let matches =
+                EQSOptions::clap().get_matches_from(vec!["eqs", "--temp-test-run", arg, value]);
+            assert!(EQSOptions::from_matches_and_config(&matches).is_err());
+        }
+    }
+
+    #[test]
+    fn test_command_line_overrides_config_file() {
+        let dir = TempDir::new("eqs_config").unwrap();
+        let path = write_config(
+            &dir,
+            r#"
+                web_path = "/web"
+                api_path = "/api/api.toml"
+                temp_test_run = true
+                eqs_port = 3
+            "#,
+        );
+        let matches = EQSOptions::clap().get_matches_from(vec![
+            "eqs",
+            "--config",
+            path.to_str().unwrap(),
+            "--assets",
+            "/cli/web",
+            "--eqs-port",
+            "4",
+        ]);
+        let opt = EQSOptions::from_matches_and_config(&matches).unwrap();
+        assert_eq!(opt.web_path, "/cli/web");
+        assert_eq!(opt.eqs_port, 4);
+        assert_eq!(opt.api_path, "/api/api.toml");
+        assert!(opt.temp_test_run);
+
+        // Without a contract address, a test run must be requested.
+        let path = write_config(&dir, "temp_test_run = false");
+        let matches =
+            EQSOptions::clap().get_matches_from(vec!["eqs", "--config", path.to_str().unwrap()]);
+        if env::var("CAPE_CONTRACT_ADDRESS").is_err() {
+            assert!(matches!(
+                EQSOptions::from_matches_and_config(&matches),
+                Err(ConfigError::MissingCapeAddress)
+            ));
+        }
+    }
 }
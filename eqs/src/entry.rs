@@ -5,12 +5,14 @@
 // This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-use crate::api_server::init_web_server;
+use crate::api_server::{init_admin_server, init_web_server};
 use crate::configuration::EQSOptions;
 use crate::eth_polling::EthPolling;
+use crate::genesis::GenesisSnapshot;
+use crate::metrics::EqsMetrics;
 use crate::query_result_state::QueryResultState;
-use crate::state_persistence::StatePersistence;
-use atomic_store::PersistenceError;
+use crate::reindex::Reindex;
+use crate::store::{open_store, EqsStore};
 
 use async_std::{
     sync::{Arc, RwLock},
@@ -20,6 +22,21 @@ use cap_rust_sandbox::{
     ethereum::{ensure_connected_to_contract, get_provider_from_url},
     universal_param::verifier_keys,
 };
+use tokio::sync::broadcast;
+
+/// Number of events buffered for each WebSocket client before it is considered to lag behind.
+const EVENT_CHANNEL_CAPACITY: usize = 1000;
+
+fn restore_genesis(
+    genesis: GenesisSnapshot,
+    store: &dyn EqsStore,
+) -> std::io::Result<QueryResultState> {
+    tracing::info!(
+        "Starting from the genesis snapshot at Ethereum block {}",
+        genesis.genesis_block_number
+    );
+    genesis.restore(store)
+}
 
 pub async fn run(opt: &EQSOptions) -> std::io::Result<()> {
     tracing::info!("Starting EQS");
@@ -31,32 +48,59 @@ pub async fn run(opt: &EQSOptions) -> std::io::Result<()> {
             .unwrap();
     }
 
-    let (state_persistence, query_result_state) = if opt.reset_state() {
-        (
-            StatePersistence::new(&opt.store_path(), "eth_query").unwrap(),
-            Arc::new(RwLock::new(QueryResultState::new(verifier_keys()))),
-        )
-    } else {
-        let tic = std::time::Instant::now();
-        let state_persistence = StatePersistence::load(&opt.store_path(), "eth_query").unwrap();
-        let query_result_state = Arc::new(RwLock::new(
-            state_persistence.load_latest_state().unwrap_or_else(|err| {
-                if let PersistenceError::FailedToFindExpectedResource { key: _ } = err {
-                    QueryResultState::new(verifier_keys())
-                } else {
-                    panic!("{:?}", err);
-                }
-            }),
-        ));
-        let toc = std::time::Instant::now();
-        tracing::info!("Restored state in {:?}", toc - tic);
-        (state_persistence, query_result_state)
+    let genesis = opt.genesis_snapshot()?;
+    let tic = std::time::Instant::now();
+    let store = open_store(opt.store_backend(), &opt.store_path(), opt.reset_state())?;
+    let mut query_result_state = match store.load_latest_state() {
+        Ok((state, height)) => {
+            if genesis.is_some() {
+                tracing::warn!("Ignoring the genesis snapshot, the store already has a state");
+            }
+            tracing::info!("Restored state at block {} in {:?}", height, tic.elapsed());
+            state
+        }
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => match genesis {
+            Some(genesis) => restore_genesis(genesis, &*store)?,
+            None => QueryResultState::new(verifier_keys()),
+        },
+        Err(err) => {
+            tracing::error!("Failed to load the persisted state: {}", err);
+            return Err(err);
+        }
     };
 
-    let _api_handle = init_web_server(opt, query_result_state.clone()).unwrap();
+    query_result_state.blocks_retained = opt.blocks_retained();
+    let query_result_state = Arc::new(RwLock::new(query_result_state));
+
+    let (event_sender, _) = broadcast::channel(EVENT_CHANNEL_CAPACITY);
+    let reindex = Arc::new(Reindex::default());
+    let _api_handle = init_web_server(
+        opt,
+        query_result_state.clone(),
+        event_sender.clone(),
+        reindex.clone(),
+    )
+    .unwrap();
+    let metrics = Arc::new(EqsMetrics::new().unwrap());
+    let _admin_handle = init_admin_server(opt, metrics.clone(), reindex.clone());
+    let _snapshot_handle = store
+        .snapshot_scheduler(
+            query_result_state.clone(),
+            opt.snapshot_interval(),
+            opt.snapshots_retained(),
+        )
+        .map(|scheduler| scheduler.spawn(opt.query_interval()));
 
     // will replace with subscription in phase 3
-    let mut eth_poll = EthPolling::new(opt, query_result_state, state_persistence).await;
+    let mut eth_poll = EthPolling::new(
+        opt,
+        query_result_state,
+        store,
+        event_sender,
+        metrics,
+        reindex,
+    )
+    .await;
 
     loop {
         if let Ok(_height) = eth_poll.check().await {}
@@ -6,8 +6,11 @@
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::configuration::{Confirmations, EQSOptions};
-use crate::query_result_state::{EthEventIndex, QueryResultState};
-use crate::state_persistence::StatePersistence;
+use crate::metrics::EqsMetrics;
+use crate::query_result_state::{BlockQueryResponse, EthEventIndex, QueryResultState};
+use crate::reindex::Reindex;
+use crate::routes::CapeEvent;
+use crate::store::EqsStore;
 
 use async_std::sync::{Arc, RwLock};
 use cap_rust_sandbox::{
@@ -15,22 +18,50 @@ use cap_rust_sandbox::{
     ethereum::EthConnection,
     ledger::{CapeTransactionKind, CapeTransition},
     model::{CapeModelTxn, Erc20Code, EthereumAddr},
-    types::{AssetCodeSol, CAPEEvents, RecordOpening as RecordOpeningSol},
+    types::{self as sol, AssetCodeSol, CAPEEvents, RecordOpening as RecordOpeningSol},
 };
 use commit::Committable;
 use core::mem;
-use ethers::abi::AbiDecode;
-use ethers::prelude::Middleware;
-use jf_cap::structs::{ReceiverMemo, RecordCommitment};
+use ethers::abi::{AbiDecode, AbiEncode};
+use ethers::prelude::{Middleware, H256};
+use jf_cap::structs::{Nullifier, ReceiverMemo, RecordCommitment};
 use jf_cap::{structs::RecordOpening, MerkleTree, TransactionNote};
 use rand_chacha::rand_core::SeedableRng;
 use rand_chacha::ChaChaRng;
 use reef::traits::{Block, Transaction};
 use seahorse::events::LedgerEvent;
+use std::collections::VecDeque;
+use std::time::Instant;
+use tokio::sync::broadcast;
+
+/// An Ethereum block processed by the EQS, with what is needed to resume processing right after
+/// it if the blocks that follow are reorganized away.
+#[derive(Clone, Debug)]
+struct BlockCheckpoint {
+    number: u64,
+    hash: H256,
+    /// The CAPE block height after processing the Ethereum block.
+    state_number: u64,
+    last_event_index: Option<EthEventIndex>,
+    last_reported_index: Option<EthEventIndex>,
+    pending_commit_event: Vec<CapeTransition>,
+}
+
+/// Return the position of the most recent checkpoint which is still part of the chain, given the
+/// current hashes of the checkpointed blocks.
+fn common_ancestor(
+    history: &VecDeque<BlockCheckpoint>,
+    current_hashes: &[Option<H256>],
+) -> Option<usize> {
+    history
+        .iter()
+        .zip(current_hashes)
+        .rposition(|(checkpoint, hash)| Some(checkpoint.hash) == *hash)
+}
 
 pub(crate) struct EthPolling {
     pub query_result_state: Arc<RwLock<QueryResultState>>,
-    pub state_persistence: StatePersistence,
+    pub store: Box<dyn EqsStore>,
     /// The index of the last event incorporated into `self`.
     pub last_event_index: Option<EthEventIndex>,
     /// The index of the earliest block which we might not have finished processing.
@@ -39,24 +70,40 @@ pub(crate) struct EthPolling {
     pub pending_commit_event: Vec<CapeTransition>,
     pub connection: EthConnection,
     pub num_confirmations: Confirmations,
+    pub event_sender: broadcast::Sender<CapeEvent>,
+    pub metrics: Arc<EqsMetrics>,
+    pub reindex: Arc<Reindex>,
+    /// The most recently processed blocks, used to detect chain reorganizations.
+    ///
+    /// This is not persisted, so a reorganization happening while the EQS is down goes unnoticed.
+    block_history: VecDeque<BlockCheckpoint>,
+    finality_depth: usize,
 }
 
 impl EthPolling {
     pub async fn new(
         opt: &EQSOptions,
         query_result_state: Arc<RwLock<QueryResultState>>,
-        state_persistence: StatePersistence,
+        store: Box<dyn EqsStore>,
+        event_sender: broadcast::Sender<CapeEvent>,
+        metrics: Arc<EqsMetrics>,
+        reindex: Arc<Reindex>,
     ) -> EthPolling {
         if opt.temp_test_run() {
             return EthPolling {
                 query_result_state,
-                state_persistence,
+                store,
                 pending_commit_event: Vec::new(),
                 last_event_index: None,
                 next_block_to_query: 0,
                 max_blocks_to_query: opt.max_ether_blocks(),
                 connection: EthConnection::for_test().await,
                 num_confirmations: opt.num_confirmations,
+                event_sender,
+                metrics,
+                reindex,
+                block_history: VecDeque::new(),
+                finality_depth: opt.finality_depth(),
             };
         }
 
@@ -114,18 +161,187 @@ impl EthPolling {
 
         EthPolling {
             query_result_state,
-            state_persistence,
+            store,
             last_event_index,
             next_block_to_query,
             max_blocks_to_query: opt.max_ether_blocks(),
             pending_commit_event: Vec::new(),
             connection,
             num_confirmations: opt.num_confirmations,
+            event_sender,
+            metrics,
+            reindex,
+            block_history: VecDeque::new(),
+            finality_depth: opt.finality_depth(),
+        }
+    }
+
+    /// Notify WebSocket subscribers of a newly committed block.
+    fn publish_block(
+        &self,
+        height: u64,
+        block_hash: H256,
+        nullifiers: Vec<Nullifier>,
+        commitments: Vec<RecordCommitment>,
+    ) {
+        if self.reindex.is_active() {
+            // Subscribers have already seen the events being replayed.
+            return;
+        }
+        for event in [
+            CapeEvent::NewBlock { height, block_hash },
+            CapeEvent::NewNullifiers { height, nullifiers },
+            CapeEvent::NewCommitments {
+                height,
+                commitments,
+            },
+        ] {
+            // Sending only fails if there are no subscribers, in which case there is nobody to
+            // notify.
+            let _ = self.event_sender.send(event);
+        }
+    }
+
+    async fn block_hash(&self, number: u64) -> Result<Option<H256>, async_std::io::Error> {
+        let block = self
+            .connection
+            .provider
+            .get_block(number)
+            .await
+            .map_err(|e| {
+                tracing::error!("Could not fetch block {}: {:?}", number, e);
+                async_std::io::Error::new(
+                    async_std::io::ErrorKind::Other,
+                    format!("Could not fetch block {}", number),
+                )
+            })?;
+        Ok(block.and_then(|block| block.hash))
+    }
+
+    /// Remember that all events up to and including Ethereum block `number` have been processed.
+    async fn push_checkpoint(&mut self, number: u64, hash: H256) {
+        let (state_number, last_reported_index) = {
+            let state = self.query_result_state.read().await;
+            (state.ledger_state.state_number, state.last_reported_index)
+        };
+        self.block_history.push_back(BlockCheckpoint {
+            number,
+            hash,
+            state_number,
+            last_event_index: self.last_event_index,
+            last_reported_index,
+            pending_commit_event: self.pending_commit_event.clone(),
+        });
+        while self.block_history.len() > self.finality_depth {
+            self.block_history.pop_front();
         }
     }
 
+    /// Check whether the processed blocks are still part of the chain, and if not, roll back to
+    /// the last one which is, so that the following blocks are processed again.
+    async fn handle_reorg(&mut self) -> Result<(), async_std::io::Error> {
+        let (number, hash) = match self.block_history.back() {
+            Some(checkpoint) => (checkpoint.number, checkpoint.hash),
+            None => return Ok(()),
+        };
+        if self.block_hash(number).await? == Some(hash) {
+            return Ok(());
+        }
+
+        let mut current_hashes = Vec::with_capacity(self.block_history.len());
+        for checkpoint in &self.block_history {
+            current_hashes.push(self.block_hash(checkpoint.number).await?);
+        }
+        let ancestor = match common_ancestor(&self.block_history, &current_hashes) {
+            Some(ancestor) => ancestor,
+            None => {
+                // None of the processed blocks we know of is left, so the state cannot be rolled
+                // back: rebuild it from the first block, as for `POST /reindex`.
+                tracing::error!(
+                    "Chain reorganization deeper than the finality depth of {} blocks, \
+                     reindexing. Consider a larger CAPE_EQS_FINALITY_DEPTH",
+                    self.finality_depth
+                );
+                if !self.reindex.request() {
+                    // A reindex is already replaying the events, start it over.
+                    self.reset_for_reindex().await;
+                }
+                return Err(async_std::io::Error::new(
+                    async_std::io::ErrorKind::Other,
+                    format!(
+                        "chain reorganization deeper than the finality depth of {} blocks",
+                        self.finality_depth
+                    ),
+                ));
+            }
+        };
+        self.block_history.truncate(ancestor + 1);
+        let checkpoint = self.block_history[ancestor].clone();
+        tracing::warn!(
+            "Chain reorganization detected, rolling back to block {} (CAPE block {})",
+            checkpoint.number,
+            checkpoint.state_number
+        );
+
+        {
+            let mut state = self.query_result_state.write().await;
+            state.rollback_to(checkpoint.state_number);
+            state.last_reported_index = checkpoint.last_reported_index;
+            self.store
+                .save_state(&state, checkpoint.state_number)
+                .expect("Failed to persist state");
+        }
+        if let Err(err) = self.store.remove_snapshots_after(checkpoint.state_number) {
+            tracing::warn!("Failed to remove snapshots: {}", err);
+        }
+        // Sending only fails if there are no subscribers.
+        let _ = self.event_sender.send(CapeEvent::Rollback {
+            height: checkpoint.state_number,
+        });
+        self.last_event_index = checkpoint.last_event_index;
+        self.pending_commit_event = checkpoint.pending_commit_event;
+        self.next_block_to_query = checkpoint.number + 1;
+        Ok(())
+    }
+
+    /// Drop the state and start processing the events again from the first block, as requested
+    /// with `POST /reindex`.
+    async fn reset_for_reindex(&mut self) {
+        tracing::warn!("Reindexing, all the events will be processed again");
+        {
+            let mut state = self.query_result_state.write().await;
+            state.reset();
+            self.store
+                .save_state(&state, 0)
+                .expect("Failed to persist state");
+        }
+        if let Err(err) = self.store.remove_snapshots_after(0) {
+            tracing::warn!("Failed to remove snapshots: {}", err);
+        }
+        self.last_event_index = None;
+        self.pending_commit_event.clear();
+        self.block_history.clear();
+        // The deployment block of the contract is not known, so start from the beginning, as for
+        // a new state.
+        self.next_block_to_query = 0;
+        self.reindex.notify_reset();
+    }
+
     pub async fn check(&mut self) -> Result<u64, async_std::io::Error> {
+        if self.reindex.take_request() {
+            self.reset_for_reindex().await;
+        }
+        let result = self.poll().await;
+        if result.is_err() {
+            self.metrics.record_poll_error();
+        }
+        result
+    }
+
+    async fn poll(&mut self) -> Result<u64, async_std::io::Error> {
         loop {
+            self.handle_reorg().await?;
+
             let fetch_from = self.next_block_to_query;
             let latest_block_number = self
                 .connection
@@ -151,12 +367,19 @@ impl EthPolling {
             };
 
             if fetch_latest < fetch_from {
+                if self.reindex.is_active() {
+                    self.reindex.finish();
+                }
                 break;
             }
 
             let fetch_until = std::cmp::min(fetch_latest, fetch_from + self.max_blocks_to_query);
 
             self.check_range(fetch_from, fetch_until).await?;
+            if self.reindex.is_active() {
+                self.reindex
+                    .record_progress(self.next_block_to_query, fetch_latest + 1);
+            }
         }
         Ok(self.next_block_to_query)
     }
@@ -170,6 +393,10 @@ impl EthPolling {
         // select cape events starting from the first block for which we do not have confirmed
         // completion of processing
 
+        // Fetch the hash of the last block before its events, so that a reorganization happening
+        // in between is detected on the next check.
+        let to_block_hash = self.block_hash(to_block).await?;
+
         let new_event_result = self
             .connection
             .contract
@@ -214,16 +441,15 @@ impl EthPolling {
 
             match filter {
                 CAPEEvents::BlockCommittedFilter(filter_data) => {
+                    let processing_start = Instant::now();
                     let memos = fetch_cape_memos(&self.connection, meta.transaction_hash)
                         .await
                         .unwrap()
                         .unwrap();
 
-                    let model_txns = decode_cape_block_from_event(filter_data)
-                        .unwrap()
-                        .into_cape_transactions()
-                        .unwrap()
-                        .0;
+                    let block = decode_cape_block_from_event(filter_data).unwrap();
+                    let block_bytes = sol::CapeBlock::from(block.clone()).encode().into();
+                    let model_txns = block.into_cape_transactions().unwrap().0;
 
                     // TODO Instead of panicking here we need to handle cases of missing memos gracefully
                     let num_txn = model_txns.len();
@@ -340,6 +566,11 @@ impl EthPolling {
                     let mut updated_state = self.query_result_state.write().await;
                     updated_state.events.push(commit_event);
                     updated_state.events.append(&mut memo_events);
+                    updated_state.insert_block(BlockQueryResponse {
+                        height: block_id,
+                        block: block_bytes,
+                        eth_txn_hash: meta.transaction_hash,
+                    });
 
                     //update merkle tree
                     if let Some(merkle_tree) = merkle_tree {
@@ -347,6 +578,7 @@ impl EthPolling {
                             merkle_tree.commitment();
                         updated_state.ledger_state.record_merkle_frontier = merkle_tree.frontier();
                     }
+                    updated_state.insert_record_commitments(&output_record_commitments);
 
                     //update transaction_by_id and transaction_id_by_hash hashmap
                     let mut record_index = 0;
@@ -378,6 +610,23 @@ impl EthPolling {
                     updated_state.last_reported_index = Some(current_index);
                     self.last_event_index = Some(current_index);
                     persist_state = true;
+
+                    let nullifiers = transitions
+                        .iter()
+                        .flat_map(|transition| transition.proven_nullifiers())
+                        .map(|(nullifier, _)| nullifier)
+                        .collect::<Vec<_>>();
+                    self.metrics.record_block(
+                        processing_start.elapsed(),
+                        nullifiers.len(),
+                        output_record_commitments.len(),
+                    );
+                    self.publish_block(
+                        block_id,
+                        meta.block_hash,
+                        nullifiers,
+                        output_record_commitments,
+                    );
                 }
                 CAPEEvents::Erc20TokensDepositedFilter(filter_data) => {
                     let ro_bytes = filter_data.ro_bytes.clone();
@@ -398,6 +647,7 @@ impl EthPolling {
                     self.last_event_index = Some(current_index);
                 }
                 CAPEEvents::FaucetInitializedFilter(filter_data) => {
+                    let processing_start = Instant::now();
                     // Obtain record opening
                     let ro_sol: RecordOpeningSol = AbiDecode::decode(filter_data.ro_bytes).unwrap();
                     let ro = ro_sol.into();
@@ -447,6 +697,7 @@ impl EthPolling {
 
                     updated_state.ledger_state.record_merkle_commitment = merkle_tree.commitment();
                     updated_state.ledger_state.record_merkle_frontier = merkle_tree.frontier();
+                    updated_state.insert_record_commitments(&[rc]);
                     updated_state
                         .events
                         .append(&mut vec![commit_event, memo_event]);
@@ -469,6 +720,9 @@ impl EthPolling {
                     updated_state.last_reported_index = Some(current_index);
                     self.last_event_index = Some(current_index);
                     persist_state = true;
+
+                    self.metrics.record_block(processing_start.elapsed(), 0, 1);
+                    self.publish_block(0, meta.block_hash, vec![], vec![rc]);
                 }
 
                 CAPEEvents::AssetSponsoredFilter(filter_data) => {
@@ -488,13 +742,183 @@ impl EthPolling {
 
         // persist the state block updates (will be more fine grained in r3)
         if persist_state {
-            self.state_persistence
-                .store_latest_state(&*self.query_result_state.read().await);
+            let state = self.query_result_state.read().await;
+            self.store
+                .save_state(&state, state.ledger_state.state_number)
+                .expect("Failed to persist state");
         }
 
         // We won't ever get here if we haven't successfully processed all events up to and including any in `to_block` from the query range.
         // This means the block we care about isn't the one in `current_index`, it's `to_block`, and if we fail partway, we're going to short circuit to the error return, not here.
         self.next_block_to_query = to_block + 1;
+        if let Some(hash) = to_block_hash {
+            self.push_checkpoint(to_block, hash).await;
+        }
         Ok(())
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use cap_rust_sandbox::ledger::{CapeBlock, CommittedCapeTransition};
+    use cap_rust_sandbox::universal_param::verifier_keys;
+    use ethers::prelude::{Address, Bytes};
+    use rand_chacha::ChaChaRng;
+
+    /// Commit a CAPE block wrapping a single random record, the way `check_range` does.
+    fn commit_wrap_block(state: &mut QueryResultState, rng: &mut ChaChaRng) {
+        let ro = RecordOpening::rand_for_test(rng);
+        let comm = RecordCommitment::from(&ro);
+        let transition = CapeTransition::Wrap {
+            ro: Box::new(ro),
+            erc20_code: Erc20Code(EthereumAddr([0; 20])),
+            src_addr: EthereumAddr([0; 20]),
+        };
+        let block_id = state.ledger_state.state_number;
+
+        state.events.push(LedgerEvent::Commit {
+            block: CapeBlock::new(vec![transition.clone()]),
+            block_id,
+            state_comm: block_id + 1,
+        });
+        state.transaction_by_id.insert(
+            (block_id, 0),
+            CommittedCapeTransition {
+                block_id,
+                txn_id: 0,
+                output_start: state.record_merkle_tree.num_leaves(),
+                output_size: 1,
+                transition: transition.clone(),
+            },
+        );
+        state
+            .transaction_id_by_hash
+            .insert(transition.commit(), (block_id, 0));
+        state.insert_block(BlockQueryResponse {
+            height: block_id,
+            block: Bytes::default(),
+            eth_txn_hash: H256::from_low_u64_be(block_id),
+        });
+        state.insert_record_commitments(&[comm]);
+        state.ledger_state.record_merkle_commitment = state.record_merkle_tree.commitment();
+        state.ledger_state.record_merkle_frontier = state.record_merkle_tree.frontier();
+        state.ledger_state.state_number += 1;
+    }
+
+    fn checkpoint(number: u64, state: &QueryResultState) -> BlockCheckpoint {
+        BlockCheckpoint {
+            number,
+            hash: H256::from_low_u64_be(number),
+            state_number: state.ledger_state.state_number,
+            last_event_index: Some((number, 0)),
+            last_reported_index: Some((number, 0)),
+            pending_commit_event: vec![],
+        }
+    }
+
+    #[test]
+    fn test_rollback_on_reorg() {
+        let mut rng = ChaChaRng::from_seed([0; 32]);
+        let mut state = QueryResultState::new(verifier_keys());
+
+        // Process Ethereum blocks 10 to 13, each committing one CAPE block.
+        let mut history = VecDeque::new();
+        let mut states = vec![];
+        for number in 10..14 {
+            commit_wrap_block(&mut state, &mut rng);
+            history.push_back(checkpoint(number, &state));
+            states.push(state.clone());
+        }
+
+        // Nothing changed on chain.
+        let hashes = history
+            .iter()
+            .map(|checkpoint| Some(checkpoint.hash))
+            .collect::<Vec<_>>();
+        assert_eq!(common_ancestor(&history, &hashes), Some(3));
+
+        // Blocks 12 and 13 are replaced by different blocks.
+        let mut reorged = hashes.clone();
+        reorged[2] = Some(H256::from_low_u64_be(100));
+        reorged[3] = None;
+        let ancestor = common_ancestor(&history, &reorged).unwrap();
+        assert_eq!(ancestor, 1);
+        assert_eq!(history[ancestor].number, 11);
+
+        // The state reverts to the one right after block 11.
+        state.rollback_to(history[ancestor].state_number);
+        let expected = &states[ancestor];
+        assert_eq!(state.ledger_state.state_number, 2);
+        assert_eq!(state.events.len(), expected.events.len());
+        let mut txn_ids = state.transaction_by_id.keys().collect::<Vec<_>>();
+        txn_ids.sort();
+        assert_eq!(txn_ids, vec![&(0, 0), &(1, 0)]);
+        assert_eq!(
+            state.transaction_id_by_hash,
+            expected.transaction_id_by_hash
+        );
+        assert_eq!(state.blocks, expected.blocks);
+        assert_eq!(
+            state.record_merkle_tree.commitment(),
+            expected.record_merkle_tree.commitment()
+        );
+        assert_eq!(
+            state.ledger_state.record_merkle_commitment,
+            expected.ledger_state.record_merkle_commitment
+        );
+        assert!(state.get_merkle_proof(2).is_none());
+
+        // Reprocessing a block after the rollback continues from the common ancestor.
+        commit_wrap_block(&mut state, &mut rng);
+        assert_eq!(state.ledger_state.state_number, 3);
+        assert_eq!(state.record_merkle_tree.num_leaves(), 3);
+
+        // A reorganization deeper than the tracked history has no common ancestor.
+        assert_eq!(common_ancestor(&history, &[None; 4]), None);
+    }
+
+    #[test]
+    fn test_reindex_replay() {
+        let mut rng = ChaChaRng::from_seed([0; 32]);
+        let mut state = QueryResultState::new(verifier_keys());
+        state.contract_address = Some(Address::from_low_u64_be(1));
+        for _ in 0..3 {
+            commit_wrap_block(&mut state, &mut rng);
+        }
+        let original = state.clone();
+
+        // Resetting drops everything learned from the chain, but keeps the configuration.
+        state.reset();
+        assert_eq!(state.ledger_state.state_number, 0);
+        assert!(state.events.is_empty());
+        assert!(state.transaction_by_id.is_empty());
+        assert!(state.blocks.is_empty());
+        assert_eq!(state.record_merkle_tree.num_leaves(), 0);
+        assert_eq!(state.contract_address, original.contract_address);
+
+        // Replaying the same events yields the original state.
+        let mut rng = ChaChaRng::from_seed([0; 32]);
+        for _ in 0..3 {
+            commit_wrap_block(&mut state, &mut rng);
+        }
+        assert_eq!(
+            state.ledger_state.state_number,
+            original.ledger_state.state_number
+        );
+        assert_eq!(state.events.len(), original.events.len());
+        assert_eq!(
+            state.transaction_id_by_hash,
+            original.transaction_id_by_hash
+        );
+        assert_eq!(state.blocks, original.blocks);
+        assert_eq!(
+            state.record_merkle_tree.commitment(),
+            original.record_merkle_tree.commitment()
+        );
+        assert_eq!(
+            state.ledger_state.record_merkle_commitment,
+            original.ledger_state.record_merkle_commitment
+        );
+    }
+}
@@ -0,0 +1,173 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Genesis snapshots, to start an EQS from a known state instead of replaying all the events of
+//! the CAPE contract.
+//!
+//! A genesis snapshot is the base64 encoding of a [GENESIS_SNAPSHOT_VERSION] byte followed by the
+//! bincode serialization of a [GenesisSnapshot]. It is created with `cape-cli eqs snapshot` and
+//! passed to the EQS with `--genesis-block`.
+
+use crate::query_result_state::QueryResultState;
+use crate::store::EqsStore;
+use serde::{Deserialize, Serialize};
+use snafu::{ResultExt, Snafu};
+
+/// Version of the genesis snapshot format.
+pub const GENESIS_SNAPSHOT_VERSION: u8 = 1;
+
+#[derive(Debug, Snafu)]
+pub enum GenesisError {
+    #[snafu(display("genesis snapshot is not valid base64: {}", source))]
+    Base64 { source: base64::DecodeError },
+
+    #[snafu(display("genesis snapshot is empty"))]
+    Empty,
+
+    #[snafu(display(
+        "unsupported genesis snapshot version {}, expected {}",
+        version,
+        GENESIS_SNAPSHOT_VERSION
+    ))]
+    UnsupportedVersion { version: u8 },
+
+    #[snafu(display("failed to (de)serialize genesis snapshot: {}", source))]
+    Bincode { source: bincode::Error },
+
+    #[snafu(display(
+        "genesis block {} precedes the last event of the state, in block {}",
+        genesis_block_number,
+        last_event_block
+    ))]
+    BlockBeforeLastEvent {
+        genesis_block_number: u64,
+        last_event_block: u64,
+    },
+}
+
+/// The EQS state after processing all the events up to Ethereum block `genesis_block_number`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct GenesisSnapshot {
+    pub genesis_block_number: u64,
+    pub state: QueryResultState,
+}
+
+impl GenesisSnapshot {
+    /// Fails if `state` includes events after `genesis_block_number`.
+    ///
+    /// The events which are emitted after the last event of `state` and up to
+    /// `genesis_block_number` are skipped by an EQS starting from this snapshot. Unless there are
+    /// no such events, `genesis_block_number` should be the block of the last event of `state`.
+    pub fn new(state: QueryResultState, genesis_block_number: u64) -> Result<Self, GenesisError> {
+        if let Some((last_event_block, _)) = state.last_reported_index {
+            if last_event_block > genesis_block_number {
+                return Err(GenesisError::BlockBeforeLastEvent {
+                    genesis_block_number,
+                    last_event_block,
+                });
+            }
+        }
+        Ok(Self {
+            genesis_block_number,
+            state,
+        })
+    }
+
+    pub fn to_base64(&self) -> Result<String, GenesisError> {
+        let mut bytes = vec![GENESIS_SNAPSHOT_VERSION];
+        bytes.extend(bincode::serialize(self).context(BincodeSnafu)?);
+        Ok(base64::encode(bytes))
+    }
+
+    pub fn from_base64(encoded: &str) -> Result<Self, GenesisError> {
+        let bytes = base64::decode(encoded.trim()).context(Base64Snafu)?;
+        let (version, snapshot) = bytes.split_first().ok_or(GenesisError::Empty)?;
+        if *version != GENESIS_SNAPSHOT_VERSION {
+            return Err(GenesisError::UnsupportedVersion { version: *version });
+        }
+        bincode::deserialize(snapshot).context(BincodeSnafu)
+    }
+
+    /// Persist the state of this snapshot as the latest state, so that polling starts after
+    /// `genesis_block_number`, and return it.
+    pub fn restore(self, store: &dyn EqsStore) -> std::io::Result<QueryResultState> {
+        let mut state = self.state;
+        // All the events in the genesis block are part of the state.
+        state.last_reported_index = Some((self.genesis_block_number, u64::MAX));
+        store.save_state(&state, state.ledger_state.state_number)?;
+        Ok(state)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::FileEqsStore;
+    use cap_rust_sandbox::universal_param::verifier_keys;
+    use ethers::prelude::Address;
+    use jf_cap::structs::AssetCode;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_genesis_snapshot_restart() {
+        let mut state = QueryResultState::new(verifier_keys());
+        state.ledger_state.state_number = 3;
+        state.last_reported_index = Some((4, 1));
+        state.contract_address = Some(Address::from_low_u64_be(2));
+        state
+            .address_from_asset
+            .insert(AssetCode::native(), Address::from_low_u64_be(1));
+
+        let encoded = GenesisSnapshot::new(state.clone(), 5)
+            .unwrap()
+            .to_base64()
+            .unwrap();
+        let snapshot = GenesisSnapshot::from_base64(&encoded).unwrap();
+        assert_eq!(snapshot.genesis_block_number, 5);
+
+        let dir = TempDir::new("eqs_genesis").unwrap();
+        let store = FileEqsStore::open(dir.path(), true).unwrap();
+        snapshot.restore(&store).unwrap();
+        drop(store);
+
+        // Restart from the persisted state.
+        let store = FileEqsStore::open(dir.path(), false).unwrap();
+        let (restored, height) = store.load_latest_state().unwrap();
+        assert_eq!(height, 3);
+        state.last_reported_index = Some((5, u64::MAX));
+        assert_eq!(
+            bincode::serialize(&restored).unwrap(),
+            bincode::serialize(&state).unwrap()
+        );
+    }
+
+    #[test]
+    fn test_genesis_snapshot_errors() {
+        let mut state = QueryResultState::new(verifier_keys());
+        state.last_reported_index = Some((6, 0));
+        assert!(matches!(
+            GenesisSnapshot::new(state.clone(), 5),
+            Err(GenesisError::BlockBeforeLastEvent { .. })
+        ));
+
+        let encoded = GenesisSnapshot::new(state, 6).unwrap().to_base64().unwrap();
+        let mut bytes = base64::decode(&encoded).unwrap();
+        bytes[0] = GENESIS_SNAPSHOT_VERSION + 1;
+        assert!(matches!(
+            GenesisSnapshot::from_base64(&base64::encode(&bytes)),
+            Err(GenesisError::UnsupportedVersion { .. })
+        ));
+        assert!(matches!(
+            GenesisSnapshot::from_base64(""),
+            Err(GenesisError::Empty)
+        ));
+        assert!(matches!(
+            GenesisSnapshot::from_base64("not base64!"),
+            Err(GenesisError::Base64 { .. })
+        ));
+    }
+}
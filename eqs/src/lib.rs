@@ -12,9 +12,16 @@ pub mod disco;
 pub mod entry;
 pub mod errors;
 pub mod eth_polling;
+pub mod genesis;
+pub mod metrics;
 pub mod query_result_state;
+pub mod rate_limit;
+pub mod reindex;
 pub mod route_parsing;
 pub mod routes;
+#[cfg(feature = "sqlite-store")]
+pub mod sqlite_store;
 pub mod state_persistence;
+pub mod store;
 
 pub use crate::entry::run as run_eqs;
@@ -6,7 +6,6 @@
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use eqs::configuration::EQSOptions;
-use structopt::StructOpt;
 
 #[async_std::main]
 async fn main() -> std::io::Result<()> {
@@ -15,5 +14,7 @@ async fn main() -> std::io::Result<()> {
         .with_ansi(false)
         .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
         .init();
-    eqs::run_eqs(&EQSOptions::from_args()).await
+    let opt = EQSOptions::from_args_and_config()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err.to_string()))?;
+    eqs::run_eqs(&opt).await
 }
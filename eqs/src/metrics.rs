@@ -0,0 +1,81 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Prometheus metrics of the EQS, served by [crate::api_server::init_metrics_server].
+
+use prometheus::{Encoder, Histogram, HistogramOpts, IntCounter, Registry, TextEncoder};
+use std::time::Duration;
+
+pub struct EqsMetrics {
+    registry: Registry,
+    blocks_processed: IntCounter,
+    block_processing_duration: Histogram,
+    nullifiers_indexed: IntCounter,
+    commitments_indexed: IntCounter,
+    eth_poll_errors: IntCounter,
+}
+
+impl EqsMetrics {
+    pub fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new();
+        let blocks_processed = IntCounter::new(
+            "cape_eqs_blocks_processed_total",
+            "Number of CAPE blocks processed",
+        )?;
+        let block_processing_duration = Histogram::with_opts(HistogramOpts::new(
+            "cape_eqs_block_processing_duration_seconds",
+            "Time spent processing a CAPE block",
+        ))?;
+        let nullifiers_indexed = IntCounter::new(
+            "cape_eqs_nullifiers_indexed_total",
+            "Number of nullifiers indexed",
+        )?;
+        let commitments_indexed = IntCounter::new(
+            "cape_eqs_commitments_indexed_total",
+            "Number of record commitments indexed",
+        )?;
+        let eth_poll_errors = IntCounter::new(
+            "cape_eqs_eth_poll_errors_total",
+            "Number of failed polls of the Ethereum node",
+        )?;
+
+        registry.register(Box::new(blocks_processed.clone()))?;
+        registry.register(Box::new(block_processing_duration.clone()))?;
+        registry.register(Box::new(nullifiers_indexed.clone()))?;
+        registry.register(Box::new(commitments_indexed.clone()))?;
+        registry.register(Box::new(eth_poll_errors.clone()))?;
+
+        Ok(Self {
+            registry,
+            blocks_processed,
+            block_processing_duration,
+            nullifiers_indexed,
+            commitments_indexed,
+            eth_poll_errors,
+        })
+    }
+
+    /// Record the processing of a CAPE block.
+    pub fn record_block(&self, duration: Duration, nullifiers: usize, commitments: usize) {
+        self.blocks_processed.inc();
+        self.block_processing_duration
+            .observe(duration.as_secs_f64());
+        self.nullifiers_indexed.inc_by(nullifiers as u64);
+        self.commitments_indexed.inc_by(commitments as u64);
+    }
+
+    pub fn record_poll_error(&self) {
+        self.eth_poll_errors.inc();
+    }
+
+    /// Encode all the metrics in the Prometheus text format.
+    pub fn encode(&self) -> Result<String, prometheus::Error> {
+        let mut buffer = Vec::new();
+        TextEncoder::new().encode(&self.registry.gather(), &mut buffer)?;
+        Ok(String::from_utf8(buffer).unwrap())
+    }
+}
@@ -8,13 +8,15 @@
 use cap_rust_sandbox::ledger::{CapeLedger, CapeTransition, CommittedCapeTransition};
 use cap_rust_sandbox::model::{CapeLedgerState, CapeRecordMerkleHistory, CAPE_MERKLE_HEIGHT};
 use commit::Commitment;
-use ethers::prelude::Address;
-use jf_cap::structs::{AssetCode, Nullifier};
-use jf_cap::MerkleTree;
+use ethers::prelude::{Address, Bytes, H256};
+use jf_cap::structs::{AssetCode, Nullifier, RecordCommitment};
+use jf_cap::{AccMemberWitness, MerkleTree};
 use key_set::VerifierKeySet;
+use reef::traits::{Block, Transaction};
 use seahorse::events::LedgerEvent;
 use serde::{Deserialize, Serialize};
-use std::collections::{HashMap, HashSet, VecDeque};
+use snafu::{ResultExt, Snafu};
+use std::collections::{BTreeMap, HashMap, HashSet, VecDeque};
 
 use crate::configuration::Confirmations;
 
@@ -26,6 +28,46 @@ use crate::configuration::Confirmations;
 /// before the event with index `i2` chronologically if and only if `i1 < i2`.
 pub type EthEventIndex = (u64, u64);
 
+/// A CAPE block committed to the contract.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub struct BlockQueryResponse {
+    pub height: u64,
+    /// ABI encoding of the block, as submitted to the CAPE contract.
+    pub block: Bytes,
+    /// Hash of the Ethereum transaction which submitted the block.
+    pub eth_txn_hash: H256,
+}
+
+/// Proof that a record commitment is included in the records Merkle tree.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct MerkleProof {
+    pub leaf: RecordCommitment,
+    /// The authentication path of the leaf, and the root it authenticates against.
+    pub witness: AccMemberWitness,
+}
+
+/// Prefix of a serialized [QueryResultState], followed by the format version.
+///
+/// States persisted before the format was versioned start with the state number instead, which
+/// cannot be this large.
+const STATE_FORMAT_MAGIC: &[u8; 8] = b"CAPE-EQS";
+
+/// Version of the format written by [QueryResultState::to_bytes].
+pub const STATE_FORMAT_VERSION: u32 = 1;
+
+#[derive(Debug, Snafu)]
+pub enum StateFormatError {
+    #[snafu(display("failed to (de)serialize the EQS state: {}", source))]
+    Bincode { source: bincode::Error },
+
+    #[snafu(display(
+        "unsupported EQS state format version {}, expected {}",
+        version,
+        STATE_FORMAT_VERSION
+    ))]
+    UnsupportedVersion { version: u32 },
+}
+
 #[derive(Clone, Debug, Serialize, Deserialize)]
 pub struct QueryResultState {
     // latest state, primary source
@@ -45,11 +87,84 @@ pub struct QueryResultState {
     pub transaction_by_id: HashMap<(u64, u64), CommittedCapeTransition>,
     pub transaction_id_by_hash: HashMap<Commitment<CapeTransition>, (u64, u64)>,
     pub address_from_asset: HashMap<AssetCode, Address>,
+    pub blocks: BTreeMap<u64, BlockQueryResponse>,
+    /// Full records Merkle tree, unlike the frontier in `ledger_state`.
+    pub record_merkle_tree: MerkleTree,
+    /// Number of most recent blocks kept in `blocks`, or 0 to keep all of them.
+    #[serde(skip)]
+    pub blocks_retained: u64,
+}
+
+/// Layout of the states persisted before the format was versioned.
+#[derive(Serialize, Deserialize)]
+pub(crate) struct QueryResultStateV0 {
+    pub ledger_state: CapeLedgerState,
+    pub nullifiers: HashSet<Nullifier>,
+    pub verifier_keys: VerifierKeySet,
+    pub last_reported_index: Option<EthEventIndex>,
+    pub contract_address: Option<Address>,
+    pub num_confirmations: Option<Confirmations>,
+    pub events: Vec<LedgerEvent<CapeLedger>>,
+    pub transaction_by_id: HashMap<(u64, u64), CommittedCapeTransition>,
+    pub transaction_id_by_hash: HashMap<Commitment<CapeTransition>, (u64, u64)>,
+    pub address_from_asset: HashMap<AssetCode, Address>,
+}
+
+impl From<QueryResultStateV0> for QueryResultState {
+    /// The records Merkle tree is rebuilt from the committed blocks. The encoded blocks were not
+    /// recorded, so the `blocks` query only returns the blocks committed after the upgrade.
+    fn from(old: QueryResultStateV0) -> Self {
+        let mut state = Self::new(old.verifier_keys);
+        for event in &old.events {
+            if let LedgerEvent::Commit { block, .. } = event {
+                for txn in block.txns() {
+                    state.insert_record_commitments(&txn.output_commitments());
+                }
+            }
+        }
+        state.ledger_state = old.ledger_state;
+        state.nullifiers = old.nullifiers;
+        state.last_reported_index = old.last_reported_index;
+        state.contract_address = old.contract_address;
+        state.num_confirmations = old.num_confirmations;
+        state.events = old.events;
+        state.transaction_by_id = old.transaction_by_id;
+        state.transaction_id_by_hash = old.transaction_id_by_hash;
+        state.address_from_asset = old.address_from_asset;
+        state
+    }
 }
 
 impl QueryResultState {
     pub const RECORD_ROOT_HISTORY_SIZE: usize = 10;
 
+    /// Serialize the state for persistence, in the current [STATE_FORMAT_VERSION].
+    pub fn to_bytes(&self) -> Result<Vec<u8>, StateFormatError> {
+        let mut bytes = STATE_FORMAT_MAGIC.to_vec();
+        bytes.extend(STATE_FORMAT_VERSION.to_le_bytes());
+        bytes.extend(bincode::serialize(self).context(BincodeSnafu)?);
+        Ok(bytes)
+    }
+
+    /// Deserialize a state written by [QueryResultState::to_bytes], or persisted before the format
+    /// was versioned.
+    pub fn from_bytes(bytes: &[u8]) -> Result<Self, StateFormatError> {
+        match bytes.strip_prefix(STATE_FORMAT_MAGIC) {
+            Some(versioned) => {
+                let version: u32 = bincode::deserialize(versioned).context(BincodeSnafu)?;
+                if version != STATE_FORMAT_VERSION {
+                    return Err(StateFormatError::UnsupportedVersion { version });
+                }
+                bincode::deserialize(&versioned[4..]).context(BincodeSnafu)
+            }
+            None => {
+                tracing::info!("Migrating the EQS state from the unversioned format");
+                let old: QueryResultStateV0 = bincode::deserialize(bytes).context(BincodeSnafu)?;
+                Ok(old.into())
+            }
+        }
+    }
+
     pub fn new(verifier_keys: VerifierKeySet) -> QueryResultState {
         let record_merkle_frontier = MerkleTree::new(CAPE_MERKLE_HEIGHT).unwrap();
         QueryResultState {
@@ -73,6 +188,126 @@ impl QueryResultState {
             transaction_by_id: HashMap::new(),
             transaction_id_by_hash: HashMap::new(),
             address_from_asset: HashMap::new(),
+            blocks: BTreeMap::new(),
+            record_merkle_tree: record_merkle_frontier,
+            blocks_retained: 0,
+        }
+    }
+
+    /// Drop everything learned from the chain, so that all the events can be processed again.
+    ///
+    /// The configuration (verifier keys, contract address and number of confirmations) is kept.
+    pub fn reset(&mut self) {
+        let mut state = Self::new(self.verifier_keys.clone());
+        state.contract_address = self.contract_address;
+        state.num_confirmations = self.num_confirmations;
+        state.blocks_retained = self.blocks_retained;
+        *self = state;
+    }
+
+    /// Record a newly committed block, dropping the oldest blocks beyond `blocks_retained`.
+    pub fn insert_block(&mut self, block: BlockQueryResponse) {
+        self.blocks.insert(block.height, block);
+        if self.blocks_retained > 0 && self.blocks.len() as u64 > self.blocks_retained {
+            let oldest_retained = *self
+                .blocks
+                .keys()
+                .nth_back(self.blocks_retained as usize - 1)
+                .unwrap();
+            self.blocks = self.blocks.split_off(&oldest_retained);
+        }
+    }
+
+    /// Append newly committed records to the full records Merkle tree.
+    pub fn insert_record_commitments<'a>(
+        &mut self,
+        comms: impl IntoIterator<Item = &'a RecordCommitment>,
+    ) {
+        for comm in comms {
+            self.record_merkle_tree.push(comm.to_field_element());
+        }
+    }
+
+    /// Return a proof of inclusion of the record with index `uid` against the current root, or
+    /// `None` if there is no such record.
+    pub fn get_merkle_proof(&self, uid: u64) -> Option<MerkleProof> {
+        let (_, proof) = self.record_merkle_tree.get_leaf(uid).expect_ok().ok()?;
+        Some(MerkleProof {
+            leaf: RecordCommitment::from_field_element(proof.leaf.0),
+            witness: AccMemberWitness {
+                merkle_path: proof.path,
+                root: self.record_merkle_tree.commitment().root_value,
+                uid,
+            },
+        })
+    }
+
+    /// Undo the effects of all CAPE blocks with height `height` or above.
+    ///
+    /// This is used to recover from a reorganization of the Ethereum chain: the events of the
+    /// dropped blocks are removed, along with their transactions, nullifiers and records, and the
+    /// state number is reset to `height`, so the blocks can be processed again from the new
+    /// chain. Asset sponsorships are not tied to a CAPE block and are kept.
+    pub fn rollback_to(&mut self, height: u64) {
+        let first_dropped = self
+            .events
+            .iter()
+            .position(|event| match event {
+                LedgerEvent::Commit { block_id, .. } => *block_id >= height,
+                LedgerEvent::Memos {
+                    transaction: Some((block_id, ..)),
+                    ..
+                } => *block_id >= height,
+                _ => false,
+            })
+            .unwrap_or(self.events.len());
+        for event in self.events.split_off(first_dropped) {
+            if let LedgerEvent::Commit { block, .. } = event {
+                for txn in block.txns() {
+                    for (nullifier, _) in txn.proven_nullifiers() {
+                        self.nullifiers.remove(&nullifier);
+                    }
+                }
+            }
+        }
+
+        self.transaction_by_id
+            .retain(|(block_id, _), _| *block_id < height);
+        self.transaction_id_by_hash
+            .retain(|_, (block_id, _)| *block_id < height);
+        self.blocks.split_off(&height);
+
+        // Rebuild the records Merkle tree from the records of the remaining blocks.
+        let num_records: u64 = self
+            .events
+            .iter()
+            .filter_map(|event| match event {
+                LedgerEvent::Commit { block, .. } => Some(block),
+                _ => None,
+            })
+            .flat_map(|block| block.txns())
+            .map(|txn| txn.output_len() as u64)
+            .sum();
+        let mut record_merkle_tree = MerkleTree::new(CAPE_MERKLE_HEIGHT).unwrap();
+        for uid in 0..num_records {
+            let (_, proof) = self.record_merkle_tree.get_leaf(uid).expect_ok().unwrap();
+            record_merkle_tree.push(proof.leaf.0);
+        }
+        self.ledger_state.record_merkle_commitment = record_merkle_tree.commitment();
+        self.ledger_state.record_merkle_frontier = record_merkle_tree.frontier();
+        self.record_merkle_tree = record_merkle_tree;
+
+        self.ledger_state.state_number = std::cmp::min(self.ledger_state.state_number, height);
+    }
+
+    /// Return the committed blocks with height in `from..to`, in increasing order of height.
+    pub fn get_blocks_range(&self, from: u64, to: u64) -> Vec<BlockQueryResponse> {
+        if from >= to {
+            return Vec::new();
         }
+        self.blocks
+            .range(from..to)
+            .map(|(_, block)| block.clone())
+            .collect()
     }
 }
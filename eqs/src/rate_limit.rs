@@ -0,0 +1,161 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Per-IP rate limiting of the EQS API.
+
+use dashmap::DashMap;
+use std::net::{IpAddr, SocketAddr};
+use std::sync::{Arc, Mutex};
+use std::time::{Duration, Instant};
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+/// Time after which the bucket of an inactive client is forgotten.
+const IDLE_TIMEOUT: Duration = Duration::from_secs(60);
+
+/// A token bucket holding up to `burst` tokens and refilled at `rps` tokens per second.
+#[derive(Clone, Debug)]
+pub struct TokenBucket {
+    tokens: f64,
+    last_update: Instant,
+}
+
+impl TokenBucket {
+    fn new(burst: u32, now: Instant) -> Self {
+        Self {
+            tokens: burst as f64,
+            last_update: now,
+        }
+    }
+
+    /// Take a token from the bucket, or return how long to wait until one is available.
+    fn take(&mut self, burst: u32, rps: f64, now: Instant) -> Result<(), Duration> {
+        let elapsed = now
+            .saturating_duration_since(self.last_update)
+            .as_secs_f64();
+        self.tokens = (self.tokens + elapsed * rps).min(burst as f64);
+        self.last_update = now;
+        if self.tokens >= 1.0 {
+            self.tokens -= 1.0;
+            Ok(())
+        } else {
+            let wait = (1.0 - self.tokens) / rps;
+            Err(Duration::from_secs_f64(
+                wait.min(IDLE_TIMEOUT.as_secs_f64()),
+            ))
+        }
+    }
+}
+
+/// Rejects requests with `429 Too Many Requests` once a client IP runs out of tokens.
+#[derive(Clone, Debug)]
+pub struct RateLimitMiddleware {
+    burst: u32,
+    rps: f64,
+    buckets: Arc<DashMap<IpAddr, TokenBucket>>,
+    last_eviction: Arc<Mutex<Instant>>,
+}
+
+impl RateLimitMiddleware {
+    /// Allow bursts of `burst` requests per client, refilled at `rps` requests per second.
+    ///
+    /// Panics unless `burst` is at least 1 and `rps` is positive and finite, which
+    /// [EQSOptions](crate::configuration::EQSOptions) checks when parsing the options.
+    pub fn new(burst: u32, rps: f64) -> Self {
+        assert!(burst >= 1, "the rate limit burst must be at least 1");
+        assert!(
+            rps.is_finite() && rps > 0.0,
+            "the rate limit must be a positive finite number of requests per second"
+        );
+        Self {
+            burst,
+            rps,
+            buckets: Default::default(),
+            last_eviction: Arc::new(Mutex::new(Instant::now())),
+        }
+    }
+
+    /// Forget the clients which have been inactive for `IDLE_TIMEOUT`.
+    ///
+    /// This runs at most once per `IDLE_TIMEOUT`, and is skipped if another request is already
+    /// evicting entries.
+    fn evict_idle(&self, now: Instant) {
+        if let Ok(mut last_eviction) = self.last_eviction.try_lock() {
+            if now.saturating_duration_since(*last_eviction) >= IDLE_TIMEOUT {
+                self.buckets.retain(|_, bucket| {
+                    now.saturating_duration_since(bucket.last_update) < IDLE_TIMEOUT
+                });
+                *last_eviction = now;
+            }
+        }
+    }
+
+    fn check(&self, ip: IpAddr) -> Result<(), Duration> {
+        let now = Instant::now();
+        self.evict_idle(now);
+        self.buckets
+            .entry(ip)
+            .or_insert_with(|| TokenBucket::new(self.burst, now))
+            .take(self.burst, self.rps, now)
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for RateLimitMiddleware {
+    async fn handle(&self, req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let ip = req
+            .peer_addr()
+            .and_then(|addr| addr.parse::<SocketAddr>().ok())
+            .map(|addr| addr.ip());
+        if let Some(ip) = ip {
+            if let Err(wait) = self.check(ip) {
+                // Round up, so that a retry after the advertised delay succeeds.
+                let retry_after = wait.as_secs() + u64::from(wait.subsec_nanos() > 0);
+                return Ok(Response::builder(StatusCode::TooManyRequests)
+                    .header("Retry-After", retry_after.to_string())
+                    .build());
+            }
+        }
+        Ok(next.run(req).await)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use tide::http::{self, Method, Url};
+
+    fn request_from(ip: &str) -> http::Request {
+        let mut req = http::Request::new(Method::Get, Url::parse("http://localhost/").unwrap());
+        req.set_peer_addr(Some(format!("{}:40000", ip)));
+        req
+    }
+
+    #[async_std::test]
+    async fn test_rate_limit() {
+        let mut app = tide::new();
+        app.with(RateLimitMiddleware::new(10, 1.0));
+        app.at("/").get(|_| async { Ok("ok") });
+
+        let mut throttled = 0;
+        for _ in 0..100 {
+            let res: http::Response = app.respond(request_from("10.0.0.1")).await.unwrap();
+            if res.status() == StatusCode::TooManyRequests {
+                assert!(res.header("Retry-After").is_some());
+                throttled += 1;
+            } else {
+                assert_eq!(res.status(), StatusCode::Ok);
+            }
+        }
+        assert!(throttled > 0);
+
+        // Another client, staying under the limit, is not affected.
+        for _ in 0..10 {
+            let res: http::Response = app.respond(request_from("10.0.0.2")).await.unwrap();
+            assert_eq!(res.status(), StatusCode::Ok);
+        }
+    }
+}
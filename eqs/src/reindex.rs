@@ -0,0 +1,172 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Full rebuild of the EQS state, triggered with `POST /reindex` on the admin port.
+//!
+//! The admin server only requests a reindex. The state is reset by the polling task the next time
+//! it runs, so that the reset cannot interleave with the processing of a range of blocks, and the
+//! polling task then reports its progress as it replays the events.
+//!
+//! The replayed events are not published to `/ws/events`, and the subscriptions open when the
+//! state is reset are closed, since the events they received are no longer part of the state.
+
+use serde::{Deserialize, Serialize};
+use std::sync::atomic::{AtomicBool, AtomicU64, Ordering};
+use tokio::sync::watch;
+
+/// Progress of a reindex, served at `/reindex/status`.
+#[derive(Clone, Copy, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ReindexStatus {
+    pub blocks_processed: u64,
+    pub total_blocks: u64,
+}
+
+/// Shared between the web servers and the polling task.
+#[derive(Debug)]
+pub struct Reindex {
+    /// A reindex was requested and the polling task has not reset the state yet.
+    requested: AtomicBool,
+    /// The state was reset, or is about to be, and is not caught up with the chain.
+    active: AtomicBool,
+    blocks_processed: AtomicU64,
+    total_blocks: AtomicU64,
+    /// Number of times the state was reset. The receiver is kept so that sending never fails.
+    resets: (watch::Sender<u64>, watch::Receiver<u64>),
+}
+
+impl Default for Reindex {
+    fn default() -> Self {
+        Self {
+            requested: Default::default(),
+            active: Default::default(),
+            blocks_processed: Default::default(),
+            total_blocks: Default::default(),
+            resets: watch::channel(0),
+        }
+    }
+}
+
+impl Reindex {
+    /// Request a reindex. Returns `false` if one is already in progress.
+    pub fn request(&self) -> bool {
+        if self.active.swap(true, Ordering::SeqCst) {
+            return false;
+        }
+        self.blocks_processed.store(0, Ordering::SeqCst);
+        self.total_blocks.store(0, Ordering::SeqCst);
+        self.requested.store(true, Ordering::SeqCst);
+        true
+    }
+
+    /// Called by the polling task. Returns `true` once per request, when the state must be reset.
+    pub fn take_request(&self) -> bool {
+        self.requested.swap(false, Ordering::SeqCst)
+    }
+
+    /// Called by the polling task once it has reset the state, to close the event subscriptions.
+    pub fn notify_reset(&self) {
+        let resets = *self.resets.1.borrow();
+        // Cannot fail, since `self` holds a receiver.
+        let _ = self.resets.0.send(resets + 1);
+    }
+
+    /// A receiver which sees a change each time the state is reset.
+    pub fn subscribe_resets(&self) -> watch::Receiver<u64> {
+        self.resets.1.clone()
+    }
+
+    pub fn is_active(&self) -> bool {
+        self.active.load(Ordering::SeqCst)
+    }
+
+    /// Record that `blocks_processed` out of `total_blocks` Ethereum blocks have been replayed.
+    pub fn record_progress(&self, blocks_processed: u64, total_blocks: u64) {
+        self.total_blocks.store(total_blocks, Ordering::SeqCst);
+        self.blocks_processed
+            .store(blocks_processed.min(total_blocks), Ordering::SeqCst);
+    }
+
+    /// Called by the polling task once the state has caught up with the chain.
+    pub fn finish(&self) {
+        if self.requested.load(Ordering::SeqCst) {
+            // The state has not been reset yet.
+            return;
+        }
+        let total_blocks = self.total_blocks.load(Ordering::SeqCst);
+        self.blocks_processed.store(total_blocks, Ordering::SeqCst);
+        self.active.store(false, Ordering::SeqCst);
+    }
+
+    pub fn status(&self) -> ReindexStatus {
+        ReindexStatus {
+            blocks_processed: self.blocks_processed.load(Ordering::SeqCst),
+            total_blocks: self.total_blocks.load(Ordering::SeqCst),
+        }
+    }
+
+    /// Percentage of the blocks replayed so far.
+    pub fn progress(&self) -> u64 {
+        let status = self.status();
+        if status.total_blocks == 0 {
+            0
+        } else {
+            status.blocks_processed * 100 / status.total_blocks
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn test_reindex_lifecycle() {
+        let reindex = Reindex::default();
+        assert!(!reindex.is_active());
+        assert!(!reindex.take_request());
+
+        assert!(reindex.request());
+        assert!(reindex.is_active());
+        // A second request is rejected while the first one is in progress.
+        assert!(!reindex.request());
+
+        // Finishing before the polling task has reset the state has no effect.
+        reindex.finish();
+        assert!(reindex.is_active());
+
+        assert!(reindex.take_request());
+        assert!(!reindex.take_request());
+        assert!(reindex.is_active());
+
+        reindex.record_progress(25, 100);
+        assert_eq!(
+            reindex.status(),
+            ReindexStatus {
+                blocks_processed: 25,
+                total_blocks: 100
+            }
+        );
+        assert_eq!(reindex.progress(), 25);
+
+        reindex.finish();
+        assert!(!reindex.is_active());
+        assert_eq!(reindex.progress(), 100);
+
+        // A new reindex can be requested once the previous one is over.
+        assert!(reindex.request());
+        assert_eq!(reindex.status(), ReindexStatus::default());
+    }
+
+    #[async_std::test]
+    async fn test_notify_reset() {
+        let reindex = Reindex::default();
+        let mut resets = reindex.subscribe_resets();
+        reindex.notify_reset();
+        resets.changed().await.unwrap();
+        assert_eq!(*resets.borrow(), 1);
+    }
+}
@@ -6,13 +6,14 @@
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::api_server::WebState;
-use crate::query_result_state::QueryResultState;
+use crate::query_result_state::{BlockQueryResponse, MerkleProof, QueryResultState};
 use crate::route_parsing::*;
 
+use async_std::{future::pending, prelude::FutureExt};
 use cap_rust_sandbox::ledger::{CapeLedger, CommitmentToCapeTransition, CommittedCapeTransition};
 use cap_rust_sandbox::model::CapeLedgerState;
-use ethers::prelude::Address;
-use jf_cap::structs::{AssetCode, Nullifier};
+use ethers::prelude::{Address, H256};
+use jf_cap::structs::{AssetCode, Nullifier, RecordCommitment};
 use net::server::response;
 use seahorse::events::LedgerEvent;
 use serde::{Deserialize, Serialize};
@@ -20,10 +21,24 @@ use std::collections::HashMap;
 use std::str::FromStr;
 use strum::IntoEnumIterator;
 use strum_macros::{AsRefStr, EnumIter, EnumString};
+use tide_websockets::{
+    tungstenite::protocol::{frame::coding::CloseCode, CloseFrame},
+    Message, WebSocketConnection,
+};
+use tokio::sync::broadcast::error::RecvError;
 
 /// Maximum number of events to return in a single response.
 const EQS_MAX_EVENT_COUNT: usize = 100;
 
+/// Maximum number of nullifiers to check in a single request.
+const EQS_MAX_NULLIFIER_COUNT: usize = 1000;
+
+/// Maximum number of block heights spanned by the range of a single blocks query.
+const EQS_MAX_BLOCK_RANGE: u64 = 1000;
+
+/// Maximum number of blocks to return in a single response.
+const EQS_MAX_BLOCK_COUNT: u64 = 100;
+
 /// Index entries for documentation fragments
 #[allow(non_camel_case_types)]
 #[derive(AsRefStr, Copy, Clone, Debug, EnumIter, EnumString)]
@@ -36,6 +51,9 @@ pub enum ApiRouteKey {
     healthcheck,
     get_wrapped_erc20_address,
     get_cape_contract_address,
+    blocks,
+    nullifiers,
+    merkle_proof,
 }
 
 /// Verify that every variant of enum ApiRouteKey is defined in api.toml
@@ -116,6 +134,35 @@ pub async fn check_nullifier(
         .contains(&bindings[":nullifier"].value.to::<Nullifier>()?))
 }
 
+/// Check which of a list of nullifiers have been published.
+///
+/// `body` is the bincode serialization of a `Vec<Nullifier>`. The result contains, for each
+/// nullifier in order, whether it has been spent.
+pub async fn check_nullifiers(
+    body: &[u8],
+    query_result_state: &QueryResultState,
+) -> Result<Vec<bool>, tide::Error> {
+    let nullifiers: Vec<Nullifier> = bincode::deserialize(body).map_err(|err| {
+        tide::Error::from_str(
+            tide::StatusCode::BadRequest,
+            format!("Invalid list of nullifiers: {}", err),
+        )
+    })?;
+    if nullifiers.len() > EQS_MAX_NULLIFIER_COUNT {
+        return Err(tide::Error::from_str(
+            tide::StatusCode::BadRequest,
+            format!(
+                "Too many nullifiers: at most {} can be checked at once",
+                EQS_MAX_NULLIFIER_COUNT
+            ),
+        ));
+    }
+    Ok(nullifiers
+        .iter()
+        .map(|nullifier| query_result_state.nullifiers.contains(nullifier))
+        .collect())
+}
+
 /// Return a list of consecutive CAPE contract events.
 pub async fn get_events_since(
     bindings: &HashMap<String, RouteBinding>,
@@ -178,6 +225,20 @@ pub async fn get_transaction_by_hash(
     }
 }
 
+/// Return a proof of inclusion of a record in the records Merkle tree.
+pub async fn get_merkle_proof(
+    bindings: &HashMap<String, RouteBinding>,
+    query_result_state: &QueryResultState,
+) -> Result<MerkleProof, tide::Error> {
+    let uid = bindings[":leaf_index"].value.as_u64()?;
+    query_result_state.get_merkle_proof(uid).ok_or_else(|| {
+        tide::Error::from_str(
+            tide::StatusCode::NotFound,
+            format!("No record with index {}", uid),
+        )
+    })
+}
+
 ///Return an ERC20 contract address, making JSON-RPC connection optional
 /// in the wallet.
 pub async fn get_wrapped_erc20_address(
@@ -190,6 +251,38 @@ pub async fn get_wrapped_erc20_address(
         .cloned())
 }
 
+/// Query parameters of the `blocks` route.
+#[derive(Clone, Debug, Default, Deserialize)]
+pub struct BlocksQuery {
+    /// Height from which to resume a paginated query.
+    pub cursor: Option<u64>,
+}
+
+/// Return the committed blocks with height in `from..to`.
+///
+/// At most `EQS_MAX_BLOCK_COUNT` blocks are returned at once. The remaining blocks of the range
+/// can be fetched by passing the height following the last returned block as the cursor.
+pub async fn get_blocks(
+    bindings: &HashMap<String, RouteBinding>,
+    query: &BlocksQuery,
+    query_result_state: &QueryResultState,
+) -> Result<Vec<BlockQueryResponse>, tide::Error> {
+    let from = bindings[":from"].value.as_u64()?;
+    let to = bindings[":to"].value.as_u64()?;
+    if from > to || to - from > EQS_MAX_BLOCK_RANGE {
+        return Err(tide::Error::from_str(
+            tide::StatusCode::BadRequest,
+            format!(
+                "Invalid block range {}..{}: the range must span at most {} blocks",
+                from, to, EQS_MAX_BLOCK_RANGE
+            ),
+        ));
+    }
+    let start = query.cursor.map_or(from, |cursor| cursor.max(from));
+    let end = to.min(start.saturating_add(EQS_MAX_BLOCK_COUNT));
+    Ok(query_result_state.get_blocks_range(start, end))
+}
+
 /// Return a JSON expression with status 200 indicating the server
 /// is up and running. The JSON expression is simply,
 ///    {"status": "available"}
@@ -203,6 +296,23 @@ pub async fn healthcheck() -> Result<tide::Response, tide::Error> {
         .build())
 }
 
+/// Header set on the responses to the queries made while the state is rebuilt.
+pub const REINDEXING_HEADER: &str = "X-Eqs-Reindexing";
+
+/// Response to the queries made while the state is rebuilt, see [crate::reindex].
+///
+/// The status is 503, and the [REINDEXING_HEADER] header, set to the progress, distinguishes it
+/// from other failures.
+pub async fn reindexing(progress: u64) -> Result<tide::Response, tide::Error> {
+    Ok(
+        tide::Response::builder(tide::StatusCode::ServiceUnavailable)
+            .header(REINDEXING_HEADER, progress.to_string())
+            .content_type(tide::http::mime::JSON)
+            .body(tide::prelude::json!({"status": "reindexing", "progress": progress}))
+            .build(),
+    )
+}
+
 /// Return the Ethereum address of the CAPE contract the EQS is connected to.
 pub async fn get_cape_contract_address(
     query_result_state: &QueryResultState,
@@ -215,13 +325,87 @@ pub async fn get_cape_contract_address(
     })
 }
 
-pub async fn dispatch_url(
+/// A notification pushed to the clients of the `/ws/events` WebSocket.
+#[derive(Clone, Debug, Serialize, Deserialize, PartialEq, Eq)]
+pub enum CapeEvent {
+    /// A CAPE block was committed in the Ethereum block with hash `block_hash`.
+    NewBlock { height: u64, block_hash: H256 },
+    /// Nullifiers published by the block at `height`.
+    NewNullifiers {
+        height: u64,
+        nullifiers: Vec<Nullifier>,
+    },
+    /// Record commitments added to the records Merkle tree by the block at `height`.
+    NewCommitments {
+        height: u64,
+        commitments: Vec<RecordCommitment>,
+    },
+    /// The blocks with height `height` and above were dropped by a chain reorganization, along
+    /// with their events. The blocks of the new chain are published again from `height`.
+    Rollback { height: u64 },
+}
+
+/// Push a [CapeEvent] to a WebSocket client for every event published by the EQS.
+///
+/// A client which falls too far behind misses events, so the connection is closed with a
+/// `RateLimited` close frame and the client must catch up by querying the EQS.
+pub async fn stream_events(
     req: tide::Request<WebState>,
+    conn: WebSocketConnection,
+) -> Result<(), tide::Error> {
+    let mut events = req.state().event_sender.subscribe();
+    let mut resets = req.state().reindex.subscribe_resets();
+    loop {
+        let next = async { Some(events.recv().await) }
+            .race(async {
+                if resets.changed().await.is_err() {
+                    pending::<()>().await;
+                }
+                None
+            })
+            .await;
+        match next {
+            Some(Ok(event)) => conn.send_json(&event).await?,
+            Some(Err(RecvError::Lagged(_))) => {
+                conn.send(Message::Close(Some(CloseFrame {
+                    code: CloseCode::Again,
+                    reason: "RateLimited".into(),
+                })))
+                .await?;
+                return Ok(());
+            }
+            Some(Err(RecvError::Closed)) => return Ok(()),
+            None => {
+                // The state was reset for a reindex, so the events sent so far may not match the
+                // rebuilt state. Clients should reconnect and resync once the reindex is over.
+                conn.send(Message::Close(Some(CloseFrame {
+                    code: CloseCode::Again,
+                    reason: "Reindexing".into(),
+                })))
+                .await?;
+                return Ok(());
+            }
+        }
+    }
+}
+
+pub async fn dispatch_url(
+    mut req: tide::Request<WebState>,
     route_pattern: &str,
     bindings: &HashMap<String, RouteBinding>,
 ) -> Result<tide::Response, tide::Error> {
     let segments = route_pattern.split_once('/').unwrap_or((route_pattern, ""));
     let key = ApiRouteKey::from_str(segments.0).expect("Unknown route");
+    let reindex = &req.state().reindex;
+    if reindex.is_active() && !matches!(key, ApiRouteKey::healthcheck) {
+        return reindexing(reindex.progress()).await;
+    }
+    // The body must be read before borrowing the state from the request.
+    let body = if req.method() == tide::http::Method::Post {
+        req.body_bytes().await?
+    } else {
+        Vec::new()
+    };
     let query_state_guard = req.state().query_result_state.read().await;
     let query_state = &*query_state_guard;
     match key {
@@ -246,5 +430,187 @@ pub async fn dispatch_url(
         ApiRouteKey::get_cape_contract_address => {
             response(&req, get_cape_contract_address(query_state).await?)
         }
+        ApiRouteKey::nullifiers => response(&req, check_nullifiers(&body, query_state).await?),
+        ApiRouteKey::merkle_proof => response(&req, get_merkle_proof(bindings, query_state).await?),
+        ApiRouteKey::blocks => {
+            let query = req.query::<BlocksQuery>()?;
+            response(&req, get_blocks(bindings, &query, query_state).await?)
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use ark_ff::UniformRand;
+    use cap_rust_sandbox::universal_param::verifier_keys;
+    use ethers::prelude::{Bytes, H256};
+    use jf_cap::structs::RecordCommitment;
+    use jf_cap::{BaseField, MerkleLeafProof, MerkleTree};
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+
+    fn range_bindings(from: u64, to: u64) -> HashMap<String, RouteBinding> {
+        [(":from", from), (":to", to)]
+            .into_iter()
+            .map(|(parameter, value)| {
+                (
+                    parameter.to_string(),
+                    RouteBinding {
+                        parameter: parameter.to_string(),
+                        ptype: UrlSegmentType::Integer,
+                        value: UrlSegmentValue::Integer(value as u128),
+                    },
+                )
+            })
+            .collect()
+    }
+
+    #[async_std::test]
+    async fn test_check_nullifiers() {
+        let rng = &mut ChaChaRng::from_seed([0; 32]);
+        let mut state = QueryResultState::new(verifier_keys());
+        let spent = (0..3)
+            .map(|_| Nullifier::random_for_test(rng))
+            .collect::<Vec<_>>();
+        let unspent = (0..2)
+            .map(|_| Nullifier::random_for_test(rng))
+            .collect::<Vec<_>>();
+        state.nullifiers.extend(spent.iter().cloned());
+
+        let query = vec![spent[0], unspent[0], spent[1], spent[2], unspent[1]];
+        let body = bincode::serialize(&query).unwrap();
+        assert_eq!(
+            check_nullifiers(&body, &state).await.unwrap(),
+            vec![true, false, true, true, false]
+        );
+
+        // Too many nullifiers.
+        let body = bincode::serialize(&vec![spent[0]; EQS_MAX_NULLIFIER_COUNT + 1]).unwrap();
+        let err = check_nullifiers(&body, &state).await.unwrap_err();
+        assert_eq!(err.status(), tide::StatusCode::BadRequest);
+
+        // Malformed body.
+        let err = check_nullifiers(&[1, 2, 3], &state).await.unwrap_err();
+        assert_eq!(err.status(), tide::StatusCode::BadRequest);
+    }
+
+    #[async_std::test]
+    async fn test_get_merkle_proof() {
+        let rng = &mut ChaChaRng::from_seed([0; 32]);
+        let mut state = QueryResultState::new(verifier_keys());
+        // Simulate a few blocks, each committing some records.
+        let mut comms = vec![];
+        for num_outputs in [1, 3, 2] {
+            let block_comms = (0..num_outputs)
+                .map(|_| RecordCommitment::from(&RecordOpening::rand_for_test(rng)))
+                .collect::<Vec<_>>();
+            state.insert_record_commitments(&block_comms);
+            comms.extend(block_comms);
+        }
+
+        for (uid, comm) in comms.iter().enumerate() {
+            let bindings = [(
+                ":leaf_index".to_string(),
+                RouteBinding {
+                    parameter: ":leaf_index".to_string(),
+                    ptype: UrlSegmentType::Integer,
+                    value: UrlSegmentValue::Integer(uid as u128),
+                },
+            )]
+            .into_iter()
+            .collect();
+            let proof = get_merkle_proof(&bindings, &state).await.unwrap();
+            assert_eq!(proof.leaf, *comm);
+            assert_eq!(proof.witness.uid, uid as u64);
+            assert_eq!(
+                proof.witness.root,
+                state.record_merkle_tree.commitment().root_value
+            );
+            MerkleTree::check_proof(
+                proof.witness.root,
+                uid as u64,
+                &MerkleLeafProof::new(comm.to_field_element(), proof.witness.merkle_path),
+            )
+            .unwrap();
+        }
+
+        assert!(state.get_merkle_proof(comms.len() as u64).is_none());
+    }
+
+    #[async_std::test]
+    async fn test_get_blocks() {
+        let mut state = QueryResultState::new(verifier_keys());
+        for height in 0..50 {
+            state.blocks.insert(
+                height,
+                BlockQueryResponse {
+                    height,
+                    block: Bytes::from(vec![height as u8]),
+                    eth_txn_hash: H256::from_low_u64_be(height),
+                },
+            );
+        }
+        let heights = |blocks: Vec<BlockQueryResponse>| {
+            blocks
+                .into_iter()
+                .map(|block| block.height)
+                .collect::<Vec<_>>()
+        };
+
+        let blocks = get_blocks(&range_bindings(10, 20), &BlocksQuery::default(), &state)
+            .await
+            .unwrap();
+        assert_eq!(heights(blocks.clone()), (10..20).collect::<Vec<_>>());
+        assert_eq!(blocks[0], state.blocks[&10]);
+
+        // Ranges extending past the latest block are truncated.
+        let blocks = get_blocks(&range_bindings(45, 100), &BlocksQuery::default(), &state)
+            .await
+            .unwrap();
+        assert_eq!(heights(blocks), (45..50).collect::<Vec<_>>());
+
+        // Pagination resumes from the cursor.
+        let query = BlocksQuery { cursor: Some(15) };
+        let blocks = get_blocks(&range_bindings(10, 20), &query, &state)
+            .await
+            .unwrap();
+        assert_eq!(heights(blocks), (15..20).collect::<Vec<_>>());
+
+        // Invalid ranges are rejected.
+        for (from, to) in [(20, 10), (0, EQS_MAX_BLOCK_RANGE + 1)] {
+            let err = get_blocks(&range_bindings(from, to), &BlocksQuery::default(), &state)
+                .await
+                .unwrap_err();
+            assert_eq!(err.status(), tide::StatusCode::BadRequest);
+        }
+    }
+
+    #[async_std::test]
+    async fn test_get_blocks_retained() {
+        let mut state = QueryResultState::new(verifier_keys());
+        state.blocks_retained = 10;
+        for height in 0..25 {
+            state.insert_block(BlockQueryResponse {
+                height,
+                block: Bytes::from(vec![height as u8]),
+                eth_txn_hash: H256::from_low_u64_be(height),
+            });
+        }
+        assert_eq!(
+            state.blocks.keys().copied().collect::<Vec<_>>(),
+            (15..25).collect::<Vec<_>>()
+        );
+
+        // Blocks which are no longer retained are not returned.
+        let blocks = get_blocks(&range_bindings(10, 20), &BlocksQuery::default(), &state)
+            .await
+            .unwrap();
+        assert_eq!(
+            blocks
+                .into_iter()
+                .map(|block| block.height)
+                .collect::<Vec<_>>(),
+            (15..20).collect::<Vec<_>>()
+        );
     }
 }
@@ -0,0 +1,157 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! [EqsStore] backed by a SQLite database.
+
+use crate::query_result_state::{QueryResultState, StateFormatError};
+use crate::state_persistence::SnapshotMeta;
+use crate::store::EqsStore;
+use rusqlite::{params, Connection, OptionalExtension};
+use std::fs;
+use std::io;
+use std::path::{Path, PathBuf};
+use std::sync::Mutex;
+
+/// Number of most recent states kept in the database.
+const SQLITE_RETAINED_STATES: u32 = 5;
+
+fn sqlite_error(err: rusqlite::Error) -> io::Error {
+    io::Error::new(io::ErrorKind::Other, err)
+}
+
+fn format_error(err: StateFormatError) -> io::Error {
+    io::Error::new(io::ErrorKind::InvalidData, err)
+}
+
+/// Store keeping the most recent states as serialized blobs in a SQLite database, keyed by CAPE
+/// block height.
+///
+/// Each retained state is reported as a snapshot whose path is the database.
+pub struct SqliteEqsStore {
+    connection: Mutex<Connection>,
+    path: PathBuf,
+}
+
+impl SqliteEqsStore {
+    pub fn open(store_path: &Path, reset: bool) -> io::Result<Self> {
+        let dir = store_path.join("eqs");
+        fs::create_dir_all(&dir)?;
+        let path = dir.join("state.sqlite");
+        let connection = Connection::open(&path).map_err(sqlite_error)?;
+        connection
+            .execute(
+                "CREATE TABLE IF NOT EXISTS states (
+                    height INTEGER PRIMARY KEY,
+                    state BLOB NOT NULL
+                )",
+                [],
+            )
+            .map_err(sqlite_error)?;
+        if reset {
+            connection
+                .execute("DELETE FROM states", [])
+                .map_err(sqlite_error)?;
+        }
+        Ok(Self {
+            connection: Mutex::new(connection),
+            path,
+        })
+    }
+}
+
+impl EqsStore for SqliteEqsStore {
+    fn save_state(&self, state: &QueryResultState, height: u64) -> io::Result<()> {
+        let bytes = state.to_bytes().map_err(format_error)?;
+        let mut connection = self.connection.lock().unwrap();
+        let txn = connection.transaction().map_err(sqlite_error)?;
+        // After a rollback, the states above `height` are stale. They must go before the
+        // retention limit is applied, so that it cannot prune the state saved here.
+        txn.execute(
+            "DELETE FROM states WHERE height > ?1",
+            params![height as i64],
+        )
+        .map_err(sqlite_error)?;
+        txn.execute(
+            "INSERT OR REPLACE INTO states (height, state) VALUES (?1, ?2)",
+            params![height as i64, bytes],
+        )
+        .map_err(sqlite_error)?;
+        txn.execute(
+            "DELETE FROM states WHERE height NOT IN
+                (SELECT height FROM states ORDER BY height DESC LIMIT ?1)",
+            params![SQLITE_RETAINED_STATES],
+        )
+        .map_err(sqlite_error)?;
+        txn.commit().map_err(sqlite_error)
+    }
+
+    fn load_latest_state(&self) -> io::Result<(QueryResultState, u64)> {
+        let latest = self
+            .connection
+            .lock()
+            .unwrap()
+            .query_row(
+                "SELECT height, state FROM states ORDER BY height DESC LIMIT 1",
+                [],
+                |row| Ok((row.get::<_, i64>(0)?, row.get::<_, Vec<u8>>(1)?)),
+            )
+            .optional()
+            .map_err(sqlite_error)?;
+        match latest {
+            Some((height, bytes)) => Ok((
+                QueryResultState::from_bytes(&bytes).map_err(format_error)?,
+                height as u64,
+            )),
+            None => Err(io::Error::new(
+                io::ErrorKind::NotFound,
+                "no state in the database",
+            )),
+        }
+    }
+
+    fn list_snapshots(&self) -> io::Result<Vec<SnapshotMeta>> {
+        let connection = self.connection.lock().unwrap();
+        let mut statement = connection
+            .prepare("SELECT height FROM states ORDER BY height")
+            .map_err(sqlite_error)?;
+        let heights = statement
+            .query_map([], |row| row.get::<_, i64>(0))
+            .map_err(sqlite_error)?
+            .collect::<Result<Vec<_>, _>>()
+            .map_err(sqlite_error)?;
+        Ok(heights
+            .into_iter()
+            .map(|height| SnapshotMeta {
+                block_height: height as u64,
+                path: self.path.clone(),
+            })
+            .collect())
+    }
+
+    fn remove_snapshots_after(&self, height: u64) -> io::Result<()> {
+        self.connection
+            .lock()
+            .unwrap()
+            .execute(
+                "DELETE FROM states WHERE height > ?1",
+                params![height as i64],
+            )
+            .map_err(sqlite_error)?;
+        Ok(())
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::store::tests::check_store;
+
+    #[test]
+    fn test_sqlite_store() {
+        check_store(|path, reset| SqliteEqsStore::open(path, reset).unwrap());
+    }
+}
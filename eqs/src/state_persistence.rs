@@ -6,23 +6,150 @@
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use crate::query_result_state::QueryResultState;
+use async_std::{
+    sync::{Arc, RwLock},
+    task::{sleep, spawn, spawn_blocking, JoinHandle},
+};
 use atomic_store::{
-    load_store::BincodeLoadStore, AtomicStore, AtomicStoreLoader, PersistenceError, RollingLog,
+    load_store::LoadStore, AtomicStore, AtomicStoreLoader, PersistenceError, RollingLog,
 };
 
+use std::fs;
+use std::io;
 use std::path::{Path, PathBuf};
+use std::sync::atomic::{AtomicBool, Ordering};
+use std::time::Duration;
 
 const EQS_RETAINED_ENTRIES: u32 = 5;
 
+/// Key tag of the state persisted by the EQS.
+pub const EQS_KEY_TAG: &str = "eth_query";
+const SNAPSHOT_EXTENSION: &str = "snapshot";
+
 // hook up with atomic_store
 
+/// Persists the state in the versioned format of [QueryResultState::to_bytes].
+#[derive(Debug, Default)]
+pub struct StateLoadStore;
+
+impl LoadStore for StateLoadStore {
+    type ParamType = QueryResultState;
+
+    fn load(&self, stream: &[u8]) -> Result<QueryResultState, PersistenceError> {
+        QueryResultState::from_bytes(stream).map_err(|err| PersistenceError::OtherLoad {
+            inner: Box::new(err),
+        })
+    }
+
+    fn store(&mut self, param: &QueryResultState) -> Result<Vec<u8>, PersistenceError> {
+        param
+            .to_bytes()
+            .map_err(|err| PersistenceError::OtherStore {
+                inner: Box::new(err),
+            })
+    }
+}
+
 pub struct StatePersistence {
     atomic_store: AtomicStore,
-    state_snapshot: RollingLog<BincodeLoadStore<QueryResultState>>,
+    state_snapshot: RollingLog<StateLoadStore>,
+    snapshot_dir: PathBuf,
+}
+
+/// A full snapshot of the EQS state, saved by a [SnapshotScheduler].
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct SnapshotMeta {
+    /// The CAPE block height of the snapshotted state.
+    pub block_height: u64,
+    /// The snapshot file, or the database for a
+    /// [SqliteEqsStore](crate::sqlite_store::SqliteEqsStore).
+    pub path: PathBuf,
+}
+
+impl SnapshotMeta {
+    pub fn load(&self) -> io::Result<QueryResultState> {
+        let bytes = fs::read(&self.path)?;
+        QueryResultState::from_bytes(&bytes)
+            .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))
+    }
+}
+
+fn snapshot_dir(store_path: &Path) -> PathBuf {
+    [store_path, Path::new("eqs"), Path::new("snapshots")]
+        .iter()
+        .collect()
+}
+
+/// Serialize `state` for [save_snapshot], returning its block height and its serialization.
+fn serialize_snapshot(state: &QueryResultState) -> io::Result<(u64, Vec<u8>)> {
+    let bytes = state
+        .to_bytes()
+        .map_err(|err| io::Error::new(io::ErrorKind::InvalidData, err))?;
+    Ok((state.ledger_state.state_number, bytes))
+}
+
+/// Write the serialized state at `block_height` to `dir`.
+///
+/// The snapshot is first written to a temporary file which is then renamed, so that a crash
+/// never leaves a partially written snapshot behind.
+fn save_snapshot(dir: &Path, block_height: u64, bytes: Vec<u8>) -> io::Result<SnapshotMeta> {
+    let path = dir.join(format!("{}.{}", block_height, SNAPSHOT_EXTENSION));
+    let tmp_path = path.with_extension("tmp");
+    fs::create_dir_all(dir)?;
+    fs::write(&tmp_path, bytes)?;
+    fs::rename(&tmp_path, &path)?;
+    Ok(SnapshotMeta { block_height, path })
+}
+
+fn list_snapshots(dir: &Path) -> Vec<SnapshotMeta> {
+    let entries = match fs::read_dir(dir) {
+        Ok(entries) => entries,
+        Err(_) => return vec![],
+    };
+    let mut snapshots = entries
+        .filter_map(|entry| {
+            let path = entry.ok()?.path();
+            if path.extension()? != SNAPSHOT_EXTENSION {
+                return None;
+            }
+            let block_height = path.file_stem()?.to_str()?.parse().ok()?;
+            Some(SnapshotMeta { block_height, path })
+        })
+        .collect::<Vec<_>>();
+    snapshots.sort_by_key(|snapshot| snapshot.block_height);
+    snapshots
+}
+
+/// Delete all but the `keep_latest` most recent snapshots in `dir`, returning how many were
+/// deleted.
+///
+/// Nothing is deleted unless all the retained snapshots can be loaded.
+fn compact_snapshots(dir: &Path, keep_latest: usize) -> io::Result<usize> {
+    let snapshots = list_snapshots(dir);
+    let (old, retained) = snapshots.split_at(snapshots.len().saturating_sub(keep_latest));
+    for snapshot in retained {
+        snapshot.load().map_err(|err| {
+            io::Error::new(
+                err.kind(),
+                format!("snapshot {:?} cannot be loaded: {}", snapshot.path, err),
+            )
+        })?;
+    }
+    for snapshot in old {
+        fs::remove_file(&snapshot.path)?;
+    }
+    Ok(old.len())
 }
 
 impl StatePersistence {
     pub fn new(store_path: &Path, key_tag: &str) -> Result<StatePersistence, PersistenceError> {
+        let snapshot_dir = snapshot_dir(store_path);
+        // Snapshots of the previous state are no longer valid.
+        if let Err(err) = fs::remove_dir_all(&snapshot_dir) {
+            if err.kind() != io::ErrorKind::NotFound {
+                tracing::warn!("Failed to remove old snapshots: {}", err);
+            }
+        }
         let mut store_path = PathBuf::from(store_path);
         store_path.push("eqs");
         let mut loader = AtomicStoreLoader::create(&store_path, key_tag)?;
@@ -35,10 +162,12 @@ impl StatePersistence {
         Ok(StatePersistence {
             atomic_store,
             state_snapshot,
+            snapshot_dir,
         })
     }
 
     pub fn load(store_path: &Path, key_tag: &str) -> Result<StatePersistence, PersistenceError> {
+        let snapshot_dir = snapshot_dir(store_path);
         let mut store_path = PathBuf::from(store_path);
         store_path.push("eqs");
         let mut loader = AtomicStoreLoader::load(&store_path, key_tag)?;
@@ -49,6 +178,7 @@ impl StatePersistence {
         Ok(StatePersistence {
             atomic_store,
             state_snapshot,
+            snapshot_dir,
         })
     }
 
@@ -65,4 +195,381 @@ impl StatePersistence {
     pub fn load_latest_state(&self) -> Result<QueryResultState, PersistenceError> {
         self.state_snapshot.load_latest()
     }
+
+    /// The available snapshots, sorted by increasing block height.
+    pub fn list_snapshots(&self) -> Vec<SnapshotMeta> {
+        list_snapshots(&self.snapshot_dir)
+    }
+
+    /// Load the most recent snapshot which can be read successfully.
+    pub fn load_latest_snapshot(&self) -> Option<QueryResultState> {
+        self.list_snapshots()
+            .iter()
+            .rev()
+            .find_map(|snapshot| match snapshot.load() {
+                Ok(state) => Some(state),
+                Err(err) => {
+                    tracing::warn!("Skipping invalid snapshot {:?}: {}", snapshot.path, err);
+                    None
+                }
+            })
+    }
+
+    /// Delete the snapshots taken above block height `height`.
+    ///
+    /// Called after rolling the state back to `height`, so that a restart does not resume from a
+    /// snapshot containing blocks which are no longer part of the chain.
+    pub fn remove_snapshots_after(&self, height: u64) {
+        for snapshot in self.list_snapshots() {
+            if snapshot.block_height > height {
+                if let Err(err) = fs::remove_file(&snapshot.path) {
+                    tracing::warn!("Failed to remove snapshot {:?}: {}", snapshot.path, err);
+                }
+            }
+        }
+    }
+
+    /// Delete all but the `keep_latest` most recent snapshots, returning how many were deleted.
+    ///
+    /// Fails without deleting anything if one of the retained snapshots cannot be loaded.
+    pub fn compact(&self, keep_latest: usize) -> io::Result<usize> {
+        compact_snapshots(&self.snapshot_dir, keep_latest)
+    }
+
+    /// Create a scheduler saving a snapshot of `state` every `interval` blocks.
+    ///
+    /// After every `keep_latest` snapshots, the older snapshots are removed in a background task,
+    /// as with [StatePersistence::compact]. If `keep_latest` is 0, snapshots are never removed.
+    pub fn snapshot_scheduler(
+        &self,
+        state: Arc<RwLock<QueryResultState>>,
+        interval: u64,
+        keep_latest: usize,
+    ) -> SnapshotScheduler {
+        SnapshotScheduler {
+            state,
+            dir: self.snapshot_dir.clone(),
+            interval,
+            keep_latest,
+            snapshots_since_compaction: 0,
+            compacting: Arc::new(AtomicBool::new(false)),
+            last_snapshot_height: self
+                .list_snapshots()
+                .last()
+                .map(|snapshot| snapshot.block_height),
+        }
+    }
+}
+
+/// Periodically saves full snapshots of the EQS state.
+pub struct SnapshotScheduler {
+    state: Arc<RwLock<QueryResultState>>,
+    dir: PathBuf,
+    interval: u64,
+    keep_latest: usize,
+    snapshots_since_compaction: usize,
+    compacting: Arc<AtomicBool>,
+    last_snapshot_height: Option<u64>,
+}
+
+impl SnapshotScheduler {
+    /// Save a snapshot if at least `interval` blocks were added since the last one.
+    pub async fn snapshot_if_due(&mut self) -> io::Result<Option<SnapshotMeta>> {
+        // The state is only locked while it is serialized, so that the polling task is not held
+        // up by the disk write.
+        let (height, bytes) = {
+            let state = self.state.read().await;
+            let height = state.ledger_state.state_number;
+            if let Some(last) = self.last_snapshot_height {
+                if height < last + self.interval {
+                    return Ok(None);
+                }
+            }
+            serialize_snapshot(&state)?
+        };
+        self.write_snapshot(height, bytes).await.map(Some)
+    }
+
+    /// Save a snapshot of the current state, regardless of the interval.
+    pub async fn force_snapshot(&mut self) -> io::Result<SnapshotMeta> {
+        let (height, bytes) = serialize_snapshot(&*self.state.read().await)?;
+        self.write_snapshot(height, bytes).await
+    }
+
+    /// Write a serialized state on the blocking thread pool, without holding the state lock.
+    async fn write_snapshot(&mut self, height: u64, bytes: Vec<u8>) -> io::Result<SnapshotMeta> {
+        let tic = std::time::Instant::now();
+        let dir = self.dir.clone();
+        let snapshot = spawn_blocking(move || save_snapshot(&dir, height, bytes)).await?;
+        self.last_snapshot_height = Some(snapshot.block_height);
+        tracing::info!(
+            "Saved snapshot at block {} in {:?}",
+            snapshot.block_height,
+            tic.elapsed()
+        );
+
+        self.snapshots_since_compaction += 1;
+        // Compaction reads every retained snapshot, so it runs on the blocking thread pool rather
+        // than holding up the caller. If the previous compaction is still running, try again after
+        // the next snapshot.
+        if self.keep_latest > 0
+            && self.snapshots_since_compaction >= self.keep_latest
+            && !self.compacting.swap(true, Ordering::SeqCst)
+        {
+            let dir = self.dir.clone();
+            let keep_latest = self.keep_latest;
+            let compacting = self.compacting.clone();
+            spawn_blocking(move || {
+                match compact_snapshots(&dir, keep_latest) {
+                    Ok(removed) => tracing::info!("Removed {} old snapshots", removed),
+                    Err(err) => tracing::error!("Failed to compact snapshots: {}", err),
+                }
+                compacting.store(false, Ordering::SeqCst);
+            });
+            self.snapshots_since_compaction = 0;
+        }
+        Ok(snapshot)
+    }
+
+    /// Check whether a snapshot is due every `period`, in a background task.
+    pub fn spawn(mut self, period: Duration) -> JoinHandle<()> {
+        spawn(async move {
+            loop {
+                if let Err(err) = self.snapshot_if_due().await {
+                    tracing::error!("Failed to save snapshot: {}", err);
+                }
+                sleep(period).await;
+            }
+        })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::query_result_state::{QueryResultStateV0, StateFormatError, STATE_FORMAT_VERSION};
+    use atomic_store::load_store::BincodeLoadStore;
+    use cap_rust_sandbox::ledger::{CapeBlock, CapeTransition};
+    use cap_rust_sandbox::model::{Erc20Code, EthereumAddr, CAPE_MERKLE_HEIGHT};
+    use cap_rust_sandbox::universal_param::verifier_keys;
+    use ethers::prelude::Address;
+    use jf_cap::structs::{AssetCode, RecordCommitment, RecordOpening};
+    use jf_cap::MerkleTree;
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+    use reef::traits::Block;
+    use seahorse::events::LedgerEvent;
+    use tempdir::TempDir;
+
+    #[test]
+    fn test_upgrade_unversioned_state() {
+        let dir = TempDir::new("eqs_upgrade").unwrap();
+        let rng = &mut ChaChaRng::from_seed([0; 32]);
+
+        // A state with two committed blocks, in the layout persisted before versioning.
+        let mut tree = MerkleTree::new(CAPE_MERKLE_HEIGHT).unwrap();
+        let mut old = QueryResultStateV0 {
+            ledger_state: QueryResultState::new(verifier_keys()).ledger_state,
+            nullifiers: Default::default(),
+            verifier_keys: verifier_keys(),
+            last_reported_index: Some((7, 1)),
+            contract_address: Some(Address::from_low_u64_be(2)),
+            num_confirmations: None,
+            events: vec![],
+            transaction_by_id: Default::default(),
+            transaction_id_by_hash: Default::default(),
+            address_from_asset: [(AssetCode::native(), Address::from_low_u64_be(1))]
+                .into_iter()
+                .collect(),
+        };
+        for block_id in 0..2 {
+            let ro = RecordOpening::rand_for_test(rng);
+            tree.push(RecordCommitment::from(&ro).to_field_element());
+            old.events.push(LedgerEvent::Commit {
+                block: CapeBlock::new(vec![CapeTransition::Wrap {
+                    ro: Box::new(ro),
+                    erc20_code: Erc20Code(EthereumAddr([0; 20])),
+                    src_addr: EthereumAddr([0; 20]),
+                }]),
+                block_id,
+                state_comm: block_id + 1,
+            });
+        }
+        old.ledger_state.state_number = 2;
+        old.ledger_state.record_merkle_commitment = tree.commitment();
+        old.ledger_state.record_merkle_frontier = tree.frontier();
+
+        // Persist it the way the previous version of the EQS did.
+        {
+            let store_path = dir.path().join("eqs");
+            let mut loader = AtomicStoreLoader::create(&store_path, "test").unwrap();
+            let mut log = RollingLog::<BincodeLoadStore<QueryResultStateV0>>::create(
+                &mut loader,
+                Default::default(),
+                "test_state",
+                1024,
+            )
+            .unwrap();
+            let mut atomic_store = AtomicStore::open(loader).unwrap();
+            log.store_resource(&old).unwrap();
+            log.commit_version().unwrap();
+            atomic_store.commit_version().unwrap();
+        }
+
+        let mut persistence = StatePersistence::load(dir.path(), "test").unwrap();
+        let state = persistence.load_latest_state().unwrap();
+        assert_eq!(state.ledger_state.state_number, 2);
+        assert_eq!(state.last_reported_index, Some((7, 1)));
+        assert_eq!(state.contract_address, Some(Address::from_low_u64_be(2)));
+        assert_eq!(state.address_from_asset, old.address_from_asset);
+        assert_eq!(state.events.len(), 2);
+        assert!(state.blocks.is_empty());
+        // The records Merkle tree is rebuilt from the committed blocks.
+        assert_eq!(state.record_merkle_tree.commitment(), tree.commitment());
+        assert!(state.get_merkle_proof(1).is_some());
+        assert!(state.get_merkle_proof(2).is_none());
+
+        // The state is written back in the current format.
+        persistence.store_latest_state(&state);
+        drop(persistence);
+        let persistence = StatePersistence::load(dir.path(), "test").unwrap();
+        assert_eq!(
+            persistence
+                .load_latest_state()
+                .unwrap()
+                .record_merkle_tree
+                .commitment(),
+            tree.commitment()
+        );
+    }
+
+    #[test]
+    fn test_state_format_version() {
+        let state = QueryResultState::new(verifier_keys());
+        let mut bytes = state.to_bytes().unwrap();
+        assert_eq!(
+            bincode::serialize(&QueryResultState::from_bytes(&bytes).unwrap()).unwrap(),
+            bincode::serialize(&state).unwrap()
+        );
+
+        // A state written by a newer version is rejected rather than misread.
+        bytes[8..12].copy_from_slice(&(STATE_FORMAT_VERSION + 1).to_le_bytes());
+        assert!(matches!(
+            QueryResultState::from_bytes(&bytes),
+            Err(StateFormatError::UnsupportedVersion { version }) if version == STATE_FORMAT_VERSION + 1
+        ));
+        assert!(QueryResultState::from_bytes(b"CAPE-EQS").is_err());
+    }
+
+    #[async_std::test]
+    async fn test_snapshot_and_restart() {
+        let dir = TempDir::new("eqs_snapshots").unwrap();
+        let mut persistence = StatePersistence::new(dir.path(), "test").unwrap();
+        let mut state = QueryResultState::new(verifier_keys());
+        state.ledger_state.state_number = 1500;
+        state.last_reported_index = Some((10, 2));
+        state
+            .address_from_asset
+            .insert(AssetCode::native(), Address::from_low_u64_be(1));
+        let state = Arc::new(RwLock::new(state));
+
+        // The first snapshot is always due.
+        let mut scheduler = persistence.snapshot_scheduler(state.clone(), 1000, 10);
+        let snapshot = scheduler.snapshot_if_due().await.unwrap().unwrap();
+        assert_eq!(snapshot.block_height, 1500);
+
+        // The next one is due after `interval` blocks.
+        state.write().await.ledger_state.state_number = 2000;
+        assert_eq!(scheduler.snapshot_if_due().await.unwrap(), None);
+        state.write().await.ledger_state.state_number = 2500;
+        let snapshot = scheduler.snapshot_if_due().await.unwrap().unwrap();
+        assert_eq!(snapshot.block_height, 2500);
+
+        persistence.store_latest_state(&*state.read().await);
+        drop(persistence);
+
+        // Simulate a restart.
+        let persistence = StatePersistence::load(dir.path(), "test").unwrap();
+        assert_eq!(
+            persistence
+                .list_snapshots()
+                .iter()
+                .map(|snapshot| snapshot.block_height)
+                .collect::<Vec<_>>(),
+            vec![1500, 2500]
+        );
+        let expected = state.read().await;
+        let loaded = persistence.load_latest_snapshot().unwrap();
+        assert_eq!(
+            loaded.ledger_state.state_number,
+            expected.ledger_state.state_number
+        );
+        assert_eq!(loaded.last_reported_index, expected.last_reported_index);
+        assert_eq!(loaded.address_from_asset, expected.address_from_asset);
+
+        // Snapshots which cannot be read are skipped.
+        fs::write(snapshot.path.with_file_name("3000.snapshot"), b"corrupt").unwrap();
+        assert_eq!(persistence.list_snapshots().len(), 3);
+        let loaded = persistence.load_latest_snapshot().unwrap();
+        assert_eq!(loaded.ledger_state.state_number, 2500);
+
+        // Resetting the state removes the snapshots.
+        drop(persistence);
+        let persistence = StatePersistence::new(dir.path(), "test").unwrap();
+        assert!(persistence.list_snapshots().is_empty());
+    }
+
+    #[async_std::test]
+    async fn test_compact() {
+        let dir = TempDir::new("eqs_compact").unwrap();
+        let persistence = StatePersistence::new(dir.path(), "test").unwrap();
+        let state = Arc::new(RwLock::new(QueryResultState::new(verifier_keys())));
+        let mut scheduler = persistence.snapshot_scheduler(state.clone(), 1, 0);
+        for height in 1..=10 {
+            state.write().await.ledger_state.state_number = height;
+            scheduler.force_snapshot().await.unwrap();
+        }
+        assert_eq!(persistence.list_snapshots().len(), 10);
+
+        assert_eq!(persistence.compact(3).unwrap(), 7);
+        let remaining = persistence.list_snapshots();
+        assert_eq!(
+            remaining
+                .iter()
+                .map(|snapshot| snapshot.block_height)
+                .collect::<Vec<_>>(),
+            vec![8, 9, 10]
+        );
+        for snapshot in &remaining {
+            assert_eq!(
+                snapshot.load().unwrap().ledger_state.state_number,
+                snapshot.block_height
+            );
+        }
+
+        // Nothing is removed if a retained snapshot is corrupt.
+        fs::write(&remaining[2].path, b"corrupt").unwrap();
+        persistence.compact(1).unwrap_err();
+        assert_eq!(persistence.list_snapshots().len(), 3);
+
+        // The scheduler compacts after every `keep_latest` snapshots, in the background.
+        let mut scheduler = persistence.snapshot_scheduler(state.clone(), 1, 2);
+        for height in 11..=12 {
+            state.write().await.ledger_state.state_number = height;
+            scheduler.force_snapshot().await.unwrap();
+        }
+        let heights = || {
+            persistence
+                .list_snapshots()
+                .iter()
+                .map(|snapshot| snapshot.block_height)
+                .collect::<Vec<_>>()
+        };
+        for _ in 0..100 {
+            if heights() == vec![11, 12] {
+                break;
+            }
+            sleep(Duration::from_millis(50)).await;
+        }
+        assert_eq!(heights(), vec![11, 12]);
+    }
 }
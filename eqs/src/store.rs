@@ -0,0 +1,268 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Storage backends for the persisted EQS state.
+//!
+//! The EQS persists its state through an [EqsStore], selected with `--store-backend`:
+//! * `file`, the default, is a [FileEqsStore], which keeps the latest states in an atomic store
+//!   and periodic full snapshots in separate files;
+//! * `sqlite`, available with the `sqlite-store` feature, is a
+//!   [SqliteEqsStore](crate::sqlite_store::SqliteEqsStore), which keeps the latest states as
+//!   blobs in a SQLite database, keyed by block height.
+
+use crate::query_result_state::QueryResultState;
+use crate::state_persistence::{SnapshotMeta, SnapshotScheduler, StatePersistence, EQS_KEY_TAG};
+use async_std::sync::{Arc, RwLock};
+use atomic_store::PersistenceError;
+use serde::{Deserialize, Serialize};
+use std::io;
+use std::path::Path;
+use std::str::FromStr;
+use std::sync::Mutex;
+
+/// Persistent storage of the EQS state.
+pub trait EqsStore: Send + Sync {
+    /// Persist `state`, the state at CAPE block `height`, as the latest state.
+    fn save_state(&self, state: &QueryResultState, height: u64) -> io::Result<()>;
+
+    /// The most recently persisted state and its CAPE block height.
+    ///
+    /// Fails with [io::ErrorKind::NotFound] if no state was persisted yet.
+    fn load_latest_state(&self) -> io::Result<(QueryResultState, u64)>;
+
+    /// The available snapshots, sorted by increasing block height.
+    fn list_snapshots(&self) -> io::Result<Vec<SnapshotMeta>>;
+
+    /// Delete the snapshots taken above block height `height`, after rolling the state back.
+    fn remove_snapshots_after(&self, height: u64) -> io::Result<()>;
+
+    /// A scheduler saving periodic snapshots of `state`, if this store keeps them separately
+    /// from the latest states.
+    fn snapshot_scheduler(
+        &self,
+        _state: Arc<RwLock<QueryResultState>>,
+        _interval: u64,
+        _keep_latest: usize,
+    ) -> Option<SnapshotScheduler> {
+        None
+    }
+}
+
+/// The kind of [EqsStore] used by the EQS.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, Serialize, Deserialize)]
+#[serde(rename_all = "lowercase")]
+pub enum StoreBackend {
+    File,
+    Sqlite,
+}
+
+impl Default for StoreBackend {
+    fn default() -> Self {
+        Self::File
+    }
+}
+
+impl FromStr for StoreBackend {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s {
+            "file" => Ok(Self::File),
+            "sqlite" => Ok(Self::Sqlite),
+            _ => Err(format!(
+                "unknown store backend {}, expected file or sqlite",
+                s
+            )),
+        }
+    }
+}
+
+/// Open the store of kind `backend` under `store_path`, deleting any persisted state if `reset`.
+pub fn open_store(
+    backend: StoreBackend,
+    store_path: &Path,
+    reset: bool,
+) -> io::Result<Box<dyn EqsStore>> {
+    match backend {
+        StoreBackend::File => Ok(Box::new(FileEqsStore::open(store_path, reset)?)),
+        #[cfg(feature = "sqlite-store")]
+        StoreBackend::Sqlite => Ok(Box::new(crate::sqlite_store::SqliteEqsStore::open(
+            store_path, reset,
+        )?)),
+        #[cfg(not(feature = "sqlite-store"))]
+        StoreBackend::Sqlite => Err(io::Error::new(
+            io::ErrorKind::Unsupported,
+            "the EQS was built without the sqlite-store feature",
+        )),
+    }
+}
+
+fn persistence_error(err: PersistenceError) -> io::Error {
+    match err {
+        PersistenceError::FailedToFindExpectedResource { .. } => {
+            io::Error::new(io::ErrorKind::NotFound, format!("{:?}", err))
+        }
+        err => io::Error::new(io::ErrorKind::Other, format!("{:?}", err)),
+    }
+}
+
+/// Store keeping the latest states in an atomic store, and periodic snapshots in files.
+pub struct FileEqsStore {
+    persistence: Mutex<StatePersistence>,
+}
+
+impl FileEqsStore {
+    pub fn open(store_path: &Path, reset: bool) -> io::Result<Self> {
+        let persistence = if reset {
+            StatePersistence::new(store_path, EQS_KEY_TAG)
+        } else {
+            StatePersistence::load(store_path, EQS_KEY_TAG)
+        }
+        .map_err(persistence_error)?;
+        Ok(Self {
+            persistence: Mutex::new(persistence),
+        })
+    }
+}
+
+impl EqsStore for FileEqsStore {
+    fn save_state(&self, state: &QueryResultState, _height: u64) -> io::Result<()> {
+        self.persistence.lock().unwrap().store_latest_state(state);
+        Ok(())
+    }
+
+    /// Falls back to the most recent snapshot if it is ahead of the persisted state, or if there
+    /// is no usable persisted state.
+    fn load_latest_state(&self) -> io::Result<(QueryResultState, u64)> {
+        let persistence = self.persistence.lock().unwrap();
+        let snapshot = persistence.load_latest_snapshot();
+        let state = match (persistence.load_latest_state(), snapshot) {
+            (Ok(state), Some(snapshot))
+                if snapshot.ledger_state.state_number > state.ledger_state.state_number =>
+            {
+                snapshot
+            }
+            (Ok(state), _) => state,
+            (Err(err), Some(snapshot)) => {
+                tracing::warn!("Restoring snapshot, failed to load state: {:?}", err);
+                snapshot
+            }
+            (Err(err), None) => return Err(persistence_error(err)),
+        };
+        let height = state.ledger_state.state_number;
+        Ok((state, height))
+    }
+
+    fn list_snapshots(&self) -> io::Result<Vec<SnapshotMeta>> {
+        Ok(self.persistence.lock().unwrap().list_snapshots())
+    }
+
+    fn remove_snapshots_after(&self, height: u64) -> io::Result<()> {
+        self.persistence
+            .lock()
+            .unwrap()
+            .remove_snapshots_after(height);
+        Ok(())
+    }
+
+    fn snapshot_scheduler(
+        &self,
+        state: Arc<RwLock<QueryResultState>>,
+        interval: u64,
+        keep_latest: usize,
+    ) -> Option<SnapshotScheduler> {
+        Some(
+            self.persistence
+                .lock()
+                .unwrap()
+                .snapshot_scheduler(state, interval, keep_latest),
+        )
+    }
+}
+
+#[cfg(test)]
+pub(crate) mod tests {
+    use super::*;
+    use cap_rust_sandbox::universal_param::verifier_keys;
+    use ethers::prelude::Address;
+    use jf_cap::structs::AssetCode;
+    use tempdir::TempDir;
+
+    fn state_at(height: u64) -> QueryResultState {
+        let mut state = QueryResultState::new(verifier_keys());
+        state.ledger_state.state_number = height;
+        state.last_reported_index = Some((height, 0));
+        state
+            .address_from_asset
+            .insert(AssetCode::native(), Address::from_low_u64_be(height));
+        state
+    }
+
+    /// Checks every [EqsStore] must pass. `open` opens the store in `dir`, resetting it if asked.
+    pub(crate) fn check_store<S: EqsStore>(open: impl Fn(&Path, bool) -> S) {
+        let dir = TempDir::new("eqs_store").unwrap();
+        let store = open(dir.path(), true);
+        assert_eq!(
+            store.load_latest_state().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+
+        // Save more states than a store may retain.
+        for height in 1..=6 {
+            store.save_state(&state_at(height), height).unwrap();
+        }
+        let (state, height) = store.load_latest_state().unwrap();
+        assert_eq!(height, 6);
+        assert_eq!(state.ledger_state.state_number, 6);
+        assert_eq!(state.last_reported_index, Some((6, 0)));
+        assert_eq!(state.address_from_asset, state_at(6).address_from_asset);
+
+        // The latest state survives a restart.
+        drop(store);
+        let store = open(dir.path(), false);
+        let (state, height) = store.load_latest_state().unwrap();
+        assert_eq!(height, 6);
+        assert_eq!(state.address_from_asset, state_at(6).address_from_asset);
+
+        // Rolling back replaces the latest state, even below the oldest retained state.
+        store.save_state(&state_at(1), 1).unwrap();
+        let (state, height) = store.load_latest_state().unwrap();
+        assert_eq!(height, 1);
+        assert_eq!(state.address_from_asset, state_at(1).address_from_asset);
+        store.remove_snapshots_after(1).unwrap();
+        assert_eq!(store.load_latest_state().unwrap().1, 1);
+        let snapshots = store.list_snapshots().unwrap();
+        assert!(snapshots.iter().all(|snapshot| snapshot.block_height <= 1));
+        assert!(snapshots
+            .windows(2)
+            .all(|pair| pair[0].block_height < pair[1].block_height));
+
+        // Resetting removes the persisted state.
+        drop(store);
+        let store = open(dir.path(), true);
+        assert_eq!(
+            store.load_latest_state().unwrap_err().kind(),
+            io::ErrorKind::NotFound
+        );
+        assert!(store.list_snapshots().unwrap().is_empty());
+    }
+
+    #[test]
+    fn test_file_store() {
+        check_store(|path, reset| FileEqsStore::open(path, reset).unwrap());
+    }
+
+    #[test]
+    fn test_store_backend_from_str() {
+        assert_eq!("file".parse::<StoreBackend>().unwrap(), StoreBackend::File);
+        assert_eq!(
+            "sqlite".parse::<StoreBackend>().unwrap(),
+            StoreBackend::Sqlite
+        );
+        assert!("rocksdb".parse::<StoreBackend>().is_err());
+    }
+}
@@ -10,7 +10,7 @@
 //! Give faucet-shower a master mnemonic for a funded wallet and a number N and it will generate N
 //! new wallets, transfer some tokens from the master wallet to each new wallet, and print the
 //! mnemonics and public keys of the newly funded wallets.
-use cap_rust_sandbox::universal_param::UNIVERSAL_PARAM;
+use cap_rust_sandbox::universal_param::universal_param;
 use cape_wallet::{
     backend::{CapeBackend, CapeBackendConfig},
     loader::CapeLoader,
@@ -91,7 +91,7 @@ async fn create_wallet(
         CapeLoader::latest_contract(opt.eqs_url.clone()).await?,
     );
     let backend = CapeBackend::new(
-        &UNIVERSAL_PARAM,
+        universal_param(),
         CapeBackendConfig {
             // We're not going to do any direct-to-contract operations that
             // would require a connection to the CAPE contract or an ETH
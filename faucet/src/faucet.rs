@@ -18,7 +18,7 @@ use async_std::{
 use atomic_store::{
     load_store::BincodeLoadStore, AppendLog, AtomicStore, AtomicStoreLoader, PersistenceError,
 };
-use cap_rust_sandbox::{ledger::CapeLedger, universal_param::UNIVERSAL_PARAM};
+use cap_rust_sandbox::{ledger::CapeLedger, universal_param::universal_param};
 use cape_wallet::{
     backend::{CapeBackend, CapeBackendConfig},
     loader::CapeLoader,
@@ -863,7 +863,7 @@ pub async fn init_web_server(
             .unwrap(),
     );
     let backend = CapeBackend::new(
-        &UNIVERSAL_PARAM,
+        universal_param(),
         CapeBackendConfig {
             // We're not going to do any direct-to-contract operations that would require a
             // connection to the CAPE contract or an ETH wallet. Everything we do will go through
@@ -997,7 +997,7 @@ async fn main() -> Result<(), std::io::Error> {
 mod test {
     use super::*;
     use async_std::task::spawn_blocking;
-    use cap_rust_sandbox::universal_param::UNIVERSAL_PARAM;
+    use cap_rust_sandbox::universal_param::universal_param;
     use cape_wallet::testing::{create_test_network, retry, rpc_url_for_test, spawn_eqs};
     use escargot::CargoBuild;
     use ethers::prelude::U256;
@@ -1112,7 +1112,7 @@ mod test {
 
     async fn parallel_request(num_requests: usize, restart: bool) {
         let mut rng = ChaChaRng::from_seed([1u8; 32]);
-        let universal_param = &UNIVERSAL_PARAM;
+        let universal_param = universal_param();
 
         // Create test network with a faucet key pair.
         let (key_stream, mnemonic) = KeyTree::random(&mut rng);
@@ -0,0 +1,214 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Authentication of submissions with a key shared between the relayer and its clients.
+//!
+//! A client authenticates a request by sending the hex encoded HMAC-SHA256 of the raw request body
+//! in the [HMAC_HEADER] header. For routes whose parameters are in the path, such as
+//! `/cancel/:tx_hash`, the HMAC covers the path of the request followed by the body, see
+//! [HmacAuth::covering_path].
+
+use hmac::{Hmac, Mac};
+use sha2::Sha256;
+use subtle::ConstantTimeEq;
+use tide::{Middleware, Next, Request, Response, StatusCode};
+
+/// Header carrying the HMAC of the request body.
+pub const HMAC_HEADER: &str = "X-Cape-HMAC";
+
+/// Key shared with the clients allowed to submit transactions.
+#[derive(Clone)]
+pub struct HmacKey(Vec<u8>);
+
+impl HmacKey {
+    pub fn new(key: impl Into<Vec<u8>>) -> Self {
+        Self(key.into())
+    }
+
+    /// The HMAC-SHA256 of `body`.
+    pub fn sign(&self, body: &[u8]) -> Vec<u8> {
+        let mut mac =
+            Hmac::<Sha256>::new_from_slice(&self.0).expect("HMAC accepts keys of any length");
+        mac.update(body);
+        mac.finalize().into_bytes().to_vec()
+    }
+
+    /// Check `tag` against the HMAC of `body`, in constant time.
+    pub fn verify(&self, body: &[u8], tag: &[u8]) -> bool {
+        self.sign(body).ct_eq(tag).into()
+    }
+}
+
+impl std::fmt::Debug for HmacKey {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        // Keep the key out of the logs.
+        f.write_str("HmacKey(..)")
+    }
+}
+
+/// Reject requests without a valid [HMAC_HEADER] with `401 Unauthorized`.
+///
+/// If no key is configured, all requests are let through.
+#[derive(Clone, Debug)]
+pub struct HmacAuth {
+    key: Option<HmacKey>,
+    cover_path: bool,
+}
+
+impl HmacAuth {
+    pub fn new(key: Option<HmacKey>) -> Self {
+        Self {
+            key,
+            cover_path: false,
+        }
+    }
+
+    /// Authenticate the path of the request (e.g. `/cancel/0x...`) followed by the body, instead
+    /// of the body alone, so that a tag cannot be replayed with other path parameters.
+    pub fn covering_path(mut self) -> Self {
+        self.cover_path = true;
+        self
+    }
+}
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for HmacAuth {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let key = match &self.key {
+            Some(key) => key,
+            None => return Ok(next.run(req).await),
+        };
+        let body = req.body_bytes().await?;
+        let message = if self.cover_path {
+            [req.url().path().as_bytes(), &body].concat()
+        } else {
+            body.clone()
+        };
+        let authorized = req
+            .header(HMAC_HEADER)
+            .and_then(|tag| hex::decode(tag.as_str()).ok())
+            .map_or(false, |tag| key.verify(&message, &tag));
+        if !authorized {
+            return Ok(Response::builder(StatusCode::Unauthorized)
+                .body(format!("missing or invalid {} header", HMAC_HEADER))
+                .build());
+        }
+        // The handler reads the body again.
+        req.set_body(body);
+        Ok(next.run(req).await)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tide::http::{Method, Url};
+
+    fn server(key: Option<HmacKey>) -> tide::Server<()> {
+        let mut server = tide::new();
+        server
+            .at("/submit")
+            .with(HmacAuth::new(key.clone()))
+            .post(|mut req: Request<()>| async move { Ok(req.body_string().await?) });
+        server
+            .at("/cancel/:tx_hash")
+            .with(HmacAuth::new(key).covering_path())
+            .post(|req: Request<()>| async move { Ok(req.param("tx_hash")?.to_string()) });
+        server
+    }
+
+    async fn post(
+        server: &tide::Server<()>,
+        path: &str,
+        body: &str,
+        tag: Option<String>,
+    ) -> tide::http::Response {
+        let mut req = tide::http::Request::new(
+            Method::Post,
+            Url::parse(&format!("http://localhost{}", path)).unwrap(),
+        );
+        if let Some(tag) = tag {
+            req.insert_header(HMAC_HEADER, tag);
+        }
+        req.set_body(body);
+        server.respond(req).await.unwrap()
+    }
+
+    async fn submit(
+        server: &tide::Server<()>,
+        body: &str,
+        tag: Option<String>,
+    ) -> tide::http::Response {
+        post(server, "/submit", body, tag).await
+    }
+
+    #[async_std::test]
+    async fn test_hmac_auth() {
+        let body = "transaction";
+
+        // Without a key, no authentication is required.
+        let mut res = submit(&server(None), body, None).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(res.body_string().await.unwrap(), body);
+
+        let key = HmacKey::new("shared secret");
+        let server = server(Some(key.clone()));
+
+        // The handler gets the authenticated body.
+        let mut res = submit(&server, body, Some(hex::encode(key.sign(body.as_bytes())))).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(res.body_string().await.unwrap(), body);
+
+        // HMAC of another body.
+        let res = submit(&server, body, Some(hex::encode(key.sign(b"other")))).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+
+        // HMAC with another key.
+        let other_key = HmacKey::new("other secret");
+        let res = submit(
+            &server,
+            body,
+            Some(hex::encode(other_key.sign(body.as_bytes()))),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+
+        // Missing header.
+        let res = submit(&server, body, None).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+    }
+
+    #[async_std::test]
+    async fn test_hmac_auth_covering_path() {
+        let key = HmacKey::new("shared secret");
+        let server = server(Some(key.clone()));
+
+        // The HMAC covers the path, which carries the parameters.
+        let tag = hex::encode(key.sign(b"/cancel/0x01"));
+        let mut res = post(&server, "/cancel/0x01", "", Some(tag.clone())).await;
+        assert_eq!(res.status(), StatusCode::Ok);
+        assert_eq!(res.body_string().await.unwrap(), "0x01");
+
+        // The tag cannot be used for another transaction.
+        let res = post(&server, "/cancel/0x02", "", Some(tag)).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+
+        // The HMAC of the body alone is not enough.
+        let res = post(
+            &server,
+            "/cancel/0x01",
+            "",
+            Some(hex::encode(key.sign(b""))),
+        )
+        .await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+
+        // Missing header.
+        let res = post(&server, "/cancel/0x01", "", None).await;
+        assert_eq!(res.status(), StatusCode::Unauthorized);
+    }
+}
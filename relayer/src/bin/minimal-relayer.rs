@@ -5,21 +5,30 @@
 // This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
-#[warn(unused_imports)]
+use async_std::prelude::*;
 use cap_rust_sandbox::{
-    ethereum::{ensure_connected_to_contract, get_provider_from_url},
+    ethereum::{ensure_connected_to_contract, get_provider_from_url_with_timeout},
     types::CAPE,
 };
 use ethers::prelude::{
     coins_bip39::English, Address, Middleware, MnemonicBuilder, Signer, SignerMiddleware,
 };
 use relayer::{
-    init_web_server, submit_empty_block_loop, NonceCountRule, WebState, DEFAULT_RELAYER_EXTRA_GAS,
-    DEFAULT_RELAYER_MAX_RETRIES, DEFAULT_RELAYER_PORT, DEFAULT_RELAYER_RETRY_INTERVAL_MS,
+    init_web_server, log_subscriber, parse_miner_key, stuck_tx_monitor, submit_empty_block_loop,
+    BackoffMultiplier, BindAddress, ConfigError, LogFormat, NonceCountRule, RelayerConfig,
+    WebState, DEFAULT_RELAYER_PORT,
 };
-use std::{num::NonZeroU64, sync::Arc, time::Duration};
+use signal_hook::consts::{SIGINT, SIGTERM};
+use signal_hook_async_std::Signals;
+use std::{num::NonZeroU64, path::PathBuf, sync::Arc, time::Duration};
 use structopt::StructOpt;
+use tokio_util::sync::CancellationToken;
 
+/// Options of the minimal relayer.
+///
+/// The submission parameters are read from the environment, see
+/// [RelayerConfig::from_env]. The corresponding flags, if given, take
+/// precedence over the environment.
 #[derive(Debug, StructOpt)]
 #[structopt(name = "Minimal CAPE Relayer")]
 struct MinimalRelayerOptions {
@@ -31,6 +40,13 @@ struct MinimalRelayerOptions {
     )]
     rpc_url: String,
 
+    /// Comma separated URLs of Ethers providers to fall back to.
+    ///
+    /// Transactions are submitted through `rpc_url` first. If that fails,
+    /// each of these providers is tried in order.
+    #[structopt(long, env = "CAPE_RELAYER_RPC_URLS", use_delimiter = true)]
+    fallback_rpc_urls: Vec<String>,
+
     /// Address for CAPE submit
     #[structopt(env = "CAPE_CONTRACT_ADDRESS")]
     cape_address: Address,
@@ -43,20 +59,9 @@ struct MinimalRelayerOptions {
     #[structopt(long, env = "CAPE_RELAYER_PORT", default_value = DEFAULT_RELAYER_PORT)]
     port: u16,
 
-    /// Determines how transaction nonces should be calculated.
-    ///
-    /// * `"mined"` - only count mined transaction when creating the nonce.
-    /// * `"pending"` - also include pending transactions when creating the nonce.
-    ///
-    /// Including "pending" transactions allows the relayer to submit the next
-    /// transaction as soon as the previous one hit the nodes' mempool.
-    #[structopt(
-        long,
-        env = "CAPE_RELAYER_NONCE_COUNT_RULE",
-        default_value = "pending",
-        verbatim_doc_comment
-    )]
-    nonce_count_rule: NonceCountRule,
+    /// Path of a Unix domain socket to listen on instead of `port`.
+    #[structopt(long, env = "CAPE_RELAYER_SOCKET_PATH")]
+    socket_path: Option<PathBuf>,
 
     /// Amount of time between submission of empty blocks.
     ///
@@ -69,40 +74,139 @@ struct MinimalRelayerOptions {
     )]
     empty_block_interval: NonZeroU64,
 
-    /// Ethereum gas limit for the relayer's Ethereum transactions.
+    /// Determines how transaction nonces should be calculated.
+    ///
+    /// * `"mined"` - only count mined transaction when creating the nonce.
+    /// * `"pending"` - also include pending transactions when creating the nonce.
+    ///
+    /// Overrides `CAPE_RELAYER_NONCE_COUNT_RULE`.
+    #[structopt(long, verbatim_doc_comment)]
+    nonce_count_rule: Option<NonceCountRule>,
+
+    /// Gas added to the estimate of the relayer's Ethereum transactions.
     ///
-    /// The default of 10M is enough to cover the gas cost of submitting one note
-    /// and crediting up to 10 pending deposits in the smart contract.
-    #[structopt(long, env = "CAPE_RELAYER_EXTRA_GAS", default_value = DEFAULT_RELAYER_EXTRA_GAS)]
-    extra_gas: NonZeroU64,
+    /// Overrides `CAPE_RELAYER_EXTRA_GAS`.
+    #[structopt(long)]
+    extra_gas: Option<NonZeroU64>,
 
     /// Maximum number of times to retry transaction submission.
     ///
-    /// We roughly have an Ethereum block every 12 seconds, and we can put a
-    /// maximum of 3 to 4 transactions into an Ethereum block. The number of
-    /// retries times the retry interval should not significantly exceed 3
-    /// seconds to avoid creating a new bottleneck for the relayer. Although in
-    /// practice nonce errors have so far been rare (about 1 per hour).
-    #[structopt(long, env = "CAPE_RELAYER_MAX_RETRIES", default_value = DEFAULT_RELAYER_MAX_RETRIES)]
-    max_retries: u64,
+    /// Overrides `CAPE_RELAYER_MAX_RETRIES`.
+    #[structopt(long)]
+    max_retries: Option<u64>,
 
     /// Amount of time to sleep (in ms) before retrying after a nonce error.
-    #[structopt(long, env = "CAPE_RELAYER_RETRY_INTERVAL_MS", default_value = DEFAULT_RELAYER_RETRY_INTERVAL_MS)]
-    retry_interval: u64,
+    ///
+    /// Overrides `CAPE_RELAYER_RETRY_INTERVAL_MS`.
+    #[structopt(long)]
+    retry_interval: Option<u64>,
+
+    /// Factor by which the retry interval grows after each failed attempt.
+    ///
+    /// Overrides `CAPE_RELAYER_BACKOFF_MULTIPLIER`.
+    #[structopt(long)]
+    backoff_multiplier: Option<BackoffMultiplier>,
+
+    /// Maximum amount of time to sleep (in ms) between two retries.
+    ///
+    /// Overrides `CAPE_RELAYER_MAX_RETRY_INTERVAL_MS`.
+    #[structopt(long)]
+    max_retry_interval: Option<u64>,
+
+    /// Number of recent submissions remembered to detect duplicates.
+    ///
+    /// Overrides `CAPE_RELAYER_DEDUP_WINDOW`.
+    #[structopt(long)]
+    dedup_window: Option<usize>,
+
+    /// Amount of time (in ms) after which a request to an Ethereum provider is
+    /// abandoned.
+    ///
+    /// Overrides `CAPE_RELAYER_PROVIDER_TIMEOUT_MS`.
+    #[structopt(long)]
+    provider_timeout: Option<u64>,
+
+    /// Public key collecting the fees of submitted blocks.
+    ///
+    /// Hex encoding of the bincode serialized `UserPubKey`. Overrides
+    /// `CAPE_RELAYER_MINER_KEY`.
+    #[structopt(long)]
+    miner_key: Option<String>,
+
+    /// Format of the logs.
+    ///
+    /// * `"full"` - human-readable lines.
+    /// * `"json"` - one JSON object per event, for log aggregators.
+    #[structopt(
+        long,
+        env = "CAPE_RELAYER_LOG_FORMAT",
+        default_value = "full",
+        verbatim_doc_comment
+    )]
+    log_format: LogFormat,
+}
+
+impl MinimalRelayerOptions {
+    /// The relayer configuration from the environment, with the parameters
+    /// given on the command line taking precedence.
+    fn relayer_config(&self) -> Result<RelayerConfig, ConfigError> {
+        let mut config = RelayerConfig::from_env()?;
+        if let Some(nonce_count_rule) = self.nonce_count_rule {
+            config.nonce_count_rule = nonce_count_rule;
+        }
+        if let Some(extra_gas) = self.extra_gas {
+            config.extra_gas = extra_gas.get();
+        }
+        if let Some(max_retries) = self.max_retries {
+            config.max_retries = max_retries;
+        }
+        if let Some(retry_interval) = self.retry_interval {
+            config.retry_interval = Duration::from_millis(retry_interval);
+        }
+        if let Some(backoff_multiplier) = self.backoff_multiplier {
+            config.backoff_multiplier = backoff_multiplier.get();
+        }
+        if let Some(max_retry_interval) = self.max_retry_interval {
+            config.max_retry_interval = Duration::from_millis(max_retry_interval);
+        }
+        if let Some(dedup_window) = self.dedup_window {
+            config.dedup_window = dedup_window;
+        }
+        if let Some(provider_timeout) = self.provider_timeout {
+            config.provider_timeout = Duration::from_millis(provider_timeout);
+        }
+        if let Some(miner_key) = &self.miner_key {
+            config.miner = Some(parse_miner_key(miner_key));
+        }
+        Ok(config)
+    }
+}
+
+/// Cancel `token` when one of `signals` is received.
+async fn cancel_on_signals(mut signals: Signals, token: CancellationToken) {
+    if let Some(signal) = signals.next().await {
+        tracing::info!("Received signal {:?}, shutting down", signal);
+        token.cancel();
+    }
 }
 
 #[async_std::main]
 async fn main() -> std::io::Result<()> {
-    tracing_subscriber::fmt()
-        .compact()
-        .with_ansi(false)
-        .with_env_filter(tracing_subscriber::EnvFilter::from_default_env())
-        .init();
-
     let opt = MinimalRelayerOptions::from_args();
+    let config = opt
+        .relayer_config()
+        .map_err(|err| std::io::Error::new(std::io::ErrorKind::InvalidInput, err))?;
+
+    tracing::subscriber::set_global_default(log_subscriber(
+        opt.log_format,
+        tracing_subscriber::EnvFilter::from_default_env(),
+        std::io::stdout,
+    ))
+    .expect("failed to set up logging");
 
     // Set up a client to submit ETH transactions.
-    let provider = get_provider_from_url(&opt.rpc_url);
+    let provider_timeout = config.provider_timeout;
+    let provider = get_provider_from_url_with_timeout(&opt.rpc_url, provider_timeout);
 
     ensure_connected_to_contract(&provider, opt.cape_address)
         .await
@@ -113,24 +217,57 @@ async fn main() -> std::io::Result<()> {
         .build()
         .expect("could not open relayer wallet")
         .with_chain_id(provider.get_chainid().await.unwrap().as_u64());
-    let client = Arc::new(SignerMiddleware::new(provider, wallet));
+    let client = Arc::new(SignerMiddleware::new(provider, wallet.clone()));
 
     // Connect to CAPE smart contract.
     let contract = CAPE::new(opt.cape_address, client);
 
-    let web_state = WebState::new(
-        contract,
-        opt.nonce_count_rule,
-        opt.extra_gas.into(),
-        opt.max_retries,
-        Duration::from_millis(opt.retry_interval),
-    );
+    // Connect to CAPE smart contract through the fallback providers.
+    let fallbacks = opt
+        .fallback_rpc_urls
+        .iter()
+        .map(|url| {
+            let client = Arc::new(SignerMiddleware::new(
+                get_provider_from_url_with_timeout(url, provider_timeout),
+                wallet.clone(),
+            ));
+            (url.clone(), CAPE::new(opt.cape_address, client))
+        })
+        .collect::<Vec<_>>();
+
+    let web_state = WebState::new(contract, config);
+    let web_state = fallbacks
+        .into_iter()
+        .fold(web_state, |web_state, (url, contract)| {
+            web_state.with_fallback(url, contract)
+        });
+    // Stop the background tasks and the web server on SIGINT or SIGTERM.
+    let token = CancellationToken::new();
+    let signals = Signals::new(&[SIGINT, SIGTERM]).expect("Failed to create signals.");
+    async_std::task::spawn(cancel_on_signals(signals, token.clone()));
+
     // Start serving CAPE transaction submissions.
     let periodic_block_submission = async_std::task::spawn(submit_empty_block_loop(
         web_state.clone(),
         Duration::from_secs(opt.empty_block_interval.into()),
+        token.clone(),
     ));
-    let web_server = init_web_server(web_state, opt.port);
-    let _result = futures::future::join(periodic_block_submission, web_server).await;
+    let stuck_tx_replacement =
+        async_std::task::spawn(stuck_tx_monitor(web_state.clone(), token.clone()));
+    let bind_address = match opt.socket_path {
+        #[cfg(unix)]
+        Some(path) => BindAddress::Unix(path),
+        #[cfg(not(unix))]
+        Some(_) => panic!("Unix domain sockets are not supported on this platform"),
+        None => BindAddress::from(opt.port),
+    };
+    // tide cannot shut down gracefully, so the server is dropped when the
+    // token is cancelled.
+    let web_server = init_web_server(web_state, bind_address).race(async move {
+        token.cancelled().await;
+        Ok(())
+    });
+    let _result =
+        futures::future::join3(periodic_block_submission, stuck_tx_replacement, web_server).await;
     Ok(())
 }
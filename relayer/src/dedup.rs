@@ -0,0 +1,115 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Detection of transactions that were already submitted by this relayer.
+//!
+//! Clients may retry a submission after a network timeout even though the
+//! first request went through. Submitting the same transaction again would
+//! only waste gas, because the contract rejects spent nullifiers.
+
+use ark_serialize::CanonicalSerialize;
+use cap_rust_sandbox::model::CapeModelTxn;
+use ethers::prelude::H256;
+use sha3::{Digest, Sha3_256};
+use std::collections::{HashMap, VecDeque};
+
+/// Content hash of the canonical serialization of a list of transactions.
+pub type TxnDigest = [u8; 32];
+
+pub fn txns_digest<'a>(txns: impl IntoIterator<Item = &'a CapeModelTxn>) -> TxnDigest {
+    let mut hasher = Sha3_256::new();
+    let mut bytes = vec![];
+    for txn in txns {
+        bytes.clear();
+        match txn {
+            CapeModelTxn::CAP(note) => note.serialize(&mut bytes).unwrap(),
+            CapeModelTxn::Burn { xfr, ro } => {
+                xfr.serialize(&mut bytes).unwrap();
+                ro.serialize(&mut bytes).unwrap();
+            }
+        }
+        hasher.update(&bytes);
+    }
+    hasher.finalize().into()
+}
+
+/// The Ethereum transaction hashes of the most recent submissions.
+///
+/// Holds at most `capacity` entries, evicting the least recently used one.
+#[derive(Debug)]
+pub struct RecentSubmissions {
+    capacity: usize,
+    hashes: HashMap<TxnDigest, H256>,
+    order: VecDeque<TxnDigest>,
+}
+
+impl RecentSubmissions {
+    pub fn new(capacity: usize) -> Self {
+        Self {
+            capacity,
+            hashes: HashMap::new(),
+            order: VecDeque::new(),
+        }
+    }
+
+    /// The hash returned when `digest` was submitted, if it is still known.
+    pub fn get(&mut self, digest: &TxnDigest) -> Option<H256> {
+        let hash = self.hashes.get(digest).copied()?;
+        self.touch(digest);
+        Some(hash)
+    }
+
+    pub fn insert(&mut self, digest: TxnDigest, hash: H256) {
+        if self.capacity == 0 {
+            return;
+        }
+        if self.hashes.insert(digest, hash).is_none() {
+            self.order.push_back(digest);
+        } else {
+            self.touch(&digest);
+        }
+        while self.order.len() > self.capacity {
+            if let Some(evicted) = self.order.pop_front() {
+                self.hashes.remove(&evicted);
+            }
+        }
+    }
+
+    pub fn len(&self) -> usize {
+        self.order.len()
+    }
+
+    pub fn is_empty(&self) -> bool {
+        self.order.is_empty()
+    }
+
+    fn touch(&mut self, digest: &TxnDigest) {
+        if let Some(pos) = self.order.iter().position(|d| d == digest) {
+            self.order.remove(pos);
+            self.order.push_back(*digest);
+        }
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+
+    #[test]
+    fn test_recent_submissions_eviction() {
+        let mut recent = RecentSubmissions::new(2);
+        recent.insert([1; 32], H256::from_low_u64_be(1));
+        recent.insert([2; 32], H256::from_low_u64_be(2));
+        // Using the first entry makes the second one the least recently used.
+        assert_eq!(recent.get(&[1; 32]), Some(H256::from_low_u64_be(1)));
+        recent.insert([3; 32], H256::from_low_u64_be(3));
+        assert_eq!(recent.len(), 2);
+        assert_eq!(recent.get(&[2; 32]), None);
+        assert_eq!(recent.get(&[1; 32]), Some(H256::from_low_u64_be(1)));
+        assert_eq!(recent.get(&[3; 32]), Some(H256::from_low_u64_be(3)));
+    }
+}
@@ -7,27 +7,55 @@
 
 #![doc = include_str!("../README.md")]
 
-#[warn(unused_imports)]
+pub mod auth;
+pub mod dedup;
+pub mod metrics;
+
+use ark_serialize::CanonicalSerialize;
+#[cfg(unix)]
+use async_std::os::unix::net::{UnixListener, UnixStream};
 use async_std::sync::{Arc, Mutex};
 use async_std::task;
+use auth::{HmacAuth, HmacKey};
 use cap_rust_sandbox::{
-    cape::{submit_block::submit_cape_block_with_memos, BlockWithMemos, CapeBlock},
+    cape::{
+        submit_block::{build_cape_block_tx, SubmitBlockError},
+        BlockWithMemos, CapeBlock,
+    },
     deploy::EthMiddleware,
     model::CapeModelTxn,
     types::CAPE,
 };
-use ethers::prelude::{BlockNumber, H256, U256};
-use jf_cap::{keys::UserPubKey, structs::ReceiverMemo, Signature};
+use dedup::{txns_digest, RecentSubmissions, TxnDigest};
+use ethers::prelude::{
+    signer::SignerMiddlewareError, BlockNumber, Http, HttpClientError, Middleware,
+    PendingTransaction, ProviderError, TransactionRequest, H256, U256,
+};
+use ethers::types::transaction::eip2718::TypedTransaction;
+use jf_cap::{keys::UserPubKey, structs::ReceiverMemo, Signature, TransactionNote};
+use metrics::Metrics;
 use net::server::{add_error_body, request_body, response};
+use request_id::{RequestIdMiddleware, REQUEST_ID_HEADER};
 use serde::{Deserialize, Serialize};
 use snafu::Snafu;
-use std::{str::FromStr, time::Duration};
+#[cfg(unix)]
+use std::path::{Path, PathBuf};
+use std::{
+    collections::HashMap,
+    future::Future,
+    net::SocketAddr,
+    num::NonZeroU64,
+    str::FromStr,
+    time::{Duration, Instant},
+};
 use tide::{
     http::headers::HeaderValue,
     security::{CorsMiddleware, Origin},
     StatusCode,
 };
-use tracing::{event, Level};
+use tokio_util::sync::CancellationToken;
+use tracing::{event, Level, Subscriber};
+use tracing_subscriber::{fmt::MakeWriter, EnvFilter};
 
 pub const DEFAULT_RELAYER_PORT: &str = "50077";
 
@@ -38,6 +66,32 @@ pub const DEFAULT_RELAYER_EXTRA_GAS: &str = "10000000"; // 10M
 pub const DEFAULT_RELAYER_RETRY_INTERVAL_MS: &str = "500";
 pub const DEFAULT_RELAYER_MAX_RETRIES: &str = "2";
 
+/// Factor by which the retry interval grows after each failed attempt.
+pub const DEFAULT_RELAYER_BACKOFF_MULTIPLIER: &str = "2.0";
+
+/// Number of recent submissions remembered to detect duplicates.
+pub const DEFAULT_RELAYER_DEDUP_WINDOW: &str = "1024";
+
+/// Maximum number of transactions submitted together in one CAPE block.
+pub const DEFAULT_RELAYER_MAX_TXNS_PER_BLOCK: &str = "50";
+
+/// Maximum size in bytes of a submitted CAPE block, the same as
+/// [cap_rust_sandbox::cape::DEFAULT_MAX_BLOCK_BYTES].
+pub const DEFAULT_RELAYER_MAX_BLOCK_BYTES: &str = "131072";
+
+/// Upper bound for the time to sleep between two retries.
+pub const DEFAULT_RELAYER_MAX_RETRY_INTERVAL_MS: &str = "5000";
+
+/// Time after which a request to an Ethereum provider is abandoned.
+pub const DEFAULT_RELAYER_PROVIDER_TIMEOUT_MS: &str = "10000";
+
+/// Time after which a submitted transaction without a receipt is resubmitted
+/// with a higher gas price.
+pub const DEFAULT_RELAYER_STUCK_TX_TIMEOUT_SECS: &str = "120";
+
+/// Highest gas price, in gwei, paid when resubmitting a stuck transaction.
+pub const DEFAULT_RELAYER_STUCK_TX_MAX_GAS_PRICE_GWEI: &str = "500";
+
 #[derive(Clone, Debug, Snafu, Serialize, Deserialize)]
 pub enum Error {
     #[snafu(display("failed to deserialize request body: {}", msg))]
@@ -46,6 +100,9 @@ pub enum Error {
     #[snafu(display("submitted transaction does not form a valid block: {}", msg))]
     BadBlock { msg: String },
 
+    #[snafu(display("memos signature does not verify: {}", msg))]
+    BadSignature { msg: String },
+
     #[snafu(display("error during transaction submission: {}", msg))]
     Submission { msg: String },
 
@@ -63,6 +120,31 @@ pub enum Error {
 
     #[snafu(display("submission failed with nonce error: {}", msg))]
     Nonce { msg: String },
+
+    #[snafu(display("simulated submission was rejected: {}", msg))]
+    SimulationFailed { msg: String },
+
+    #[snafu(display("failed to cancel transaction: {}", msg))]
+    CancelFailed { msg: String },
+
+    #[snafu(display("Ethereum provider did not respond in time: {}", msg))]
+    ProviderTimeout { msg: String },
+
+    #[snafu(display(
+        "block of {} transactions exceeds the limit of {} transactions",
+        actual,
+        max
+    ))]
+    BlockTooLarge { actual: usize, max: usize },
+
+    #[snafu(display("block of {} bytes exceeds the limit of {} bytes", actual, max))]
+    BlockBytesTooLarge { actual: usize, max: usize },
+
+    #[snafu(display("submission failed with all providers {:?}: {:?}", providers, msgs))]
+    AllProvidersFailed {
+        providers: Vec<String>,
+        msgs: Vec<String>,
+    },
 }
 
 impl net::Error for Error {
@@ -74,12 +156,19 @@ impl net::Error for Error {
         match self {
             Self::Deserialize { .. }
             | Self::BadBlock { .. }
+            | Self::BadSignature { .. }
             | Self::RootNotFound { .. }
-            | Self::BlockAndPendingDepositsQueueEmpty { .. } => StatusCode::BadRequest,
+            | Self::BlockAndPendingDepositsQueueEmpty { .. }
+            | Self::BlockTooLarge { .. }
+            | Self::SimulationFailed { .. }
+            | Self::CancelFailed { .. } => StatusCode::BadRequest,
             Self::Submission { .. }
             | Self::CallContract { .. }
             | Self::Internal { .. }
-            | Self::Nonce { .. } => StatusCode::InternalServerError,
+            | Self::Nonce { .. }
+            | Self::AllProvidersFailed { .. } => StatusCode::InternalServerError,
+            Self::ProviderTimeout { .. } => StatusCode::GatewayTimeout,
+            Self::BlockBytesTooLarge { .. } => StatusCode::PayloadTooLarge,
         }
     }
 }
@@ -88,31 +177,432 @@ fn server_error<E: Into<Error>>(err: E) -> tide::Error {
     net::server_error(err)
 }
 
+/// Parameters of the relayer.
+///
+/// [RelayerConfig::default] uses the `DEFAULT_RELAYER_*` constants, and
+/// [RelayerConfig::from_env] reads the `CAPE_RELAYER_*` environment variables.
+/// Individual parameters can be set with a [RelayerConfigBuilder].
+#[derive(Clone, Debug)]
+pub struct RelayerConfig {
+    /// Determines how transaction nonces should be calculated.
+    ///
+    /// Including "pending" transactions allows the relayer to submit the next
+    /// transaction as soon as the previous one hit the nodes' mempool.
+    pub nonce_count_rule: NonceCountRule,
+    /// Gas added to the estimate of each block submission, to account for
+    /// crediting the pending deposits.
+    ///
+    /// The default of 10M is enough to cover the gas cost of submitting one
+    /// note and crediting up to 10 pending deposits in the smart contract.
+    pub extra_gas: u64,
+    /// Maximum number of times to retry a submission after a nonce error.
+    ///
+    /// We roughly have an Ethereum block every 12 seconds, and we can put a
+    /// maximum of 3 to 4 transactions into an Ethereum block. The number of
+    /// retries times the retry interval should not significantly exceed 3
+    /// seconds to avoid creating a new bottleneck for the relayer.
+    pub max_retries: u64,
+    /// Amount of time to sleep before the first retry.
+    pub retry_interval: Duration,
+    /// Factor by which the retry interval grows after each failed attempt.
+    pub backoff_multiplier: f64,
+    /// Upper bound for the time to sleep between two retries.
+    pub max_retry_interval: Duration,
+    /// Number of recent submissions remembered to detect duplicates.
+    pub dedup_window: usize,
+    /// Maximum number of transactions submitted together in one CAPE block.
+    ///
+    /// Larger blocks risk exceeding the Ethereum block gas limit.
+    pub max_txns_per_block: usize,
+    /// Maximum size in bytes of a CAPE block, as measured by
+    /// [CapeBlock::size_in_bytes].
+    ///
+    /// Most Ethereum nodes do not relay transactions larger than 128KB.
+    pub max_block_bytes: usize,
+    /// Time after which a request to an Ethereum provider is abandoned.
+    pub provider_timeout: Duration,
+    /// Time after which a submitted transaction without a receipt is replaced
+    /// by [stuck_tx_monitor]. Must not be zero.
+    pub stuck_tx_timeout: Duration,
+    /// Highest gas price, in wei, paid by [stuck_tx_monitor] for a
+    /// replacement. A transaction which cannot be replaced below this price
+    /// is left as is.
+    pub stuck_tx_max_gas_price: U256,
+    /// Fee collector for submitted blocks. If `None`, fees are sent to the
+    /// default public key.
+    pub miner: Option<UserPubKey>,
+    /// Key authenticating submissions and cancellations, see [auth]. If
+    /// `None`, anyone can submit or cancel transactions.
+    pub hmac_key: Option<HmacKey>,
+}
+
+impl Default for RelayerConfig {
+    fn default() -> Self {
+        Self {
+            nonce_count_rule: NonceCountRule::Pending,
+            extra_gas: DEFAULT_RELAYER_EXTRA_GAS.parse().unwrap(),
+            max_retries: DEFAULT_RELAYER_MAX_RETRIES.parse().unwrap(),
+            retry_interval: Duration::from_millis(
+                DEFAULT_RELAYER_RETRY_INTERVAL_MS.parse().unwrap(),
+            ),
+            backoff_multiplier: DEFAULT_RELAYER_BACKOFF_MULTIPLIER.parse().unwrap(),
+            max_retry_interval: Duration::from_millis(
+                DEFAULT_RELAYER_MAX_RETRY_INTERVAL_MS.parse().unwrap(),
+            ),
+            dedup_window: DEFAULT_RELAYER_DEDUP_WINDOW.parse().unwrap(),
+            max_txns_per_block: DEFAULT_RELAYER_MAX_TXNS_PER_BLOCK.parse().unwrap(),
+            max_block_bytes: DEFAULT_RELAYER_MAX_BLOCK_BYTES.parse().unwrap(),
+            provider_timeout: Duration::from_millis(
+                DEFAULT_RELAYER_PROVIDER_TIMEOUT_MS.parse().unwrap(),
+            ),
+            stuck_tx_timeout: Duration::from_secs(
+                DEFAULT_RELAYER_STUCK_TX_TIMEOUT_SECS.parse().unwrap(),
+            ),
+            stuck_tx_max_gas_price: gwei(
+                DEFAULT_RELAYER_STUCK_TX_MAX_GAS_PRICE_GWEI.parse().unwrap(),
+            ),
+            miner: None,
+            hmac_key: None,
+        }
+    }
+}
+
+/// `amount` gwei, in wei.
+fn gwei(amount: u64) -> U256 {
+    U256::from(amount) * U256::exp10(9)
+}
+
+/// An invalid parameter of a [RelayerConfig].
+#[derive(Clone, Debug, Snafu)]
+pub enum ConfigError {
+    #[snafu(display("invalid value {:?} for {}: {}", value, var, msg))]
+    InvalidVar {
+        var: String,
+        value: String,
+        msg: String,
+    },
+
+    #[snafu(display("backoff multiplier {} is not a finite number of at least 1", value))]
+    InvalidBackoffMultiplier { value: f64 },
+}
+
+/// A valid [RelayerConfig::backoff_multiplier]: finite and at least 1, so that
+/// the retry interval never shrinks.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct BackoffMultiplier(f64);
+
+impl BackoffMultiplier {
+    pub fn new(value: f64) -> Result<Self, ConfigError> {
+        if value.is_finite() && value >= 1.0 {
+            Ok(Self(value))
+        } else {
+            Err(ConfigError::InvalidBackoffMultiplier { value })
+        }
+    }
+
+    pub fn get(self) -> f64 {
+        self.0
+    }
+}
+
+impl FromStr for BackoffMultiplier {
+    type Err = String;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let value = s.parse::<f64>().map_err(|err| err.to_string())?;
+        Self::new(value).map_err(|err| err.to_string())
+    }
+}
+
+/// Parse the variable `var` looked up with `vars`, or `default` if it is not
+/// set.
+fn var_or<T>(
+    vars: &impl Fn(&str) -> Option<String>,
+    var: &str,
+    default: &str,
+) -> Result<T, ConfigError>
+where
+    T: FromStr,
+    T::Err: std::fmt::Display,
+{
+    let value = vars(var).unwrap_or_else(|| default.to_string());
+    value
+        .parse()
+        .map_err(|err: T::Err| ConfigError::InvalidVar {
+            var: var.to_string(),
+            msg: err.to_string(),
+            value,
+        })
+}
+
+/// Parse the hex encoding of a bincode serialized `UserPubKey`.
+///
+/// Panics if `key` is not a valid encoding.
+pub fn parse_miner_key(key: &str) -> UserPubKey {
+    let bytes = hex::decode(key.trim_start_matches("0x")).expect("invalid miner key hex");
+    bincode::deserialize(&bytes).expect("invalid miner key")
+}
+
+impl RelayerConfig {
+    /// Read the configuration from the environment.
+    ///
+    /// Each parameter not set in the environment takes its default value.
+    /// Fails if a variable is set to an invalid value, except for an invalid
+    /// miner key, which panics (see [parse_miner_key]).
+    ///
+    /// * `CAPE_RELAYER_NONCE_COUNT_RULE`: `"mined"` or `"pending"`
+    /// * `CAPE_RELAYER_EXTRA_GAS`: non-zero gas amount
+    /// * `CAPE_RELAYER_MAX_RETRIES`
+    /// * `CAPE_RELAYER_RETRY_INTERVAL_MS`
+    /// * `CAPE_RELAYER_BACKOFF_MULTIPLIER`: finite number of at least 1
+    /// * `CAPE_RELAYER_MAX_RETRY_INTERVAL_MS`
+    /// * `CAPE_RELAYER_DEDUP_WINDOW`
+    /// * `CAPE_RELAYER_MAX_TXNS_PER_BLOCK`
+    /// * `CAPE_RELAYER_MAX_BLOCK_BYTES`
+    /// * `CAPE_RELAYER_PROVIDER_TIMEOUT_MS`
+    /// * `CAPE_RELAYER_STUCK_TX_TIMEOUT_SECS`: non-zero number of seconds
+    /// * `CAPE_RELAYER_STUCK_TX_MAX_GAS_PRICE_GWEI`
+    /// * `CAPE_RELAYER_MINER_KEY`: hex encoding of the bincode serialized `UserPubKey`
+    /// * `CAPE_RELAYER_HMAC_KEY`: key shared with the clients allowed to submit
+    pub fn from_env() -> Result<Self, ConfigError> {
+        Self::from_vars(|var| std::env::var(var).ok())
+    }
+
+    /// Read the configuration from the variables looked up with `vars`.
+    ///
+    /// The variables are the same as for [RelayerConfig::from_env].
+    pub fn from_vars(vars: impl Fn(&str) -> Option<String>) -> Result<Self, ConfigError> {
+        let miner = vars("CAPE_RELAYER_MINER_KEY").map(|key| parse_miner_key(&key));
+        Ok(Self {
+            nonce_count_rule: var_or(&vars, "CAPE_RELAYER_NONCE_COUNT_RULE", "pending")?,
+            extra_gas: var_or::<NonZeroU64>(
+                &vars,
+                "CAPE_RELAYER_EXTRA_GAS",
+                DEFAULT_RELAYER_EXTRA_GAS,
+            )?
+            .get(),
+            max_retries: var_or(
+                &vars,
+                "CAPE_RELAYER_MAX_RETRIES",
+                DEFAULT_RELAYER_MAX_RETRIES,
+            )?,
+            retry_interval: Duration::from_millis(var_or(
+                &vars,
+                "CAPE_RELAYER_RETRY_INTERVAL_MS",
+                DEFAULT_RELAYER_RETRY_INTERVAL_MS,
+            )?),
+            backoff_multiplier: var_or::<BackoffMultiplier>(
+                &vars,
+                "CAPE_RELAYER_BACKOFF_MULTIPLIER",
+                DEFAULT_RELAYER_BACKOFF_MULTIPLIER,
+            )?
+            .get(),
+            max_retry_interval: Duration::from_millis(var_or(
+                &vars,
+                "CAPE_RELAYER_MAX_RETRY_INTERVAL_MS",
+                DEFAULT_RELAYER_MAX_RETRY_INTERVAL_MS,
+            )?),
+            dedup_window: var_or(
+                &vars,
+                "CAPE_RELAYER_DEDUP_WINDOW",
+                DEFAULT_RELAYER_DEDUP_WINDOW,
+            )?,
+            max_txns_per_block: var_or(
+                &vars,
+                "CAPE_RELAYER_MAX_TXNS_PER_BLOCK",
+                DEFAULT_RELAYER_MAX_TXNS_PER_BLOCK,
+            )?,
+            max_block_bytes: var_or(
+                &vars,
+                "CAPE_RELAYER_MAX_BLOCK_BYTES",
+                DEFAULT_RELAYER_MAX_BLOCK_BYTES,
+            )?,
+            provider_timeout: Duration::from_millis(var_or(
+                &vars,
+                "CAPE_RELAYER_PROVIDER_TIMEOUT_MS",
+                DEFAULT_RELAYER_PROVIDER_TIMEOUT_MS,
+            )?),
+            stuck_tx_timeout: Duration::from_secs(
+                var_or::<NonZeroU64>(
+                    &vars,
+                    "CAPE_RELAYER_STUCK_TX_TIMEOUT_SECS",
+                    DEFAULT_RELAYER_STUCK_TX_TIMEOUT_SECS,
+                )?
+                .get(),
+            ),
+            stuck_tx_max_gas_price: gwei(var_or(
+                &vars,
+                "CAPE_RELAYER_STUCK_TX_MAX_GAS_PRICE_GWEI",
+                DEFAULT_RELAYER_STUCK_TX_MAX_GAS_PRICE_GWEI,
+            )?),
+            miner,
+            hmac_key: vars("CAPE_RELAYER_HMAC_KEY").map(HmacKey::new),
+        })
+    }
+
+    /// Start building a configuration from the defaults.
+    pub fn builder() -> RelayerConfigBuilder {
+        RelayerConfigBuilder::default()
+    }
+}
+
+/// Builder for a [RelayerConfig], starting from [RelayerConfig::default].
+#[derive(Clone, Debug, Default)]
+pub struct RelayerConfigBuilder {
+    config: RelayerConfig,
+}
+
+impl RelayerConfigBuilder {
+    pub fn nonce_count_rule(mut self, nonce_count_rule: NonceCountRule) -> Self {
+        self.config.nonce_count_rule = nonce_count_rule;
+        self
+    }
+
+    pub fn extra_gas(mut self, extra_gas: u64) -> Self {
+        self.config.extra_gas = extra_gas;
+        self
+    }
+
+    pub fn max_retries(mut self, max_retries: u64) -> Self {
+        self.config.max_retries = max_retries;
+        self
+    }
+
+    pub fn retry_interval(mut self, retry_interval: Duration) -> Self {
+        self.config.retry_interval = retry_interval;
+        self
+    }
+
+    /// Fails if `backoff_multiplier` is not finite or less than 1.
+    pub fn backoff_multiplier(mut self, backoff_multiplier: f64) -> Result<Self, ConfigError> {
+        self.config.backoff_multiplier = BackoffMultiplier::new(backoff_multiplier)?.get();
+        Ok(self)
+    }
+
+    pub fn max_retry_interval(mut self, max_retry_interval: Duration) -> Self {
+        self.config.max_retry_interval = max_retry_interval;
+        self
+    }
+
+    pub fn dedup_window(mut self, dedup_window: usize) -> Self {
+        self.config.dedup_window = dedup_window;
+        self
+    }
+
+    pub fn max_txns_per_block(mut self, max_txns_per_block: usize) -> Self {
+        self.config.max_txns_per_block = max_txns_per_block;
+        self
+    }
+
+    pub fn max_block_bytes(mut self, max_block_bytes: usize) -> Self {
+        self.config.max_block_bytes = max_block_bytes;
+        self
+    }
+
+    pub fn provider_timeout(mut self, provider_timeout: Duration) -> Self {
+        self.config.provider_timeout = provider_timeout;
+        self
+    }
+
+    /// Panics if `stuck_tx_timeout` is zero.
+    pub fn stuck_tx_timeout(mut self, stuck_tx_timeout: Duration) -> Self {
+        assert!(
+            !stuck_tx_timeout.is_zero(),
+            "the stuck transaction timeout must not be zero"
+        );
+        self.config.stuck_tx_timeout = stuck_tx_timeout;
+        self
+    }
+
+    /// Gas price, in wei, above which stuck transactions are not replaced.
+    pub fn stuck_tx_max_gas_price(mut self, stuck_tx_max_gas_price: U256) -> Self {
+        self.config.stuck_tx_max_gas_price = stuck_tx_max_gas_price;
+        self
+    }
+
+    /// Collect the fees of submitted blocks with `miner`.
+    pub fn miner(mut self, miner: UserPubKey) -> Self {
+        self.config.miner = Some(miner);
+        self
+    }
+
+    /// Require submissions and cancellations to be authenticated with `hmac_key`.
+    pub fn hmac_key(mut self, hmac_key: HmacKey) -> Self {
+        self.config.hmac_key = Some(hmac_key);
+        self
+    }
+
+    pub fn build(self) -> RelayerConfig {
+        self.config
+    }
+}
+
 #[derive(Clone)]
 pub struct WebState {
-    contract: CAPE<EthMiddleware>,
-    nonce_count_rule: NonceCountRule,
-    extra_gas: u64,
-    max_retries: u64,
-    retry_interval: Duration,
-    block_submission_mutex: Arc<Mutex<()>>,
+    /// The CAPE contract, accessed through the primary provider followed by
+    /// any fallback providers, each labeled with the provider's URL.
+    contracts: Vec<(String, CAPE<EthMiddleware>)>,
+    config: RelayerConfig,
+    block_submission_mutex: Arc<Mutex<RecentSubmissions>>,
+    last_submitted_hash: Arc<Mutex<Option<H256>>>,
+    last_submitted_at: Arc<Mutex<Option<Instant>>>,
+    /// Submitted transactions not known to be mined yet, with their block,
+    /// nonce and submission time, watched by [stuck_tx_monitor].
+    pending_txs: Arc<Mutex<HashMap<H256, (BlockWithMemos, u64, Instant)>>>,
+    metrics: Metrics,
 }
 
 impl WebState {
-    pub fn new(
-        contract: CAPE<EthMiddleware>,
-        nonce_count_rule: NonceCountRule,
-        extra_gas: u64,
-        max_retries: u64,
-        retry_interval: Duration,
-    ) -> Self {
+    pub fn new(contract: CAPE<EthMiddleware>, config: RelayerConfig) -> Self {
         Self {
-            contract,
-            nonce_count_rule,
-            extra_gas,
-            max_retries,
-            retry_interval,
-            block_submission_mutex: Arc::new(Mutex::new(())),
+            contracts: vec![("primary".to_string(), contract)],
+            block_submission_mutex: Arc::new(Mutex::new(RecentSubmissions::new(
+                config.dedup_window,
+            ))),
+            last_submitted_hash: Arc::new(Mutex::new(None)),
+            last_submitted_at: Arc::new(Mutex::new(None)),
+            pending_txs: Arc::new(Mutex::new(HashMap::new())),
+            metrics: Metrics::new(),
+            config,
+        }
+    }
+
+    /// Submit through `contract` if all previously added providers fail.
+    ///
+    /// `url` identifies the provider in error messages.
+    pub fn with_fallback(mut self, url: impl Into<String>, contract: CAPE<EthMiddleware>) -> Self {
+        self.contracts.push((url.into(), contract));
+        self
+    }
+
+    /// The CAPE contract accessed through the primary provider.
+    fn contract(&self) -> &CAPE<EthMiddleware> {
+        &self.contracts[0].1
+    }
+
+    /// The fee collector for submitted blocks.
+    ///
+    /// If no miner is configured, fees are sent to the default public key.
+    pub fn miner(&self) -> UserPubKey {
+        self.config.miner.clone().unwrap_or_default()
+    }
+
+    pub fn metrics(&self) -> &Metrics {
+        &self.metrics
+    }
+
+    /// The amount of time to sleep before retry number `attempt + 1`.
+    ///
+    /// The interval grows as `retry_interval * backoff_multiplier^attempt`
+    /// and is capped at `max_retry_interval`.
+    fn retry_delay(&self, attempt: u64) -> Duration {
+        let config = &self.config;
+        let factor = config.backoff_multiplier.powi(attempt as i32);
+        let delay = config.retry_interval.as_secs_f64() * factor;
+        if !delay.is_finite() || delay >= config.max_retry_interval.as_secs_f64() {
+            config.max_retry_interval
+        } else {
+            Duration::from_secs_f64(delay)
         }
     }
 }
@@ -124,6 +614,41 @@ pub struct SubmitBody {
     pub signature: Signature,
 }
 
+impl SubmitBody {
+    /// Check that `signature` signs `memos` with the memos verification key
+    /// of the transaction.
+    pub fn validate_signature(&self) -> Result<(), Error> {
+        let note = match &self.transaction {
+            CapeModelTxn::CAP(note) => note.clone(),
+            CapeModelTxn::Burn { xfr, .. } => TransactionNote::from((**xfr).clone()),
+        };
+        note.verify_receiver_memos_signature(&self.memos, &self.signature)
+            .map_err(|err| Error::BadSignature {
+                msg: err.to_string(),
+            })
+    }
+}
+
+/// Operational information about a running relayer, returned by `/status`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct RelayerStatus {
+    /// Number of leaves in the records Merkle tree of the CAPE contract.
+    pub num_leaves: u64,
+    /// Hash of the last Ethereum transaction submitted by this relayer.
+    pub last_submitted_hash: Option<H256>,
+    /// Seconds elapsed since the last successful submission.
+    pub secs_since_last_submission: Option<u64>,
+    /// Gas limit of the latest Ethereum block.
+    pub gas_limit: U256,
+}
+
+/// Result of a simulated submission, returned by `/validate`.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ValidationResult {
+    pub valid: bool,
+    pub error: Option<String>,
+}
+
 /// Determines how transaction nonces should be calculated.
 #[derive(Clone, Debug, Copy, Serialize, Deserialize)]
 pub enum NonceCountRule {
@@ -156,16 +681,130 @@ impl FromStr for NonceCountRule {
     }
 }
 
+/// Format of the relayer logs.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum LogFormat {
+    /// Human-readable lines.
+    Full,
+    /// One JSON object per event, with the event fields as keys.
+    Json,
+}
+
+type ParseLogFormatError = &'static str;
+
+impl FromStr for LogFormat {
+    type Err = ParseLogFormatError;
+
+    fn from_str(input: &str) -> Result<LogFormat, Self::Err> {
+        match input {
+            "full" => Ok(LogFormat::Full),
+            "json" => Ok(LogFormat::Json),
+            _ => Err("Unable to parse, use \"full\" or \"json\""),
+        }
+    }
+}
+
+/// Build the subscriber printing the relayer logs that pass `filter` to `writer`.
+pub fn log_subscriber<W>(
+    format: LogFormat,
+    filter: EnvFilter,
+    writer: W,
+) -> Box<dyn Subscriber + Send + Sync>
+where
+    W: for<'a> MakeWriter<'a> + Send + Sync + 'static,
+{
+    let builder = tracing_subscriber::fmt()
+        .with_ansi(false)
+        .with_env_filter(filter)
+        .with_writer(writer);
+    match format {
+        LogFormat::Full => Box::new(builder.compact().finish()),
+        LogFormat::Json => Box::new(builder.json().finish()),
+    }
+}
+
+/// How long [healthcheck] waits for the Ethereum node to report its block number.
+const HEALTHCHECK_TIMEOUT: Duration = Duration::from_secs(3);
+
 /// Return a JSON expression with status 200 indicating the server
-/// is up and running. The JSON expression is simply,
-///    {"status": "available"}
-/// When the server is running but unable to process requests
-/// normally, a response with status 503 and payload {"status":
-/// "unavailable"} should be added.
-async fn healthcheck(_req: tide::Request<WebState>) -> Result<tide::Response, tide::Error> {
+/// is up and running and the Ethereum node is reachable,
+///    {"status": "available", "eth_block": N}
+/// where N is the latest block number reported by the primary provider.
+/// If the node does not answer within [HEALTHCHECK_TIMEOUT], the response
+/// has status 503 and payload {"status": "degraded", "error": "..."}.
+async fn healthcheck(req: tide::Request<WebState>) -> Result<tide::Response, tide::Error> {
+    let client = req.state().contract().client();
+    let (status, body) =
+        match async_std::future::timeout(HEALTHCHECK_TIMEOUT, client.get_block_number()).await {
+            Ok(Ok(block_number)) => (
+                200,
+                tide::prelude::json!({"status": "available", "eth_block": block_number.as_u64()}),
+            ),
+            Ok(Err(err)) => (
+                503,
+                tide::prelude::json!({"status": "degraded", "error": err.to_string()}),
+            ),
+            Err(_) => (
+                503,
+                tide::prelude::json!({
+                    "status": "degraded",
+                    "error": format!("Ethereum node did not respond in {:?}", HEALTHCHECK_TIMEOUT),
+                }),
+            ),
+        };
+    Ok(tide::Response::builder(status)
+        .content_type(tide::http::mime::JSON)
+        .body(body)
+        .build())
+}
+
+/// Return a JSON expression with status 200 reporting the operational
+/// state of the relayer, see [RelayerStatus].
+async fn status(req: tide::Request<WebState>) -> Result<tide::Response, tide::Error> {
+    let web_state = req.state();
+    let num_leaves = web_state
+        .contract()
+        .get_num_leaves()
+        .call()
+        .await
+        .map_err(|err| {
+            server_error(Error::CallContract {
+                msg: err.to_string(),
+            })
+        })?;
+    let gas_limit = web_state
+        .contract()
+        .client()
+        .get_block(BlockNumber::Latest)
+        .await
+        .map_err(|err| {
+            server_error(Error::CallContract {
+                msg: err.to_string(),
+            })
+        })?
+        .map(|block| block.gas_limit)
+        .unwrap_or_default();
+    let status = RelayerStatus {
+        num_leaves: num_leaves.as_u64(),
+        last_submitted_hash: *web_state.last_submitted_hash.lock().await,
+        secs_since_last_submission: web_state
+            .last_submitted_at
+            .lock()
+            .await
+            .map(|at| at.elapsed().as_secs()),
+        gas_limit,
+    };
     Ok(tide::Response::builder(200)
         .content_type(tide::http::mime::JSON)
-        .body(tide::prelude::json!({"status": "available"}))
+        .body(tide::prelude::json!(status))
+        .build())
+}
+
+/// Return all metrics in the Prometheus text format.
+async fn metrics_endpoint(req: tide::Request<WebState>) -> Result<tide::Response, tide::Error> {
+    Ok(tide::Response::builder(200)
+        .content_type(prometheus::TEXT_FORMAT)
+        .body(req.state().metrics.render())
         .build())
 }
 
@@ -184,6 +823,147 @@ async fn submit_endpoint(mut req: tide::Request<WebState>) -> Result<tide::Respo
         .map_err(server_error)?;
     response(&req, ret)
 }
+async fn submit_batch_endpoint(
+    mut req: tide::Request<WebState>,
+) -> Result<tide::Response, tide::Error> {
+    let bodies: Vec<SubmitBody> = request_body(&mut req).await.map_err(|err| {
+        server_error(Error::Deserialize {
+            msg: err.to_string(),
+        })
+    })?;
+    let ret = relay_batch(
+        req.state(),
+        bodies
+            .into_iter()
+            .map(|body| (body.transaction, body.memos, body.signature))
+            .collect(),
+    )
+    .await
+    .map_err(server_error)?;
+    response(&req, ret)
+}
+
+/// Percentage of the original gas price paid by a replacement transaction.
+///
+/// Nodes only accept a replacement if the gas price is raised by at least 10%.
+const CANCEL_GAS_PRICE_PERCENT: u64 = 120;
+
+async fn cancel_endpoint(req: tide::Request<WebState>) -> Result<tide::Response, tide::Error> {
+    let tx_hash = req.param("tx_hash")?.parse::<H256>().map_err(|err| {
+        server_error(Error::Deserialize {
+            msg: err.to_string(),
+        })
+    })?;
+    let ret = cancel(req.state(), tx_hash).await.map_err(server_error)?;
+    response(&req, ret)
+}
+
+/// Replace a stuck Ethereum transaction submitted by this relayer.
+///
+/// The replacement is a zero-value transfer from the relayer to itself, with
+/// the nonce of the stuck transaction and a gas price raised by
+/// [CANCEL_GAS_PRICE_PERCENT]. An empty CAPE block is not used as the
+/// replacement because it would revert whenever the pending deposits queue is
+/// empty.
+///
+/// Returns the hash of the replacement transaction.
+async fn cancel(web_state: &WebState, tx_hash: H256) -> Result<H256, Error> {
+    let _guard = web_state.block_submission_mutex.lock().await;
+
+    let client = web_state.contract().client();
+    let tx = client
+        .get_transaction(tx_hash)
+        .await
+        .map_err(|err| Error::CancelFailed {
+            msg: err.to_string(),
+        })?
+        .ok_or_else(|| Error::CancelFailed {
+            msg: format!("transaction {:#x} not found", tx_hash),
+        })?;
+    if tx.block_number.is_some() {
+        return Err(Error::CancelFailed {
+            msg: format!("transaction {:#x} is already mined", tx_hash),
+        });
+    }
+    let relayer_address = client.address();
+    if tx.from != relayer_address {
+        return Err(Error::CancelFailed {
+            msg: format!("transaction {:#x} was not sent by this relayer", tx_hash),
+        });
+    }
+
+    let gas_price = std::cmp::max(
+        tx.gas_price.unwrap_or_default(),
+        tx.max_fee_per_gas.unwrap_or_default(),
+    ) * CANCEL_GAS_PRICE_PERCENT
+        / 100;
+    let replacement = TransactionRequest::new()
+        .from(relayer_address)
+        .to(relayer_address)
+        .value(0u64)
+        .nonce(tx.nonce)
+        .gas_price(gas_price);
+    let pending = client
+        .send_transaction(replacement, None)
+        .await
+        .map_err(|err| Error::CancelFailed {
+            msg: err.to_string(),
+        })?;
+    event!(
+        Level::INFO,
+        tx_hash = ?tx_hash,
+        replacement_hash = ?*pending,
+        "Transaction replaced"
+    );
+    Ok(*pending)
+}
+
+/// Check whether a transaction would be accepted by the CAPE contract, without
+/// paying any gas.
+async fn validate_endpoint(
+    mut req: tide::Request<WebState>,
+) -> Result<tide::Response, tide::Error> {
+    let SubmitBody {
+        transaction,
+        memos,
+        signature,
+    } = request_body(&mut req).await.map_err(|err| {
+        server_error(Error::Deserialize {
+            msg: err.to_string(),
+        })
+    })?;
+    let ret = validate(req.state(), transaction, memos, signature, None).await;
+    response(&req, ret)
+}
+
+async fn validate(
+    web_state: &WebState,
+    transaction: CapeModelTxn,
+    memos: Vec<ReceiverMemo>,
+    sig: Signature,
+    gas: Option<U256>,
+) -> ValidationResult {
+    let miner = web_state.miner();
+    let result = match build_block_with_memos(
+        vec![(transaction, memos, sig)],
+        &miner,
+        web_state.config.max_block_bytes,
+    ) {
+        Ok(block) => simulate_block(web_state, block, gas).await,
+        Err(err) => Err(err),
+    };
+    match result {
+        Ok(()) => ValidationResult {
+            valid: true,
+            error: None,
+        },
+        Err(err) => ValidationResult {
+            valid: false,
+            error: Some(err.to_string()),
+        },
+    }
+}
+
 /// This function implements the core logic of the relayer
 ///
 /// * `contract` -  CAPE contract instance to submit the block information to
@@ -199,144 +979,693 @@ async fn relay(
     memos: Vec<ReceiverMemo>,
     sig: Signature,
 ) -> Result<H256, Error> {
-    let miner = UserPubKey::default();
-    let block = BlockWithMemos {
-        block: CapeBlock::from_cape_transactions(vec![transaction], miner.address()).map_err(
-            |err| Error::BadBlock {
-                msg: err.to_string(),
-            },
-        )?,
-        memos: vec![(memos, sig)],
+    let body = SubmitBody {
+        transaction,
+        memos,
+        signature: sig,
     };
-    // These log statements show what's being submitted to Ethereum blockchain,
-    // except for the memos.
-    event!(Level::INFO, "Submitting CAPE block: {:?}", block);
+    body.validate_signature()?;
+    relay_batch(
+        web_state,
+        vec![(body.transaction, body.memos, body.signature)],
+    )
+    .await
+}
+
+/// Submit several transactions together in a single CAPE block.
+///
+/// The memos signature of every transaction is checked before the block is
+/// submitted, so that a single bad transaction does not cost any gas.
+///
+/// Fails with [Error::BadBlock] if there are no transactions, with
+/// [Error::BlockTooLarge] if there are more transactions than
+/// [RelayerConfig::max_txns_per_block], and with [Error::BlockBytesTooLarge]
+/// if the block is larger than [RelayerConfig::max_block_bytes].
+async fn relay_batch(
+    web_state: &WebState,
+    transactions: Vec<(CapeModelTxn, Vec<ReceiverMemo>, Signature)>,
+) -> Result<H256, Error> {
+    if transactions.is_empty() {
+        return Err(Error::BadBlock {
+            msg: "a batch must contain at least one transaction".into(),
+        });
+    }
+    let max = web_state.config.max_txns_per_block;
+    if transactions.len() > max {
+        return Err(Error::BlockTooLarge {
+            actual: transactions.len(),
+            max,
+        });
+    }
+    let miner = web_state.miner();
+    let digest = txns_digest(transactions.iter().map(|(txn, _, _)| txn));
+    let block = build_block_with_memos(transactions, &miner, web_state.config.max_block_bytes)?;
+    // Show what's being submitted to Ethereum blockchain, except for the memos.
     event!(
         Level::INFO,
-        "Submitting CAPE block: {:?}",
-        cap_rust_sandbox::types::CapeBlock::from(block.block.clone())
+        note_count = block.block.note_types.len(),
+        block = ?cap_rust_sandbox::types::CapeBlock::from(block.block.clone()),
+        "Submitting CAPE block"
     );
-    submit_block(web_state, block).await
+    submit_block_to_chain(web_state, block, Some(digest)).await
 }
 
-async fn submit_empty_block(web_state: &WebState) -> Result<H256, Error> {
-    let miner = UserPubKey::default();
+/// Assemble a block from user transactions, checking the memos signatures
+/// and that the block is at most `max_bytes` large.
+///
+/// This does not interact with the CAPE contract.
+fn build_block_with_memos(
+    transactions: Vec<(CapeModelTxn, Vec<ReceiverMemo>, Signature)>,
+    miner: &UserPubKey,
+    max_bytes: usize,
+) -> Result<BlockWithMemos, Error> {
+    let mut txns = vec![];
+    let mut memos = vec![];
+    for (transaction, txn_memos, sig) in transactions {
+        txns.push(transaction);
+        memos.push((txn_memos, sig));
+    }
     let block = BlockWithMemos {
-        block: CapeBlock::from_cape_transactions(vec![], miner.address()).map_err(|err| {
-            Error::BadBlock {
+        block: CapeBlock::from_cape_transactions_with_max_size(txns, miner.address(), None)
+            .map_err(|err| Error::BadBlock {
                 msg: err.to_string(),
-            }
-        })?,
-        memos: vec![],
+            })?,
+        memos,
     };
-    submit_block(web_state, block).await
+    let size = block.block.size_in_bytes().map_err(|err| Error::Internal {
+        msg: err.to_string(),
+    })?;
+    if size > max_bytes {
+        return Err(Error::BlockBytesTooLarge {
+            actual: size,
+            max: max_bytes,
+        });
+    }
+    block.verify_memos().map_err(|err| Error::BadBlock {
+        msg: err.to_string(),
+    })?;
+    Ok(block)
 }
 
-async fn submit_block(web_state: &WebState, block: BlockWithMemos) -> Result<H256, Error> {
-    let _guard = web_state.block_submission_mutex.lock().await;
+/// Check whether the CAPE contract would accept a block, without submitting
+/// it.
+///
+/// The submission is simulated with an `eth_call`, so no gas is spent. If
+/// `gas` is `None` the node uses its own gas estimate.
+async fn simulate_block(
+    web_state: &WebState,
+    block: BlockWithMemos,
+    gas: Option<U256>,
+) -> Result<(), Error> {
+    let mut memos_bytes: Vec<u8> = vec![];
+    block
+        .memos
+        .serialize(&mut memos_bytes)
+        .map_err(|err| Error::Internal {
+            msg: err.to_string(),
+        })?;
+    let mut call = web_state
+        .contract()
+        .submit_cape_block_with_memos(block.block.into(), memos_bytes.into());
+    if let Some(gas) = gas {
+        call = call.gas(gas);
+    }
+    call.call().await.map_err(|err| Error::SimulationFailed {
+        msg: err.to_string(),
+    })
+}
 
-    let mut attempt = 0;
-    let pending = loop {
-        let result = submit_cape_block_with_memos(
-            &web_state.contract,
-            block.clone(),
-            web_state.nonce_count_rule.into(),
-            web_state.extra_gas,
-        )
-        .await
-        .map_err(|err| {
-            let msg = err.to_string();
-            if msg.contains("replacement transaction underpriced") || msg.contains("nonce too low")
-            {
-                Error::Nonce { msg }
-            } else if msg.contains("Root not found") {
-                Error::RootNotFound { msg }
-            } else if msg.contains("Block must be non-empty") {
-                Error::BlockAndPendingDepositsQueueEmpty { msg }
-            } else {
-                Error::Submission { msg }
-            }
-        });
-        if matches!(result, Err(Error::Nonce { .. })) && attempt < web_state.max_retries {
-            tracing::info!(
-                "Nonce error, retry {} in {:?}",
-                attempt + 1,
-                web_state.retry_interval
-            );
-            async_std::task::sleep(web_state.retry_interval).await;
-            attempt += 1;
-        } else {
-            break result;
-        }
-    }?;
+/// Submit a block without any transaction, to credit pending deposits.
+///
+/// [CapeBlock::is_empty] is true for the submitted block.
+async fn submit_empty_block(web_state: &WebState) -> Result<H256, Error> {
+    let block = BlockWithMemos::new_empty(web_state.miner()).map_err(|err| Error::Internal {
+        msg: err.to_string(),
+    })?;
+    submit_block_to_chain(web_state, block, None).await
+}
+
+/// Submit a block to the CAPE contract.
+///
+/// If `digest` identifies transactions which were recently submitted, the
+/// previous Ethereum transaction hash is returned instead of submitting them
+/// again.
+async fn submit_block_to_chain(
+    web_state: &WebState,
+    block: BlockWithMemos,
+    digest: Option<TxnDigest>,
+) -> Result<H256, Error> {
+    let mut recent = web_state.block_submission_mutex.lock().await;
+    if let Some(hash) = digest.and_then(|digest| recent.get(&digest)) {
+        event!(
+            Level::INFO,
+            tx_hash = ?hash,
+            "Ignoring duplicate submission"
+        );
+        return Ok(hash);
+    }
+
+    let note_count = block.block.note_types.len();
+    let start = Instant::now();
+    let result = submit_block_with_failover(web_state, block.clone()).await;
+    web_state
+        .metrics
+        .observe_submission(&result, start.elapsed().as_secs_f64());
+    let (hash, contract) = result?;
 
     // The pending transaction itself doesn't serialize well, but all the relevant information is
     // contained in the transaction hash. The client can reconstruct the pending transaction from
     // the hash using a particular provider.
-    event!(
+    log_block_submitted(hash, note_count);
+    if let Some(digest) = digest {
+        recent.insert(digest, hash);
+    }
+    *web_state.last_submitted_hash.lock().await = Some(hash);
+    *web_state.last_submitted_at.lock().await = Some(Instant::now());
+    track_pending_tx(web_state, contract, hash, block).await;
+
+    // Account for the gas once the transaction is mined, without making the
+    // client wait for it. Once the transaction is mined or dropped, it no
+    // longer needs watching, whether or not [stuck_tx_monitor] is running.
+    let provider = contract.client().provider().clone();
+    let metrics = web_state.metrics.clone();
+    let pending_txs = web_state.pending_txs.clone();
+    task::spawn(async move {
+        if let Ok(Some(receipt)) = PendingTransaction::new(hash, &provider).await {
+            if let Some(gas_used) = receipt.gas_used {
+                metrics.add_gas_used(gas_used.as_u64());
+            }
+        }
+        pending_txs.lock().await.remove(&hash);
+    });
+    Ok(hash)
+}
+
+/// Remember a submitted transaction, so that [stuck_tx_monitor] can replace it
+/// if it is not mined in time.
+async fn track_pending_tx(
+    web_state: &WebState,
+    contract: &CAPE<EthMiddleware>,
+    hash: H256,
+    block: BlockWithMemos,
+) {
+    match contract.client().get_transaction(hash).await {
+        Ok(Some(tx)) => {
+            web_state
+                .pending_txs
+                .lock()
+                .await
+                .insert(hash, (block, tx.nonce.as_u64(), Instant::now()));
+        }
+        Ok(None) => {}
+        Err(err) => event!(
+            Level::WARN,
+            tx_hash = ?hash,
+            error = %err,
+            "Cannot watch submitted transaction"
+        ),
+    }
+}
+
+/// Log the hash of the Ethereum transaction carrying a CAPE block.
+fn log_block_submitted(hash: H256, note_count: usize) {
+    event!(
         Level::INFO,
-        "Submitted Ethereum transaction hash ETH H256: {:#x}",
-        *pending
+        tx_hash = ?hash,
+        note_count,
+        "Block submitted"
     );
+}
+
+/// Submit a block through each provider in turn, until one of them succeeds.
+///
+/// Only generic submission errors and provider timeouts which happen before
+/// the transaction is sent cause a failover. Once the transaction may have
+/// reached a node, submitting it again through another provider could
+/// include the block twice. Other errors mean that a node did process the
+/// submission and rejected it, so another provider would reject it as well.
+///
+/// Returns the transaction hash and the contract handle it was submitted with.
+async fn submit_block_with_failover(
+    web_state: &WebState,
+    block: BlockWithMemos,
+) -> Result<(H256, &CAPE<EthMiddleware>), Error> {
+    let mut providers = vec![];
+    let mut errors = vec![];
+    for (url, contract) in &web_state.contracts {
+        match submit_block_with_retries(web_state, contract, block.clone()).await {
+            Ok(hash) => return Ok((hash, contract)),
+            Err(AttemptError::BeforeSend(
+                err @ (Error::Submission { .. } | Error::ProviderTimeout { .. }),
+            )) => {
+                event!(Level::WARN, provider = %url, error = %err, "Submission failed");
+                providers.push(url.clone());
+                errors.push(err);
+            }
+            Err(err) => return Err(err.into_error()),
+        }
+    }
+    if errors.len() == 1 {
+        Err(errors.remove(0))
+    } else {
+        let msgs = errors
+            .into_iter()
+            .map(|err| match err {
+                Error::Submission { msg } | Error::ProviderTimeout { msg } => msg,
+                err => err.to_string(),
+            })
+            .collect();
+        Err(Error::AllProvidersFailed { providers, msgs })
+    }
+}
+
+/// Error of an attempt to submit a block through a single provider.
+#[derive(Debug)]
+enum AttemptError {
+    /// The attempt failed while building the transaction, before anything
+    /// was sent to the node.
+    BeforeSend(Error),
+    /// The attempt failed while sending the transaction, which may have
+    /// reached the node.
+    Send(Error),
+}
+
+impl AttemptError {
+    fn error(&self) -> &Error {
+        match self {
+            Self::BeforeSend(err) | Self::Send(err) => err,
+        }
+    }
+
+    fn into_error(self) -> Error {
+        match self {
+            Self::BeforeSend(err) | Self::Send(err) => err,
+        }
+    }
+}
+
+/// Whether the HTTP request underlying `err` timed out.
+fn is_timeout(err: &SubmitBlockError) -> bool {
+    match err {
+        SignerMiddlewareError::MiddlewareError(ProviderError::JsonRpcClientError(err)) => {
+            matches!(
+                err.downcast_ref::<HttpClientError>(),
+                Some(HttpClientError::ReqwestError(err)) if err.is_timeout()
+            )
+        }
+        _ => false,
+    }
+}
+
+/// Classify an error returned by the provider during a submission.
+fn submission_error(err: SubmitBlockError) -> Error {
+    if is_timeout(&err) {
+        return Error::ProviderTimeout {
+            msg: err.to_string(),
+        };
+    }
+    let msg = err.to_string();
+    if msg.contains("replacement transaction underpriced") || msg.contains("nonce too low") {
+        Error::Nonce { msg }
+    } else if msg.contains("Root not found") {
+        Error::RootNotFound { msg }
+    } else if msg.contains("Block must be non-empty") {
+        Error::BlockAndPendingDepositsQueueEmpty { msg }
+    } else {
+        Error::Submission { msg }
+    }
+}
+
+/// Run a request to the provider, failing after [RelayerConfig::provider_timeout].
+async fn with_provider_timeout<T>(
+    web_state: &WebState,
+    request: impl Future<Output = Result<T, SubmitBlockError>>,
+) -> Result<T, Error> {
+    let timeout = web_state.config.provider_timeout;
+    async_std::future::timeout(timeout, request)
+        .await
+        .map_err(|_| Error::ProviderTimeout {
+            msg: format!("no answer after {:?}", timeout),
+        })?
+        .map_err(submission_error)
+}
+
+/// Build and send the transaction submitting `block`, once.
+async fn submit_block_once<'a>(
+    web_state: &WebState,
+    contract: &'a CAPE<EthMiddleware>,
+    block: BlockWithMemos,
+) -> Result<PendingTransaction<'a, Http>, AttemptError> {
+    let tx = with_provider_timeout(
+        web_state,
+        build_cape_block_tx(
+            contract,
+            block,
+            web_state.config.nonce_count_rule.into(),
+            web_state.config.extra_gas,
+        ),
+    )
+    .await
+    .map_err(AttemptError::BeforeSend)?;
+    with_provider_timeout(web_state, contract.client().send_transaction(tx, None))
+        .await
+        .map_err(AttemptError::Send)
+}
+
+async fn submit_block_with_retries(
+    web_state: &WebState,
+    contract: &CAPE<EthMiddleware>,
+    block: BlockWithMemos,
+) -> Result<H256, AttemptError> {
+    let pending = retry_on_nonce_error(web_state, async_std::task::sleep, || {
+        submit_block_once(web_state, contract, block.clone())
+    })
+    .await?;
     Ok(*pending)
 }
 
+/// Run `attempt` until it returns something other than an [Error::Nonce], or
+/// until `max_retries` retries failed.
+///
+/// Before each retry, `sleep` is called with [WebState::retry_delay].
+async fn retry_on_nonce_error<T, Fut, SleepFut>(
+    web_state: &WebState,
+    mut sleep: impl FnMut(Duration) -> SleepFut,
+    mut attempt: impl FnMut() -> Fut,
+) -> Result<T, AttemptError>
+where
+    Fut: Future<Output = Result<T, AttemptError>>,
+    SleepFut: Future<Output = ()>,
+{
+    let mut retries = 0;
+    loop {
+        let result = attempt().await;
+        let is_nonce_error =
+            matches!(&result, Err(err) if matches!(err.error(), Error::Nonce { .. }));
+        if is_nonce_error && retries < web_state.config.max_retries {
+            let delay = web_state.retry_delay(retries);
+            event!(
+                Level::INFO,
+                retry = retries + 1,
+                delay = ?delay,
+                "Nonce error, retrying"
+            );
+            sleep(delay).await;
+            web_state.metrics.inc_retries();
+            retries += 1;
+        } else {
+            break result;
+        }
+    }
+}
+
+/// Sleep for `duration`, or until `token` is cancelled.
+///
+/// Returns whether the token was cancelled.
+async fn sleep_unless_cancelled(duration: Duration, token: &CancellationToken) -> bool {
+    async_std::future::timeout(duration, token.cancelled())
+        .await
+        .is_ok()
+}
+
+/// Periodically submit an empty block to credit pending deposits.
+///
+/// Returns once `token` is cancelled.
 pub async fn submit_empty_block_loop(
     web_state: WebState,
     empty_block_interval: Duration,
+    token: CancellationToken,
 ) -> Result<(), Error> {
-    loop {
-        async_std::task::sleep(empty_block_interval).await;
+    while !token.is_cancelled() {
+        if sleep_unless_cancelled(empty_block_interval, &token).await {
+            break;
+        }
 
         // If the pending deposits queue is NOT empty, submit an empty block
 
-        // The queue is empty if we cannot access the first element.
-        let queue_is_empty = web_state
-            .contract
-            .pending_deposits(U256::from(0u64))
-            .call()
-            .await
-            .is_err();
+        // The queue is empty if we cannot access the first element through
+        // any of the providers.
+        let mut queue_is_empty = true;
+        for (_, contract) in &web_state.contracts {
+            if contract
+                .pending_deposits(U256::from(0u64))
+                .call()
+                .await
+                .is_ok()
+            {
+                queue_is_empty = false;
+                break;
+            }
+        }
 
         if !queue_is_empty {
             match submit_empty_block(&web_state).await {
                 Ok(_) => {
-                    event!(Level::INFO, "Empty block submitted.");
+                    event!(Level::INFO, "Empty block submitted");
                 }
                 Err(err) => match err {
                     // This can still happen due to a race condition.
                     Error::BlockAndPendingDepositsQueueEmpty { .. } => event!(
                         Level::INFO,
-                        "Block rejected: both block and pending deposits queue are empty.{}",
-                        err
+                        error = %err,
+                        "Block rejected: both block and pending deposits queue are empty"
                     ),
-                    _ => event!(Level::ERROR, "Failed to submit empty block {}", err),
+                    _ => event!(Level::ERROR, error = %err, "Failed to submit empty block"),
                 },
             };
         }
     }
+    Ok(())
+}
+
+/// Percentage of the original gas price paid when resubmitting a stuck block.
+const STUCK_TX_GAS_PRICE_PERCENT: u64 = 130;
+
+/// Upper bound for the time between two checks of the pending transactions.
+const STUCK_TX_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+/// Resubmit blocks whose transaction is not mined within the configured
+/// `stuck_tx_timeout`.
+///
+/// The replacement carries the same block with the nonce of the stuck
+/// transaction and a gas price raised by [STUCK_TX_GAS_PRICE_PERCENT], up to
+/// `stuck_tx_max_gas_price`, so at most one of them is mined. A replacement is
+/// watched like any other submission and is replaced again if it gets stuck as
+/// well.
+///
+/// Returns once `token` is cancelled.
+pub async fn stuck_tx_monitor(web_state: WebState, token: CancellationToken) {
+    let timeout = web_state.config.stuck_tx_timeout;
+    while !token.is_cancelled() {
+        if sleep_unless_cancelled(std::cmp::min(timeout, STUCK_TX_POLL_INTERVAL), &token).await {
+            break;
+        }
+
+        // The block submission lock is not taken: a replacement reuses the
+        // nonce of the stuck transaction, so it cannot conflict with new
+        // submissions, and holding the lock across the provider requests
+        // would stall them.
+        let expired = web_state
+            .pending_txs
+            .lock()
+            .await
+            .iter()
+            .filter(|(_, (_, _, submitted_at))| submitted_at.elapsed() >= timeout)
+            .map(|(hash, _)| *hash)
+            .collect::<Vec<_>>();
+        for hash in expired {
+            if let Err(err) = replace_stuck_tx(&web_state, hash).await {
+                event!(
+                    Level::WARN,
+                    tx_hash = ?hash,
+                    error = %err,
+                    "Failed to replace stuck transaction"
+                );
+            }
+        }
+    }
+}
+
+/// Resubmit the block of the pending transaction `hash`, unless it is mined.
+async fn replace_stuck_tx(web_state: &WebState, hash: H256) -> Result<(), Error> {
+    let client = web_state.contract().client();
+    if client
+        .get_transaction_receipt(hash)
+        .await
+        .map_err(|err| Error::Submission {
+            msg: err.to_string(),
+        })?
+        .is_some()
+    {
+        web_state.pending_txs.lock().await.remove(&hash);
+        return Ok(());
+    }
+    let stuck = match client
+        .get_transaction(hash)
+        .await
+        .map_err(|err| Error::Submission {
+            msg: err.to_string(),
+        })? {
+        Some(tx) => tx,
+        None => {
+            // The node forgot about the transaction, we cannot tell which gas
+            // price would replace it.
+            web_state.pending_txs.lock().await.remove(&hash);
+            return Err(Error::Submission {
+                msg: format!("transaction {:#x} not found", hash),
+            });
+        }
+    };
+    let (block, nonce, _) = match web_state.pending_txs.lock().await.get(&hash) {
+        Some(entry) => entry.clone(),
+        None => return Ok(()),
+    };
+
+    let mut memos_bytes: Vec<u8> = vec![];
+    block
+        .memos
+        .serialize(&mut memos_bytes)
+        .map_err(|err| Error::Internal {
+            msg: err.to_string(),
+        })?;
+    let mut tx: TypedTransaction = web_state
+        .contract()
+        .submit_cape_block_with_memos(block.block.clone().into(), memos_bytes.into())
+        .tx;
+    let stuck_gas_price = std::cmp::max(
+        stuck.gas_price.unwrap_or_default(),
+        stuck.max_fee_per_gas.unwrap_or_default(),
+    );
+    let max_gas_price = web_state.config.stuck_tx_max_gas_price;
+    let gas_price = std::cmp::min(
+        stuck_gas_price * STUCK_TX_GAS_PRICE_PERCENT / 100,
+        max_gas_price,
+    );
+    if gas_price <= stuck_gas_price {
+        // Wait for another timeout before trying again.
+        if let Some(entry) = web_state.pending_txs.lock().await.get_mut(&hash) {
+            entry.2 = Instant::now();
+        }
+        return Err(Error::Submission {
+            msg: format!(
+                "gas price {} of transaction {:#x} is already at the limit of {}",
+                stuck_gas_price, hash, max_gas_price
+            ),
+        });
+    }
+    tx.set_nonce(nonce)
+        .set_gas(stuck.gas)
+        .set_gas_price(gas_price);
+
+    let result = client.send_transaction(tx, None).await;
+    let mut pending_txs = web_state.pending_txs.lock().await;
+    match result {
+        Ok(replacement) => {
+            event!(
+                Level::INFO,
+                tx_hash = ?hash,
+                replacement_hash = ?*replacement,
+                gas_price = %gas_price,
+                "Stuck transaction resubmitted"
+            );
+            pending_txs.remove(&hash);
+            pending_txs.insert(*replacement, (block, nonce, Instant::now()));
+            Ok(())
+        }
+        Err(err) => {
+            let msg = err.to_string();
+            if msg.contains("nonce too low") {
+                // The stuck transaction, or one replacing it, was mined
+                // meanwhile.
+                pending_txs.remove(&hash);
+            }
+            Err(Error::Submission { msg })
+        }
+    }
+}
+
+/// Where the web server listens for connections.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub enum BindAddress {
+    /// A TCP address and port.
+    Tcp(SocketAddr),
+    /// A Unix domain socket, created at the given path.
+    ///
+    /// A socket left at the path by a server which is no longer running is
+    /// removed first.
+    #[cfg(unix)]
+    Unix(PathBuf),
+}
+
+/// Remove the Unix domain socket at `path` if no server is listening on it.
+///
+/// Other kinds of files are left alone, so that binding fails instead of
+/// deleting them.
+#[cfg(unix)]
+async fn remove_stale_socket(path: &Path) -> std::io::Result<()> {
+    use std::os::unix::fs::FileTypeExt;
+
+    match std::fs::symlink_metadata(path) {
+        Ok(metadata) if metadata.file_type().is_socket() => {
+            if UnixStream::connect(path).await.is_err() {
+                std::fs::remove_file(path)?;
+            }
+            Ok(())
+        }
+        Ok(_) => Ok(()),
+        Err(err) if err.kind() == std::io::ErrorKind::NotFound => Ok(()),
+        Err(err) => Err(err),
+    }
+}
+
+impl From<u16> for BindAddress {
+    /// Listen on `port` on all interfaces.
+    fn from(port: u16) -> Self {
+        Self::Tcp(SocketAddr::from(([0, 0, 0, 0], port)))
+    }
 }
 
 /// This function starts the web server
 pub fn init_web_server(
     web_state: WebState,
-    port: u16,
+    bind_address: impl Into<BindAddress>,
 ) -> task::JoinHandle<Result<(), std::io::Error>> {
+    let auth = HmacAuth::new(web_state.config.hmac_key.clone());
     let mut web_server = tide::with_state(web_state);
-    web_server.with(
+    web_server.with(RequestIdMiddleware).with(
         CorsMiddleware::new()
             .allow_methods("GET, POST".parse::<HeaderValue>().unwrap())
             .allow_headers("*".parse::<HeaderValue>().unwrap())
             .allow_origin(Origin::from("*"))
+            .expose_headers(REQUEST_ID_HEADER.parse::<HeaderValue>().unwrap())
             .allow_credentials(true),
     );
     web_server.at("/healthcheck").get(healthcheck);
+    web_server.at("/status").get(status);
+    web_server.at("/metrics").get(metrics_endpoint);
     web_server
         .with(add_error_body::<_, Error>)
         .at("/submit")
+        .with(auth.clone())
         .post(submit_endpoint);
-    let addr = format!("0.0.0.0:{}", port);
-    async_std::task::spawn(web_server.listen(addr))
+    web_server
+        .at("/submit_batch")
+        .with(auth.clone())
+        .post(submit_batch_endpoint);
+    web_server.at("/validate").post(validate_endpoint);
+    web_server
+        .at("/cancel/:tx_hash")
+        .with(auth.covering_path())
+        .post(cancel_endpoint);
+    match bind_address.into() {
+        BindAddress::Tcp(addr) => async_std::task::spawn(web_server.listen(addr.to_string())),
+        #[cfg(unix)]
+        BindAddress::Unix(path) => async_std::task::spawn(async move {
+            remove_stale_socket(&path).await?;
+            let listener = UnixListener::bind(path).await?;
+            web_server.listen(listener).await
+        }),
+    }
 }
 
 #[cfg(any(test, feature = "testing"))]
@@ -346,13 +1675,19 @@ pub mod testing {
     use cap_rust_sandbox::test_utils::upcast_test_cape_to_cape;
     use cap_rust_sandbox::{
         deploy::deploy_test_cape, ledger::CapeLedger, test_utils::create_faucet, types::TestCAPE,
+        universal_param::universal_param,
     };
     use jf_cap::{
         keys::UserKeyPair,
-        structs::{RecordCommitment, RecordOpening},
-        MerkleTree,
+        sign_receiver_memos,
+        structs::{AssetDefinition, FreezeFlag, RecordCommitment, RecordOpening},
+        transfer::{TransferNote, TransferNoteInput},
+        AccMemberWitness, MerkleTree,
     };
+    use proptest::prelude::*;
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
     use reef::Ledger;
+    use std::iter::once;
     use std::time::Duration;
 
     #[allow(dead_code)]
@@ -360,10 +1695,7 @@ pub mod testing {
         pub fn for_test(contract: &TestCAPE<EthMiddleware>) -> Self {
             Self::new(
                 upcast_test_cape_to_cape(contract.clone()),
-                NonceCountRule::Pending,
-                DEFAULT_RELAYER_EXTRA_GAS.parse().unwrap(),
-                DEFAULT_RELAYER_MAX_RETRIES.parse().unwrap(),
-                Duration::from_millis(DEFAULT_RELAYER_RETRY_INTERVAL_MS.parse().unwrap()),
+                RelayerConfig::default(),
             )
         }
     }
@@ -394,17 +1726,141 @@ pub mod testing {
         )
     }
 
+    /// Generate a native transfer of 1 from the faucet record, with leaf index 0, to `receiver`.
+    pub fn generate_transfer(
+        rng: &mut ChaChaRng,
+        faucet: &UserKeyPair,
+        faucet_rec: RecordOpening,
+        receiver: UserPubKey,
+        records: &MerkleTree,
+    ) -> (CapeModelTxn, Vec<ReceiverMemo>, Signature) {
+        let (txn, memos, sig, _) =
+            generate_transfer_with_outputs(rng, faucet, faucet_rec, 0, &[(receiver, 1)], records);
+        (txn, memos, sig)
+    }
+
+    /// Generate a native transfer spending the record `input` with leaf index
+    /// `uid` and paying a fee of 1.
+    ///
+    /// Also returns the openings of all the outputs, starting with the fee
+    /// change record.
+    pub fn generate_transfer_with_outputs(
+        rng: &mut ChaChaRng,
+        owner: &UserKeyPair,
+        input: RecordOpening,
+        uid: u64,
+        receivers: &[(UserPubKey, u64)],
+        records: &MerkleTree,
+    ) -> (
+        CapeModelTxn,
+        Vec<ReceiverMemo>,
+        Signature,
+        Vec<RecordOpening>,
+    ) {
+        let srs = universal_param();
+        let xfr_prove_key = jf_cap::proof::transfer::preprocess(
+            srs,
+            1,
+            receivers.len() + 1,
+            CapeLedger::merkle_height(),
+        )
+        .unwrap()
+        .0;
+        let valid_until = 2u64.pow(jf_cap::constants::MAX_TIMESTAMP_LEN as u32) - 1;
+        let inputs = vec![TransferNoteInput {
+            ro: input,
+            acc_member_witness: AccMemberWitness::lookup_from_tree(records, uid)
+                .expect_ok()
+                .unwrap()
+                .1,
+            owner_keypair: owner,
+            cred: None,
+        }];
+        let outputs = receivers
+            .iter()
+            .map(|(receiver, amount)| {
+                RecordOpening::new(
+                    rng,
+                    (*amount).into(),
+                    AssetDefinition::native(),
+                    receiver.clone(),
+                    FreezeFlag::Unfrozen,
+                )
+            })
+            .collect::<Vec<_>>();
+        let (note, sign_key, fee_output) = TransferNote::generate_native(
+            rng,
+            inputs,
+            &outputs,
+            1u64.into(),
+            valid_until,
+            &xfr_prove_key,
+        )
+        .unwrap();
+        let txn = CapeModelTxn::CAP(TransactionNote::Transfer(Box::new(note)));
+        let ros = once(fee_output).chain(outputs).collect::<Vec<_>>();
+        let memos = ros
+            .iter()
+            .map(|ro| ReceiverMemo::from_ro(rng, ro, &[]).unwrap())
+            .collect::<Vec<_>>();
+        let sig = sign_receiver_memos(&sign_key, &memos).unwrap();
+        (txn, memos, sig, ros)
+    }
+
+    /// A [SubmitBody] with a random native transfer, its memos and a valid memos signature.
+    ///
+    /// The transfer spends a random record from a fresh Merkle tree, so it passes the checks made
+    /// by the relayer itself but is rejected by the CAPE contract, which does not know its root.
+    pub fn arb_submit_body() -> BoxedStrategy<SubmitBody> {
+        (prop::collection::vec(1u64..1000, 1..=2), any::<u64>())
+            .prop_map(|(amounts, seed)| {
+                let mut rng = ChaChaRng::seed_from_u64(seed);
+                let owner = UserKeyPair::generate(&mut rng);
+                let total = amounts.iter().sum::<u64>() + 1;
+                let input = RecordOpening::new(
+                    &mut rng,
+                    total.into(),
+                    AssetDefinition::native(),
+                    owner.pub_key(),
+                    FreezeFlag::Unfrozen,
+                );
+                let mut records = MerkleTree::new(CapeLedger::merkle_height()).unwrap();
+                records.push(RecordCommitment::from(&input).to_field_element());
+
+                let receivers = amounts
+                    .into_iter()
+                    .map(|amount| (UserKeyPair::generate(&mut rng).pub_key(), amount))
+                    .collect::<Vec<_>>();
+                let (transaction, memos, signature, _) = generate_transfer_with_outputs(
+                    &mut rng, &owner, input, 0, &receivers, &records,
+                );
+                SubmitBody {
+                    transaction,
+                    memos,
+                    signature,
+                }
+            })
+            .boxed()
+    }
+
     const RELAYER_STARTUP_RETRIES: usize = 8;
 
-    pub async fn wait_for_server(port: u16) {
+    pub async fn wait_for_server(bind_address: impl Into<BindAddress>) {
         // Wait for the server to come up and start serving.
+        let bind_address = bind_address.into();
         let mut backoff = Duration::from_millis(100);
         for _ in 0..RELAYER_STARTUP_RETRIES {
-            if surf::connect(format!("http://localhost:{}", port))
-                .send()
-                .await
-                .is_ok()
-            {
+            let connected = match &bind_address {
+                BindAddress::Tcp(addr) => {
+                    surf::connect(format!("http://localhost:{}", addr.port()))
+                        .send()
+                        .await
+                        .is_ok()
+                }
+                #[cfg(unix)]
+                BindAddress::Unix(path) => UnixStream::connect(path).await.is_ok(),
+            };
+            if connected {
                 return;
             }
             sleep(backoff).await;
@@ -444,33 +1900,36 @@ mod test {
     use cap_rust_sandbox::test_utils::upcast_test_cape_to_cape;
     use cap_rust_sandbox::{
         cape::CAPEConstructorArgs,
-        ethereum::{deploy, get_funded_client},
+        deploy::deploy_test_cape,
+        ethereum::{
+            deploy, get_funded_client, get_provider_from_url, get_provider_from_url_with_timeout,
+        },
         ledger::CapeLedger,
-        model::CapeModelTxn,
-        test_utils::contract_abi_path,
+        test_utils::{arb_burn_txn, arb_mint_txn, arb_transfer_txn, contract_abi_path},
         types::CAPE,
-        universal_param::UNIVERSAL_PARAM,
     };
-    use ethers::{prelude::PendingTransaction, providers::Middleware};
+    use ethers::prelude::{Address, Bytes, LocalWallet, Signer, SignerMiddleware};
     use jf_cap::{
         keys::UserKeyPair,
-        sign_receiver_memos,
-        structs::{AssetDefinition, FreezeFlag, RecordOpening},
-        transfer::{TransferNote, TransferNoteInput},
-        AccMemberWitness, MerkleTree, TransactionNote,
+        structs::{RecordCommitment, RecordOpening},
+        MerkleTree,
     };
     use lazy_static::lazy_static;
     use net::{
         client::{parse_error_body, response_body},
         Error as _,
     };
+    use proptest::prelude::*;
     use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
     use reef::traits::Ledger;
-    use std::iter::once;
+    use std::collections::HashSet;
     use surf::Url;
+    use tempdir::TempDir;
     use testing::{
-        deploy_cape_contract_with_faucet, start_minimal_relayer_for_test, wait_for_server,
+        arb_submit_body, deploy_cape_contract_with_faucet, generate_transfer,
+        generate_transfer_with_outputs, start_minimal_relayer_for_test, wait_for_server,
     };
+    use tracing_test::traced_test;
 
     lazy_static! {
         static ref PORT: Arc<Mutex<u16>> = {
@@ -487,51 +1946,49 @@ mod test {
         port
     }
 
-    fn generate_transfer(
+    /// Split the record `input`, given with its leaf index, into `count` records of `amount` owned
+    /// by `owner`, relaying one transfer per new record.
+    ///
+    /// The contract only verifies transfers of the sizes in `SUPPORTED_TRANSFER_SIZES`, so each
+    /// transfer has 1 input and 2 outputs: the new record and the change, which is spent by the
+    /// next transfer. Returns the new records with their leaf index.
+    async fn split_record(
         rng: &mut ChaChaRng,
-        faucet: &UserKeyPair,
-        faucet_rec: RecordOpening,
-        receiver: UserPubKey,
-        records: &MerkleTree,
-    ) -> (CapeModelTxn, Vec<ReceiverMemo>, Signature) {
-        let srs = &UNIVERSAL_PARAM;
-        let xfr_prove_key =
-            jf_cap::proof::transfer::preprocess(srs, 1, 2, CapeLedger::merkle_height())
-                .unwrap()
-                .0;
-        let valid_until = 2u64.pow(jf_cap::constants::MAX_TIMESTAMP_LEN as u32) - 1;
-        let inputs = vec![TransferNoteInput {
-            ro: faucet_rec,
-            acc_member_witness: AccMemberWitness::lookup_from_tree(records, 0)
-                .expect_ok()
+        web_state: &WebState,
+        owner: &UserKeyPair,
+        input: (RecordOpening, u64),
+        count: usize,
+        amount: u64,
+        records: &mut MerkleTree,
+    ) -> Vec<(RecordOpening, u64)> {
+        let provider = web_state.contract().client().provider().clone();
+        let mut change = input;
+        let mut split = vec![];
+        for _ in 0..count {
+            let (transaction, memos, signature, ros) = generate_transfer_with_outputs(
+                rng,
+                owner,
+                change.0,
+                change.1,
+                &[(owner.pub_key(), amount)],
+                records,
+            );
+            let first_uid = records.num_leaves();
+            for comm in transaction.commitments() {
+                records.push(comm.to_field_element());
+            }
+            let hash = relay(web_state, transaction, memos, signature)
+                .await
+                .unwrap();
+            PendingTransaction::new(hash, &provider)
+                .await
                 .unwrap()
-                .1,
-            owner_keypair: faucet,
-            cred: None,
-        }];
-        let outputs = vec![RecordOpening::new(
-            rng,
-            1u64.into(),
-            AssetDefinition::native(),
-            receiver,
-            FreezeFlag::Unfrozen,
-        )];
-        let (note, sign_key, fee_output) = TransferNote::generate_native(
-            rng,
-            inputs,
-            &outputs,
-            1u64.into(),
-            valid_until,
-            &xfr_prove_key,
-        )
-        .unwrap();
-        let txn = CapeModelTxn::CAP(TransactionNote::Transfer(Box::new(note)));
-        let memos = once(fee_output)
-            .chain(outputs)
-            .map(|ro| ReceiverMemo::from_ro(rng, &ro, &[]).unwrap())
-            .collect::<Vec<_>>();
-        let sig = sign_receiver_memos(&sign_key, &memos).unwrap();
-        (txn, memos, sig)
+                .ensure_mined();
+            // The fee change comes first, followed by the new record.
+            change = (ros[0].clone(), first_uid);
+            split.push((ros[1].clone(), first_uid + 1));
+        }
+        split
     }
 
     #[async_std::test]
@@ -555,10 +2012,11 @@ mod test {
 
         let web_state = WebState::new(
             upcast_test_cape_to_cape(contract.clone()),
-            nonce_count_rule,
-            DEFAULT_RELAYER_EXTRA_GAS.parse().unwrap(),
-            DEFAULT_RELAYER_MAX_RETRIES.parse().unwrap(),
-            Duration::from_millis(DEFAULT_RELAYER_RETRY_INTERVAL_MS.parse().unwrap()),
+            RelayerConfig::builder()
+                .nonce_count_rule(nonce_count_rule)
+                // Disable deduplication so that we can resubmit the same transaction.
+                .dedup_window(0)
+                .build(),
         );
 
         // Submit a transaction and verify that the 2 output commitments get added to the contract's
@@ -579,57 +2037,1060 @@ mod test {
         assert_eq!(contract.get_num_leaves().call().await.unwrap(), 3.into());
     }
 
-    fn get_client(port: u16) -> surf::Client {
-        let client: surf::Client = surf::Config::new()
-            .set_base_url(Url::parse(&format!("http://localhost:{}", port)).unwrap())
-            .try_into()
-            .unwrap();
-        client.with(parse_error_body::<Error>)
-    }
-
     #[async_std::test]
-    async fn test_submit() {
+    async fn test_submit_batch() {
         let mut rng = ChaChaRng::from_seed([42; 32]);
         let user = UserKeyPair::generate(&mut rng);
 
         let port = get_port().await;
-        let (contract, faucet, faucet_rec, records) =
+        let (contract, faucet, faucet_rec, mut records) =
             start_minimal_relayer_for_test(port, None).await;
         let client = get_client(port);
         let provider = contract.client().provider().clone();
-        let (transaction, memos, signature) =
-            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
-        let submit_body = SubmitBody {
-            transaction,
-            memos,
-            signature,
-        };
 
+        // Split the faucet record so that we have two records to spend in
+        // the same block.
+        let split = split_record(
+            &mut rng,
+            &WebState::for_test(&contract),
+            &faucet,
+            (faucet_rec, 0),
+            2,
+            10,
+            &mut records,
+        )
+        .await;
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 5u64.into());
+
+        let batch = split
+            .into_iter()
+            .map(|(ro, uid)| {
+                let (transaction, memos, signature, _) = generate_transfer_with_outputs(
+                    &mut rng,
+                    &faucet,
+                    ro,
+                    uid,
+                    &[(user.pub_key(), 1)],
+                    &records,
+                );
+                SubmitBody {
+                    transaction,
+                    memos,
+                    signature,
+                }
+            })
+            .collect::<Vec<_>>();
+
+        // Both transactions end up in a single Ethereum transaction.
         let mut res = client
-            .post("/submit")
-            .body_json(&submit_body)
+            .post("/submit_batch")
+            .body_json(&batch)
             .unwrap()
             .send()
             .await
             .unwrap();
         let hash = response_body::<H256>(&mut res).await.unwrap();
-        let receipt = PendingTransaction::new(hash, &provider);
-        receipt.await.unwrap().ensure_mined();
-        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 3u64.into());
+        PendingTransaction::new(hash, &provider)
+            .await
+            .unwrap()
+            .ensure_mined();
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 9u64.into());
 
-        // Submit the same transaction again to trigger a Submission error.
-        match Error::from_client_error(
-            client
-                .post("/submit")
-                .body_json(&submit_body)
-                .unwrap()
+        // An empty batch is rejected instead of submitting an empty block.
+        let res = client
+            .post("/submit_batch")
+            .body_json(&Vec::<SubmitBody>::new())
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(res.status(), StatusCode::BadRequest);
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 9u64.into());
+    }
+
+    #[async_std::test]
+    async fn test_max_txns_per_block() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let (contract, faucet, faucet_rec, mut records) =
+            deploy_cape_contract_with_faucet(None).await;
+        let web_state = WebState::new(
+            upcast_test_cape_to_cape(contract.clone()),
+            RelayerConfig::builder().max_txns_per_block(1).build(),
+        );
+        let provider = contract.client().provider().clone();
+
+        // Split the faucet record so that we have two records to spend.
+        let split = split_record(
+            &mut rng,
+            &web_state,
+            &faucet,
+            (faucet_rec, 0),
+            2,
+            10,
+            &mut records,
+        )
+        .await;
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 5u64.into());
+
+        let transfers = split
+            .into_iter()
+            .map(|(ro, uid)| {
+                generate_transfer_with_outputs(
+                    &mut rng,
+                    &faucet,
+                    ro,
+                    uid,
+                    &[(user.pub_key(), 1)],
+                    &records,
+                )
+            })
+            .map(|(transaction, memos, signature, _)| (transaction, memos, signature))
+            .collect::<Vec<_>>();
+
+        // Both transactions do not fit in one block.
+        match relay_batch(&web_state, transfers.clone()).await {
+            Err(Error::BlockTooLarge { actual, max }) => {
+                assert_eq!(actual, 2);
+                assert_eq!(max, 1);
+            }
+            res => panic!("expected BlockTooLarge, got {:?}", res),
+        }
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 5u64.into());
+
+        // They can be submitted in separate blocks.
+        for (transaction, memos, signature) in transfers {
+            let hash = relay(&web_state, transaction, memos, signature)
+                .await
+                .unwrap();
+            PendingTransaction::new(hash, &provider)
+                .await
+                .unwrap()
+                .ensure_mined();
+        }
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 9u64.into());
+    }
+
+    #[async_std::test]
+    async fn test_max_block_bytes() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let (contract, faucet, faucet_rec, records) = deploy_cape_contract_with_faucet(None).await;
+        let (transaction, memos, sig) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        let web_state = WebState::new(
+            upcast_test_cape_to_cape(contract.clone()),
+            RelayerConfig::builder().max_block_bytes(1024).build(),
+        );
+
+        // A block with a single transfer is larger than 1KB, so it is rejected
+        // before anything is sent to the contract.
+        match relay(&web_state, transaction, memos, sig).await {
+            Err(err @ Error::BlockBytesTooLarge { actual, max }) => {
+                assert!(actual > 1024);
+                assert_eq!(max, 1024);
+                assert_eq!(net::Error::status(&err), StatusCode::PayloadTooLarge);
+            }
+            res => panic!("expected BlockBytesTooLarge, got {:?}", res),
+        }
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 1u64.into());
+    }
+
+    #[async_std::test]
+    async fn test_relay_concurrent_load() {
+        const NUM_TASKS: u64 = 10;
+
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let (contract, faucet, faucet_rec, mut records) =
+            deploy_cape_contract_with_faucet(None).await;
+        let web_state = WebState::for_test(&contract);
+        let provider = contract.client().provider().clone();
+
+        // Split the faucet record so that each task has its own record to
+        // spend.
+        let split = split_record(
+            &mut rng,
+            &web_state,
+            &faucet,
+            (faucet_rec, 0),
+            NUM_TASKS as usize,
+            10,
+            &mut records,
+        )
+        .await;
+        let leaves_before = contract.get_num_leaves().call().await.unwrap();
+        assert_eq!(leaves_before, (1 + 2 * NUM_TASKS).into());
+
+        // Generate all the transfers up front, so that the submissions race.
+        let transfers = split
+            .into_iter()
+            .map(|(ro, uid)| {
+                let (transaction, memos, signature, _) = generate_transfer_with_outputs(
+                    &mut rng,
+                    &faucet,
+                    ro,
+                    uid,
+                    &[(user.pub_key(), 1)],
+                    &records,
+                );
+                (transaction, memos, signature)
+            })
+            .collect::<Vec<_>>();
+        let tasks = transfers
+            .into_iter()
+            .map(|(transaction, memos, signature)| {
+                let web_state = web_state.clone();
+                task::spawn(async move { relay(&web_state, transaction, memos, signature).await })
+            })
+            .collect::<Vec<_>>();
+
+        let results =
+            async_std::future::timeout(Duration::from_secs(60), futures::future::join_all(tasks))
+                .await
+                .expect("concurrent submissions timed out");
+
+        // The block submission mutex serializes the submissions, so none of
+        // them can get a stale nonce.
+        let mut hashes = HashSet::new();
+        for result in results {
+            let hash = match result {
+                Ok(hash) => hash,
+                Err(err @ Error::Nonce { .. }) => {
+                    panic!("submissions were not serialized: {}", err)
+                }
+                Err(err) => panic!("submission failed: {}", err),
+            };
+            PendingTransaction::new(hash, &provider)
+                .await
+                .unwrap()
+                .ensure_mined();
+            hashes.insert(hash);
+        }
+        assert_eq!(hashes.len(), NUM_TASKS as usize);
+
+        // Each transfer adds a fee change record and an output record.
+        assert_eq!(
+            contract.get_num_leaves().call().await.unwrap(),
+            leaves_before + 2 * NUM_TASKS
+        );
+    }
+
+    #[async_std::test]
+    async fn test_relay_invalid_signature() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let (contract, faucet, faucet_rec, records) = deploy_cape_contract_with_faucet(None).await;
+        let web_state = WebState::for_test(&contract);
+
+        let (_, _, signature) = generate_transfer(
+            &mut rng,
+            &faucet,
+            faucet_rec.clone(),
+            user.pub_key(),
+            &records,
+        );
+        let (transaction, memos, _) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        // The signature of the other transaction does not sign these memos.
+        let body = SubmitBody {
+            transaction,
+            memos,
+            signature,
+        };
+        assert!(matches!(
+            body.validate_signature(),
+            Err(Error::BadSignature { .. })
+        ));
+
+        // The submission is rejected before anything is sent to the contract.
+        let nonce = contract
+            .client()
+            .get_transaction_count(contract.client().address(), None)
+            .await
+            .unwrap();
+        match relay(&web_state, body.transaction, body.memos, body.signature).await {
+            Err(Error::BadSignature { .. }) => {}
+            res => panic!("expected BadSignature error, got {:?}", res),
+        }
+        assert_eq!(
+            contract
+                .client()
+                .get_transaction_count(contract.client().address(), None)
+                .await
+                .unwrap(),
+            nonce
+        );
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 1u64.into());
+    }
+
+    #[async_std::test]
+    async fn test_submit_batch_invalid_signature() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let port = get_port().await;
+        let (contract, faucet, faucet_rec, records) =
+            start_minimal_relayer_for_test(port, None).await;
+        let client = get_client(port);
+
+        let (transaction, memos, signature) = generate_transfer(
+            &mut rng,
+            &faucet,
+            faucet_rec.clone(),
+            user.pub_key(),
+            &records,
+        );
+        let valid = SubmitBody {
+            transaction,
+            memos,
+            signature,
+        };
+        let (transaction, memos, _) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        // Use the signature of the other transaction, which does not sign
+        // these memos.
+        let invalid = SubmitBody {
+            transaction,
+            memos,
+            signature: valid.signature.clone(),
+        };
+
+        match Error::from_client_error(
+            client
+                .post("/submit_batch")
+                .body_json(&vec![valid, invalid])
+                .unwrap()
                 .send()
                 .await
-                .expect_err("expected submission of invalid transaction to fail"),
+                .expect_err("expected submission of invalid batch to fail"),
         ) {
-            Error::Submission { .. } => {}
-            err => panic!("expected Submission error, got {:?}", err),
+            Error::BadBlock { .. } => {}
+            err => panic!("expected BadBlock error, got {:?}", err),
+        }
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 1u64.into());
+    }
+
+    #[async_std::test]
+    async fn test_status() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let port = get_port().await;
+        let (contract, faucet, faucet_rec, records) =
+            start_minimal_relayer_for_test(port, None).await;
+        let client = get_client(port);
+        let provider = contract.client().provider().clone();
+
+        let status: RelayerStatus = client.get("/status").recv_json().await.unwrap();
+        assert_eq!(status.last_submitted_hash, None);
+        assert_eq!(status.secs_since_last_submission, None);
+        assert_eq!(status.num_leaves, 1);
+
+        let (transaction, memos, signature) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        let submit_body = SubmitBody {
+            transaction,
+            memos,
+            signature,
         };
+        let mut res = client
+            .post("/submit")
+            .body_json(&submit_body)
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let hash = response_body::<H256>(&mut res).await.unwrap();
+        PendingTransaction::new(hash, &provider)
+            .await
+            .unwrap()
+            .ensure_mined();
+
+        let status: RelayerStatus = client.get("/status").recv_json().await.unwrap();
+        assert_eq!(status.last_submitted_hash, Some(hash));
+        assert!(status.secs_since_last_submission.is_some());
+        assert_eq!(status.num_leaves, 3);
+    }
+
+    #[async_std::test]
+    async fn test_relay_with_miner() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+        let miner = UserKeyPair::generate(&mut rng);
+
+        let (contract, faucet, faucet_rec, records) = deploy_cape_contract_with_faucet(None).await;
+        let (transaction, memos, sig) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        let provider = contract.client().provider().clone();
+
+        let web_state = WebState::new(
+            upcast_test_cape_to_cape(contract.clone()),
+            RelayerConfig::builder().miner(miner.pub_key()).build(),
+        );
+        let hash = relay(&web_state, transaction, memos, sig).await.unwrap();
+        PendingTransaction::new(hash, &provider)
+            .await
+            .unwrap()
+            .ensure_mined();
+
+        // Decode the submitted block from the calldata of the Ethereum
+        // transaction.
+        let tx = provider.get_transaction(hash).await.unwrap().unwrap();
+        let (block, _) = contract
+            .decode::<(cap_rust_sandbox::types::CapeBlock, Bytes), _>(
+                "submitCapeBlockWithMemos",
+                tx.input,
+            )
+            .unwrap();
+        let block = CapeBlock::try_from(block).unwrap();
+        assert_eq!(block.miner_addr, miner.address());
+        assert_ne!(block.miner_addr, UserPubKey::default().address());
+    }
+
+    #[async_std::test]
+    async fn test_cancel() {
+        let port = get_port().await;
+        let (contract, _, _, _) = start_minimal_relayer_for_test(port, None).await;
+        let client = get_client(port);
+        let eth_client = contract.client();
+        let provider = eth_client.provider().clone();
+        let address = eth_client.address();
+
+        // A transaction with a nonce gap cannot be mined, so it is stuck in
+        // the mempool.
+        let nonce = eth_client
+            .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+            .await
+            .unwrap();
+        let gas_price = provider.get_gas_price().await.unwrap();
+        let stuck = eth_client
+            .send_transaction(
+                TransactionRequest::new()
+                    .to(address)
+                    .value(0u64)
+                    .nonce(nonce + 1)
+                    .gas_price(gas_price),
+                None,
+            )
+            .await
+            .unwrap();
+        let stuck = *stuck;
+
+        let mut res = client
+            .post(format!("/cancel/{:#x}", stuck))
+            .send()
+            .await
+            .unwrap();
+        let replacement = response_body::<H256>(&mut res).await.unwrap();
+        assert_ne!(replacement, stuck);
+
+        // Fill the nonce gap, after which the replacement can be mined.
+        eth_client
+            .send_transaction(
+                TransactionRequest::new()
+                    .to(address)
+                    .value(0u64)
+                    .nonce(nonce),
+                None,
+            )
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .ensure_mined();
+        PendingTransaction::new(replacement, &provider)
+            .await
+            .unwrap()
+            .ensure_mined();
+        assert!(provider
+            .get_transaction_receipt(stuck)
+            .await
+            .unwrap()
+            .is_none());
+
+        // Mined transactions cannot be cancelled.
+        match Error::from_client_error(
+            client
+                .post(format!("/cancel/{:#x}", replacement))
+                .send()
+                .await
+                .expect_err("expected cancelling a mined transaction to fail"),
+        ) {
+            Error::CancelFailed { .. } => {}
+            err => panic!("expected CancelFailed error, got {:?}", err),
+        }
+    }
+
+    #[async_std::test]
+    async fn test_stuck_tx_monitor() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let (contract, faucet, faucet_rec, records) = deploy_cape_contract_with_faucet(None).await;
+        let (transaction, memos, sig) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        let web_state = WebState::new(
+            upcast_test_cape_to_cape(contract.clone()),
+            RelayerConfig::builder()
+                .stuck_tx_timeout(Duration::from_millis(100))
+                .build(),
+        );
+        let eth_client = contract.client();
+        let provider = eth_client.provider().clone();
+        let address = eth_client.address();
+
+        // Submit the block with a nonce gap, so that it is stuck in the
+        // mempool, and at the lowest gas price the node accepts.
+        let block = build_block_with_memos(
+            vec![(transaction, memos, sig)],
+            &web_state.miner(),
+            web_state.config.max_block_bytes,
+        )
+        .unwrap();
+        let mut memos_bytes: Vec<u8> = vec![];
+        block.memos.serialize(&mut memos_bytes).unwrap();
+        let nonce = eth_client
+            .get_transaction_count(address, Some(BlockNumber::Pending.into()))
+            .await
+            .unwrap();
+        let mut tx: TypedTransaction = web_state
+            .contract()
+            .submit_cape_block_with_memos(block.block.clone().into(), memos_bytes.into())
+            .tx;
+        tx.set_nonce(nonce + 1)
+            .set_gas(DEFAULT_RELAYER_EXTRA_GAS.parse::<u64>().unwrap())
+            .set_gas_price(provider.get_gas_price().await.unwrap());
+        let input = tx.data().cloned().unwrap();
+        let stuck = *eth_client.send_transaction(tx, None).await.unwrap();
+        web_state
+            .pending_txs
+            .lock()
+            .await
+            .insert(stuck, (block, (nonce + 1).as_u64(), Instant::now()));
+
+        // Wait for the monitor to resubmit the block. Holding the lock
+        // prevents the replacement from being replaced again while we look at
+        // it.
+        let token = CancellationToken::new();
+        let monitor = task::spawn(stuck_tx_monitor(web_state.clone(), token.clone()));
+        loop {
+            async_std::task::sleep(Duration::from_millis(100)).await;
+            let pending_txs = web_state.pending_txs.lock().await;
+            if let Some(replacement) = pending_txs.keys().find(|hash| **hash != stuck) {
+                assert_eq!(pending_txs.len(), 1);
+                let replacement_tx = provider
+                    .get_transaction(*replacement)
+                    .await
+                    .unwrap()
+                    .unwrap();
+                assert_eq!(replacement_tx.nonce, nonce + 1);
+                assert_eq!(replacement_tx.input, input);
+                break;
+            }
+        }
+
+        // Fill the nonce gap, after which the replacement can be mined.
+        eth_client
+            .send_transaction(
+                TransactionRequest::new()
+                    .to(address)
+                    .value(0u64)
+                    .nonce(nonce),
+                None,
+            )
+            .await
+            .unwrap()
+            .await
+            .unwrap()
+            .ensure_mined();
+
+        // The monitor stops watching the block once it is mined.
+        while !web_state.pending_txs.lock().await.is_empty() {
+            async_std::task::sleep(Duration::from_millis(100)).await;
+        }
+        assert!(provider
+            .get_transaction_receipt(stuck)
+            .await
+            .unwrap()
+            .is_none());
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 3.into());
+
+        token.cancel();
+        monitor.await;
+    }
+
+    #[async_std::test]
+    async fn test_relay_fallback_provider() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let (contract, faucet, faucet_rec, records) = deploy_cape_contract_with_faucet(None).await;
+        let (transaction, memos, sig) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        let provider = contract.client().provider().clone();
+
+        // The primary provider is not listening, so every request fails.
+        let unreachable = CAPE::new(
+            contract.address(),
+            Arc::new(SignerMiddleware::new(
+                get_provider_from_url("http://localhost:1"),
+                contract.client().signer().clone(),
+            )),
+        );
+        let state_without_fallback = WebState::new(
+            unreachable.clone(),
+            // Disable deduplication so that we can resubmit the same transaction.
+            RelayerConfig::builder().dedup_window(0).build(),
+        );
+        let web_state = state_without_fallback
+            .clone()
+            .with_fallback("fallback", upcast_test_cape_to_cape(contract.clone()));
+
+        let hash = relay(&web_state, transaction.clone(), memos.clone(), sig.clone())
+            .await
+            .unwrap();
+        PendingTransaction::new(hash, &provider)
+            .await
+            .unwrap()
+            .ensure_mined();
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 3.into());
+
+        // If all providers fail, the errors of each are reported.
+        let web_state = state_without_fallback.with_fallback("also unreachable", unreachable);
+        match relay(&web_state, transaction, memos, sig).await {
+            Err(Error::AllProvidersFailed { providers, msgs }) => {
+                assert_eq!(providers, vec!["primary", "also unreachable"]);
+                assert_eq!(msgs.len(), 2);
+            }
+            res => panic!("expected AllProvidersFailed error, got {:?}", res),
+        }
+    }
+
+    #[async_std::test]
+    async fn test_healthcheck() {
+        let port = get_port().await;
+        let (contract, _, _, _) = start_minimal_relayer_for_test(port, None).await;
+        let mut res = surf::get(format!("http://localhost:{}/healthcheck", port))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 200);
+        let body: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(body["status"], "available");
+        assert!(body["eth_block"].as_u64().is_some());
+
+        // A relayer whose provider is not listening reports that it is degraded.
+        let unreachable = CAPE::new(
+            contract.address(),
+            Arc::new(SignerMiddleware::new(
+                get_provider_from_url("http://localhost:1"),
+                contract.client().signer().clone(),
+            )),
+        );
+        let port = get_port().await;
+        let web_state = WebState::new(unreachable, RelayerConfig::default());
+        init_web_server(web_state, port);
+        wait_for_server(port).await;
+        let mut res = surf::get(format!("http://localhost:{}/healthcheck", port))
+            .await
+            .unwrap();
+        assert_eq!(res.status(), 503);
+        let body: serde_json::Value = res.body_json().await.unwrap();
+        assert_eq!(body["status"], "degraded");
+        assert!(body["error"].is_string());
+    }
+
+    #[async_std::test]
+    async fn test_provider_timeout() {
+        // A server which accepts connections but never answers.
+        let listener = async_std::net::TcpListener::bind("127.0.0.1:0")
+            .await
+            .unwrap();
+        let url = format!("http://{}", listener.local_addr().unwrap());
+        async_std::task::spawn(async move {
+            let mut connections = vec![];
+            while let Ok((stream, _)) = listener.accept().await {
+                connections.push(stream);
+            }
+        });
+
+        let timeout = Duration::from_millis(100);
+        let wallet = LocalWallet::new(&mut ChaChaRng::from_seed([42; 32])).with_chain_id(1u64);
+        let contract = CAPE::new(
+            Address::random(),
+            Arc::new(SignerMiddleware::new(
+                get_provider_from_url_with_timeout(&url, timeout),
+                wallet,
+            )),
+        );
+        // The relayer's own timeout is much longer, so that the error comes
+        // from the HTTP client and is recognized as a timeout by its type.
+        let web_state = WebState::new(
+            contract,
+            RelayerConfig::builder()
+                .provider_timeout(Duration::from_secs(60))
+                .build(),
+        );
+
+        let start = Instant::now();
+        match submit_empty_block(&web_state).await {
+            Err(Error::ProviderTimeout { .. }) => {}
+            res => panic!("expected ProviderTimeout error, got {:?}", res),
+        }
+        assert!(start.elapsed() < Duration::from_secs(5));
+    }
+
+    #[async_std::test]
+    async fn test_validate() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let port = get_port().await;
+        let (contract, faucet, faucet_rec, records) =
+            start_minimal_relayer_for_test(port, None).await;
+        let client = get_client(port);
+        let (transaction, memos, signature) = generate_transfer(
+            &mut rng,
+            &faucet,
+            faucet_rec.clone(),
+            user.pub_key(),
+            &records,
+        );
+        let submit_body = SubmitBody {
+            transaction,
+            memos,
+            signature,
+        };
+
+        let mut res = client
+            .post("/validate")
+            .body_json(&submit_body)
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let result = response_body::<ValidationResult>(&mut res).await.unwrap();
+        assert!(result.valid);
+        assert_eq!(result.error, None);
+        // Validation does not submit anything.
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 1u64.into());
+
+        // Not enough gas to process the block.
+        let result = validate(
+            &WebState::for_test(&contract),
+            submit_body.transaction.clone(),
+            submit_body.memos.clone(),
+            submit_body.signature.clone(),
+            Some(50_000u64.into()),
+        )
+        .await;
+        assert!(!result.valid);
+        assert!(result.error.is_some());
+
+        // A transaction proven against a Merkle root the contract doesn't know.
+        let mut unknown_records = records.clone();
+        unknown_records.push(RecordCommitment::from(&faucet_rec).to_field_element());
+        let (transaction, memos, signature) = generate_transfer(
+            &mut rng,
+            &faucet,
+            faucet_rec,
+            user.pub_key(),
+            &unknown_records,
+        );
+        let mut res = client
+            .post("/validate")
+            .body_json(&SubmitBody {
+                transaction,
+                memos,
+                signature,
+            })
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let result = response_body::<ValidationResult>(&mut res).await.unwrap();
+        assert!(!result.valid);
+        assert!(result.error.unwrap().contains("Root not found"));
+    }
+
+    #[test]
+    fn test_relayer_config_from_vars() {
+        // The variables are passed explicitly rather than through the process
+        // environment, which is shared with the tests running concurrently.
+        let vars = HashMap::from([
+            ("CAPE_RELAYER_NONCE_COUNT_RULE", "mined"),
+            ("CAPE_RELAYER_MAX_RETRIES", "7"),
+            ("CAPE_RELAYER_PROVIDER_TIMEOUT_MS", "100"),
+        ]);
+        let config =
+            RelayerConfig::from_vars(|var| vars.get(var).map(|value| value.to_string())).unwrap();
+
+        assert!(matches!(config.nonce_count_rule, NonceCountRule::Mined));
+        assert_eq!(config.max_retries, 7);
+        assert_eq!(config.provider_timeout, Duration::from_millis(100));
+        // Unset variables take the default value.
+        let default = RelayerConfig::default();
+        assert_eq!(config.extra_gas, default.extra_gas);
+        assert_eq!(config.dedup_window, default.dedup_window);
+        assert_eq!(config.max_txns_per_block, default.max_txns_per_block);
+        assert_eq!(
+            config.stuck_tx_max_gas_price,
+            default.stuck_tx_max_gas_price
+        );
+        assert!(config.miner.is_none());
+    }
+
+    #[test]
+    fn test_relayer_config_invalid_backoff_multiplier() {
+        for value in ["0.5", "-2", "NaN", "inf", "two"] {
+            let vars = HashMap::from([("CAPE_RELAYER_BACKOFF_MULTIPLIER", value)]);
+            let err = RelayerConfig::from_vars(|var| vars.get(var).map(|value| value.to_string()))
+                .unwrap_err();
+            assert!(
+                err.to_string().contains("CAPE_RELAYER_BACKOFF_MULTIPLIER"),
+                "{}",
+                err
+            );
+        }
+        for value in [0.5, -2.0, f64::NAN, f64::INFINITY] {
+            assert!(matches!(
+                RelayerConfig::builder().backoff_multiplier(value),
+                Err(ConfigError::InvalidBackoffMultiplier { .. })
+            ));
+        }
+        // A multiplier of 1 keeps the retry interval constant.
+        let config = RelayerConfig::builder()
+            .backoff_multiplier(1.0)
+            .unwrap()
+            .build();
+        assert_eq!(config.backoff_multiplier, 1.0);
+    }
+
+    #[test]
+    #[should_panic(expected = "the stuck transaction timeout must not be zero")]
+    fn test_relayer_config_zero_stuck_tx_timeout() {
+        RelayerConfig::builder().stuck_tx_timeout(Duration::ZERO);
+    }
+
+    #[async_std::test]
+    async fn test_retry_delay_backoff() {
+        let contract = deploy_test_cape().await;
+        let web_state = WebState::new(
+            upcast_test_cape_to_cape(contract),
+            RelayerConfig::builder()
+                .max_retries(10)
+                .retry_interval(Duration::from_millis(100))
+                .backoff_multiplier(2.0)
+                .unwrap()
+                .max_retry_interval(Duration::from_millis(1000))
+                .build(),
+        );
+        let delays = (0..6)
+            .map(|attempt| web_state.retry_delay(attempt))
+            .collect::<Vec<_>>();
+        assert_eq!(
+            delays,
+            [100, 200, 400, 800, 1000, 1000]
+                .into_iter()
+                .map(Duration::from_millis)
+                .collect::<Vec<_>>()
+        );
+    }
+
+    #[async_std::test]
+    async fn test_retry_on_nonce_error_sleeps() {
+        let contract = deploy_test_cape().await;
+        let web_state = WebState::new(
+            upcast_test_cape_to_cape(contract),
+            RelayerConfig::builder()
+                .max_retries(5)
+                .retry_interval(Duration::from_millis(100))
+                .backoff_multiplier(2.0)
+                .unwrap()
+                .max_retry_interval(Duration::from_millis(1000))
+                .build(),
+        );
+        let nonce_error = || {
+            AttemptError::Send(Error::Nonce {
+                msg: "nonce too low".into(),
+            })
+        };
+
+        // The attempt succeeds after `failures` nonce errors; the mock sleep
+        // records the delays instead of waiting.
+        let run = |failures: u64| {
+            let web_state = &web_state;
+            async move {
+                let mut attempts = 0;
+                let mut delays = vec![];
+                let result = retry_on_nonce_error(
+                    web_state,
+                    |delay| {
+                        delays.push(delay);
+                        async {}
+                    },
+                    || {
+                        attempts += 1;
+                        let result = if attempts > failures {
+                            Ok(attempts)
+                        } else {
+                            Err(nonce_error())
+                        };
+                        async move { result }
+                    },
+                )
+                .await;
+                (result, delays)
+            }
+        };
+        let millis = |delays: &[u64]| {
+            delays
+                .iter()
+                .copied()
+                .map(Duration::from_millis)
+                .collect::<Vec<_>>()
+        };
+
+        let (result, delays) = run(0).await;
+        assert_eq!(result.unwrap(), 1);
+        assert!(delays.is_empty());
+
+        let (result, delays) = run(4).await;
+        assert_eq!(result.unwrap(), 5);
+        assert_eq!(delays, millis(&[100, 200, 400, 800]));
+
+        // After `max_retries` retries, the nonce error is returned.
+        let (result, delays) = run(10).await;
+        assert!(matches!(
+            result.unwrap_err().into_error(),
+            Error::Nonce { .. }
+        ));
+        assert_eq!(delays, millis(&[100, 200, 400, 800, 1000]));
+
+        // Other errors are not retried.
+        let mut delays = vec![];
+        let result: Result<(), _> = retry_on_nonce_error(
+            &web_state,
+            |delay| {
+                delays.push(delay);
+                async {}
+            },
+            || async {
+                Err(AttemptError::BeforeSend(Error::Submission {
+                    msg: "reverted".into(),
+                }))
+            },
+        )
+        .await;
+        assert!(matches!(
+            result.unwrap_err().into_error(),
+            Error::Submission { .. }
+        ));
+        assert!(delays.is_empty());
+    }
+
+    fn get_client(port: u16) -> surf::Client {
+        let client: surf::Client = surf::Config::new()
+            .set_base_url(Url::parse(&format!("http://localhost:{}", port)).unwrap())
+            .try_into()
+            .unwrap();
+        client.with(parse_error_body::<Error>)
+    }
+
+    #[cfg(unix)]
+    #[async_std::test]
+    async fn test_submit_unix_socket() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let (contract, faucet, faucet_rec, records) = deploy_cape_contract_with_faucet(None).await;
+        let dir = TempDir::new("relayer_socket").unwrap();
+        // A socket left behind by a previous server does not prevent binding.
+        drop(std::os::unix::net::UnixListener::bind(dir.path().join("relayer.sock")).unwrap());
+        let socket = BindAddress::Unix(dir.path().join("relayer.sock"));
+        init_web_server(WebState::for_test(&contract), socket.clone());
+        wait_for_server(socket.clone()).await;
+
+        let (transaction, memos, signature) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        let mut req = tide::http::Request::post("http://localhost/submit");
+        req.insert_header("Accept", "application/json");
+        req.set_body(
+            tide::http::Body::from_json(&SubmitBody {
+                transaction,
+                memos,
+                signature,
+            })
+            .unwrap(),
+        );
+        let stream = UnixStream::connect(dir.path().join("relayer.sock"))
+            .await
+            .unwrap();
+        let mut res = async_h1::connect(stream, req).await.unwrap();
+        assert_eq!(res.status(), 200);
+        let hash: H256 = res.body_json().await.unwrap();
+        PendingTransaction::new(hash, contract.client().provider())
+            .await
+            .unwrap()
+            .ensure_mined();
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 3u64.into());
+    }
+
+    #[async_std::test]
+    async fn test_submit() {
+        let mut rng = ChaChaRng::from_seed([42; 32]);
+        let user = UserKeyPair::generate(&mut rng);
+
+        let port = get_port().await;
+        let (contract, faucet, faucet_rec, records) =
+            start_minimal_relayer_for_test(port, None).await;
+        let client = get_client(port);
+        let provider = contract.client().provider().clone();
+        let (transaction, memos, signature) =
+            generate_transfer(&mut rng, &faucet, faucet_rec, user.pub_key(), &records);
+        let submit_body = SubmitBody {
+            transaction,
+            memos,
+            signature,
+        };
+
+        let mut res = client
+            .post("/submit")
+            .body_json(&submit_body)
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        let hash = response_body::<H256>(&mut res).await.unwrap();
+        let receipt = PendingTransaction::new(hash, &provider);
+        receipt.await.unwrap().ensure_mined();
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 3u64.into());
+
+        // The successful submission shows up in the metrics.
+        let metrics = client.get("/metrics").recv_string().await.unwrap();
+        assert!(metrics
+            .lines()
+            .any(|line| line == "cape_relayer_submissions_total{outcome=\"success\"} 1"));
+
+        // Submitting the same transaction again returns the original hash
+        // without submitting anything to the contract.
+        let mut res = client
+            .post("/submit")
+            .body_json(&submit_body)
+            .unwrap()
+            .send()
+            .await
+            .unwrap();
+        assert_eq!(response_body::<H256>(&mut res).await.unwrap(), hash);
+        let metrics = client.get("/metrics").recv_string().await.unwrap();
+        assert!(metrics
+            .lines()
+            .any(|line| line == "cape_relayer_submissions_total{outcome=\"success\"} 1"));
+        assert_eq!(contract.get_num_leaves().call().await.unwrap(), 3u64.into());
 
         // Deploy a new contract (without faucet setup) and submit the same
         // transaction to trigger a "Root not found" error.
@@ -665,13 +3126,7 @@ mod test {
             CAPE::new(address, deployer)
         };
         let port = get_port().await;
-        let web_state = WebState::new(
-            contract,
-            NonceCountRule::Pending,
-            DEFAULT_RELAYER_EXTRA_GAS.parse().unwrap(),
-            DEFAULT_RELAYER_MAX_RETRIES.parse().unwrap(),
-            Duration::from_millis(DEFAULT_RELAYER_RETRY_INTERVAL_MS.parse().unwrap()),
-        );
+        let web_state = WebState::new(contract, RelayerConfig::default());
         init_web_server(web_state, port);
         wait_for_server(port).await;
         let client = get_client(port);
@@ -688,4 +3143,81 @@ mod test {
             err => panic!("expected 'Root not found' error, got {:?}", err),
         }
     }
+
+    struct LogBuffer(Arc<std::sync::Mutex<Vec<u8>>>);
+
+    impl std::io::Write for LogBuffer {
+        fn write(&mut self, buf: &[u8]) -> std::io::Result<usize> {
+            self.0.lock().unwrap().write(buf)
+        }
+
+        fn flush(&mut self) -> std::io::Result<()> {
+            Ok(())
+        }
+    }
+
+    #[test]
+    #[traced_test]
+    fn test_log_block_submitted() {
+        let hash = H256::from_low_u64_be(1);
+        log_block_submitted(hash, 2);
+        assert!(logs_contain(&format!("tx_hash={:?}", hash)));
+
+        let buf = Arc::new(std::sync::Mutex::new(vec![]));
+        let writer = {
+            let buf = buf.clone();
+            move || LogBuffer(buf.clone())
+        };
+        let subscriber = log_subscriber(LogFormat::Json, EnvFilter::new("info"), writer);
+        tracing::subscriber::with_default(subscriber, || log_block_submitted(hash, 2));
+        let output = String::from_utf8(buf.lock().unwrap().clone()).unwrap();
+        let line: serde_json::Value = serde_json::from_str(output.trim()).unwrap();
+        assert_eq!(line["fields"]["tx_hash"], format!("{:?}", hash));
+        assert_eq!(line["fields"]["note_count"], 2);
+        assert_eq!(line["fields"]["message"], "Block submitted");
+    }
+
+    #[test]
+    fn proptest_relay_arb_submit_body() {
+        let contract = task::block_on(deploy_test_cape());
+        let web_state = WebState::for_test(&contract);
+        proptest!(ProptestConfig::with_cases(4), |(body in arb_submit_body())| {
+            // The body is well formed, so if the relayer rejects it, it is because of the
+            // contract.
+            if let Err(err) = task::block_on(relay(
+                &web_state,
+                body.transaction,
+                body.memos,
+                body.signature,
+            )) {
+                prop_assert!(
+                    !matches!(err, Error::BadSignature { .. } | Error::BadBlock { .. }),
+                    "unexpected error: {}",
+                    err
+                );
+            }
+        });
+    }
+
+    proptest! {
+        #![proptest_config(ProptestConfig::with_cases(4))]
+
+        #[test]
+        fn proptest_submit_body_serde_round_trip(
+            body in arb_submit_body(),
+            transaction in prop_oneof![arb_transfer_txn(), arb_mint_txn(), arb_burn_txn()],
+        ) {
+            let other = SubmitBody {
+                transaction,
+                ..body.clone()
+            };
+            for body in [body, other] {
+                let json = serde_json::to_string(&body).unwrap();
+                let parsed: SubmitBody = serde_json::from_str(&json).unwrap();
+                prop_assert_eq!(&parsed.transaction, &body.transaction);
+                prop_assert_eq!(&parsed.memos, &body.memos);
+                prop_assert_eq!(&parsed.signature, &body.signature);
+            }
+        }
+    }
 }
@@ -0,0 +1,109 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Prometheus metrics exposed by the relayer on `/metrics`.
+
+use crate::Error;
+use async_std::sync::Arc;
+use prometheus::{
+    Encoder, Histogram, HistogramOpts, IntCounter, IntCounterVec, Opts, Registry, TextEncoder,
+};
+
+#[derive(Clone)]
+pub struct Metrics {
+    registry: Arc<Registry>,
+    submissions: IntCounterVec,
+    submission_duration: Histogram,
+    retries: IntCounter,
+    gas_used: IntCounter,
+}
+
+impl Metrics {
+    pub fn new() -> Self {
+        let registry = Registry::new();
+        let submissions = IntCounterVec::new(
+            Opts::new(
+                "cape_relayer_submissions_total",
+                "Number of CAPE block submissions by outcome",
+            ),
+            &["outcome"],
+        )
+        .unwrap();
+        let submission_duration = Histogram::with_opts(HistogramOpts::new(
+            "cape_relayer_submission_duration_seconds",
+            "Time taken to submit a CAPE block, including retries",
+        ))
+        .unwrap();
+        let retries = IntCounter::new(
+            "cape_relayer_retries_total",
+            "Number of submissions retried after a nonce error",
+        )
+        .unwrap();
+        let gas_used = IntCounter::new(
+            "cape_relayer_gas_used_total",
+            "Gas used by mined CAPE block submissions",
+        )
+        .unwrap();
+        registry.register(Box::new(submissions.clone())).unwrap();
+        registry
+            .register(Box::new(submission_duration.clone()))
+            .unwrap();
+        registry.register(Box::new(retries.clone())).unwrap();
+        registry.register(Box::new(gas_used.clone())).unwrap();
+        Self {
+            registry: Arc::new(registry),
+            submissions,
+            submission_duration,
+            retries,
+            gas_used,
+        }
+    }
+
+    pub fn registry(&self) -> &Registry {
+        &self.registry
+    }
+
+    /// Record the outcome of a block submission.
+    pub fn observe_submission<T>(&self, result: &Result<T, Error>, duration_secs: f64) {
+        let outcome = match result {
+            Ok(_) => "success",
+            Err(Error::Nonce { .. }) => "nonce_error",
+            Err(Error::RootNotFound { .. }) => "root_not_found",
+            Err(_) => "other",
+        };
+        self.submissions.with_label_values(&[outcome]).inc();
+        self.submission_duration.observe(duration_secs);
+    }
+
+    pub fn inc_retries(&self) {
+        self.retries.inc();
+    }
+
+    pub fn add_gas_used(&self, gas: u64) {
+        self.gas_used.inc_by(gas);
+    }
+
+    /// Number of submissions with the given outcome so far.
+    pub fn submissions(&self, outcome: &str) -> u64 {
+        self.submissions.with_label_values(&[outcome]).get()
+    }
+
+    /// Render all metrics in the Prometheus text format.
+    pub fn render(&self) -> String {
+        let mut buf = vec![];
+        TextEncoder::new()
+            .encode(&self.registry.gather(), &mut buf)
+            .unwrap();
+        String::from_utf8(buf).unwrap()
+    }
+}
+
+impl Default for Metrics {
+    fn default() -> Self {
+        Self::new()
+    }
+}
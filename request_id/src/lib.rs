@@ -0,0 +1,125 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Correlation of requests across the CAPE services.
+//!
+//! [RequestIdMiddleware] tags each request with the ID in its [REQUEST_ID_HEADER] header, or with a
+//! random UUID if there is none or it is not a [valid ID](is_valid_request_id). All log events emitted while handling the request are recorded in
+//! a span carrying the ID, and the ID is echoed in the response header, so a client can forward it
+//! to the next service it calls.
+
+use tide::{Middleware, Next, Request};
+use tracing::Instrument;
+use uuid::Uuid;
+
+/// Header carrying the request ID.
+pub const REQUEST_ID_HEADER: &str = "X-Request-Id";
+
+/// Maximum length of a request ID taken from a request header.
+pub const MAX_REQUEST_ID_LEN: usize = 64;
+
+/// Whether `id` can be used as a request ID: it is not empty, has at most [MAX_REQUEST_ID_LEN]
+/// characters, and only contains ASCII letters, digits and `-`.
+///
+/// The ID ends up in the logs and in response headers, so anything else is replaced.
+pub fn is_valid_request_id(id: &str) -> bool {
+    !id.is_empty()
+        && id.len() <= MAX_REQUEST_ID_LEN
+        && id.bytes().all(|b| b.is_ascii_alphanumeric() || b == b'-')
+}
+
+/// The ID of the request being handled, available to handlers with `req.ext::<RequestId>()`.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub struct RequestId(pub String);
+
+#[derive(Clone, Copy, Debug, Default)]
+pub struct RequestIdMiddleware;
+
+#[tide::utils::async_trait]
+impl<State: Clone + Send + Sync + 'static> Middleware<State> for RequestIdMiddleware {
+    async fn handle(&self, mut req: Request<State>, next: Next<'_, State>) -> tide::Result {
+        let id = match req.header(REQUEST_ID_HEADER) {
+            Some(id) if is_valid_request_id(id.as_str()) => id.as_str().to_string(),
+            _ => Uuid::new_v4().to_string(),
+        };
+        req.set_ext(RequestId(id.clone()));
+        let span = tracing::info_span!("request", request_id = %id);
+        let mut res = next.run(req).instrument(span).await;
+        res.insert_header(REQUEST_ID_HEADER, id);
+        Ok(res)
+    }
+}
+
+#[cfg(test)]
+mod test {
+    use super::*;
+    use tide::http::{Method, Url};
+    use tracing_test::traced_test;
+
+    fn server() -> tide::Server<()> {
+        let mut server = tide::new();
+        server.with(RequestIdMiddleware);
+        server.at("/").get(|req: Request<()>| async move {
+            let id = req.ext::<RequestId>().unwrap().0.clone();
+            tracing::info!("handling request");
+            Ok(id)
+        });
+        server
+    }
+
+    async fn get(server: &tide::Server<()>, id: Option<&str>) -> tide::http::Response {
+        let mut req =
+            tide::http::Request::new(Method::Get, Url::parse("http://localhost/").unwrap());
+        if let Some(id) = id {
+            req.insert_header(REQUEST_ID_HEADER, id);
+        }
+        server.respond(req).await.unwrap()
+    }
+
+    #[async_std::test]
+    #[traced_test]
+    async fn test_request_id_propagation() {
+        let server = server();
+
+        // A given ID is passed to the handler, logged and echoed.
+        let mut res = get(&server, Some("test-request-id")).await;
+        assert_eq!(
+            res.header(REQUEST_ID_HEADER).unwrap().as_str(),
+            "test-request-id"
+        );
+        assert_eq!(res.body_string().await.unwrap(), "test-request-id");
+        assert!(logs_contain("request_id=test-request-id"));
+
+        // Without a header, a fresh UUID is used.
+        let mut res = get(&server, None).await;
+        let id = res.header(REQUEST_ID_HEADER).unwrap().as_str().to_string();
+        assert!(Uuid::parse_str(&id).is_ok());
+        assert_eq!(res.body_string().await.unwrap(), id);
+        assert!(logs_contain(&format!("request_id={}", id)));
+    }
+
+    #[async_std::test]
+    async fn test_invalid_request_id() {
+        let server = server();
+
+        let longest = "a".repeat(MAX_REQUEST_ID_LEN);
+        let mut res = get(&server, Some(&longest)).await;
+        assert_eq!(res.body_string().await.unwrap(), longest);
+
+        let too_long = "a".repeat(MAX_REQUEST_ID_LEN + 1);
+        for id in [too_long.as_str(), "", "id with spaces", "id_1", "a/b"] {
+            let mut res = get(&server, Some(id)).await;
+            let echoed = res.header(REQUEST_ID_HEADER).unwrap().as_str().to_string();
+            assert!(
+                Uuid::parse_str(&echoed).is_ok(),
+                "{:?} was not replaced",
+                id
+            );
+            assert_eq!(res.body_string().await.unwrap(), echoed);
+        }
+    }
+}
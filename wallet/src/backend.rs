@@ -712,7 +712,7 @@ mod test {
     };
     use crate::{CapeWallet, CapeWalletExt};
     use cap_rust_sandbox::{
-        deploy::deploy_erc20_token, ethereum::get_funded_client, universal_param::UNIVERSAL_PARAM,
+        deploy::deploy_erc20_token, ethereum::get_funded_client, universal_param::universal_param,
     };
     use ethers::types::{TransactionRequest, U256};
     use jf_cap::structs::AssetCode;
@@ -725,7 +725,7 @@ mod test {
     #[async_std::test]
     async fn test_transfer() {
         let mut rng = ChaChaRng::from_seed([1u8; 32]);
-        let universal_param = &UNIVERSAL_PARAM;
+        let universal_param = universal_param();
         let (sender_key, relayer_url, address_book_url, contract_address, _) =
             create_test_network(&mut rng, universal_param, None).await;
         let (eqs_url, _eqs_dir, _join_eqs) = spawn_eqs(contract_address).await;
@@ -853,7 +853,7 @@ mod test {
     #[async_std::test]
     async fn test_anonymous_erc20_transfer() {
         let mut rng = ChaChaRng::from_seed([1u8; 32]);
-        let universal_param = &UNIVERSAL_PARAM;
+        let universal_param = universal_param();
         let (wrapper_key, relayer_url, address_book_url, contract_address, _) =
             create_test_network(&mut rng, universal_param, None).await;
         let (eqs_url, _eqs_dir, _join_eqs) = spawn_eqs(contract_address).await;
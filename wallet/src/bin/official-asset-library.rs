@@ -6,7 +6,7 @@
 // You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
 
 use async_std::task::sleep;
-use cap_rust_sandbox::universal_param::UNIVERSAL_PARAM;
+use cap_rust_sandbox::universal_param::universal_param;
 use cape_wallet::{
     backend::{CapeBackend, CapeBackendConfig},
     loader::CapeLoader,
@@ -385,7 +385,7 @@ impl GenerateCommand {
                 .map_err(wallet_error)?,
         );
         let backend = CapeBackend::new(
-            &UNIVERSAL_PARAM,
+            universal_param(),
             CapeBackendConfig {
                 web3_provider: Some(self.rpc_url),
                 eth_mnemonic: Some(self.eth_mnemonic.to_string()),
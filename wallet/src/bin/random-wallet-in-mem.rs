@@ -12,7 +12,7 @@
 // This test is still a work in progrogress.  See: https://github.com/EspressoSystems/cape/issues/649
 // for everything left before it works properly.
 
-use cap_rust_sandbox::{deploy::deploy_erc20_token, universal_param::UNIVERSAL_PARAM};
+use cap_rust_sandbox::{deploy::deploy_erc20_token, universal_param::universal_param};
 use cape_wallet::backend::{CapeBackend, CapeBackendConfig};
 use cape_wallet::loader::CapeLoader;
 use cape_wallet::testing::get_burn_amount;
@@ -318,7 +318,7 @@ async fn main() {
     let mut balances = HashMap::new();
     let args = Args::from_args();
     let mut rng = ChaChaRng::seed_from_u64(args.seed.unwrap_or(0));
-    let universal_param = &UNIVERSAL_PARAM;
+    let universal_param = universal_param();
     let tmp_dir = TempDir::new("random_in_mem_test_sender").unwrap();
     tmp_dirs.push(tmp_dir);
     let (network, mut wallet) = if args.demo_connection {
@@ -10,7 +10,7 @@
 // integration with the EQS.  See Issue: https://github.com/EspressoSystems/cape/issues/548
 
 use async_std::task::sleep;
-use cap_rust_sandbox::{deploy::deploy_erc20_token, universal_param::UNIVERSAL_PARAM};
+use cap_rust_sandbox::{deploy::deploy_erc20_token, universal_param::universal_param};
 use cape_wallet::backend::{CapeBackend, CapeBackendConfig};
 use cape_wallet::loader::CapeLoader;
 use cape_wallet::testing::create_test_network;
@@ -109,7 +109,7 @@ async fn main() {
 
     let mut rng = ChaChaRng::seed_from_u64(args.seed.unwrap_or(0));
 
-    let universal_param = &UNIVERSAL_PARAM;
+    let universal_param = universal_param();
 
     // Everyone creates own relayer and EQS, not sure it works without EQS
     let (sender_key, relayer_url, address_book_url, contract_address, _) =
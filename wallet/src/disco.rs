@@ -8,6 +8,7 @@
 //! Configurable API loading.
 
 #![allow(clippy::format_push_string)]
+use serde_json::{json, Map, Value};
 use std::fs::read_to_string;
 use std::path::{Path, PathBuf};
 use std::str::FromStr;
@@ -30,10 +31,15 @@ pub fn load_messages(path: &Path) -> toml::Value {
 #[allow(non_camel_case_types)]
 #[derive(AsRefStr, Copy, Clone, Debug, EnumIter, EnumString, strum_macros::Display)]
 pub enum ApiRouteKey {
+    assets,
+    auditlog,
     buildsponsor,
     buildwrap,
     closewallet,
+    consolidate,
+    consolidate_preview,
     exportasset,
+    export_keys,
     freeze,
     getaddress,
     getaccount,
@@ -44,6 +50,7 @@ pub enum ApiRouteKey {
     getprivatekey,
     getrecords,
     importasset,
+    import_keys,
     healthcheck,
     importkey,
     lastusedkeystore,
@@ -53,12 +60,17 @@ pub enum ApiRouteKey {
     newkey,
     newwallet,
     openwallet,
+    pending_transactions,
+    portfolio,
     recordopening,
     recoverkey,
     resetpassword,
     send,
+    send_multi,
+    session,
     submitsponsor,
     submitwrap,
+    sync,
     transaction,
     transactionhistory,
     unfreeze,
@@ -210,6 +222,91 @@ pub fn compose_help(api: &toml::Value) -> String {
     help
 }
 
+/// Compose `api.toml` into an OpenAPI 3.0 specification.
+///
+/// Each route pattern becomes a path, with parameters like `:amount` written as `{amount}` and
+/// typed according to their [UrlSegmentType]. Request and response bodies are only described in
+/// the `DOC` of each route. Every route fails with the error described by `error_schema`.
+pub fn compose_openapi(api: &toml::Value, error_schema: Value) -> Value {
+    let mut paths = Map::new();
+    if let Some(api_map) = api["route"].as_table() {
+        for (key, entry) in api_map {
+            let method = entry
+                .get("METHOD")
+                .and_then(|method| method.as_str())
+                .unwrap_or("GET")
+                .to_lowercase();
+            let doc = entry["DOC"]
+                .as_str()
+                .expect("DOC must be a string in api.toml")
+                .trim();
+            let patterns = entry["PATH"].as_array().expect("Expecting TOML array.");
+            for pattern in patterns {
+                let pattern = pattern
+                    .as_str()
+                    .expect("PATH must be an array of strings in api.toml");
+                let mut path = String::new();
+                let mut parameters = vec![];
+                for segment in pattern.split('/') {
+                    path.push('/');
+                    match segment.strip_prefix(':') {
+                        Some(name) => {
+                            path += &format!("{{{}}}", name);
+                            let ty = entry[segment]
+                                .as_str()
+                                .expect("Parameter types must be strings in api.toml");
+                            parameters.push(json!({
+                                "name": name,
+                                "in": "path",
+                                "required": true,
+                                "schema": UrlSegmentType::from_str(ty)
+                                    .expect("Invalid parameter type in api.toml")
+                                    .openapi_schema(),
+                            }));
+                        }
+                        None => path += segment,
+                    }
+                }
+                let operation = json!({
+                    "operationId": pattern.replace(':', "").replace('/', "_"),
+                    "tags": [key],
+                    "summary": doc.lines().next().unwrap_or_default(),
+                    "description": doc,
+                    "parameters": parameters,
+                    "responses": {
+                        "200": {
+                            "description": "Success",
+                            "content": { "application/json": { "schema": {} } },
+                        },
+                        "default": {
+                            "description": "Error",
+                            "content": {
+                                "application/json": {
+                                    "schema": { "$ref": "#/components/schemas/Error" },
+                                },
+                            },
+                        },
+                    },
+                });
+                paths.insert(path, json!({ method.as_str(): operation }));
+            }
+        }
+    }
+    json!({
+        "openapi": "3.0.3",
+        "info": {
+            "title": "CAPE wallet API",
+            "version": env!("CARGO_PKG_VERSION"),
+        },
+        "paths": paths,
+        "components": {
+            "schemas": {
+                "Error": error_schema,
+            },
+        },
+    })
+}
+
 /// Returns the default path to the API file.
 pub fn default_api_path() -> PathBuf {
     const API_FILE: &str = "api/api.toml";
@@ -246,3 +343,19 @@ pub enum UrlSegmentType {
     Base64,
     Literal,
 }
+
+impl UrlSegmentType {
+    /// The OpenAPI schema of a URL parameter of this type.
+    pub fn openapi_schema(&self) -> Value {
+        match self {
+            Self::Boolean => json!({ "type": "boolean" }),
+            Self::Hexadecimal => json!({ "type": "string", "pattern": "^[0-9a-fA-F]+$" }),
+            Self::Integer => json!({ "type": "integer", "minimum": 0 }),
+            Self::TaggedBase64 => {
+                json!({ "type": "string", "pattern": "^[^~]*~[A-Za-z0-9_-]*$" })
+            }
+            Self::Base64 => json!({ "type": "string", "format": "base64url" }),
+            Self::Literal => json!({ "type": "string" }),
+        }
+    }
+}
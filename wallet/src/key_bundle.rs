@@ -0,0 +1,116 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Password-encrypted backups of wallet keys.
+//!
+//! A [KeyBundle] is serialized with bincode and encrypted with AES-256-GCM, using a key derived
+//! from a password with PBKDF2-HMAC-SHA256. The encrypted bundle consists of the salt, the nonce,
+//! and the ciphertext, in that order.
+
+use crate::wallet::CapeWalletError;
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use hmac::Hmac;
+use jf_cap::keys::{AuditorKeyPair, FreezerKeyPair, UserKeyPair};
+use rand::{CryptoRng, RngCore};
+use serde::{Deserialize, Serialize};
+use sha2::Sha256;
+
+const SALT_LEN: usize = 16;
+const NONCE_LEN: usize = 12;
+const PBKDF2_ROUNDS: u32 = 100_000;
+
+/// The key pairs of a wallet.
+#[derive(Clone, Debug, Default, Deserialize, Serialize)]
+pub struct KeyBundle {
+    pub sending: Vec<UserKeyPair>,
+    pub viewing: Vec<AuditorKeyPair>,
+    pub freezing: Vec<FreezerKeyPair>,
+}
+
+fn derive_key(password: &str, salt: &[u8]) -> [u8; 32] {
+    let mut key = [0u8; 32];
+    pbkdf2::pbkdf2::<Hmac<Sha256>>(password.as_bytes(), salt, PBKDF2_ROUNDS, &mut key);
+    key
+}
+
+impl KeyBundle {
+    /// Serialize and encrypt the bundle with a key derived from `password`.
+    pub fn encrypt(
+        &self,
+        password: &str,
+        rng: &mut (impl RngCore + CryptoRng),
+    ) -> Result<Vec<u8>, CapeWalletError> {
+        let mut salt = [0u8; SALT_LEN];
+        rng.fill_bytes(&mut salt);
+        let mut nonce = [0u8; NONCE_LEN];
+        rng.fill_bytes(&mut nonce);
+
+        let key = derive_key(password, &salt);
+        let cipher = Aes256Gcm::new(Key::from_slice(&key));
+        let plaintext = bincode::serialize(self)?;
+        let ciphertext = cipher
+            .encrypt(Nonce::from_slice(&nonce), plaintext.as_ref())
+            .map_err(|_| CapeWalletError::Failed {
+                msg: String::from("failed to encrypt key bundle"),
+            })?;
+        Ok(salt.into_iter().chain(nonce).chain(ciphertext).collect())
+    }
+
+    /// Decrypt and deserialize a bundle produced by [KeyBundle::encrypt].
+    ///
+    /// Fails if `password` is not the password used to encrypt the bundle, or if the bundle has
+    /// been tampered with.
+    pub fn decrypt(bytes: &[u8], password: &str) -> Result<Self, CapeWalletError> {
+        if bytes.len() < SALT_LEN + NONCE_LEN {
+            return Err(CapeWalletError::Failed {
+                msg: String::from("key bundle is truncated"),
+            });
+        }
+        let (salt, rest) = bytes.split_at(SALT_LEN);
+        let (nonce, ciphertext) = rest.split_at(NONCE_LEN);
+
+        let key = derive_key(password, salt);
+        let cipher = Aes256Gcm::new(Key::from_slice(&key));
+        let plaintext = cipher
+            .decrypt(Nonce::from_slice(nonce), ciphertext)
+            .map_err(|_| CapeWalletError::Failed {
+                msg: String::from("failed to decrypt key bundle: wrong password or corrupt bundle"),
+            })?;
+        Ok(bincode::deserialize(&plaintext)?)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
+
+    #[test]
+    fn test_key_bundle_round_trip() {
+        let mut rng = ChaChaRng::from_seed([0; 32]);
+        let bundle = KeyBundle {
+            sending: vec![UserKeyPair::generate(&mut rng)],
+            viewing: vec![AuditorKeyPair::generate(&mut rng)],
+            freezing: vec![FreezerKeyPair::generate(&mut rng)],
+        };
+        let encrypted = bundle.encrypt("password", &mut rng).unwrap();
+
+        let decrypted = KeyBundle::decrypt(&encrypted, "password").unwrap();
+        assert_eq!(decrypted.sending[0].pub_key(), bundle.sending[0].pub_key());
+        assert_eq!(decrypted.viewing[0].pub_key(), bundle.viewing[0].pub_key());
+        assert_eq!(
+            decrypted.freezing[0].pub_key(),
+            bundle.freezing[0].pub_key()
+        );
+
+        KeyBundle::decrypt(&encrypted, "wrong password").unwrap_err();
+        KeyBundle::decrypt(&encrypted[..SALT_LEN], "password").unwrap_err();
+    }
+}
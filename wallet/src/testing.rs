@@ -179,7 +179,9 @@ pub fn rpc_url_for_test() -> Url {
 pub async fn spawn_eqs(cape_address: Address) -> (Url, TempDir, JoinHandle<std::io::Result<()>>) {
     let dir = TempDir::new("wallet_testing_eqs").unwrap();
     let eqs_port = port().await;
+    let metrics_port = port().await;
     let opt = EQSOptions {
+        config: None,
         web_path: String::new(),
         api_path: [
             std::path::Path::new(env!("CARGO_MANIFEST_DIR")),
@@ -196,10 +198,19 @@ pub async fn spawn_eqs(cape_address: Address) -> (Url, TempDir, JoinHandle<std::
         query_interval: 500,
         ethers_block_max: 5000,
         eqs_port,
+        metrics_port,
         cape_address: Some(cape_address),
         rpc_url: rpc_url_for_test().to_string(),
         temp_test_run: false,
         num_confirmations: Confirmations::default(),
+        snapshot_interval: 1000,
+        snapshots_retained: 10,
+        finality_depth: 12,
+        blocks_retained: 100000,
+        rate_limit_burst: 200,
+        rate_limit_rps: 100.0,
+        genesis_block: None,
+        store_backend: Default::default(),
     };
     let join = spawn(async move { run_eqs(&opt).await });
     let url = Url::parse(&format!("http://localhost:{}", eqs_port)).unwrap();
@@ -18,13 +18,13 @@ use jf_cap::{
     structs::{AssetCode, AssetDefinition as JfAssetDefinition, AssetPolicy as JfAssetPolicy},
 };
 use net::UserAddress;
-use reef::cap;
+use reef::{cap, AuditMemoOpening};
 use seahorse::{
     accounts::{AccountInfo, KeyPair},
     asset_library::Icon,
     events::EventIndex,
     txn_builder::RecordInfo,
-    MintInfo,
+    MintInfo, RecordAmount,
 };
 use serde::{Deserialize, Serialize};
 use std::collections::HashMap;
@@ -325,6 +325,83 @@ pub struct UpdateAsset {
     pub icon: Option<String>,
 }
 
+/// A receiver of a transfer, see [SendMulti].
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Recipient {
+    pub address: UserAddress,
+    pub amount: u128,
+}
+
+/// Parameters of a transfer to multiple receivers in a single transaction.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SendMulti {
+    pub asset: AssetCode,
+    pub recipients: Vec<Recipient>,
+    pub fee: u128,
+    /// The account paying for the transfer, or any account of the wallet if not given.
+    pub sender: Option<UserAddress>,
+}
+
+/// Request body of the `consolidate` route.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct Consolidate {
+    pub asset: AssetCode,
+    /// The maximum number of records to merge, capped at [Consolidate::max_inputs_limit].
+    #[serde(default = "Consolidate::default_max_inputs")]
+    pub max_inputs: usize,
+    pub fee: u128,
+}
+
+impl Consolidate {
+    pub const DEFAULT_MAX_INPUTS: usize = 3;
+
+    fn default_max_inputs() -> usize {
+        Self::DEFAULT_MAX_INPUTS
+    }
+
+    /// The most records of `asset` a single transfer can merge.
+    ///
+    /// The CAPE contract supports transfers with at most 3 inputs, and a transfer of a non-native
+    /// asset also spends a native record to pay the fee.
+    pub fn max_inputs_limit(asset: &AssetCode) -> usize {
+        if *asset == AssetCode::native() {
+            3
+        } else {
+            2
+        }
+    }
+}
+
+/// The records that `consolidate` would merge into one, see [Consolidate].
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub struct ConsolidatePreview {
+    /// The account owning the records, which also receives the merged record.
+    pub address: UserAddress,
+    pub records: Vec<RecordInfo>,
+    /// The total amount of the records. For the native asset, the merged record is smaller by the
+    /// fee.
+    ///
+    /// The wallet chooses the inputs of the transfer itself, so when several records have the same
+    /// amount, the records it spends may differ from `records`, but their total is the same.
+    pub balance: U256,
+}
+
+/// Request body of the `export_keys` route.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ExportKeys {
+    /// The password to encrypt the bundle with.
+    pub password: String,
+}
+
+/// Request body of the `import_keys` route.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct ImportKeys {
+    /// Base64 encoding of a bundle returned by `export_keys`.
+    pub bundle: String,
+    /// The password the bundle was exported with.
+    pub password: String,
+}
+
 #[derive(Debug, PartialEq, Eq, Deserialize, Serialize)]
 /// Public keys for spending, viewing and freezing assets.
 pub enum PubKey {
@@ -404,6 +481,34 @@ pub struct BalanceInfo {
     pub assets: HashMap<AssetCode, AssetInfo>,
 }
 
+/// A change in the balance of one asset in one account of the wallet.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct BalanceUpdate {
+    pub asset: AssetCode,
+    pub address: UserAddress,
+    pub new_balance: U256,
+    /// The new balance minus the old balance, saturated to the range of `i128`.
+    pub delta: i128,
+}
+
+/// The balance of one asset across all accounts of the wallet, with its value in USD.
+#[derive(Clone, Debug, PartialEq, Serialize, Deserialize)]
+pub struct AssetPortfolioEntry {
+    pub asset: AssetInfo,
+    pub balance: U256,
+    /// The price of one unit of the asset according to the price oracle, if it is known.
+    pub price_usd: Option<f64>,
+    pub value_usd: Option<f64>,
+}
+
+/// The value of the assets of the wallet, see [AssetPortfolioEntry].
+#[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
+pub struct PortfolioSummary {
+    pub entries: Vec<AssetPortfolioEntry>,
+    /// The total value of the assets with a known price, or `None` if no price is known.
+    pub total_usd: Option<f64>,
+}
+
 #[ser_test(ark(false))]
 #[derive(Clone, Debug, Default, PartialEq, Serialize, Deserialize)]
 pub struct WalletSummary {
@@ -422,6 +527,56 @@ pub struct WalletSummary {
     pub latest_contract: String,
 }
 
+/// Synchronization status of the wallet with the EQS.
+#[ser_test(ark(false))]
+#[derive(Clone, Debug, Default, PartialEq, Eq, Serialize, Deserialize)]
+pub struct SyncStatus {
+    /// Whether the wallet has processed all events reported by the EQS.
+    pub synced: bool,
+    /// The time (as an event index) at which the wallet last synced with the EQS.
+    pub sync_time: u64,
+    /// The real-world time (as an event index) according to the EQS.
+    pub real_time: u64,
+}
+
+/// An output of a committed transaction, as revealed by a viewing key.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub struct AuditEntry {
+    /// Index of the block containing the transaction.
+    pub block: u64,
+    pub asset: AssetCode,
+    pub amount: RecordAmount,
+    /// Owner of the first input of the transaction, if the asset policy reveals it.
+    pub sender_address: Option<UserAddress>,
+    pub receiver_address: UserAddress,
+}
+
+impl AuditEntry {
+    /// Get an entry for each output revealed by the opening of a viewing memo.
+    ///
+    /// Outputs whose amount or owner is not revealed by the asset policy are skipped.
+    pub fn from_opening(block: u64, opening: AuditMemoOpening) -> Vec<Self> {
+        let sender_address = opening
+            .inputs
+            .first()
+            .and_then(|input| input.user_address.clone())
+            .map(UserAddress::from);
+        opening
+            .outputs
+            .into_iter()
+            .filter_map(|output| {
+                Some(Self {
+                    block,
+                    asset: opening.asset.code,
+                    amount: output.amount?.into(),
+                    sender_address: sender_address.clone(),
+                    receiver_address: output.user_address?.into(),
+                })
+            })
+            .collect()
+    }
+}
+
 #[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
 pub struct Record {
     pub address: UserAddress,
@@ -40,10 +40,11 @@
 //! route handling, is defined in the [cape_wallet] crate.
 
 mod routes;
+mod session;
 mod web;
 
 use crate::web::{init_server, NodeOpt};
-use cap_rust_sandbox::universal_param::UNIVERSAL_PARAM;
+use cap_rust_sandbox::universal_param::universal_param;
 use rand_chacha::{rand_core::SeedableRng, ChaChaRng};
 use structopt::StructOpt;
 
@@ -56,7 +57,7 @@ async fn main() -> Result<(), std::io::Error> {
 
     // It can take a little while to unpack the universal params. Start doing this in the background
     // while the server is starting and the user is going through the login process.
-    async_std::task::spawn(async { &UNIVERSAL_PARAM });
+    async_std::task::spawn(async { universal_param() });
 
     init_server(ChaChaRng::from_entropy(), &NodeOpt::from_args())?.await?;
     Ok(())
@@ -67,23 +68,29 @@ mod tests {
     use super::*;
     use crate::{
         routes::CapeAPIError,
+        session::{
+            decrypt, ephemeral_key_pair, session_key, SessionInitResponse, ENCRYPTED_MSGPACK,
+            SESSION_HEADER,
+        },
         web::{
             DEFAULT_ETH_ADDR, DEFAULT_NATIVE_AMT_IN_FAUCET_ADDR,
             DEFAULT_NATIVE_AMT_IN_WRAPPER_ADDR, DEFAULT_WRAPPED_AMT,
         },
     };
     use ark_serialize::CanonicalDeserialize;
-    use async_std::fs;
+    use async_std::{fs, stream::StreamExt};
+    use async_tungstenite::async_std::connect_async;
     use cap_rust_sandbox::{ledger::CapeLedger, model::EthereumAddr};
     use cape_wallet::{
+        disco,
         mocks::test_asset_signing_key,
         testing::{port, retry},
         ui::*,
     };
-    use ethers::prelude::{Address, U256};
+    use ethers::prelude::{Address, H256, U256};
     use jf_cap::{
         keys::{AuditorKeyPair, AuditorPubKey, FreezerKeyPair, FreezerPubKey, UserKeyPair},
-        structs::{AssetCode, AssetDefinition as JfAssetDefinition, AssetPolicy},
+        structs::{AssetCode, AssetDefinition as JfAssetDefinition, AssetPolicy, FreezeFlag},
     };
     use net::{client, UserAddress};
     use seahorse::{
@@ -136,6 +143,10 @@ mod tests {
 
     impl TestServer {
         async fn new() -> Self {
+            Self::with_options(|options| options).await
+        }
+
+        async fn with_options(options: impl FnOnce(NodeOpt) -> NodeOpt) -> Self {
             let port = port().await;
 
             // Run a server in the background that is unique to this test. Note that the server task
@@ -144,7 +155,10 @@ mod tests {
             // ends. This is ok, since each test's server task should be idle once
             // the test is over.
             let temp_dir = TempDir::new("test_wallet_api_storage").unwrap();
-            let options = NodeOpt::for_test(port as u16, temp_dir.path().to_path_buf());
+            let options = options(NodeOpt::for_test(
+                port as u16,
+                temp_dir.path().to_path_buf(),
+            ));
             init_server(ChaChaRng::from_seed([42; 32]), &options).unwrap();
             Self::wait(port).await;
 
@@ -659,6 +673,93 @@ mod tests {
     }
 
     #[cfg(feature = "slow-tests")]
+    #[async_std::test]
+    #[traced_test]
+    async fn test_ws_balances() {
+        let server = TestServer::new().await;
+        let url = format!("ws://localhost:{}/ws/balances", server.options().port);
+
+        // Should fail if a wallet is not already open.
+        let (mut ws, _) = connect_async(&url).await.unwrap();
+        let msg = ws.next().await.unwrap().unwrap();
+        assert!(matches!(
+            serde_json::from_str::<CapeAPIError>(msg.to_text().unwrap()).unwrap(),
+            CapeAPIError::MissingWallet
+        ));
+
+        // Now open a wallet.
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+
+        // The first message is the wallet summary.
+        let (mut ws, _) = connect_async(&url).await.unwrap();
+        let msg = ws.next().await.unwrap().unwrap();
+        let summary = serde_json::from_str::<WalletSummary>(msg.to_text().unwrap()).unwrap();
+        let info = server.get::<WalletSummary>("getinfo").await.unwrap();
+        assert_eq!(summary.addresses, info.addresses);
+        assert_eq!(summary.assets, info.assets);
+
+        // Receiving records should push an update for each balance that changes, until the pushed
+        // balances match the balances reported by the wallet.
+        server
+            .get::<TransactionReceipt<CapeLedger>>("populatefortest")
+            .await
+            .unwrap();
+        let mut balances = HashMap::<(UserAddress, AssetCode), U256>::new();
+        loop {
+            let msg = ws.next().await.unwrap().unwrap();
+            let update = serde_json::from_str::<BalanceUpdate>(msg.to_text().unwrap()).unwrap();
+            let old_balance = balances
+                .insert((update.address, update.asset), update.new_balance)
+                .unwrap_or_default();
+            assert_ne!(update.new_balance, old_balance);
+            if update.new_balance > old_balance {
+                assert_eq!(
+                    U256::from(update.delta as u128),
+                    update.new_balance - old_balance
+                );
+            } else {
+                assert_eq!(
+                    U256::from(-update.delta as u128),
+                    old_balance - update.new_balance
+                );
+            }
+
+            let expected = match server
+                .get::<BalanceInfo>("getbalance/all")
+                .await
+                .unwrap()
+                .balances
+            {
+                Balances::All { by_account, .. } => by_account
+                    .into_iter()
+                    .flat_map(|(address, balances)| {
+                        balances
+                            .into_iter()
+                            .map(move |(asset, balance)| ((address.clone(), asset), balance))
+                    })
+                    .filter(|(_, balance)| !balance.is_zero())
+                    .collect::<HashMap<_, _>>(),
+                balances => panic!("Expected Balances::All, found {:?}", balances),
+            };
+            balances.retain(|_, balance| !balance.is_zero());
+            if balances == expected {
+                break;
+            }
+        }
+        // The faucet account received the native asset.
+        assert!(balances
+            .keys()
+            .any(|(_, asset)| *asset == AssetCode::native()));
+    }
+
     #[async_std::test]
     #[traced_test]
     async fn test_aggregate_balance() {
@@ -1412,6 +1513,135 @@ mod tests {
         .await;
     }
 
+    #[async_std::test]
+    #[traced_test]
+    async fn test_freeze() {
+        // Set parameters.
+        let description = base64::encode_config(&[3u8; 32], base64::URL_SAFE_NO_PAD);
+        let amount = 10;
+        let fee = 1;
+        let mut rng = ChaChaRng::from_seed([51u8; 32]);
+
+        // Should fail if a wallet is not already open.
+        let server = TestServer::new().await;
+        let dummy_address = UserAddress::from(UserKeyPair::generate(&mut rng).address());
+        server
+            .requires_wallet_post::<TransactionReceipt<CapeLedger>>(&format!(
+                "freeze/asset/{}/target/{}/fee/{}",
+                AssetCode::native(),
+                dummy_address,
+                fee
+            ))
+            .await;
+        server
+            .requires_wallet_post::<TransactionReceipt<CapeLedger>>(&format!(
+                "unfreeze/asset/{}/target/{}/fee/{}",
+                AssetCode::native(),
+                dummy_address,
+                fee
+            ))
+            .await;
+
+        // Open a wallet with some initial grants and keys.
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("freezer-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+        let receipt = server
+            .get::<TransactionReceipt<CapeLedger>>("populatefortest")
+            .await
+            .unwrap();
+        let fee_address: UserAddress = receipt.submitters[0].clone().into();
+
+        // Define a freezable asset and mint some of it to one of our addresses.
+        let info = server.get::<WalletSummary>("getinfo").await.unwrap();
+        let asset = server
+            .post::<AssetInfo>(&format!(
+                "newasset/description/{}/freezing_key/{}/viewing_key/{}/view_amount/true/view_address/true",
+                description, info.freezing_keys[0], info.viewing_keys[0]
+            ))
+            .await
+            .unwrap()
+            .definition
+            .code;
+        let target: UserAddress = info.sending_keys[0].address().into();
+        server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "mint/asset/{}/amount/{}/fee/{}/minter/{}/recipient/{}",
+                asset, amount, fee, fee_address, target
+            ))
+            .await
+            .unwrap();
+        retry(|| async {
+            server
+                .get::<BalanceInfo>(&format!("getbalance/address/{}/asset/{}", target, asset))
+                .await
+                .unwrap()
+                .balances
+                == Balances::One(amount.into())
+        })
+        .await;
+
+        // Check that all records of the asset owned by the target have the given freeze flag.
+        let has_freeze_flag = |flag: FreezeFlag| {
+            let server = &server;
+            let target = &target.0;
+            async move {
+                let records = server.get::<Vec<RecordInfo>>("getrecords").await.unwrap();
+                let records = records
+                    .iter()
+                    .filter(|record| {
+                        record.ro.asset_def.code == asset && &record.ro.pub_key.address() == target
+                    })
+                    .collect::<Vec<_>>();
+                !records.is_empty() && records.iter().all(|record| record.ro.freeze_flag == flag)
+            }
+        };
+
+        // Freezing should fail with an unknown asset, and unfreezing should fail if there is
+        // nothing frozen.
+        server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "freeze/asset/{}/target/{}/fee/{}/{}",
+                AssetDefinition::dummy(),
+                target,
+                fee_address,
+                fee
+            ))
+            .await
+            .expect_err("freeze succeeded with an unknown asset");
+        server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "unfreeze/asset/{}/target/{}/fee/{}/{}",
+                asset, target, fee_address, fee
+            ))
+            .await
+            .expect_err("unfreeze succeeded without frozen records");
+
+        // Freeze and unfreeze the target's records.
+        server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "freeze/asset/{}/target/{}/fee/{}/{}",
+                asset, target, fee_address, fee
+            ))
+            .await
+            .unwrap();
+        retry(|| has_freeze_flag(FreezeFlag::Frozen)).await;
+        server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "unfreeze/asset/{}/target/{}/fee/{}/{}",
+                asset, target, fee_address, fee
+            ))
+            .await
+            .unwrap();
+        retry(|| has_freeze_flag(FreezeFlag::Unfrozen)).await;
+    }
+
     #[async_std::test]
     #[traced_test]
     async fn test_unwrap() {
@@ -1662,7 +1892,7 @@ mod tests {
         let dst_address = unfunded_account.unwrap();
 
         // Make a transfer with a given sender address.
-        server
+        let send_receipt = server
             .post::<TransactionReceipt<CapeLedger>>(&format!(
                 "send/sender/{}/asset/{}/recipient/{}/amount/{}/fee/{}",
                 src_address,
@@ -1704,6 +1934,20 @@ mod tests {
         })
         .await;
 
+        // Look up the transaction by its hash.
+        let hash = H256::from(<[u8; 32]>::from(send_receipt.uid.0));
+        let entry = server
+            .get::<TransactionHistoryEntry>(&format!("transaction/{:#x}", hash))
+            .await
+            .unwrap();
+        assert_eq!(entry.kind, "send");
+        assert_eq!(entry.senders, vec![src_address.clone()]);
+        // An unknown hash is not found.
+        server
+            .get::<TransactionHistoryEntry>(&format!("transaction/{:#x}", H256::zero()))
+            .await
+            .unwrap_err();
+
         // Make a transfer without a sender address.
         server
             .post::<TransactionReceipt<CapeLedger>>(&format!(
@@ -1757,7 +2001,7 @@ mod tests {
 
         assert_eq!(history[0].kind, "send");
         assert_eq!(history[0].asset, AssetCode::native());
-        assert_eq!(history[0].senders, vec![src_address]);
+        assert_eq!(history[0].senders, vec![src_address.clone()]);
         assert_eq!(
             history[0].receivers,
             vec![(dst_address.clone(), "100".to_string())]
@@ -1768,7 +2012,10 @@ mod tests {
         assert_eq!(history[1].asset, AssetCode::native());
         // We don't necessarily know the senders for the second transaction, since we allowed the
         // wallet to choose.
-        assert_eq!(history[1].receivers, vec![(dst_address, "100".to_string())]);
+        assert_eq!(
+            history[1].receivers,
+            vec![(dst_address.clone(), "100".to_string())]
+        );
         assert_eq!(history[1].status, "accepted");
 
         // Check :from and :count.
@@ -1794,79 +2041,825 @@ mod tests {
             .await
             .unwrap();
         assert_eq!(&history[1..], from_history3);
+
+        // Make 3 more transfers, for a history of at least 5 entries.
+        for i in 1..=3u64 {
+            server
+                .post::<TransactionReceipt<CapeLedger>>(&format!(
+                    "send/sender/{}/asset/{}/recipient/{}/amount/{}/fee/{}",
+                    src_address,
+                    &AssetCode::native(),
+                    dst_address,
+                    10,
+                    1
+                ))
+                .await
+                .unwrap();
+            retry(|| async {
+                server
+                    .get::<BalanceInfo>(&format!(
+                        "getbalance/address/{}/asset/{}",
+                        dst_address,
+                        AssetCode::native()
+                    ))
+                    .await
+                    .unwrap()
+                    .balances
+                    == Balances::One((200 + 10 * i).into())
+            })
+            .await;
+        }
+        let (history, _) = server
+            .get::<(Vec<TransactionHistoryEntry>, HashMap<AssetCode, AssetInfo>)>(
+                "transactionhistory",
+            )
+            .await
+            .unwrap();
+        assert!(history.len() >= 5);
+
+        // Check the length of the history.
+        assert_eq!(
+            server
+                .get::<usize>("transactionhistory/count")
+                .await
+                .unwrap(),
+            history.len()
+        );
+
+        // Check reverse ordering.
+        let (reversed, _) = server
+            .get::<(Vec<TransactionHistoryEntry>, HashMap<AssetCode, AssetInfo>)>(
+                "transactionhistory?reverse=true",
+            )
+            .await
+            .unwrap();
+        assert_eq!(reversed, history.iter().rev().cloned().collect::<Vec<_>>());
+        // With `reverse`, `:from` skips the most recent entries.
+        let (most_recent, _) = server
+            .get::<(Vec<TransactionHistoryEntry>, HashMap<AssetCode, AssetInfo>)>(
+                "transactionhistory/from/0/count/5?reverse=true",
+            )
+            .await
+            .unwrap();
+        assert_eq!(most_recent, reversed[..5]);
+        let (page, _) = server
+            .get::<(Vec<TransactionHistoryEntry>, HashMap<AssetCode, AssetInfo>)>(
+                "transactionhistory/from/2/count/2?reverse=true",
+            )
+            .await
+            .unwrap();
+        assert_eq!(page, reversed[2..4]);
     }
 
     #[async_std::test]
     #[traced_test]
-    async fn test_getaccount() {
+    async fn test_openapi() {
         let server = TestServer::new().await;
-        let mut rng = ChaChaRng::from_seed([1; 32]);
+        let spec = server
+            .get::<openapiv3::OpenAPI>("openapi.json")
+            .await
+            .unwrap();
+        assert!(spec.components.unwrap().schemas.contains_key("Error"));
+
+        // Every route pattern of the API file is described, with the same method and parameters.
+        let api = disco::load_messages(&server.options.api_path());
+        for route in api["route"].as_table().unwrap().values() {
+            let method = route
+                .get("METHOD")
+                .map_or("GET", |method| method.as_str().unwrap());
+            for pattern in route["PATH"].as_array().unwrap() {
+                let segments = pattern.as_str().unwrap().split('/').collect::<Vec<_>>();
+                let path = segments
+                    .iter()
+                    .map(|segment| match segment.strip_prefix(':') {
+                        Some(name) => format!("/{{{}}}", name),
+                        None => format!("/{}", segment),
+                    })
+                    .collect::<String>();
+                let item = match spec.paths.paths.get(&path) {
+                    Some(openapiv3::ReferenceOr::Item(item)) => item,
+                    _ => panic!("{} is missing from the OpenAPI spec", path),
+                };
+                let operation = match method {
+                    "GET" => item.get.as_ref(),
+                    "POST" => item.post.as_ref(),
+                    _ => None,
+                }
+                .unwrap_or_else(|| panic!("{} {} is missing from the OpenAPI spec", method, path));
+                assert_eq!(
+                    operation.parameters.len(),
+                    segments
+                        .iter()
+                        .filter(|segment| segment.starts_with(':'))
+                        .count()
+                );
+            }
+        }
+    }
 
-        // Should fail if a wallet is not already open.
-        server
-            .requires_wallet::<Account>(&format!(
-                "getaccount/{}",
-                UserKeyPair::generate(&mut rng).address(),
-            ))
-            .await;
-        server
-            .requires_wallet::<Account>(&format!(
-                "getaccount/{}",
-                UserKeyPair::generate(&mut rng).pub_key(),
-            ))
-            .await;
-        server
-            .requires_wallet::<Account>(&format!(
-                "getaccount/{}",
-                AuditorKeyPair::generate(&mut rng).pub_key(),
-            ))
+    #[async_std::test]
+    #[traced_test]
+    async fn test_portfolio() {
+        // Serve the price of the native asset only.
+        let oracle_port = port().await;
+        let mut oracle = tide::new();
+        oracle.at("/prices").get(|_| async {
+            let prices = HashMap::from([(AssetCode::native().to_string(), 0.5)]);
+            Ok(tide::Body::from_json(&prices)?)
+        });
+        async_std::task::spawn(oracle.listen(format!("0.0.0.0:{}", oracle_port)));
+        TestServer::wait(oracle_port).await;
+        let oracle_url = Url::parse(&format!("http://localhost:{}/prices", oracle_port)).unwrap();
+
+        // Nothing listens on this port, so the oracle is unavailable.
+        let unavailable_url =
+            Url::parse(&format!("http://localhost:{}/prices", port().await)).unwrap();
+
+        for (oracle_url, available) in [(oracle_url, true), (unavailable_url, false)] {
+            let server = TestServer::with_options(|options| NodeOpt {
+                price_oracle_url: Some(oracle_url),
+                ..options
+            })
             .await;
+            server
+                .requires_wallet::<PortfolioSummary>("portfolio")
+                .await;
+            server
+                .post::<()>(&format!(
+                    "newwallet/{}/{}/path/{}",
+                    server.get::<String>("getmnemonic").await.unwrap(),
+                    base64("my-password".as_bytes()),
+                    server.path()
+                ))
+                .await
+                .unwrap();
+            server
+                .get::<TransactionReceipt<CapeLedger>>("populatefortest")
+                .await
+                .unwrap();
+
+            let portfolio = server.get::<PortfolioSummary>("portfolio").await.unwrap();
+            assert!(portfolio
+                .entries
+                .iter()
+                .any(|entry| entry.asset.definition == AssetDefinition::native()));
+            for entry in &portfolio.entries {
+                if available && entry.asset.definition == AssetDefinition::native() {
+                    assert_eq!(entry.price_usd, Some(0.5));
+                    assert_eq!(entry.value_usd, Some(0.5 * entry.balance.as_u128() as f64));
+                } else {
+                    assert_eq!(entry.price_usd, None);
+                    assert_eq!(entry.value_usd, None);
+                }
+            }
+            let values = portfolio
+                .entries
+                .iter()
+                .filter_map(|entry| entry.value_usd)
+                .collect::<Vec<_>>();
+            if available {
+                assert_eq!(portfolio.total_usd, Some(values.iter().sum()));
+            } else {
+                assert!(values.is_empty());
+                assert_eq!(portfolio.total_usd, None);
+            }
+        }
+    }
+
+    #[async_std::test]
+    #[traced_test]
+    async fn test_auditlog() {
+        let description = base64::encode_config(&[4u8; 32], base64::URL_SAFE_NO_PAD);
+        let amount = 10;
+        let transfer_amount = 3;
+        let fee = 1;
+        let mut rng = ChaChaRng::from_seed([52u8; 32]);
+        // The maximum number of blocks which can be requested at once.
+        const MAX_BLOCKS: u64 = 1000;
+
+        // Should fail if a wallet is not already open.
+        let server = TestServer::new().await;
+        let dummy_key = AuditorKeyPair::generate(&mut rng).pub_key();
         server
-            .requires_wallet::<Account>(&format!(
-                "getaccount/{}",
-                FreezerKeyPair::generate(&mut rng).pub_key(),
+            .requires_wallet::<Vec<AuditEntry>>(&format!(
+                "auditlog/key/{}/from/0/to/100",
+                dummy_key
             ))
             .await;
 
-        // Now open a wallet.
+        // Open a wallet with some initial grants and keys.
         server
             .post::<()>(&format!(
                 "newwallet/{}/{}/path/{}",
                 server.get::<String>("getmnemonic").await.unwrap(),
-                base64("my-password".as_bytes()),
+                base64("auditor-password".as_bytes()),
                 server.path()
             ))
             .await
             .unwrap();
-        // Populate the wallet with some dummy data so we have a balance of an asset to send.
-        server
+        let receipt = server
             .get::<TransactionReceipt<CapeLedger>>("populatefortest")
             .await
             .unwrap();
+        let fee_address: UserAddress = receipt.submitters[0].clone().into();
 
-        // Get the wrapped asset type.
+        // Define an asset which reveals amounts and addresses to our viewing key, and mint some of
+        // it to one of our addresses.
         let info = server.get::<WalletSummary>("getinfo").await.unwrap();
-        let asset = if info.assets[0].definition.code == AssetCode::native() {
-            info.assets[1].clone()
-        } else {
-            info.assets[0].clone()
-        };
-
-        // The wrapper addressed it so we can check the account interface.
-        let mut addresses = info.addresses.into_iter();
-        let address = loop {
-            let address = addresses.next().unwrap();
-            println!(
-                "{:?}",
-                server
-                    .get::<BalanceInfo>(&format!(
-                        "getbalance/address/{}/asset/{}",
-                        address,
-                        AssetCode::native()
-                    ))
-                    .await
-                    .unwrap()
-            );
+        let viewing_key = info.viewing_keys[0].clone();
+        let asset = server
+            .post::<AssetInfo>(&format!(
+                "newasset/description/{}/viewing_key/{}/view_amount/true/view_address/true",
+                description, viewing_key
+            ))
+            .await
+            .unwrap()
+            .definition
+            .code;
+        let sender: UserAddress = info.sending_keys[0].address().into();
+        server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "mint/asset/{}/amount/{}/fee/{}/minter/{}/recipient/{}",
+                asset, amount, fee, fee_address, sender
+            ))
+            .await
+            .unwrap();
+        retry(|| async {
+            server
+                .get::<BalanceInfo>(&format!("getbalance/address/{}/asset/{}", sender, asset))
+                .await
+                .unwrap()
+                .balances
+                == Balances::One(amount.into())
+        })
+        .await;
+
+        // Transfer some of the asset to a new address.
+        let receiver: UserAddress = match server.post::<PubKey>("newkey/sending").await.unwrap() {
+            PubKey::Sending(key) => key.address().into(),
+            key => panic!("Expected PubKey::Sending, found {:?}", key),
+        };
+        server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "send/asset/{}/recipient/{}/amount/{}/fee/{}",
+                asset, receiver, transfer_amount, fee
+            ))
+            .await
+            .unwrap();
+        retry(|| async {
+            server
+                .get::<BalanceInfo>(&format!("getbalance/address/{}/asset/{}", receiver, asset))
+                .await
+                .unwrap()
+                .balances
+                == Balances::One(transfer_amount.into())
+        })
+        .await;
+
+        // The viewing key should reveal the transfer.
+        let entries = server
+            .get::<Vec<AuditEntry>>(&format!(
+                "auditlog/key/{}/from/0/to/{}",
+                viewing_key,
+                MAX_BLOCKS - 1
+            ))
+            .await
+            .unwrap();
+        let transfer = entries
+            .iter()
+            .find(|entry| entry.receiver_address == receiver)
+            .unwrap();
+        assert_eq!(transfer.asset, asset);
+        assert_eq!(transfer.amount, transfer_amount.into());
+        assert_eq!(transfer.sender_address, Some(sender.clone()));
+        assert!(entries.iter().all(|entry| entry.asset == asset));
+
+        // Restricting the block range to before the transfer should hide it.
+        let range = server
+            .get::<Vec<AuditEntry>>(&format!(
+                "auditlog/key/{}/from/0/to/{}",
+                viewing_key,
+                transfer.block - 1
+            ))
+            .await
+            .unwrap();
+        assert!(range.iter().all(|entry| entry.receiver_address != receiver));
+
+        // A range starting at the transfer's block should still reveal it.
+        let range = server
+            .get::<Vec<AuditEntry>>(&format!(
+                "auditlog/key/{}/from/{}/to/{}",
+                viewing_key, transfer.block, transfer.block
+            ))
+            .await
+            .unwrap();
+        assert!(range.contains(transfer));
+
+        // Ranges which are too long or empty should be rejected.
+        for (from, to) in [(0, MAX_BLOCKS), (transfer.block, transfer.block - 1)] {
+            server
+                .get::<Vec<AuditEntry>>(&format!(
+                    "auditlog/key/{}/from/{}/to/{}",
+                    viewing_key, from, to
+                ))
+                .await
+                .expect_err(&format!("auditlog succeeded with range {}..={}", from, to));
+        }
+
+        // A viewing key which is not used by any asset should not reveal anything.
+        let other_key = match server.post::<PubKey>("newkey/viewing").await.unwrap() {
+            PubKey::Viewing(key) => key,
+            key => panic!("Expected PubKey::Viewing, found {:?}", key),
+        };
+        assert_eq!(
+            server
+                .get::<Vec<AuditEntry>>(&format!(
+                    "auditlog/key/{}/from/0/to/{}",
+                    other_key,
+                    MAX_BLOCKS - 1
+                ))
+                .await
+                .unwrap(),
+            vec![]
+        );
+
+        // A viewing key which does not belong to the wallet should be rejected.
+        server
+            .get::<Vec<AuditEntry>>(&format!(
+                "auditlog/key/{}/from/0/to/{}",
+                dummy_key,
+                MAX_BLOCKS - 1
+            ))
+            .await
+            .expect_err("auditlog succeeded with an unknown viewing key");
+    }
+
+    #[async_std::test]
+    #[traced_test]
+    async fn test_sync() {
+        let server = TestServer::new().await;
+
+        // Should fail if a wallet is not already open.
+        server.requires_wallet_post::<SyncStatus>("sync").await;
+
+        // Now open a wallet and populate it.
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+        let receipt = server
+            .get::<TransactionReceipt<CapeLedger>>("populatefortest")
+            .await
+            .unwrap();
+        let src_address: UserAddress = receipt.submitters[0].clone().into();
+        let dst_address: UserAddress = server
+            .get::<WalletSummary>("getinfo")
+            .await
+            .unwrap()
+            .addresses
+            .into_iter()
+            .find(|address| *address != src_address)
+            .unwrap();
+
+        // Submit a transaction and sync, without polling for the result.
+        server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "send/sender/{}/asset/{}/recipient/{}/amount/{}/fee/{}",
+                src_address,
+                AssetCode::native(),
+                dst_address,
+                100,
+                1
+            ))
+            .await
+            .unwrap();
+        let status = server.post::<SyncStatus>("sync/timeout/60").await.unwrap();
+        assert!(status.synced);
+        assert_eq!(status.sync_time, status.real_time);
+
+        // The transaction should now be in the history.
+        let (history, _) = server
+            .get::<(Vec<TransactionHistoryEntry>, HashMap<AssetCode, AssetInfo>)>(
+                "transactionhistory",
+            )
+            .await
+            .unwrap();
+        let entry = history.last().unwrap();
+        assert_eq!(entry.kind, "send");
+        assert_eq!(entry.senders, vec![src_address]);
+        assert_eq!(entry.receivers, vec![(dst_address, "100".to_string())]);
+        assert_eq!(entry.status, "accepted");
+    }
+
+    #[async_std::test]
+    #[traced_test]
+    async fn test_pending_transactions() {
+        let server = TestServer::new().await;
+
+        // Should fail if a wallet is not already open.
+        server
+            .requires_wallet::<Vec<TransactionReceipt<CapeLedger>>>("pending_transactions")
+            .await;
+
+        // Now open a wallet and populate it.
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+        let receipt = server
+            .get::<TransactionReceipt<CapeLedger>>("populatefortest")
+            .await
+            .unwrap();
+        let src_address: UserAddress = receipt.submitters[0].clone().into();
+        let dst_address: UserAddress = server
+            .get::<WalletSummary>("getinfo")
+            .await
+            .unwrap()
+            .addresses
+            .into_iter()
+            .find(|address| *address != src_address)
+            .unwrap();
+        server.post::<SyncStatus>("sync").await.unwrap();
+        assert_eq!(
+            server
+                .get::<Vec<TransactionReceipt<CapeLedger>>>("pending_transactions")
+                .await
+                .unwrap(),
+            vec![]
+        );
+
+        // Submit a transaction and check that it is pending, but only for the native asset.
+        let receipt = server
+            .post::<TransactionReceipt<CapeLedger>>(&format!(
+                "send/sender/{}/asset/{}/recipient/{}/amount/{}/fee/{}",
+                src_address,
+                AssetCode::native(),
+                dst_address,
+                100,
+                1
+            ))
+            .await
+            .unwrap();
+        assert_eq!(
+            server
+                .get::<Vec<TransactionReceipt<CapeLedger>>>(&format!(
+                    "pending_transactions?asset={}",
+                    AssetCode::native()
+                ))
+                .await
+                .unwrap(),
+            vec![receipt.clone()]
+        );
+        let mut rng = ChaChaRng::from_seed([3; 32]);
+        assert_eq!(
+            server
+                .get::<Vec<TransactionReceipt<CapeLedger>>>(&format!(
+                    "pending_transactions?asset={}",
+                    AssetCode::random(&mut rng).0
+                ))
+                .await
+                .unwrap(),
+            vec![]
+        );
+
+        // Once the wallet is synced, the transaction is no longer pending.
+        assert!(server.post::<SyncStatus>("sync").await.unwrap().synced);
+        assert_eq!(
+            server
+                .get::<Vec<TransactionReceipt<CapeLedger>>>("pending_transactions")
+                .await
+                .unwrap(),
+            vec![]
+        );
+    }
+
+    #[async_std::test]
+    #[traced_test]
+    async fn test_send_multi() {
+        let server = TestServer::new().await;
+        let mut rng = ChaChaRng::from_seed([2; 32]);
+        let send_multi = |params: SendMulti| {
+            let server = &server;
+            async move {
+                let mut res = server
+                    .client
+                    .post("send_multi")
+                    .body_json(&params)
+                    .unwrap()
+                    .send()
+                    .await?;
+                client::response_body::<TransactionReceipt<CapeLedger>>(&mut res).await
+            }
+        };
+
+        // Should fail if a wallet is not already open.
+        send_multi(SendMulti {
+            asset: AssetCode::native(),
+            recipients: vec![Recipient {
+                address: UserKeyPair::generate(&mut rng).address().into(),
+                amount: 1,
+            }],
+            fee: 1,
+            sender: None,
+        })
+        .await
+        .expect_err("send_multi succeeded without an open wallet");
+
+        // Now open a wallet, populate it, and create a new address to receive funds.
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+        let receipt = server
+            .get::<TransactionReceipt<CapeLedger>>("populatefortest")
+            .await
+            .unwrap();
+        let src_address: UserAddress = receipt.submitters[0].clone().into();
+        let dst_address1: UserAddress = match server.post::<PubKey>("newkey/sending").await.unwrap()
+        {
+            PubKey::Sending(pub_key) => pub_key.address().into(),
+            key => panic!("Expected PubKey::Sending, found {:?}", key),
+        };
+        let dst_address2: UserAddress = match server.post::<PubKey>("newkey/sending").await.unwrap()
+        {
+            PubKey::Sending(pub_key) => pub_key.address().into(),
+            key => panic!("Expected PubKey::Sending, found {:?}", key),
+        };
+        let params = |amount1, amount2| SendMulti {
+            asset: AssetCode::native(),
+            recipients: vec![
+                Recipient {
+                    address: dst_address1.clone(),
+                    amount: amount1,
+                },
+                Recipient {
+                    address: dst_address2.clone(),
+                    amount: amount2,
+                },
+            ],
+            fee: 1,
+            sender: Some(src_address.clone()),
+        };
+
+        // Should fail if the sender can't cover the outputs and the fee.
+        send_multi(params(DEFAULT_NATIVE_AMT_IN_FAUCET_ADDR, 1))
+            .await
+            .expect_err("send_multi succeeded with insufficient balance");
+
+        // Send to both addresses in one transaction.
+        send_multi(params(100, 50)).await.unwrap();
+        for (address, amount) in [(&dst_address1, 100u64), (&dst_address2, 50u64)] {
+            retry(|| async {
+                server
+                    .get::<BalanceInfo>(&format!(
+                        "getbalance/address/{}/asset/{}",
+                        address,
+                        AssetCode::native()
+                    ))
+                    .await
+                    .unwrap()
+                    .balances
+                    == Balances::One(amount.into())
+            })
+            .await;
+        }
+        retry(|| async {
+            server
+                .get::<BalanceInfo>(&format!(
+                    "getbalance/address/{}/asset/{}",
+                    src_address,
+                    AssetCode::native()
+                ))
+                .await
+                .unwrap()
+                .balances
+                == Balances::One((DEFAULT_NATIVE_AMT_IN_FAUCET_ADDR - 151).into())
+        })
+        .await;
+    }
+
+    #[async_std::test]
+    #[traced_test]
+    async fn test_consolidate() {
+        let server = TestServer::new().await;
+        let consolidate = |params: Consolidate| {
+            let server = &server;
+            async move {
+                let mut res = server
+                    .client
+                    .post("consolidate")
+                    .body_json(&params)
+                    .unwrap()
+                    .send()
+                    .await?;
+                client::response_body::<TransactionReceipt<CapeLedger>>(&mut res).await
+            }
+        };
+
+        // Should fail if a wallet is not already open.
+        server
+            .requires_wallet::<ConsolidatePreview>(&format!(
+                "consolidate_preview/{}",
+                AssetCode::native()
+            ))
+            .await;
+        consolidate(Consolidate {
+            asset: AssetCode::native(),
+            max_inputs: Consolidate::DEFAULT_MAX_INPUTS,
+            fee: 1,
+        })
+        .await
+        .expect_err("consolidate succeeded without an open wallet");
+
+        // Open a wallet and give a new address 3 records of the native asset.
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+        let receipt = server
+            .get::<TransactionReceipt<CapeLedger>>("populatefortest")
+            .await
+            .unwrap();
+        let src_address: UserAddress = receipt.submitters[0].clone().into();
+        let dst_address: UserAddress = match server.post::<PubKey>("newkey/sending").await.unwrap()
+        {
+            PubKey::Sending(pub_key) => pub_key.address().into(),
+            key => panic!("Expected PubKey::Sending, found {:?}", key),
+        };
+        let dst_records = || async {
+            server
+                .get::<Vec<RecordInfo>>("getrecords")
+                .await
+                .unwrap()
+                .into_iter()
+                .filter(|record| {
+                    record.ro.asset_def.code == AssetCode::native()
+                        && UserAddress::from(record.ro.pub_key.address()) == dst_address
+                })
+                .map(|record| record.amount())
+                .collect::<Vec<_>>()
+        };
+        // Each transfer can have at most 3 outputs, including the change, so fund the address in
+        // two transfers.
+        for amounts in [vec![20, 30], vec![50]] {
+            let num_records = dst_records().await.len() + amounts.len();
+            server
+                .client
+                .post("send_multi")
+                .body_json(&SendMulti {
+                    asset: AssetCode::native(),
+                    recipients: amounts
+                        .into_iter()
+                        .map(|amount| Recipient {
+                            address: dst_address.clone(),
+                            amount,
+                        })
+                        .collect(),
+                    fee: 1,
+                    sender: Some(src_address.clone()),
+                })
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            retry(|| async { dst_records().await.len() == num_records }).await;
+        }
+
+        // The preview selects the largest records of the account with the most records.
+        let preview = server
+            .get::<ConsolidatePreview>(&format!("consolidate_preview/{}", AssetCode::native()))
+            .await
+            .unwrap();
+        assert_eq!(preview.address, dst_address);
+        assert_eq!(preview.records.len(), 3);
+        assert_eq!(preview.balance, 100u64.into());
+        let preview = server
+            .get::<ConsolidatePreview>(&format!(
+                "consolidate_preview/{}/max_inputs/2",
+                AssetCode::native()
+            ))
+            .await
+            .unwrap();
+        assert_eq!(preview.records.len(), 2);
+        assert_eq!(preview.balance, 80u64.into());
+        // The number of inputs is capped at the largest transfer the contract supports.
+        let preview = server
+            .get::<ConsolidatePreview>(&format!(
+                "consolidate_preview/{}/max_inputs/4",
+                AssetCode::native()
+            ))
+            .await
+            .unwrap();
+        assert_eq!(preview.records.len(), 3);
+
+        // Merge all 3 records, paying the fee from them.
+        consolidate(Consolidate {
+            asset: AssetCode::native(),
+            max_inputs: Consolidate::DEFAULT_MAX_INPUTS,
+            fee: 1,
+        })
+        .await
+        .unwrap();
+        retry(|| async { dst_records().await == vec![99u64.into()] }).await;
+
+        // There is nothing left to merge.
+        server
+            .get::<ConsolidatePreview>(&format!("consolidate_preview/{}", AssetCode::native()))
+            .await
+            .unwrap_err();
+    }
+
+    #[async_std::test]
+    #[traced_test]
+    async fn test_getaccount() {
+        let server = TestServer::new().await;
+        let mut rng = ChaChaRng::from_seed([1; 32]);
+
+        // Should fail if a wallet is not already open.
+        server
+            .requires_wallet::<Account>(&format!(
+                "getaccount/{}",
+                UserKeyPair::generate(&mut rng).address(),
+            ))
+            .await;
+        server
+            .requires_wallet::<Account>(&format!(
+                "getaccount/{}",
+                UserKeyPair::generate(&mut rng).pub_key(),
+            ))
+            .await;
+        server
+            .requires_wallet::<Account>(&format!(
+                "getaccount/{}",
+                AuditorKeyPair::generate(&mut rng).pub_key(),
+            ))
+            .await;
+        server
+            .requires_wallet::<Account>(&format!(
+                "getaccount/{}",
+                FreezerKeyPair::generate(&mut rng).pub_key(),
+            ))
+            .await;
+
+        // Now open a wallet.
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+        // Populate the wallet with some dummy data so we have a balance of an asset to send.
+        server
+            .get::<TransactionReceipt<CapeLedger>>("populatefortest")
+            .await
+            .unwrap();
+
+        // Get the wrapped asset type.
+        let info = server.get::<WalletSummary>("getinfo").await.unwrap();
+        let asset = if info.assets[0].definition.code == AssetCode::native() {
+            info.assets[1].clone()
+        } else {
+            info.assets[0].clone()
+        };
+
+        // The wrapper addressed it so we can check the account interface.
+        let mut addresses = info.addresses.into_iter();
+        let address = loop {
+            let address = addresses.next().unwrap();
+            println!(
+                "{:?}",
+                server
+                    .get::<BalanceInfo>(&format!(
+                        "getbalance/address/{}/asset/{}",
+                        address,
+                        AssetCode::native()
+                    ))
+                    .await
+                    .unwrap()
+            );
             if Balances::One(DEFAULT_NATIVE_AMT_IN_WRAPPER_ADDR.into())
                 == server
                     .get::<BalanceInfo>(&format!(
@@ -2383,6 +3376,57 @@ mod tests {
         assert_eq!(asset, import);
     }
 
+    #[async_std::test]
+    #[traced_test]
+    async fn test_assets_search() {
+        let server = TestServer::new().await;
+        server
+            .requires_wallet::<Vec<AssetInfo>>(&format!("assets/search/{}", base64(b"A")))
+            .await;
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+
+        for (symbol, description) in [("BCC", "three"), ("ABB", "two"), ("AAA", "cyan")] {
+            server
+                .post::<AssetInfo>(&format!(
+                    "newasset/symbol/{}/description/{}",
+                    base64(symbol.as_bytes()),
+                    base64(description.as_bytes())
+                ))
+                .await
+                .unwrap();
+        }
+        let search = |query: &'static str| {
+            let server = &server;
+            async move {
+                server
+                    .get::<Vec<AssetInfo>>(&format!("assets/search/{}", base64(query.as_bytes())))
+                    .await
+                    .unwrap()
+                    .into_iter()
+                    .filter_map(|asset| asset.symbol)
+                    // The native asset may match as well, only check the assets we created.
+                    .filter(|symbol| ["AAA", "ABB", "BCC"].contains(&symbol.as_str()))
+                    .collect::<Vec<_>>()
+            }
+        };
+
+        // Matching is case-insensitive, and the results are sorted by symbol.
+        assert_eq!(search("a").await, vec!["AAA", "ABB"]);
+        assert_eq!(search("b").await, vec!["ABB", "BCC"]);
+        // Assets with a matching symbol come before those with a matching description.
+        assert_eq!(search("c").await, vec!["BCC", "AAA"]);
+        assert_eq!(search("T").await, vec!["ABB", "BCC"]);
+        assert!(search("xyz").await.is_empty());
+    }
+
     #[async_std::test]
     #[traced_test]
     async fn test_updateasset() {
@@ -2502,6 +3546,108 @@ mod tests {
         assert_eq!(info.icon.unwrap(), icon);
     }
 
+    #[async_std::test]
+    #[traced_test]
+    async fn test_export_import_keys() {
+        let server = TestServer::new().await;
+        let export_keys = |server: &TestServer, password: &str| {
+            let req = server
+                .client
+                .post("export_keys")
+                .body_json(&ExportKeys {
+                    password: password.to_string(),
+                })
+                .unwrap();
+            async move {
+                let mut res = req.send().await?;
+                client::response_body::<String>(&mut res).await
+            }
+        };
+        let import_keys = |server: &TestServer, bundle: &str, password: &str| {
+            let req = server
+                .client
+                .post("import_keys")
+                .body_json(&ImportKeys {
+                    bundle: bundle.to_string(),
+                    password: password.to_string(),
+                })
+                .unwrap();
+            async move {
+                let mut res = req.send().await?;
+                client::response_body::<Vec<PubKey>>(&mut res).await
+            }
+        };
+
+        // Should fail if a wallet is not already open.
+        export_keys(&server, "backup-password")
+            .await
+            .expect_err("export_keys succeeded without an open wallet");
+        import_keys(&server, "", "backup-password")
+            .await
+            .expect_err("import_keys succeeded without an open wallet");
+
+        // Open a wallet with keys of every type, and export them.
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+        server.post::<PubKey>("newkey/sending").await.unwrap();
+        server.post::<PubKey>("newkey/viewing").await.unwrap();
+        server.post::<PubKey>("newkey/freezing").await.unwrap();
+        let info = server.get::<WalletSummary>("getinfo").await.unwrap();
+        let bundle = export_keys(&server, "backup-password").await.unwrap();
+
+        // Import the keys into a different wallet.
+        let restored = TestServer::new().await;
+        restored
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                restored.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                restored.path()
+            ))
+            .await
+            .unwrap();
+        import_keys(&restored, &bundle, "wrong-password")
+            .await
+            .expect_err("import_keys succeeded with the wrong password");
+        let added = import_keys(&restored, &bundle, "backup-password")
+            .await
+            .unwrap();
+        assert_eq!(
+            added.len(),
+            info.sending_keys.len() + info.viewing_keys.len() + info.freezing_keys.len()
+        );
+
+        // The restored wallet should have all the original keys.
+        let restored_info = restored.get::<WalletSummary>("getinfo").await.unwrap();
+        for key in &info.sending_keys {
+            assert!(restored_info.sending_keys.contains(key));
+            assert!(added.contains(&PubKey::Sending(key.clone())));
+        }
+        for key in &info.viewing_keys {
+            assert!(restored_info.viewing_keys.contains(key));
+            assert!(added.contains(&PubKey::Viewing(key.clone())));
+        }
+        for key in &info.freezing_keys {
+            assert!(restored_info.freezing_keys.contains(key));
+            assert!(added.contains(&PubKey::Freezing(key.clone())));
+        }
+
+        // Importing the same keys again should not add anything.
+        assert_eq!(
+            import_keys(&restored, &bundle, "backup-password")
+                .await
+                .unwrap(),
+            vec![]
+        );
+    }
+
     #[async_std::test]
     #[traced_test]
     async fn test_getprivatekey() {
@@ -2619,6 +3765,97 @@ mod tests {
         }
     }
 
+    #[async_std::test]
+    #[traced_test]
+    async fn test_getprivatekey_encrypted() {
+        let server = TestServer::new().await;
+        let mut rng = ChaChaRng::from_seed([1; 32]);
+        server
+            .post::<()>(&format!(
+                "newwallet/{}/{}/path/{}",
+                server.get::<String>("getmnemonic").await.unwrap(),
+                base64("my-password".as_bytes()),
+                server.path()
+            ))
+            .await
+            .unwrap();
+        let sending_key = match server.post::<PubKey>("newkey/sending").await.unwrap() {
+            PubKey::Sending(key) => key,
+            key => panic!("Expected PubKey::Sending, found {:?}", key),
+        };
+        let path = format!("getprivatekey/{}", sending_key);
+
+        // Encrypted responses require a session.
+        server
+            .client
+            .get(&path)
+            .header("Accept", ENCRYPTED_MSGPACK)
+            .send()
+            .await
+            .expect_err("encrypted response without a session");
+
+        // Perform the key exchange for two clients.
+        let mut sessions = vec![];
+        for _ in 0..2 {
+            let (secret, client_init) = ephemeral_key_pair(&mut rng);
+            let mut res = server
+                .client
+                .post("session/init")
+                .body_json(&client_init)
+                .unwrap()
+                .send()
+                .await
+                .unwrap();
+            let server_init: SessionInitResponse = client::response_body(&mut res).await.unwrap();
+            let key = session_key(&secret, server_init.public_key);
+            sessions.push((server_init.session_id, key));
+        }
+        assert_ne!(sessions[0].0, sessions[1].0);
+
+        // An unknown session is rejected.
+        server
+            .client
+            .get(&path)
+            .header("Accept", ENCRYPTED_MSGPACK)
+            .header(SESSION_HEADER, "unknown")
+            .send()
+            .await
+            .expect_err("encrypted response with an unknown session");
+
+        // The second session does not replace the first one: each response is encrypted under the
+        // key of the session it was requested with.
+        for (i, (session_id, key)) in sessions.iter().enumerate() {
+            let mut res = server
+                .client
+                .get(&path)
+                .header("Accept", ENCRYPTED_MSGPACK)
+                .header(SESSION_HEADER, session_id.as_str())
+                .send()
+                .await
+                .unwrap();
+            assert_eq!(res.content_type().unwrap().essence(), ENCRYPTED_MSGPACK);
+            let body = res.body_bytes().await.unwrap();
+
+            // The body cannot be read without the session key.
+            assert!(rmp_serde::from_slice::<PrivateKey>(&body).is_err());
+            assert!(decrypt(&[0; 32], &body).is_none());
+            let (_, other_key) = &sessions[1 - i];
+            assert!(decrypt(other_key, &body).is_none());
+
+            let plaintext = decrypt(key, &body).unwrap();
+            match rmp_serde::from_slice::<PrivateKey>(&plaintext).unwrap() {
+                PrivateKey::Sending(key) => assert_eq!(key.pub_key(), sending_key),
+                key => panic!("Expected PrivateKey::Sending, found {:?}", key),
+            }
+        }
+
+        // Without the header, the response is not encrypted.
+        match server.get::<PrivateKey>(&path).await.unwrap() {
+            PrivateKey::Sending(key) => assert_eq!(key.pub_key(), sending_key),
+            key => panic!("Expected PrivateKey::Sending, found {:?}", key),
+        }
+    }
+
     #[async_std::test]
     #[traced_test]
     async fn test_recordopening() {
@@ -8,8 +8,13 @@
 //! Web server endpoint handlers.
 
 #![allow(clippy::let_unit_value)]
+use crate::session::{init_session, SensitiveResponse};
 use crate::web::{NodeOpt, WebState};
-use async_std::fs::{read_dir, File};
+use async_std::{
+    fs::{read_dir, File},
+    sync::Mutex,
+    task::sleep,
+};
 use cap_rust_sandbox::{ledger::CapeLedger, model::Erc20Code};
 use cape_wallet::{
     disco::{ApiRouteKey, UrlSegmentType},
@@ -17,7 +22,7 @@ use cape_wallet::{
     ui::*,
     wallet::{CapeWalletError, CapeWalletExt},
 };
-use ethers::prelude::Address;
+use ethers::prelude::{Address, H256, U256};
 use futures::{prelude::*, stream::iter};
 use jf_cap::{
     keys::{AuditorPubKey, FreezerPubKey, UserKeyPair, UserPubKey},
@@ -35,10 +40,11 @@ use seahorse::{
     asset_library::Icon,
     events::{EventIndex, EventSource},
     hd::KeyTree,
-    txn_builder::{RecordInfo, TransactionReceipt},
+    txn_builder::{RecordInfo, TransactionError, TransactionReceipt, TransactionStatus},
     WalletBackend, WalletStorage,
 };
 use serde::{Deserialize, Serialize};
+use serde_json::json;
 use snafu::Snafu;
 use std::collections::HashMap;
 use std::fmt::Debug;
@@ -49,6 +55,7 @@ use std::str::FromStr;
 use std::time::Duration;
 use tagged_base64::TaggedBase64;
 use tide::{Request, StatusCode};
+use tide_websockets::WebSocketConnection;
 
 #[derive(Debug, Snafu, Serialize, Deserialize)]
 #[snafu(module(error))]
@@ -62,6 +69,9 @@ pub enum CapeAPIError {
     #[snafu(display("you must open a wallet to use this enpdoint"))]
     MissingWallet,
 
+    #[snafu(display("unknown or missing session, initialize a session with session/init first"))]
+    MissingSession,
+
     #[snafu(display("invalid parameter: expected {}, got {}", expected, actual))]
     Param { expected: String, actual: String },
 
@@ -71,6 +81,9 @@ pub enum CapeAPIError {
     #[snafu(display("failed to deserialize request parameter: {}", msg))]
     Deserialize { msg: String },
 
+    #[snafu(display("no transaction with hash {} in the wallet's history", hash))]
+    TransactionNotFound { hash: String },
+
     #[snafu(display("internal server error: {}", msg))]
     Internal { msg: String },
 }
@@ -85,12 +98,52 @@ impl net::Error for CapeAPIError {
             | Self::Tag { .. }
             | Self::Deserialize { .. }
             | Self::OpenWallet { .. }
-            | Self::MissingWallet => StatusCode::BadRequest,
+            | Self::MissingWallet
+            | Self::MissingSession => StatusCode::BadRequest,
+            Self::TransactionNotFound { .. } => StatusCode::NotFound,
             Self::Wallet { .. } | Self::Internal { .. } => StatusCode::InternalServerError,
         }
     }
 }
 
+impl CapeAPIError {
+    /// The OpenAPI schema of a serialized error.
+    ///
+    /// Variants with fields are serialized as `{"<variant>": {<fields>}}`, others as `"<variant>"`.
+    /// This must be kept in sync with the variants above.
+    pub fn openapi_schema() -> serde_json::Value {
+        let variant = |name: &str, fields: &[&str]| {
+            let properties = fields
+                .iter()
+                .map(|field| (field.to_string(), json!({ "type": "string" })))
+                .collect::<serde_json::Map<_, _>>();
+            json!({
+                "type": "object",
+                "required": [name],
+                "properties": {
+                    name: {
+                        "type": "object",
+                        "required": fields,
+                        "properties": properties,
+                    },
+                },
+            })
+        };
+        json!({
+            "oneOf": [
+                { "type": "string", "enum": ["MissingWallet", "MissingSession"] },
+                variant("Wallet", &["msg"]),
+                variant("OpenWallet", &["msg"]),
+                variant("Param", &["expected", "actual"]),
+                variant("Tag", &["expected", "actual"]),
+                variant("Deserialize", &["msg"]),
+                variant("TransactionNotFound", &["hash"]),
+                variant("Internal", &["msg"]),
+            ],
+        })
+    }
+}
+
 pub fn server_error<E: Into<CapeAPIError>>(err: E) -> tide::Error {
     net::server_error(err)
 }
@@ -153,7 +206,7 @@ mod backend {
 #[cfg(not(test))]
 mod backend {
     use super::*;
-    use cap_rust_sandbox::universal_param::UNIVERSAL_PARAM;
+    use cap_rust_sandbox::universal_param::universal_param;
     use cape_wallet::backend::{CapeBackend, CapeBackendConfig};
 
     pub type Backend = CapeBackend<'static>;
@@ -165,7 +218,7 @@ mod backend {
         loader: &mut CapeLoader,
     ) -> Result<Backend, CapeWalletError> {
         CapeBackend::new(
-            &UNIVERSAL_PARAM,
+            universal_param(),
             CapeBackendConfig {
                 web3_provider: options.web3_provider(),
                 eqs_url: options.eqs_url(),
@@ -598,6 +651,272 @@ async fn getinfo(wallet: &mut Option<Wallet>) -> Result<WalletSummary, tide::Err
     })
 }
 
+// Search the known assets for a case-insensitive substring of the symbol or description. Assets
+// with a matching symbol come first, each group sorted by symbol.
+async fn assets(
+    bindings: &HashMap<String, RouteBinding>,
+    wallet: &mut Option<Wallet>,
+) -> Result<Vec<AssetInfo>, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let query = bindings[":query"].value.as_string()?.to_lowercase();
+    let contains_query = |field: &Option<String>| {
+        field
+            .as_ref()
+            .map_or(false, |field| field.to_lowercase().contains(&query))
+    };
+    let mut matches = known_assets(wallet)
+        .await
+        .into_values()
+        .filter_map(|asset| {
+            if contains_query(&asset.symbol) {
+                Some((true, asset))
+            } else if contains_query(&asset.description) {
+                Some((false, asset))
+            } else {
+                None
+            }
+        })
+        .collect::<Vec<_>>();
+    matches.sort_by(|(symbol_match1, asset1), (symbol_match2, asset2)| {
+        symbol_match2
+            .cmp(symbol_match1)
+            .then_with(|| asset1.symbol.cmp(&asset2.symbol))
+    });
+    Ok(matches.into_iter().map(|(_, asset)| asset).collect())
+}
+
+// Time to wait for the price oracle before treating all prices as unknown.
+const PRICE_ORACLE_TIMEOUT: Duration = Duration::from_secs(5);
+
+// Get the USD price of assets from the price oracle. Returns no prices if no oracle is configured,
+// or if it is unavailable.
+async fn asset_prices(options: &NodeOpt) -> HashMap<AssetCode, f64> {
+    let url = match &options.price_oracle_url {
+        Some(url) => url,
+        None => return HashMap::new(),
+    };
+    let prices = async_std::future::timeout(PRICE_ORACLE_TIMEOUT, async {
+        surf::get(url).recv_json::<HashMap<String, f64>>().await
+    })
+    .await;
+    match prices {
+        Ok(Ok(prices)) => prices
+            .into_iter()
+            .filter_map(|(code, price)| Some((AssetCode::from_str(&code).ok()?, price)))
+            .collect(),
+        _ => HashMap::new(),
+    }
+}
+
+fn u256_to_f64(value: U256) -> f64 {
+    value
+        .0
+        .iter()
+        .rev()
+        .fold(0.0, |acc, limb| acc * 2f64.powi(64) + *limb as f64)
+}
+
+// The balance of each known asset across all accounts, valued with the prices of the price oracle.
+async fn portfolio(
+    options: &NodeOpt,
+    wallet: &mut Option<Wallet>,
+) -> Result<PortfolioSummary, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let prices = asset_prices(options).await;
+    let pub_keys = wallet.pub_keys().await;
+    let mut entries = vec![];
+    for asset in known_assets(wallet).await.into_values() {
+        let mut balance = U256::zero();
+        for pub_key in &pub_keys {
+            balance += wallet
+                .balance_breakdown(&pub_key.address(), &asset.definition.code)
+                .await;
+        }
+        let price_usd = prices.get(&asset.definition.code).copied();
+        entries.push(AssetPortfolioEntry {
+            asset,
+            balance,
+            price_usd,
+            value_usd: price_usd.map(|price| price * u256_to_f64(balance)),
+        });
+    }
+    let total_usd = entries
+        .iter()
+        .filter_map(|entry| entry.value_usd)
+        .reduce(|total, value| total + value);
+    Ok(PortfolioSummary { entries, total_usd })
+}
+
+// Time between two checks for balance changes in `watch_balances`.
+const BALANCE_POLL_INTERVAL: Duration = Duration::from_secs(1);
+
+// Once a balance change is detected, `watch_balances` waits until the balances have not changed for
+// this long before pushing updates, so that a burst of changes results in a single update per
+// balance.
+const BALANCE_UPDATE_DEBOUNCE: Duration = Duration::from_millis(100);
+
+// Get the balance of each known asset in each account of the wallet, or fail if no wallet is open.
+async fn current_balances(
+    wallet: &Mutex<Option<Wallet>>,
+) -> Result<HashMap<(UserAddress, AssetCode), U256>, CapeAPIError> {
+    let wallet = &*wallet.lock().await;
+    let wallet = wallet.as_ref().ok_or(CapeAPIError::MissingWallet)?;
+    let mut balances = HashMap::new();
+    for pub_key in wallet.pub_keys().await {
+        for asset in wallet.assets().await {
+            let code = asset.definition.code;
+            let balance = wallet.balance_breakdown(&pub_key.address(), &code).await;
+            balances.insert((pub_key.address().into(), code), balance);
+        }
+    }
+    Ok(balances)
+}
+
+fn balance_delta(old: U256, new: U256) -> i128 {
+    let max = U256::from(i128::MAX as u128);
+    if new >= old {
+        (new - old).min(max).as_u128() as i128
+    } else {
+        -((old - new).min(max).as_u128() as i128)
+    }
+}
+
+// Push live balance updates to a WebSocket client.
+//
+// The first message is the current `WalletSummary`. After that, a `BalanceUpdate` is sent for every
+// balance that changes. If no wallet is open, or the wallet gets closed, a `CapeAPIError` is sent
+// and the connection is closed.
+pub async fn watch_balances(
+    req: Request<WebState>,
+    conn: WebSocketConnection,
+) -> Result<(), tide::Error> {
+    let state = req.state();
+    let summary = {
+        let wallet = &mut *state.wallet.lock().await;
+        if wallet.is_none() {
+            return conn.send_json(&CapeAPIError::MissingWallet).await;
+        }
+        getinfo(wallet).await?
+    };
+    conn.send_json(&summary).await?;
+
+    let mut balances = match current_balances(&state.wallet).await {
+        Ok(balances) => balances,
+        Err(err) => return conn.send_json(&err).await,
+    };
+    loop {
+        sleep(BALANCE_POLL_INTERVAL).await;
+        let mut new_balances = match current_balances(&state.wallet).await {
+            Ok(new_balances) => new_balances,
+            Err(err) => return conn.send_json(&err).await,
+        };
+        if new_balances == balances {
+            continue;
+        }
+
+        // Wait for the balances to settle.
+        loop {
+            sleep(BALANCE_UPDATE_DEBOUNCE).await;
+            let settled = match current_balances(&state.wallet).await {
+                Ok(settled) => settled,
+                Err(err) => return conn.send_json(&err).await,
+            };
+            if settled == new_balances {
+                break;
+            }
+            new_balances = settled;
+        }
+
+        for ((address, asset), new_balance) in &new_balances {
+            let old_balance = balances
+                .get(&(address.clone(), *asset))
+                .cloned()
+                .unwrap_or_default();
+            if old_balance != *new_balance {
+                conn.send_json(&BalanceUpdate {
+                    asset: *asset,
+                    address: address.clone(),
+                    new_balance: *new_balance,
+                    delta: balance_delta(old_balance, *new_balance),
+                })
+                .await?;
+            }
+        }
+        balances = new_balances;
+    }
+}
+
+// Default time `sync` waits for the wallet to catch up with the EQS.
+const DEFAULT_SYNC_TIMEOUT: Duration = Duration::from_secs(60);
+
+async fn sync(
+    bindings: &HashMap<String, RouteBinding>,
+    wallet: &mut Option<Wallet>,
+) -> Result<SyncStatus, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let timeout = match bindings.get(":timeout") {
+        Some(param) => Duration::from_secs(param.value.as_u64()?),
+        None => DEFAULT_SYNC_TIMEOUT,
+    };
+
+    // Fail early if the EQS is unreachable.
+    wallet.scan_status().await.map_err(wallet_error)?;
+    // Stop waiting when the timeout expires, and report how far the wallet got.
+    let _ = async_std::future::timeout(timeout, async {
+        let mut backoff = Duration::from_millis(100);
+        loop {
+            if let Ok((sync_time, real_time)) = wallet.scan_status().await {
+                if sync_time.index(EventSource::QueryService)
+                    >= real_time.index(EventSource::QueryService)
+                {
+                    break;
+                }
+            }
+            sleep(backoff).await;
+            backoff *= 2;
+        }
+    })
+    .await;
+
+    let (sync_time, real_time) = wallet.scan_status().await.map_err(wallet_error)?;
+    let sync_time = sync_time.index(EventSource::QueryService) as u64;
+    let real_time = real_time.index(EventSource::QueryService) as u64;
+    Ok(SyncStatus {
+        synced: sync_time >= real_time,
+        sync_time,
+        real_time,
+    })
+}
+
+/// The maximum number of blocks which can be requested from `auditlog` at once.
+const MAX_AUDIT_LOG_BLOCKS: u64 = 1000;
+
+async fn auditlog(
+    bindings: &HashMap<String, RouteBinding>,
+    wallet: &mut Option<Wallet>,
+) -> Result<Vec<AuditEntry>, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let viewing_key = bindings[":viewing_key"].value.to::<AuditorPubKey>()?;
+    let from = bindings[":from"].value.as_u64()?;
+    let to = bindings[":to"].value.as_u64()?;
+    if to < from || to - from >= MAX_AUDIT_LOG_BLOCKS {
+        return Err(server_error(CapeAPIError::Param {
+            expected: format!(
+                "a range of at most {} blocks starting at {}",
+                MAX_AUDIT_LOG_BLOCKS, from
+            ),
+            actual: format!("{}..={}", from, to),
+        }));
+    }
+    Ok(wallet
+        .audit_log(&viewing_key, from, to)
+        .await
+        .map_err(wallet_error)?
+        .into_iter()
+        .flat_map(|(block, opening)| AuditEntry::from_opening(block, opening))
+        .collect())
+}
+
 async fn getaddress(wallet: &mut Option<Wallet>) -> Result<Vec<UserAddress>, tide::Error> {
     let wallet = require_wallet(wallet)?;
     Ok(wallet
@@ -957,6 +1276,74 @@ async fn mint(
         .await?)
 }
 
+// Freeze (if `outputs_frozen` is `FreezeFlag::Frozen`) or unfreeze all records of an asset owned by
+// a target address.
+//
+// The wallet must hold the freezing key of the asset and must be able to view the records of the
+// target address.
+async fn freeze(
+    bindings: &HashMap<String, RouteBinding>,
+    wallet: &mut Option<Wallet>,
+    outputs_frozen: FreezeFlag,
+) -> Result<TransactionReceipt<CapeLedger>, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+
+    let asset = bindings[":asset"].value.to::<AssetCode>()?;
+    let target = bindings[":address"].value.to::<UserAddress>()?.0;
+    let fee_address = match bindings.get(":fee_address") {
+        Some(param) => Some(param.value.to::<UserAddress>()?.0),
+        None => None,
+    };
+    let fee = bindings[":fee_amount"].value.as_u128()?;
+
+    if wallet.asset(asset).await.is_none() {
+        return Err(wallet_error(CapeWalletError::UndefinedAsset { asset }));
+    }
+
+    // Collect the total amount of the target's records which are not yet in the desired state.
+    let inputs_frozen = match outputs_frozen {
+        FreezeFlag::Frozen => FreezeFlag::Unfrozen,
+        FreezeFlag::Unfrozen => FreezeFlag::Frozen,
+    };
+    let amount = wallet
+        .records()
+        .await
+        .filter(|record| {
+            record.ro.asset_def.code == asset
+                && record.ro.pub_key.address() == target
+                && record.ro.freeze_flag == inputs_frozen
+        })
+        .fold(U256::zero(), |total, record| {
+            total + U256::from(record.amount())
+        });
+    if amount.is_zero() {
+        return Err(wallet_error(CapeWalletError::Failed {
+            msg: format!(
+                "no {} records of asset {} owned by {}",
+                match inputs_frozen {
+                    FreezeFlag::Frozen => "frozen",
+                    FreezeFlag::Unfrozen => "unfrozen",
+                },
+                asset,
+                UserAddress::from(target)
+            ),
+        }));
+    }
+
+    Ok(match outputs_frozen {
+        FreezeFlag::Frozen => {
+            wallet
+                .freeze(fee_address.as_ref(), fee, &asset, amount, target)
+                .await?
+        }
+        FreezeFlag::Unfrozen => {
+            wallet
+                .unfreeze(fee_address.as_ref(), fee, &asset, amount, target)
+                .await?
+        }
+    })
+}
+
 async fn unwrap(
     bindings: &HashMap<String, RouteBinding>,
     wallet: &mut Option<Wallet>,
@@ -1063,6 +1450,203 @@ pub async fn send(
     }
 }
 
+fn insufficient_balance(asset: AssetCode, required: U256, actual: U256) -> tide::Error {
+    wallet_error(CapeWalletError::TransactionError {
+        source: TransactionError::InsufficientBalance {
+            asset,
+            required: required.into(),
+            actual: actual.into(),
+        },
+    })
+}
+
+// Transfer an asset to multiple recipients in a single transaction, see `SendMulti`.
+pub async fn send_multi(
+    req: &mut Request<WebState>,
+    wallet: &mut Option<Wallet>,
+) -> Result<TransactionReceipt<CapeLedger>, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let params: SendMulti = request_body(req).await?;
+    if params.recipients.is_empty() {
+        return Err(server_error(CapeAPIError::Param {
+            expected: String::from("at least one recipient"),
+            actual: String::from("no recipients"),
+        }));
+    }
+
+    // Check the balance of the sender up front, to fail early with a meaningful error.
+    let senders = match &params.sender {
+        Some(sender) => vec![sender.0.clone()],
+        None => wallet
+            .pub_keys()
+            .await
+            .into_iter()
+            .map(|pub_key| pub_key.address())
+            .collect(),
+    };
+    let mut balance = U256::zero();
+    let mut native_balance = U256::zero();
+    for sender in &senders {
+        balance += wallet.balance_breakdown(sender, &params.asset).await;
+        native_balance += wallet.balance_breakdown(sender, &AssetCode::native()).await;
+    }
+    let total = params
+        .recipients
+        .iter()
+        .fold(U256::zero(), |total, recipient| {
+            total + U256::from(recipient.amount)
+        });
+    let fee = U256::from(params.fee);
+    if params.asset == AssetCode::native() {
+        if total + fee > balance {
+            return Err(insufficient_balance(params.asset, total + fee, balance));
+        }
+    } else {
+        if total > balance {
+            return Err(insufficient_balance(params.asset, total, balance));
+        }
+        if fee > native_balance {
+            return Err(insufficient_balance(
+                AssetCode::native(),
+                fee,
+                native_balance,
+            ));
+        }
+    }
+
+    let receivers = params
+        .recipients
+        .into_iter()
+        .map(|recipient| (recipient.address.0, recipient.amount))
+        .collect::<Vec<_>>();
+    wallet
+        .transfer(
+            params.sender.map(|sender| sender.0).as_ref(),
+            &params.asset,
+            &receivers,
+            params.fee,
+        )
+        .await
+        .map_err(wallet_error)
+}
+
+// Select the records of `asset` to merge: the `max_inputs` largest spendable records of the account
+// with the most of them, with `max_inputs` capped at `Consolidate::max_inputs_limit`.
+//
+// The wallet does not let us choose the inputs of a transfer, so the selection mirrors its own,
+// which spends the largest records first. A transfer of the total amount of the
+// selected records thus spends exactly those records. Since the merged record is then among the
+// largest, repeated consolidation eventually folds in the smaller records too.
+async fn consolidation(
+    wallet: &Wallet,
+    asset: AssetCode,
+    max_inputs: usize,
+) -> Result<ConsolidatePreview, tide::Error> {
+    if max_inputs < 2 {
+        return Err(server_error(CapeAPIError::Param {
+            expected: String::from("max_inputs of at least 2"),
+            actual: max_inputs.to_string(),
+        }));
+    }
+    let max_inputs = max_inputs.min(Consolidate::max_inputs_limit(&asset));
+    let records = wallet.records().await.collect::<Vec<_>>();
+    let (address, mut records) = wallet
+        .pub_keys()
+        .await
+        .into_iter()
+        .map(|pub_key| {
+            let owned = records
+                .iter()
+                .filter(|record| {
+                    record.ro.asset_def.code == asset
+                        && record.ro.freeze_flag == FreezeFlag::Unfrozen
+                        && record.ro.pub_key == pub_key
+                })
+                .cloned()
+                .collect::<Vec<_>>();
+            (pub_key.address(), owned)
+        })
+        .max_by_key(|(_, owned)| owned.len())
+        .filter(|(_, owned)| owned.len() >= 2)
+        .ok_or_else(|| {
+            server_error(CapeAPIError::Param {
+                expected: String::from("an account with at least 2 records of the asset"),
+                actual: asset.to_string(),
+            })
+        })?;
+    records.sort_by_key(|record| std::cmp::Reverse(U256::from(record.amount())));
+    records.truncate(max_inputs);
+    let balance = records.iter().fold(U256::zero(), |total, record| {
+        total + U256::from(record.amount())
+    });
+    Ok(ConsolidatePreview {
+        address: address.into(),
+        records,
+        balance,
+    })
+}
+
+// Preview the records `consolidate` would merge, with `:max_inputs` defaulting to
+// `Consolidate::DEFAULT_MAX_INPUTS`.
+async fn consolidate_preview(
+    bindings: &HashMap<String, RouteBinding>,
+    wallet: &mut Option<Wallet>,
+) -> Result<ConsolidatePreview, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let asset = bindings[":asset"].value.to::<AssetCode>()?;
+    let max_inputs = match bindings.get(":max_inputs") {
+        Some(param) => param.value.as_usize()?,
+        None => Consolidate::DEFAULT_MAX_INPUTS,
+    };
+    consolidation(wallet, asset, max_inputs).await
+}
+
+// Merge records of an asset into one with a transfer to their owner, see `Consolidate`.
+async fn consolidate(
+    req: &mut Request<WebState>,
+    wallet: &mut Option<Wallet>,
+) -> Result<TransactionReceipt<CapeLedger>, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let params: Consolidate = request_body(req).await?;
+    let preview = consolidation(wallet, params.asset, params.max_inputs).await?;
+    let address = preview.address.0;
+    let fee = U256::from(params.fee);
+    let amount = if params.asset == AssetCode::native() {
+        // The fee is paid from the merged records.
+        if preview.balance <= fee {
+            return Err(insufficient_balance(params.asset, fee + 1, preview.balance));
+        }
+        preview.balance - fee
+    } else {
+        let native_balance = wallet
+            .balance_breakdown(&address, &AssetCode::native())
+            .await;
+        if fee > native_balance {
+            return Err(insufficient_balance(
+                AssetCode::native(),
+                fee,
+                native_balance,
+            ));
+        }
+        preview.balance
+    };
+    if amount > U256::from(u128::MAX) {
+        return Err(server_error(CapeAPIError::Param {
+            expected: String::from("a total amount of at most u128::MAX"),
+            actual: amount.to_string(),
+        }));
+    }
+    wallet
+        .transfer(
+            Some(&address),
+            &params.asset,
+            &[(address.clone(), amount.as_u128())],
+            params.fee,
+        )
+        .await
+        .map_err(wallet_error)
+}
+
 pub async fn get_records(wallet: &mut Option<Wallet>) -> Result<Vec<RecordInfo>, tide::Error> {
     let wallet = require_wallet(wallet)?;
     Ok(wallet.records().await.collect::<Vec<_>>())
@@ -1287,7 +1871,15 @@ async fn recordopening(
     Ok(ro)
 }
 
+// Query parameters of `transactionhistory`.
+#[derive(Debug, Deserialize)]
+struct TransactionHistoryQuery {
+    #[serde(default)]
+    reverse: bool,
+}
+
 async fn transactionhistory(
+    req: &Request<WebState>,
     bindings: &HashMap<String, RouteBinding>,
     wallet: &mut Option<Wallet>,
 ) -> Result<
@@ -1298,17 +1890,28 @@ async fn transactionhistory(
     tide::Error,
 > {
     let wallet = require_wallet(wallet)?;
+    let query: TransactionHistoryQuery = req.query()?;
     let history = wallet.transaction_history().await.map_err(wallet_error)?;
     let assets = known_assets(wallet).await;
-    let from = match bindings.get(":from") {
-        Some(param) => history.len().saturating_sub(param.value.as_usize()?),
-        None => 0,
-    };
-    let to = match bindings.get(":count") {
-        Some(param) => from + param.value.as_usize()?,
+    let count = match bindings.get(":count") {
+        Some(param) => param.value.as_usize()?,
         None => history.len(),
     };
-    let selected = iter(history.into_iter().skip(from).take(to - from))
+    let selected: Vec<_> = if query.reverse {
+        // Most recent first, so `:from` is the number of most recent entries to skip.
+        let from = match bindings.get(":from") {
+            Some(param) => param.value.as_usize()?,
+            None => 0,
+        };
+        history.into_iter().rev().skip(from).take(count).collect()
+    } else {
+        let from = match bindings.get(":from") {
+            Some(param) => history.len().saturating_sub(param.value.as_usize()?),
+            None => 0,
+        };
+        history.into_iter().skip(from).take(count).collect()
+    };
+    let selected = iter(selected)
         .then(|entry| TransactionHistoryEntry::from_wallet(wallet, entry))
         .collect::<Vec<_>>()
         .await;
@@ -1319,6 +1922,126 @@ async fn transactionhistory(
     Ok((selected, asset_map))
 }
 
+async fn transactionhistory_count(wallet: &mut Option<Wallet>) -> Result<usize, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    Ok(wallet
+        .transaction_history()
+        .await
+        .map_err(wallet_error)?
+        .len())
+}
+
+// Look up the entry of the wallet's history, pending or not, for the transaction with a given
+// hex encoded hash.
+async fn transaction(
+    bindings: &HashMap<String, RouteBinding>,
+    wallet: &mut Option<Wallet>,
+) -> Result<TransactionHistoryEntry, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let param = bindings[":hash"].value.as_string()?;
+    let hash = H256::from_str(&param).map_err(|_| {
+        server_error(CapeAPIError::Param {
+            expected: String::from("0x-prefixed hex encoded H256"),
+            actual: param.clone(),
+        })
+    })?;
+    let entry = wallet
+        .transaction_history()
+        .await
+        .map_err(wallet_error)?
+        .into_iter()
+        .find(|entry| entry.hash.map(|h| H256::from(<[u8; 32]>::from(h))) == Some(hash))
+        .ok_or_else(|| server_error(CapeAPIError::TransactionNotFound { hash: param }))?;
+    Ok(TransactionHistoryEntry::from_wallet(wallet, entry).await)
+}
+
+// Query parameters of `pending_transactions`.
+#[derive(Debug, Deserialize)]
+struct PendingTransactionsQuery {
+    asset: Option<String>,
+}
+
+// List the receipts of transactions in the wallet's history which have not been accepted, i.e.
+// which are still pending or have been rejected, optionally only for a given asset.
+async fn pending_transactions(
+    req: &Request<WebState>,
+    wallet: &mut Option<Wallet>,
+) -> Result<Vec<TransactionReceipt<CapeLedger>>, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let query: PendingTransactionsQuery = req.query()?;
+    let asset = match query.asset {
+        Some(asset) => Some(AssetCode::from_str(&asset).map_err(|_| {
+            server_error(CapeAPIError::Param {
+                expected: String::from("AssetCode"),
+                actual: asset.clone(),
+            })
+        })?),
+        None => None,
+    };
+
+    let mut pending = vec![];
+    for entry in wallet.transaction_history().await.map_err(wallet_error)? {
+        if asset.map_or(false, |asset| asset != entry.asset) {
+            continue;
+        }
+        // Entries without a receipt were received from other users, and are always accepted.
+        if let Some(receipt) = entry.receipt {
+            if matches!(
+                wallet.transaction_status(&receipt).await,
+                Ok(TransactionStatus::Pending
+                    | TransactionStatus::AwaitingMemos
+                    | TransactionStatus::Rejected)
+            ) {
+                pending.push(receipt);
+            }
+        }
+    }
+    Ok(pending)
+}
+
+async fn export_keys(
+    req: &mut Request<WebState>,
+    wallet: &mut Option<Wallet>,
+) -> Result<String, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let params: ExportKeys = request_body(req).await?;
+    let bundle = wallet
+        .export_keys_encrypted(&params.password)
+        .await
+        .map_err(wallet_error)?;
+    Ok(base64::encode(&bundle))
+}
+
+async fn import_keys(
+    req: &mut Request<WebState>,
+    wallet: &mut Option<Wallet>,
+) -> Result<Vec<PubKey>, tide::Error> {
+    let wallet = require_wallet(wallet)?;
+    let params: ImportKeys = request_body(req).await?;
+    let bundle = base64::decode(&params.bundle)?;
+    let added = wallet
+        .import_keys_encrypted(&bundle, &params.password)
+        .await
+        .map_err(wallet_error)?;
+    Ok(added
+        .sending
+        .into_iter()
+        .map(|key_pair| PubKey::Sending(key_pair.pub_key()))
+        .chain(
+            added
+                .viewing
+                .into_iter()
+                .map(|key_pair| PubKey::Viewing(key_pair.pub_key())),
+        )
+        .chain(
+            added
+                .freezing
+                .into_iter()
+                .map(|key_pair| PubKey::Freezing(key_pair.pub_key())),
+        )
+        .collect())
+}
+
 async fn getprivatekey(
     bindings: &HashMap<String, RouteBinding>,
     wallet: &mut Option<Wallet>,
@@ -1369,11 +2092,24 @@ pub async fn dispatch_url(
     let wallet = &mut *state.wallet.lock().await;
     let key = ApiRouteKey::from_str(segments.0).expect("Unknown route");
     match key {
+        ApiRouteKey::assets => response(&req, assets(bindings, wallet).await?),
+        ApiRouteKey::auditlog => response(&req, auditlog(bindings, wallet).await?),
         ApiRouteKey::buildsponsor => response(&req, buildsponsor(bindings, wallet).await?),
         ApiRouteKey::buildwrap => response(&req, buildwrap(bindings, wallet).await?),
         ApiRouteKey::closewallet => response(&req, closewallet(wallet).await?),
+        ApiRouteKey::consolidate => {
+            let res = consolidate(&mut req, wallet).await?;
+            response(&req, res)
+        }
+        ApiRouteKey::consolidate_preview => {
+            response(&req, consolidate_preview(bindings, wallet).await?)
+        }
         ApiRouteKey::exportasset => response(&req, exportasset(bindings, wallet).await?),
-        ApiRouteKey::freeze => dummy_url_eval(route_pattern, bindings),
+        ApiRouteKey::export_keys => {
+            let res = export_keys(&mut req, wallet).await?;
+            response(&req, res)
+        }
+        ApiRouteKey::freeze => response(&req, freeze(bindings, wallet, FreezeFlag::Frozen).await?),
         ApiRouteKey::getaddress => response(&req, getaddress(wallet).await?),
         ApiRouteKey::getaccount => response(&req, getaccount(bindings, wallet).await?),
         ApiRouteKey::getaccounts => response(&req, getaccounts(&route_params, wallet).await?),
@@ -1384,7 +2120,15 @@ pub async fn dispatch_url(
             let res = importasset(&mut req, wallet).await?;
             response(&req, res)
         }
-        ApiRouteKey::getprivatekey => response(&req, getprivatekey(bindings, wallet).await?),
+        ApiRouteKey::import_keys => {
+            let res = import_keys(&mut req, wallet).await?;
+            response(&req, res)
+        }
+        ApiRouteKey::getprivatekey => {
+            SensitiveResponse(getprivatekey(bindings, wallet).await?)
+                .into_response(&req, rng)
+                .await
+        }
         ApiRouteKey::getrecords => response(&req, get_records(wallet).await?),
         ApiRouteKey::healthcheck => healthcheck().await,
         ApiRouteKey::importkey => dummy_url_eval(route_pattern, bindings),
@@ -1401,6 +2145,10 @@ pub async fn dispatch_url(
             &req,
             openwallet(options, bindings, rng, faucet_key_pair, wallet).await?,
         ),
+        ApiRouteKey::pending_transactions => {
+            response(&req, pending_transactions(&req, wallet).await?)
+        }
+        ApiRouteKey::portfolio => response(&req, portfolio(options, wallet).await?),
         ApiRouteKey::recordopening => response(&req, recordopening(bindings, wallet).await?),
         ApiRouteKey::recoverkey => {
             response(&req, recoverkey(&route_params, bindings, wallet).await?)
@@ -1410,6 +2158,14 @@ pub async fn dispatch_url(
             resetpassword(options, bindings, rng, faucet_key_pair, wallet).await?,
         ),
         ApiRouteKey::send => response(&req, send(bindings, wallet).await?),
+        ApiRouteKey::send_multi => {
+            let res = send_multi(&mut req, wallet).await?;
+            response(&req, res)
+        }
+        ApiRouteKey::session => {
+            let res = init_session(&mut req, rng).await?;
+            response(&req, res)
+        }
         ApiRouteKey::submitsponsor => {
             let res = submitsponsor(&mut req, bindings, wallet).await?;
             response(&req, res)
@@ -1418,11 +2174,18 @@ pub async fn dispatch_url(
             let res = submitwrap(&mut req, bindings, wallet).await?;
             response(&req, res)
         }
-        ApiRouteKey::transaction => dummy_url_eval(route_pattern, bindings),
-        ApiRouteKey::transactionhistory => {
-            response(&req, transactionhistory(bindings, wallet).await?)
+        ApiRouteKey::sync => response(&req, sync(bindings, wallet).await?),
+        ApiRouteKey::transaction => match route_params[0] {
+            "status" | "await" => dummy_url_eval(route_pattern, bindings),
+            _ => response(&req, transaction(bindings, wallet).await?),
+        },
+        ApiRouteKey::transactionhistory => match route_params[0] {
+            "count" => response(&req, transactionhistory_count(wallet).await?),
+            _ => response(&req, transactionhistory(&req, bindings, wallet).await?),
+        },
+        ApiRouteKey::unfreeze => {
+            response(&req, freeze(bindings, wallet, FreezeFlag::Unfrozen).await?)
         }
-        ApiRouteKey::unfreeze => dummy_url_eval(route_pattern, bindings),
         ApiRouteKey::unwrap => response(&req, unwrap(bindings, wallet).await?),
         ApiRouteKey::updateasset => {
             let res = updateasset(&mut req, bindings, wallet).await?;
@@ -0,0 +1,192 @@
+// Copyright (c) 2022 Espresso Systems (espressosys.com)
+// This file is part of the Configurable Asset Privacy for Ethereum (CAPE) library.
+//
+// This program is free software: you can redistribute it and/or modify it under the terms of the GNU General Public License as published by the Free Software Foundation, either version 3 of the License, or (at your option) any later version.
+// This program is distributed in the hope that it will be useful, but WITHOUT ANY WARRANTY; without even the implied warranty of MERCHANTABILITY or FITNESS FOR A PARTICULAR PURPOSE. See the GNU General Public License for more details.
+// You should have received a copy of the GNU General Public License along with this program. If not, see <https://www.gnu.org/licenses/>.
+
+//! Encryption of sensitive responses, such as private keys.
+//!
+//! A client establishes a session key with `POST session/init`, sending an X25519 public key and
+//! receiving the server's ephemeral public key and a session id in return. Both sides derive the
+//! session key as the SHA-256 hash of the Diffie-Hellman shared secret. Each client has its own
+//! session, so establishing a session does not affect the others.
+//!
+//! A [SensitiveResponse] requested with the [ENCRYPTED_MSGPACK] `Accept` header and the session id
+//! in the [SESSION_HEADER] header is serialized with MessagePack and encrypted with AES-256-GCM
+//! under the key of that session. The body consists of the nonce followed by the ciphertext.
+//! Without the `Accept` header, the response is serialized as usual.
+
+use crate::routes::{server_error, CapeAPIError};
+use crate::web::WebState;
+use aes_gcm::{
+    aead::{Aead, NewAead},
+    Aes256Gcm, Key, Nonce,
+};
+use net::server::{request_body, response};
+use rand::{CryptoRng, RngCore};
+use rand_chacha::ChaChaRng;
+use serde::{Deserialize, Serialize};
+use sha2::{Digest, Sha256};
+use std::collections::{HashMap, VecDeque};
+use tide::Request;
+use x25519_dalek::{PublicKey, StaticSecret};
+
+/// Media type of an encrypted [SensitiveResponse].
+pub const ENCRYPTED_MSGPACK: &str = "application/vnd.cape.encrypted+msgpack";
+
+/// Header carrying the id of the session whose key encrypts a [SensitiveResponse].
+pub const SESSION_HEADER: &str = "Cape-Session-Id";
+
+/// Maximum number of sessions kept at a time. Beyond this, the oldest session is dropped.
+pub const MAX_SESSIONS: usize = 1024;
+
+const NONCE_LEN: usize = 12;
+
+/// Key encrypting the sensitive responses of a session.
+pub type SessionKey = [u8; 32];
+
+/// An X25519 public key, as sent to `session/init`.
+#[derive(Clone, Copy, Debug, Deserialize, Serialize)]
+pub struct SessionInit {
+    pub public_key: [u8; 32],
+}
+
+/// The response of `session/init`: the server's X25519 public key and the id of the new session.
+#[derive(Clone, Debug, Deserialize, Serialize)]
+pub struct SessionInitResponse {
+    pub public_key: [u8; 32],
+    pub session_id: String,
+}
+
+/// The keys of the established sessions, by session id.
+#[derive(Debug, Default)]
+pub struct Sessions {
+    keys: HashMap<String, SessionKey>,
+    /// Session ids, oldest first.
+    order: VecDeque<String>,
+}
+
+impl Sessions {
+    /// Add a session, dropping the oldest one if there are already [MAX_SESSIONS].
+    pub fn insert(&mut self, id: String, key: SessionKey) {
+        if self.order.len() >= MAX_SESSIONS {
+            if let Some(oldest) = self.order.pop_front() {
+                self.keys.remove(&oldest);
+            }
+        }
+        self.order.push_back(id.clone());
+        self.keys.insert(id, key);
+    }
+
+    pub fn get(&self, id: &str) -> Option<SessionKey> {
+        self.keys.get(id).copied()
+    }
+}
+
+/// Generate an ephemeral X25519 key pair.
+pub fn ephemeral_key_pair(rng: &mut (impl RngCore + CryptoRng)) -> (StaticSecret, SessionInit) {
+    let mut bytes = [0u8; 32];
+    rng.fill_bytes(&mut bytes);
+    let secret = StaticSecret::from(bytes);
+    let public_key = PublicKey::from(&secret).to_bytes();
+    (secret, SessionInit { public_key })
+}
+
+/// Derive the session key shared with the owner of the public key `peer`.
+pub fn session_key(secret: &StaticSecret, peer: [u8; 32]) -> SessionKey {
+    let shared = secret.diffie_hellman(&PublicKey::from(peer));
+    Sha256::digest(shared.as_bytes()).into()
+}
+
+/// Encrypt `plaintext` under `key` with a random nonce.
+pub fn encrypt(
+    key: &SessionKey,
+    plaintext: &[u8],
+    rng: &mut (impl RngCore + CryptoRng),
+) -> Result<Vec<u8>, tide::Error> {
+    let mut nonce = [0u8; NONCE_LEN];
+    rng.fill_bytes(&mut nonce);
+    let ciphertext = Aes256Gcm::new(Key::from_slice(key))
+        .encrypt(Nonce::from_slice(&nonce), plaintext)
+        .map_err(|_| {
+            server_error(CapeAPIError::Internal {
+                msg: String::from("failed to encrypt response"),
+            })
+        })?;
+    Ok(nonce.into_iter().chain(ciphertext).collect())
+}
+
+/// Decrypt a message produced by [encrypt].
+///
+/// Returns `None` if `key` is not the key the message was encrypted with.
+#[cfg(test)]
+pub fn decrypt(key: &SessionKey, bytes: &[u8]) -> Option<Vec<u8>> {
+    if bytes.len() < NONCE_LEN {
+        return None;
+    }
+    let (nonce, ciphertext) = bytes.split_at(NONCE_LEN);
+    Aes256Gcm::new(Key::from_slice(key))
+        .decrypt(Nonce::from_slice(nonce), ciphertext)
+        .ok()
+}
+
+/// Complete the key exchange started by the client, establishing a new session.
+///
+/// Returns the server's ephemeral public key and the id of the session.
+pub async fn init_session(
+    req: &mut Request<WebState>,
+    rng: &mut ChaChaRng,
+) -> Result<SessionInitResponse, tide::Error> {
+    let client: SessionInit = request_body(req).await?;
+    let (secret, server) = ephemeral_key_pair(rng);
+    let session_id = format!("{:016x}{:016x}", rng.next_u64(), rng.next_u64());
+    req.state()
+        .sessions
+        .lock()
+        .await
+        .insert(session_id.clone(), session_key(&secret, client.public_key));
+    Ok(SessionInitResponse {
+        public_key: server.public_key,
+        session_id,
+    })
+}
+
+/// A response which is encrypted under the session key if the client asks for it.
+pub struct SensitiveResponse<T>(pub T);
+
+impl<T: Serialize> SensitiveResponse<T> {
+    pub async fn into_response(
+        self,
+        req: &Request<WebState>,
+        rng: &mut ChaChaRng,
+    ) -> Result<tide::Response, tide::Error> {
+        let encrypted = req
+            .header("Accept")
+            .map_or(false, |accept| accept.as_str() == ENCRYPTED_MSGPACK);
+        if !encrypted {
+            return response(req, self.0);
+        }
+
+        let session_id = req
+            .header(SESSION_HEADER)
+            .ok_or_else(|| server_error(CapeAPIError::MissingSession))?;
+        let key = req
+            .state()
+            .sessions
+            .lock()
+            .await
+            .get(session_id.as_str())
+            .ok_or_else(|| server_error(CapeAPIError::MissingSession))?;
+        let plaintext = rmp_serde::to_vec_named(&self.0).map_err(|err| {
+            server_error(CapeAPIError::Internal {
+                msg: err.to_string(),
+            })
+        })?;
+        let body = encrypt(&key, &plaintext, rng)?;
+        Ok(tide::Response::builder(200)
+            .content_type(ENCRYPTED_MSGPACK)
+            .body(body)
+            .build())
+    }
+}
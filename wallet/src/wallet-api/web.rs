@@ -12,7 +12,10 @@
 //! of the actual routes is defined in [crate::routes].
 
 #![allow(clippy::format_push_string)]
-use crate::routes::{dispatch_url, CapeAPIError, RouteBinding, UrlSegmentValue, Wallet};
+use crate::routes::{
+    dispatch_url, watch_balances, CapeAPIError, RouteBinding, UrlSegmentValue, Wallet,
+};
+use crate::session::Sessions;
 use async_std::{
     sync::{Arc, Mutex},
     task::{spawn, JoinHandle},
@@ -23,6 +26,7 @@ use ethers::prelude::{Address, H160};
 use jf_cap::{keys::UserKeyPair, structs::AssetCode};
 use net::server;
 use rand_chacha::ChaChaRng;
+use request_id::{RequestIdMiddleware, REQUEST_ID_HEADER};
 use std::collections::hash_map::HashMap;
 use std::fs::create_dir_all;
 use std::path::{Path, PathBuf};
@@ -34,6 +38,7 @@ use tide::{
     http::{headers::HeaderValue, Method, Url},
     security::{CorsMiddleware, Origin},
 };
+use tide_websockets::WebSocket;
 
 pub const DEFAULT_ETH_ADDR: Address = H160([2; 20]);
 pub const DEFAULT_WRAPPED_AMT: u128 = 1000;
@@ -97,6 +102,11 @@ pub struct NodeOpt {
     /// Minimum amount of time to wait between polling requests to EQS.
     #[structopt(long, env = "CAPE_WALLET_MIN_POLLING_DELAY", default_value = "500")]
     pub min_polling_delay_ms: u64,
+
+    /// URL of a price oracle, returning the USD price of assets as a JSON map from asset code to
+    /// price.
+    #[structopt(long, env = "CAPE_PRICE_ORACLE_URL")]
+    pub price_oracle_url: Option<Url>,
 }
 
 impl Default for NodeOpt {
@@ -118,6 +128,7 @@ impl Default for NodeOpt {
             rpc_url: None,
             eth_mnemonic: None,
             min_polling_delay_ms: 500,
+            price_oracle_url: None,
         }
     }
 }
@@ -222,6 +233,8 @@ pub struct WebState {
     pub(crate) rng: Arc<Mutex<ChaChaRng>>,
     pub(crate) faucet_key_pair: UserKeyPair,
     pub(crate) options: NodeOpt,
+    /// Sessions established with `session/init`, see [crate::session].
+    pub(crate) sessions: Arc<Mutex<Sessions>>,
 }
 
 // Get the route pattern that matches the URL of a request, and the bindings for parameters in the
@@ -508,13 +521,16 @@ pub fn init_server(
         rng: Arc::new(Mutex::new(rng)),
         faucet_key_pair,
         options: options.clone(),
+        sessions: Arc::new(Mutex::new(Sessions::default())),
     });
     web_server
+        .with(RequestIdMiddleware)
         .with(
             CorsMiddleware::new()
                 .allow_methods("GET, POST".parse::<HeaderValue>().unwrap())
                 .allow_headers("*".parse::<HeaderValue>().unwrap())
                 .allow_origin(Origin::from("*"))
+                .expose_headers(REQUEST_ID_HEADER.parse::<HeaderValue>().unwrap())
                 .allow_credentials(true),
         )
         .with(server::trace)
@@ -531,6 +547,21 @@ pub fn init_server(
                 .build())
         });
 
+    // Describe the routes from the configuration file as an OpenAPI specification.
+    web_server
+        .at("/openapi.json")
+        .get(|req: tide::Request<WebState>| async move {
+            let spec = disco::compose_openapi(&req.state().api, CapeAPIError::openapi_schema());
+            Ok(tide::Response::builder(200)
+                .body(tide::Body::from_json(&spec)?)
+                .build())
+        });
+
+    // Push live balance updates over a WebSocket, see [crate::routes::watch_balances].
+    web_server
+        .at("/ws/balances")
+        .get(WebSocket::new(watch_balances));
+
     // Add routes from a configuration file.
     if let Some(api_map) = api["route"].as_table() {
         api_map.values().for_each(|v| {
@@ -7,19 +7,26 @@
 
 //! Instantiation of [seahorse::Wallet] for CAPE.
 
+use crate::key_bundle::KeyBundle;
 use async_std::{fs, sync::Arc};
 use async_trait::async_trait;
 use cap_rust_sandbox::{deploy::EthMiddleware, ledger::*, model::*};
+use futures::stream::StreamExt;
 use jf_cap::{
-    keys::UserAddress,
+    keys::{AuditorPubKey, UserAddress},
     structs::{AssetCode, AssetDefinition, AssetPolicy, FreezeFlag, RecordOpening},
     VerKey,
 };
+use reef::{
+    traits::{Block as _, Transaction as _},
+    AuditMemoOpening,
+};
 use seahorse::{
-    events::EventIndex,
+    events::{EventIndex, EventSource, LedgerEvent},
     txn_builder::{TransactionError, TransactionReceipt},
     AssetInfo, RecordAmount, Wallet, WalletBackend, WalletError,
 };
+use std::collections::HashMap;
 use std::path::Path;
 use std::time::Duration;
 
@@ -240,6 +247,30 @@ pub trait CapeWalletExt<'a, Backend: CapeWalletBackend<'a> + Sync + 'a> {
 
     /// The latest contract, in use by the EQS.
     async fn latest_contract_address(&self) -> Result<Erc20Code, CapeWalletError>;
+
+    /// Open the viewing memos of committed transactions using one of this wallet's viewing keys.
+    ///
+    /// Returns the block index and the opened memo of each transaction in blocks `from..=to` which
+    /// can be decrypted with the private key corresponding to `viewing_key`. Transactions which are
+    /// not viewable by `viewing_key` are skipped.
+    async fn audit_log(
+        &self,
+        viewing_key: &AuditorPubKey,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<(u64, AuditMemoOpening)>, CapeWalletError>;
+
+    /// Export all key pairs of this wallet as a [KeyBundle] encrypted with `password`.
+    async fn export_keys_encrypted(&self, password: &str) -> Result<Vec<u8>, CapeWalletError>;
+
+    /// Add the key pairs of an encrypted [KeyBundle] to this wallet.
+    ///
+    /// Keys which are already in the wallet are skipped. Returns the keys which were added.
+    async fn import_keys_encrypted(
+        &mut self,
+        bundle: &[u8],
+        password: &str,
+    ) -> Result<KeyBundle, CapeWalletError>;
 }
 
 #[async_trait]
@@ -497,4 +528,116 @@ impl<'a, Backend: CapeWalletBackend<'a> + Sync + 'a> CapeWalletExt<'a, Backend>
     async fn latest_contract_address(&self) -> Result<Erc20Code, CapeWalletError> {
         self.lock().await.backend().latest_contract_address().await
     }
+
+    async fn audit_log(
+        &self,
+        viewing_key: &AuditorPubKey,
+        from: u64,
+        to: u64,
+    ) -> Result<Vec<(u64, AuditMemoOpening)>, CapeWalletError> {
+        let key_pair = self.get_auditor_private_key(viewing_key).await?;
+        let keys = HashMap::from([(viewing_key.clone(), key_pair)]);
+        let assets = self
+            .assets()
+            .await
+            .into_iter()
+            .map(|asset| (asset.definition.code, asset.definition))
+            .collect::<HashMap<_, _>>();
+
+        // Replay the events reported by the EQS so far, opening the memos of each transaction in
+        // the requested range of blocks. Every block height is reported by at least one commit
+        // event, so the commit of block `from` comes after at least `from` other events, which we
+        // can skip.
+        let eqs_time = self.lock().await.backend().eqs_time().await?;
+        if from as usize >= eqs_time.index(EventSource::QueryService) {
+            return Ok(vec![]);
+        }
+        let start = EventIndex::from_source(EventSource::QueryService, from as usize);
+        let mut events = self
+            .lock()
+            .await
+            .backend()
+            .subscribe(start, Some(eqs_time))
+            .await;
+        let mut entries = Vec::new();
+        while let Some((event, _)) = events.next().await {
+            if let LedgerEvent::Commit {
+                block, block_id, ..
+            } = event
+            {
+                if block_id > to {
+                    break;
+                }
+                if block_id < from {
+                    continue;
+                }
+                for txn in block.txns() {
+                    if let Ok(opening) = txn.open_audit_memo(&assets, &keys) {
+                        entries.push((block_id, opening));
+                    }
+                }
+            }
+        }
+        Ok(entries)
+    }
+
+    async fn export_keys_encrypted(&self, password: &str) -> Result<Vec<u8>, CapeWalletError> {
+        let mut bundle = KeyBundle::default();
+        for pub_key in self.pub_keys().await {
+            bundle
+                .sending
+                .push(self.get_user_private_key(&pub_key.address()).await?);
+        }
+        for pub_key in self.auditor_pub_keys().await {
+            bundle
+                .viewing
+                .push(self.get_auditor_private_key(&pub_key).await?);
+        }
+        for pub_key in self.freezer_pub_keys().await {
+            bundle
+                .freezing
+                .push(self.get_freezer_private_key(&pub_key).await?);
+        }
+        bundle.encrypt(password, self.lock().await.rng())
+    }
+
+    async fn import_keys_encrypted(
+        &mut self,
+        bundle: &[u8],
+        password: &str,
+    ) -> Result<KeyBundle, CapeWalletError> {
+        let bundle = KeyBundle::decrypt(bundle, password)?;
+        let mut added = KeyBundle::default();
+
+        let pub_keys = self.pub_keys().await;
+        for key_pair in bundle.sending {
+            if !pub_keys.contains(&key_pair.pub_key()) {
+                // Scan from the beginning of the ledger, since the key may own existing records.
+                self.add_user_key(
+                    key_pair.clone(),
+                    "imported sending key".into(),
+                    EventIndex::default(),
+                )
+                .await?;
+                added.sending.push(key_pair);
+            }
+        }
+        let pub_keys = self.auditor_pub_keys().await;
+        for key_pair in bundle.viewing {
+            if !pub_keys.contains(&key_pair.pub_key()) {
+                self.add_audit_key(key_pair.clone(), "imported viewing key".into())
+                    .await?;
+                added.viewing.push(key_pair);
+            }
+        }
+        let pub_keys = self.freezer_pub_keys().await;
+        for key_pair in bundle.freezing {
+            if !pub_keys.contains(&key_pair.pub_key()) {
+                self.add_freeze_key(key_pair.clone(), "imported freezing key".into())
+                    .await?;
+                added.freezing.push(key_pair);
+            }
+        }
+        Ok(added)
+    }
 }